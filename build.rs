@@ -1,6 +1,29 @@
 // build.rs
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
     // 将第二个参数 embed_resource::NONE 加回来
     embed_resource::compile("app.rc", embed_resource::NONE);
-}
\ No newline at end of file
+
+    // 编译日期 (UTC)，“关于”窗口中显示。源代码修改后重新运行，日期随之更新
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() / 86_400).unwrap_or(0);
+    let (year, month, day) = civil_from_days(days as i64);
+    println!("cargo:rustc-env=BUILD_DATE={:04}-{:02}-{:02}", year, month, day);
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=app.rc");
+}
+
+// 从 1970-01-01 起的天数换算为公历日期 (Howard Hinnant 的 civil_from_days 算法)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,276 @@
+// src/about_ui.rs
+// “关于”窗口：版本、编译日期、当前语言和语音、语音引擎，以及正在运行的监控。
+// “复制诊断信息”把这些内容以英文纯文本放到剪贴板，方便附在问题报告中。
+// 窗口类、字体和 DPI 的处理与设置窗口共用 (见 settings_ui)
+
+use std::ffi::c_void;
+use std::sync::atomic::AtomicIsize;
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+
+use windows::core::{w, HSTRING};
+use windows::Win32::Foundation::{GlobalFree, HANDLE, HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::HFONT;
+use windows::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+use windows::Win32::System::SystemServices::SS_LEFT;
+use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, GetDlgItem, GetWindowLongPtrW, SendMessageW, SetWindowLongPtrW,
+    BS_DEFPUSHBUTTON, CREATESTRUCTW, CW_USEDEFAULT, DC_HASDEFID, DM_GETDEFID, ES_AUTOVSCROLL, ES_MULTILINE, ES_READONLY,
+    GWLP_USERDATA, HMENU, WINDOW_STYLE,
+    WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_DPICHANGED, WM_SETFONT, WS_BORDER, WS_CAPTION, WS_CHILD,
+    WS_EX_DLGMODALFRAME, WS_SYSMENU, WS_TABSTOP, WS_VISIBLE, WS_VSCROLL,
+};
+
+use crate::config;
+use crate::i18n;
+use crate::settings_ui::{
+    activate_dialog_window, control_text, create_dialog_font, delete_dialog_font, engine_status_text, fit_to_dpi,
+    handle_dpi_changed, record_layout, register_dialog_class, register_dialog_window, unregister_dialog_window, window_dpi, BASE_DPI,
+};
+use crate::AppState;
+use log::{error, info};
+
+const IDC_ABOUT_TITLE: i32 = 101;
+const IDC_ABOUT_DETAILS: i32 = 102;
+const IDC_COPY_DIAGNOSTICS: i32 = 103;
+const IDOK: i32 = 1;
+const IDCANCEL: i32 = 2;
+
+// “关于”窗口在 96 DPI 下的大小
+const ABOUT_WIDTH: i32 = 480;
+const ABOUT_HEIGHT: i32 = 370;
+
+// windows crate 的 CF_UNICODETEXT 在 Win32_System_Ole 中
+const CF_UNICODETEXT: u32 = 13;
+
+const BUILD_DATE: &str = env!("BUILD_DATE");
+
+static ABOUT_CLASS_NAME: Lazy<HSTRING> = Lazy::new(|| HSTRING::from("AdvancedBeeperAboutWindowClass"));
+// 已经打开的窗口 (0 表示没有)
+static ABOUT_WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+struct AboutWindowData {
+    // 窗口中显示的文字 (当前语言) 和复制到剪贴板的诊断信息 (英文)
+    details: String,
+    diagnostics: String,
+    copy_text: String,
+    close_text: String,
+    h_font: HFONT,
+    dpi: u32,
+    layout: Vec<(HWND, RECT)>,
+}
+
+fn register_about_class() {
+    static REGISTER_ONCE: std::sync::Once = std::sync::Once::new();
+    REGISTER_ONCE.call_once(|| register_dialog_class(&ABOUT_CLASS_NAME, Some(about_wnd_proc)));
+}
+
+pub fn show(parent: HWND, app_state: Arc<Mutex<AppState>>) {
+    if activate_dialog_window(&ABOUT_WINDOW) { return; }
+    register_about_class();
+    let instance = unsafe { GetModuleHandleW(None).unwrap() };
+
+    let (window_title, data) = {
+        let state = app_state.lock().unwrap();
+        let i18n = &state.i18n_manager;
+        let version = env!("CARGO_PKG_VERSION");
+        let locale = i18n.locale().to_string();
+        let language = i18n::available_locales().into_iter()
+            .find(|(code, _)| *code == locale)
+            .map(|(_, name)| format!("{} ({})", name, locale))
+            .unwrap_or_else(|| locale.clone());
+        let voice = state.tts_engine.voice();
+        let voice_name = voice.as_ref().map(|voice| voice.name.clone())
+            .unwrap_or_else(|| control_text(i18n, "about_voice_none", "(none)"));
+        let backend = state.tts_engine.backend_name();
+        let monitors = state.running_monitors.join(", ");
+        let count = state.running_monitors.len().to_string();
+
+        let line = |key: &str, fallback: &str, params: &[(&str, &str)]| {
+            i18n.get_text_with_params(key, params).unwrap_or_else(|| {
+                params.iter().fold(fallback.to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+            })
+        };
+        let details = [
+            line("about_version", "Version {version}, built {date}", &[("version", version), ("date", BUILD_DATE)]),
+            line("about_language", "Language: {language}", &[("language", &language)]),
+            line("about_voice", "Voice: {voice}", &[("voice", &voice_name)]),
+            line("about_backend", "Speech backend: {backend}", &[("backend", backend)]),
+            engine_status_text(i18n, state.engine_health),
+            line("about_monitors", "Monitors running ({count}): {names}", &[("count", &count), ("names", &monitors)]),
+        ].join("\r\n");
+
+        let diagnostics = [
+            format!("CO/MP/UT/ER {} (built {})", version, BUILD_DATE),
+            format!("Locale: {}", language),
+            match &voice {
+                Some(voice) => format!("Voice: {} [{}, {}]", voice.name, voice.id, voice.language),
+                None => "Voice: none".to_string(),
+            },
+            format!("Speech backend: {}", backend),
+            format!("Speech engine status: {:?}", state.engine_health),
+            format!("Monitors running ({}): {}", count, monitors),
+            format!("Config: {}{}", config::config_path().display(), if config::is_portable() { " (portable)" } else { "" }),
+        ].join("\r\n");
+
+        let title = control_text(i18n, "about_window_title", "About CO/MP/UT/ER");
+        let data = Box::new(AboutWindowData {
+            details,
+            diagnostics,
+            copy_text: control_text(i18n, "about_button_copy", "Copy diagnostics"),
+            close_text: control_text(i18n, "about_button_close", "Close"),
+            h_font: HFONT::default(),
+            dpi: BASE_DPI,
+            layout: vec![],
+        });
+        (title, data)
+    };
+    let data_ptr = Box::into_raw(data);
+
+    let hwnd = match unsafe {
+        CreateWindowExW(
+            WS_EX_DLGMODALFRAME,
+            &*ABOUT_CLASS_NAME,
+            &HSTRING::from(window_title),
+            WS_CAPTION | WS_SYSMENU | WS_VISIBLE,
+            CW_USEDEFAULT, CW_USEDEFAULT, ABOUT_WIDTH, ABOUT_HEIGHT,
+            Some(parent),
+            None,
+            Some(instance.into()),
+            Some(data_ptr as *mut c_void),
+        )
+    } {
+        Ok(hwnd) => hwnd,
+        Err(e) => {
+            error!("创建“关于”窗口失败: {}", e);
+            unsafe { let _ = Box::from_raw(data_ptr); };
+            return;
+        }
+    };
+    register_dialog_window(&ABOUT_WINDOW, hwnd);
+
+    if let Ok(close_button) = unsafe { GetDlgItem(Some(hwnd), IDOK) } {
+        unsafe { let _ = SetFocus(Some(close_button)); };
+    }
+}
+
+extern "system" fn about_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_CREATE => {
+            let create_struct = unsafe { &*(lparam.0 as *const CREATESTRUCTW) };
+            let data_ptr = create_struct.lpCreateParams as *mut AboutWindowData;
+            unsafe { SetWindowLongPtrW(hwnd, GWLP_USERDATA, data_ptr as isize) };
+            let data = unsafe { &mut *data_ptr };
+
+            data.dpi = window_dpi(hwnd);
+            data.h_font = create_dialog_font(data.dpi);
+            create_controls(hwnd, data);
+            data.layout = record_layout(hwnd);
+            fit_to_dpi(hwnd, &data.layout, ABOUT_WIDTH, ABOUT_HEIGHT, data.dpi);
+            LRESULT(0)
+        }
+        // IsDialogMessageW 询问默认按钮：按 Enter 时关闭窗口
+        DM_GETDEFID => LRESULT(((DC_HASDEFID as isize) << 16) | IDOK as isize),
+        WM_DPICHANGED => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut AboutWindowData };
+            if data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
+            let data = unsafe { &mut *data_ptr };
+            data.dpi = handle_dpi_changed(hwnd, wparam, lparam, &data.layout, &mut data.h_font);
+            LRESULT(0)
+        }
+        WM_COMMAND => {
+            match (wparam.0 & 0xFFFF) as i32 {
+                IDC_COPY_DIAGNOSTICS => {
+                    let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut AboutWindowData };
+                    if !data_ptr.is_null() {
+                        copy_to_clipboard(hwnd, &unsafe { &*data_ptr }.diagnostics);
+                    }
+                }
+                IDOK | IDCANCEL => unsafe { let _ = DestroyWindow(hwnd); },
+                _ => {}
+            }
+            LRESULT(0)
+        }
+        WM_CLOSE => {
+            unsafe { let _ = DestroyWindow(hwnd); };
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            let data_ptr = unsafe { SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0) as *mut AboutWindowData };
+            if !data_ptr.is_null() {
+                let data = unsafe { Box::from_raw(data_ptr) };
+                delete_dialog_font(data.h_font);
+            }
+            unregister_dialog_window(&ABOUT_WINDOW, hwnd);
+            LRESULT(0)
+        }
+        _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+    }
+}
+
+fn create_controls(parent: HWND, data: &AboutWindowData) {
+    let instance = unsafe { GetModuleHandleW(None).unwrap() };
+    let h_font = data.h_font;
+
+    unsafe {
+        let set_font = |hwnd: HWND| {
+            if !h_font.is_invalid() {
+                SendMessageW(hwnd, WM_SETFONT, Some(WPARAM(h_font.0 as usize)), Some(LPARAM(1)));
+            }
+        };
+
+        let h_title = CreateWindowExW(Default::default(), w!("STATIC"), w!("CO/MP/UT/ER"), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 15, 425, 25, Some(parent), Some(HMENU((IDC_ABOUT_TITLE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_title);
+
+        // 只读的多行编辑框：内容可以用键盘浏览和选取，读屏软件也能逐行朗读
+        let h_details = CreateWindowExW(Default::default(), w!("EDIT"), &HSTRING::from(data.details.as_str()), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_BORDER.0 | WS_VSCROLL.0 | (ES_MULTILINE as u32) | (ES_READONLY as u32) | (ES_AUTOVSCROLL as u32)), 20, 45, 425, 220, Some(parent), Some(HMENU((IDC_ABOUT_DETAILS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_details);
+
+        let h_copy_btn = CreateWindowExW(Default::default(), w!("BUTTON"), &HSTRING::from(data.copy_text.as_str()), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0), 20, 280, 180, 30, Some(parent), Some(HMENU((IDC_COPY_DIAGNOSTICS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_copy_btn);
+
+        let h_close_btn = CreateWindowExW(Default::default(), w!("BUTTON"), &HSTRING::from(data.close_text.as_str()), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | (BS_DEFPUSHBUTTON as u32)), 345, 280, 100, 30, Some(parent), Some(HMENU((IDOK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_close_btn);
+    }
+}
+
+/// 以 CF_UNICODETEXT 格式放到剪贴板，日志查看窗口也使用。成功后剪贴板拥有这块内存，失败时自己释放
+pub(crate) fn copy_to_clipboard(hwnd: HWND, text: &str) {
+    let wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        if OpenClipboard(Some(hwnd)).is_err() {
+            error!("打开剪贴板失败，没有复制。");
+            return;
+        }
+        let _ = EmptyClipboard();
+        let copied = match GlobalAlloc(GMEM_MOVEABLE, wide.len() * std::mem::size_of::<u16>()) {
+            Ok(memory) => {
+                let target = GlobalLock(memory) as *mut u16;
+                if target.is_null() {
+                    let _ = GlobalFree(Some(memory));
+                    false
+                } else {
+                    std::ptr::copy_nonoverlapping(wide.as_ptr(), target, wide.len());
+                    let _ = GlobalUnlock(memory);
+                    match SetClipboardData(CF_UNICODETEXT, Some(HANDLE(memory.0))) {
+                        Ok(_) => true,
+                        Err(_) => {
+                            let _ = GlobalFree(Some(memory));
+                            false
+                        }
+                    }
+                }
+            }
+            Err(_) => false,
+        };
+        let _ = CloseClipboard();
+        if copied {
+            info!("已复制到剪贴板。");
+        } else {
+            error!("复制到剪贴板失败。");
+        }
+    }
+}
@@ -0,0 +1,212 @@
+// src/announce_policy.rs
+
+use log::warn;
+use windows::Win32::System::SystemInformation::GetLocalTime;
+use windows::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE, QUNS_QUIET_TIME, QUNS_RUNNING_D3D_FULL_SCREEN};
+use windows::Win32::UI::WindowsAndMessaging::{SystemParametersInfoW, SPI_GETSCREENREADER, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS};
+
+use crate::config::{QuietHours, QuietMode};
+use crate::tts_engine::SpeechPriority;
+
+/// 当前所有可能让播报暂缓的原因。新的保持安静的理由加在这里，
+/// 它们之间的优先顺序统一在 decide 中决定，而不是分散在各处判断
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Suppressors {
+    /// 用户从托盘菜单暂停了播报
+    pub paused: bool,
+    /// 当前生效的安静时段的模式，不在安静时段时为 None
+    pub quiet_hours: Option<QuietMode>,
+    /// Windows 专注助手 (勿扰时间) 已开启
+    pub focus_assist: bool,
+    /// 正在运行全屏程序、全屏游戏或处于演示模式
+    pub fullscreen: bool,
+    /// 屏幕阅读器正在运行，而用户要求此时保持安静 (mute_with_screen_reader)
+    pub screen_reader_muted: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    Speak,
+    /// 不播报语音，只响与优先级对应的蜂鸣
+    Beep,
+    /// 暂缓播报，等用户有空时再汇总
+    Defer,
+    Suppress(&'static str),
+}
+
+impl SpeechPriority {
+    /// 暂停时也播报的安全相关提醒。其他原因同样不会让它暂缓：
+    /// 安静时段、专注助手、全屏程序和屏幕阅读器都不会
+    pub fn overrides_pause(self) -> bool {
+        self == SpeechPriority::Critical
+    }
+
+    /// 不受静音和仅蜂鸣的安静时段影响的播报 (High 和 Critical)。仅 Critical 的安静时段
+    /// 只放行 Critical；对 High 而言其他原因依然有效
+    pub fn overrides_quiet_hours(self) -> bool {
+        self >= SpeechPriority::High
+    }
+}
+
+/// 决定一条播报是否说出来。
+///
+/// | 优先级       | 暂停 | 安静时段: 静音 | 仅蜂鸣 | 仅 Critical | 屏幕阅读器 | 专注助手 / 全屏 |
+/// |--------------|------|----------------|--------|-------------|------------|-----------------|
+/// | Critical     | 播报 | 播报           | 播报   | 播报        | 播报       | 播报            |
+/// | High         | 丢弃 | 播报           | 播报   | 丢弃        | 丢弃       | 暂缓            |
+/// | Normal / Low | 丢弃 | 丢弃           | 蜂鸣   | 丢弃        | 丢弃       | 暂缓            |
+///
+/// 靠前的列优先：暂停时安静时段中的 High 播报也被丢弃，被安静时段拦下的播报不会暂缓。
+/// 表中安静时段下 High 为“播报”，指的是安静时段不拦它，后面各列依然适用。
+///
+/// `user_requested` 表示用户主动要求的播报 (计时器、状态查询)，总是播报，
+/// 因为这些规则针对的都是用户没有要求的播报
+pub fn decide(priority: SpeechPriority, user_requested: bool, suppressors: &Suppressors) -> Decision {
+    if user_requested || priority.overrides_pause() {
+        return Decision::Speak;
+    }
+    if suppressors.paused {
+        return Decision::Suppress("paused");
+    }
+    match suppressors.quiet_hours {
+        // Critical 在上面已经播报了
+        Some(QuietMode::CriticalOnly) => return Decision::Suppress("quiet hours"),
+        Some(QuietMode::Silence) if !priority.overrides_quiet_hours() => return Decision::Suppress("quiet hours"),
+        Some(QuietMode::BeepOnly) if !priority.overrides_quiet_hours() => return Decision::Beep,
+        _ => {}
+    }
+    if suppressors.screen_reader_muted {
+        return Decision::Suppress("screen reader");
+    }
+    if suppressors.focus_assist || suppressors.fullscreen {
+        return Decision::Defer;
+    }
+    Decision::Speak
+}
+
+/// Windows 报告的用户忙碌状态：(专注助手, 全屏或演示模式)。查询失败时视为不忙，以免丢失播报
+pub fn busy_state() -> (bool, bool) {
+    match unsafe { SHQueryUserNotificationState() } {
+        Ok(state) => (state == QUNS_QUIET_TIME, matches!(state, QUNS_BUSY | QUNS_RUNNING_D3D_FULL_SCREEN | QUNS_PRESENTATION_MODE)),
+        Err(e) => {
+            warn!("查询专注助手状态失败: {}", e);
+            (false, false)
+        }
+    }
+}
+
+/// Windows 当前是否要求程序不要打扰用户 (专注助手、演示模式或全屏程序)
+pub fn is_focus_assist_active() -> bool {
+    let (focus_assist, fullscreen) = busy_state();
+    focus_assist || fullscreen
+}
+
+/// 讲述人、NVDA 等屏幕阅读器是否正在运行
+pub fn is_screen_reader_active() -> bool {
+    let mut active = windows::core::BOOL::default();
+    let queried = unsafe { SystemParametersInfoW(SPI_GETSCREENREADER, 0, Some(&mut active as *mut _ as *mut _), SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0)) };
+    queried.is_ok() && active.as_bool()
+}
+
+/// 当前本地时间是否在安静时段内。时间无效时总是不在
+pub fn is_quiet_now(quiet_hours: &QuietHours) -> bool {
+    let Some((start, end)) = quiet_hours.minutes() else { return false };
+    let now = unsafe { GetLocalTime() };
+    in_window(u32::from(now.wHour) * 60 + u32::from(now.wMinute), start, end)
+}
+
+/// 当前生效的安静时段模式，不在安静时段时为 None
+pub fn active_quiet_mode(quiet_hours: Option<&QuietHours>) -> Option<QuietMode> {
+    quiet_hours.filter(|quiet_hours| is_quiet_now(quiet_hours)).map(|quiet_hours| quiet_hours.mode)
+}
+
+/// `minute` 是否在 [start, end) 之内，都是从午夜起的分钟数。结束早于开始的时段跨过午夜，
+/// 开始等于结束的时段不包含任何时间
+fn in_window(minute: u32, start: u32, end: u32) -> bool {
+    if start <= end {
+        start <= minute && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIORITIES: [SpeechPriority; 4] = [SpeechPriority::Low, SpeechPriority::Normal, SpeechPriority::High, SpeechPriority::Critical];
+
+    fn only(set: impl FnOnce(&mut Suppressors)) -> Suppressors {
+        let mut suppressors = Suppressors::default();
+        set(&mut suppressors);
+        suppressors
+    }
+
+    // 每一行只有一个原因生效，依次是 Low、Normal、High 和 Critical 的预期结果
+    #[test]
+    fn decision_matrix() {
+        use Decision::*;
+        let quiet = Suppress("quiet hours");
+        let rows: [(&str, Suppressors, [Decision; 4]); 8] = [
+            ("nothing", Suppressors::default(), [Speak, Speak, Speak, Speak]),
+            ("paused", only(|s| s.paused = true), [Suppress("paused"), Suppress("paused"), Suppress("paused"), Speak]),
+            ("silence", only(|s| s.quiet_hours = Some(QuietMode::Silence)), [quiet, quiet, Speak, Speak]),
+            ("beep only", only(|s| s.quiet_hours = Some(QuietMode::BeepOnly)), [Beep, Beep, Speak, Speak]),
+            ("critical only", only(|s| s.quiet_hours = Some(QuietMode::CriticalOnly)), [quiet, quiet, quiet, Speak]),
+            ("screen reader", only(|s| s.screen_reader_muted = true), [Suppress("screen reader"), Suppress("screen reader"), Suppress("screen reader"), Speak]),
+            ("focus assist", only(|s| s.focus_assist = true), [Defer, Defer, Defer, Speak]),
+            ("full screen", only(|s| s.fullscreen = true), [Defer, Defer, Defer, Speak]),
+        ];
+        for (name, suppressors, expected) in rows {
+            for (priority, expected) in PRIORITIES.into_iter().zip(expected) {
+                assert_eq!(decide(priority, false, &suppressors), expected, "{} / {:?}", name, priority);
+            }
+        }
+    }
+
+    #[test]
+    fn user_requested_is_always_spoken() {
+        let everything = Suppressors {
+            paused: true,
+            quiet_hours: Some(QuietMode::Silence),
+            focus_assist: true,
+            fullscreen: true,
+            screen_reader_muted: true,
+        };
+        for priority in PRIORITIES {
+            assert_eq!(decide(priority, true, &everything), Decision::Speak, "{:?}", priority);
+        }
+    }
+
+    #[test]
+    fn earlier_suppressors_take_precedence() {
+        let paused_at_night = Suppressors { paused: true, quiet_hours: Some(QuietMode::BeepOnly), ..Default::default() };
+        assert_eq!(decide(SpeechPriority::Normal, false, &paused_at_night), Decision::Suppress("paused"));
+        assert_eq!(decide(SpeechPriority::High, false, &paused_at_night), Decision::Suppress("paused"));
+
+        // 被安静时段丢弃的播报不会暂缓到之后汇总
+        let quiet_and_busy = Suppressors { quiet_hours: Some(QuietMode::Silence), focus_assist: true, ..Default::default() };
+        assert_eq!(decide(SpeechPriority::Normal, false, &quiet_and_busy), Decision::Suppress("quiet hours"));
+        // High 不受安静时段影响，但仍要等专注助手结束
+        assert_eq!(decide(SpeechPriority::High, false, &quiet_and_busy), Decision::Defer);
+
+        let everything = Suppressors {
+            paused: true,
+            quiet_hours: Some(QuietMode::Silence),
+            focus_assist: true,
+            fullscreen: true,
+            screen_reader_muted: true,
+        };
+        assert_eq!(decide(SpeechPriority::Critical, false, &everything), Decision::Speak);
+    }
+
+    #[test]
+    fn quiet_hours_window_crosses_midnight() {
+        let (start, end) = (22 * 60 + 30, 7 * 60);
+        assert!(in_window(23 * 60, start, end));
+        assert!(in_window(0, start, end));
+        assert!(!in_window(7 * 60, start, end));
+        assert!(!in_window(12 * 60, start, end));
+        assert!(!in_window(12 * 60, 8 * 60, 8 * 60));
+    }
+}
@@ -0,0 +1,158 @@
+// src/audio_ducking.rs
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use windows::core::Interface;
+use windows::Win32::Media::Audio::{
+    eConsole, eRender, IAudioSessionControl2, IAudioSessionManager2, IMMDeviceEnumerator, ISimpleAudioVolume, MMDeviceEnumerator,
+};
+use windows::Win32::System::Com::{CoCreateInstance, CoTaskMemFree, CLSCTX_ALL};
+use windows::Win32::System::Threading::GetCurrentProcessId;
+
+// The original volumes are written here before anything is lowered, so that a crash
+// mid-utterance can be undone the next time the app starts.
+fn state_path() -> PathBuf {
+    crate::config::data_dir().join("ducking_state.json")
+}
+
+/// A session's volume as written to the state file. Older versions stored only the original.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedVolume {
+    Levels { original: f32, ducked: f32 },
+    Original(f32),
+}
+
+struct DuckedSession {
+    volume: ISimpleAudioVolume,
+    original: f32,
+    ducked: f32,
+}
+
+/// Temporarily lowers the volume of other applications' audio sessions while we speak.
+/// Must be used from a single thread with COM initialized.
+#[derive(Default)]
+pub struct AudioDucker {
+    // Keyed by session instance identifier, which stays the same for the life of the session.
+    sessions: HashMap<String, DuckedSession>,
+    since: Option<Instant>,
+}
+
+impl AudioDucker {
+    /// How long other applications have been ducked, `None` when nothing is lowered.
+    pub fn ducked_for(&self) -> Option<std::time::Duration> {
+        self.since.map(|since| since.elapsed())
+    }
+
+    /// Lowers every other session on the default output device by `percent`.
+    /// Does nothing while already ducked, so back-to-back utterances stay at the same level.
+    pub fn duck(&mut self, percent: u32) {
+        if self.since.is_some() { return; }
+        let factor = 1.0 - percent.min(100) as f32 / 100.0;
+        let sessions = match other_render_sessions() {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                warn!("枚举音频会话失败，无法降低其他程序的音量: {}", e);
+                return;
+            }
+        };
+        for (id, volume) in sessions {
+            let Ok(original) = (unsafe { volume.GetMasterVolume() }) else { continue };
+            self.sessions.insert(id, DuckedSession { volume, original, ducked: original * factor });
+        }
+        self.since = Some(Instant::now());
+        self.save_state();
+        for session in self.sessions.values() {
+            unsafe { session.volume.SetMasterVolume(session.ducked, std::ptr::null()).ok() };
+        }
+        info!("已降低 {} 个其他程序的音量。", self.sessions.len());
+    }
+
+    /// Puts back the saved volumes. Sessions that have closed in the meantime are skipped,
+    /// and a session whose volume the user changed while ducked is left as the user set it.
+    pub fn restore(&mut self) {
+        if self.since.take().is_none() { return; }
+        for (_, session) in self.sessions.drain() {
+            match unsafe { session.volume.GetMasterVolume() } {
+                Ok(current) if (current - session.ducked).abs() < 0.01 => {
+                    unsafe { session.volume.SetMasterVolume(session.original, std::ptr::null()).ok() };
+                }
+                _ => {}
+            }
+        }
+        std::fs::remove_file(state_path()).ok();
+        info!("已恢复其他程序的音量。");
+    }
+
+    fn save_state(&self) {
+        let saved: HashMap<&str, SavedVolume> = self.sessions.iter()
+            .map(|(id, s)| (id.as_str(), SavedVolume::Levels { original: s.original, ducked: s.ducked }))
+            .collect();
+        let result = serde_json::to_string(&saved).map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(state_path(), json));
+        if let Err(e) = result {
+            warn!("保存降低音量前的原始音量失败: {}", e);
+        }
+    }
+}
+
+impl Drop for AudioDucker {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Restores volumes left lowered by a previous run that exited while speaking. As in
+/// `restore`, a session whose volume was changed since is left as it is.
+/// Must be called from a thread with COM initialized.
+pub fn restore_after_crash() {
+    let Ok(json) = std::fs::read_to_string(state_path()) else { return };
+    let saved: HashMap<String, SavedVolume> = serde_json::from_str(&json).unwrap_or_default();
+    let mut restored = 0;
+    if let Ok(sessions) = other_render_sessions() {
+        for (id, volume) in sessions {
+            let original = match saved.get(&id) {
+                Some(SavedVolume::Levels { original, ducked }) => {
+                    let unchanged = unsafe { volume.GetMasterVolume() }.is_ok_and(|current| (current - ducked).abs() < 0.01);
+                    if !unchanged { continue; }
+                    *original
+                }
+                Some(SavedVolume::Original(original)) => *original,
+                None => continue,
+            };
+            if unsafe { volume.SetMasterVolume(original, std::ptr::null()) }.is_ok() {
+                restored += 1;
+            }
+        }
+    }
+    std::fs::remove_file(state_path()).ok();
+    info!("上次运行退出时其他程序的音量仍处于降低状态，已恢复 {} 个。", restored);
+}
+
+/// Audio sessions on the default output device that belong to other processes.
+fn other_render_sessions() -> windows::core::Result<Vec<(String, ISimpleAudioVolume)>> {
+    let own_pid = unsafe { GetCurrentProcessId() };
+    let mut result = Vec::new();
+    unsafe {
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?;
+        let manager = device.Activate::<IAudioSessionManager2>(CLSCTX_ALL, None)?;
+        let sessions = manager.GetSessionEnumerator()?;
+        for i in 0..sessions.GetCount()? {
+            let Ok(control) = sessions.GetSession(i).and_then(|s| s.cast::<IAudioSessionControl2>()) else { continue };
+            // Process ID 0 is the system sounds session.
+            let pid = control.GetProcessId().unwrap_or(0);
+            if pid == 0 || pid == own_pid { continue; }
+            let Ok(id_ptr) = control.GetSessionInstanceIdentifier() else { continue };
+            let id = id_ptr.to_string().unwrap_or_default();
+            CoTaskMemFree(Some(id_ptr.0 as *const _));
+            if let Ok(volume) = control.cast::<ISimpleAudioVolume>() {
+                result.push((id, volume));
+            }
+        }
+    }
+    Ok(result)
+}
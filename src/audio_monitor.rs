@@ -0,0 +1,171 @@
+// src/audio_monitor.rs
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use log::{info, warn, error};
+use windows::core::{implement, Interface, PCWSTR, PWSTR};
+use windows::Win32::Foundation::PROPERTYKEY;
+use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+use windows::Win32::Media::Audio::{
+    eConsole, eRender, EDataFlow, ERole, Headphones, Headset, IMMDevice, IMMDeviceEnumerator, IMMEndpoint,
+    IMMNotificationClient, IMMNotificationClient_Impl, MMDeviceEnumerator, DEVICE_STATE, DEVICE_STATE_ACTIVE,
+    PKEY_AudioEndpoint_FormFactor,
+};
+use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ};
+use windows::Win32::System::Com::StructuredStorage::{PropVariantClear, PropVariantToStringAlloc, PropVariantToUInt32};
+use windows::Win32::Foundation::HWND;
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+// Windows usually switches the default render device within a second or two of a
+// headset appearing; anything slower means it is not going to switch at all.
+const HEADPHONE_ROUTING_GRACE: Duration = Duration::from_secs(3);
+
+/// Endpoint changes forwarded from the COM callback to the monitor thread.
+/// The callbacks must return quickly, so all device queries happen on the monitor thread.
+enum EndpointNotification {
+    DeviceActivated(String),
+    DefaultRenderChanged(String),
+}
+
+#[implement(IMMNotificationClient)]
+struct EndpointNotificationClient {
+    notifications: mpsc::Sender<EndpointNotification>,
+}
+
+impl IMMNotificationClient_Impl for EndpointNotificationClient_Impl {
+    fn OnDeviceStateChanged(&self, device_id: &PCWSTR, new_state: DEVICE_STATE) -> windows::core::Result<()> {
+        if new_state == DEVICE_STATE_ACTIVE {
+            let id = unsafe { device_id.to_string() }.unwrap_or_default();
+            self.notifications.send(EndpointNotification::DeviceActivated(id)).ok();
+        }
+        Ok(())
+    }
+
+    fn OnDeviceAdded(&self, device_id: &PCWSTR) -> windows::core::Result<()> {
+        let id = unsafe { device_id.to_string() }.unwrap_or_default();
+        self.notifications.send(EndpointNotification::DeviceActivated(id)).ok();
+        Ok(())
+    }
+
+    fn OnDeviceRemoved(&self, _device_id: &PCWSTR) -> windows::core::Result<()> {
+        Ok(())
+    }
+
+    fn OnDefaultDeviceChanged(&self, flow: EDataFlow, role: ERole, default_device_id: &PCWSTR) -> windows::core::Result<()> {
+        if flow == eRender && role == eConsole && !default_device_id.is_null() {
+            let id = unsafe { default_device_id.to_string() }.unwrap_or_default();
+            self.notifications.send(EndpointNotification::DefaultRenderChanged(id)).ok();
+        }
+        Ok(())
+    }
+
+    fn OnPropertyValueChanged(&self, _device_id: &PCWSTR, _key: &PROPERTYKEY) -> windows::core::Result<()> {
+        Ok(())
+    }
+}
+
+/// Starts the audio endpoint watcher on its own MTA thread. Default device changes are always
+/// reported so the speech engine can be rebuilt; `warn_headphones` enables the
+/// headphones-not-default warning.
+pub fn start_audio_monitor(sender: mpsc::Sender<SystemEvent>, hwnd: HWND, warn_headphones: bool) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("音频监控线程 COM 初始化失败。");
+            return;
+        }
+        if let Err(e) = run_audio_monitor(sender, hwnd_value, warn_headphones) {
+            error!("音频端点监控启动失败: {}", e);
+        }
+    });
+}
+
+fn run_audio_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize, warn_headphones: bool) -> windows::core::Result<()> {
+    let enumerator: IMMDeviceEnumerator = unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+    let (notification_tx, notification_rx) = mpsc::channel();
+    let client: IMMNotificationClient = EndpointNotificationClient { notifications: notification_tx }.into();
+    unsafe { enumerator.RegisterEndpointNotificationCallback(&client)? };
+    info!("音频端点监控已启动。");
+
+    // The headphone endpoint we are waiting on, and when to give up waiting for Windows to switch to it.
+    let mut pending_headphones: Option<(String, Instant)> = None;
+    loop {
+        let timeout = pending_headphones.as_ref()
+            .map(|(_, deadline)| deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::from_secs(3600));
+
+        match notification_rx.recv_timeout(timeout) {
+            Ok(EndpointNotification::DeviceActivated(id)) => {
+                if warn_headphones && is_headphone_render_endpoint(&enumerator, &id) {
+                    info!("检测到耳机类音频端点接入: {}", id);
+                    pending_headphones = Some((id, Instant::now() + HEADPHONE_ROUTING_GRACE));
+                }
+            }
+            Ok(EndpointNotification::DefaultRenderChanged(id)) => {
+                if pending_headphones.as_ref().is_some_and(|(pending_id, _)| *pending_id == id) {
+                    pending_headphones = None;
+                }
+                send_event(&sender, hwnd_value, SystemEvent::DefaultAudioDeviceChanged);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let Some((headphone_id, _)) = pending_headphones.take() else { continue };
+                let default_device = unsafe { enumerator.GetDefaultAudioEndpoint(eRender, eConsole) };
+                let Ok(default_device) = default_device else { continue };
+                if device_id(&default_device).as_deref() != Some(headphone_id.as_str()) {
+                    let current_device = friendly_name(&default_device).unwrap_or_default();
+                    warn!("耳机已接入，但默认音频输出仍为: {}", current_device);
+                    send_event(&sender, hwnd_value, SystemEvent::HeadphonesNotDefault { current_device });
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    unsafe { enumerator.UnregisterEndpointNotificationCallback(&client).ok() };
+    Ok(())
+}
+
+fn is_headphone_render_endpoint(enumerator: &IMMDeviceEnumerator, id: &str) -> bool {
+    let wide_id = windows::core::HSTRING::from(id);
+    let Ok(device) = (unsafe { enumerator.GetDevice(&wide_id) }) else { return false };
+    let is_render = device.cast::<IMMEndpoint>()
+        .and_then(|endpoint| unsafe { endpoint.GetDataFlow() })
+        .is_ok_and(|flow| flow == eRender);
+    if !is_render { return false; }
+
+    let form_factor = read_u32_property(&device, &PKEY_AudioEndpoint_FormFactor);
+    form_factor == Some(Headphones.0 as u32) || form_factor == Some(Headset.0 as u32)
+}
+
+pub(crate) fn device_id(device: &IMMDevice) -> Option<String> {
+    unsafe {
+        let id: PWSTR = device.GetId().ok()?;
+        let result = id.to_string().ok();
+        CoTaskMemFree(Some(id.0 as *const _));
+        result
+    }
+}
+
+pub(crate) fn friendly_name(device: &IMMDevice) -> Option<String> {
+    unsafe {
+        let store = device.OpenPropertyStore(STGM_READ).ok()?;
+        let mut value = store.GetValue(&PKEY_Device_FriendlyName).ok()?;
+        let text = PropVariantToStringAlloc(&value).ok();
+        PropVariantClear(&mut value).ok();
+        let text = text?;
+        let result = text.to_string().ok();
+        CoTaskMemFree(Some(text.0 as *const _));
+        result
+    }
+}
+
+fn read_u32_property(device: &IMMDevice, key: &PROPERTYKEY) -> Option<u32> {
+    unsafe {
+        let store = device.OpenPropertyStore(STGM_READ).ok()?;
+        let mut value = store.GetValue(key).ok()?;
+        let result = PropVariantToUInt32(&value).ok();
+        PropVariantClear(&mut value).ok();
+        result
+    }
+}
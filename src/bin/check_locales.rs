@@ -0,0 +1,95 @@
+// src/bin/check_locales.rs
+// 把翻译与英文语言档案比较，列出缺少的条目、多余的条目和参数不同的条目。提交翻译前运行:
+//   check_locales                    检查 locales 文件夹中的所有语言档案
+//   check_locales locales/ja.json    只检查指定的档案 (与同一文件夹中的 en.json 比较)
+// 没有问题时返回 0，有问题时返回 1，无法读取英文语言档案或参数有误时返回 2。
+
+#[path = "../locale_check.rs"]
+mod locale_check;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use serde_json::{Map, Value};
+
+const USAGE: &str = "用法: check_locales [语言档案或文件夹...]";
+const REFERENCE_FILE: &str = "en.json";
+
+fn read_object(path: &Path) -> Result<Map<String, Value>, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("无法读取: {}", e))?;
+    match serde_json::from_str::<Value>(&data) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err("内容不是 JSON 对象".to_string()),
+        Err(e) => Err(format!("不是有效的 JSON: {}", e)),
+    }
+}
+
+// 文件夹中除英文以外的语言档案，按文件名排序。带点的文件名 (例如 zh.custom.json) 不是语言档案
+fn locale_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: 无法读取文件夹: {}", dir.display(), e))?;
+    let mut files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name != REFERENCE_FILE && name.strip_suffix(".json").is_some_and(|code| !code.is_empty() && !code.contains('.'))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn parse_args() -> Result<Vec<PathBuf>, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(flag) = args.iter().find(|arg| arg.starts_with("--")) {
+        return Err(format!("未知参数: {}", flag));
+    }
+    let targets = if args.is_empty() { vec![PathBuf::from("locales")] } else { args.into_iter().map(PathBuf::from).collect() };
+    let mut files = Vec::new();
+    for target in targets {
+        if target.is_dir() {
+            files.extend(locale_files(&target)?);
+        } else {
+            files.push(target);
+        }
+    }
+    Ok(files)
+}
+
+fn main() -> ExitCode {
+    let files = match parse_args() {
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let mut failed = 0;
+    for file in &files {
+        let reference_path = file.parent().unwrap_or(Path::new("")).join(REFERENCE_FILE);
+        let reference = match read_object(&reference_path) {
+            Ok(reference) => reference,
+            Err(message) => {
+                eprintln!("{}: {}", reference_path.display(), message);
+                return ExitCode::from(2);
+            }
+        };
+        let problems = match read_object(file) {
+            Ok(translation) => locale_check::compare(&reference, &translation).iter().map(ToString::to_string).collect(),
+            Err(message) => vec![message],
+        };
+        for problem in &problems {
+            println!("{}: {}", file.display(), problem);
+        }
+        if problems.is_empty() {
+            println!("{}: 与 {} 一致", file.display(), reference_path.display());
+        } else {
+            println!("{}: {} 处问题", file.display(), problems.len());
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        println!("{} 个语言档案中有 {} 个有问题。", files.len(), failed);
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
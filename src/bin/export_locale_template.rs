@@ -0,0 +1,125 @@
+// src/bin/export_locale_template.rs
+// 生成翻译新语言用的模板：英文语言档案中的每个条目都是空的，由翻译者填写，前面附上英文原文
+// ("_en_<条目>") 和必须保留的参数 ("_params_<条目>")。空的条目在程序中使用英文。
+//   export_locale_template pt.json                        生成完整的模板
+//   export_locale_template --existing locales/pt.json     只列出已有翻译中缺少的条目，输出到屏幕；
+//                                                         已有翻译的其他问题 (与 check_locales 相同) 另外列出
+// 成功时返回 0，参数有误或无法读写文件时返回 2。
+
+#[path = "../locale_check.rs"]
+mod locale_check;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use serde_json::{Map, Value};
+
+const USAGE: &str = "用法: export_locale_template [--existing <已有的语言档案>] [输出文件]";
+const REFERENCE: &str = include_str!("../../locales/en.json");
+const LANGUAGE_NAME_KEY: &str = "_language_name";
+const NOTE: &str = "Fill in the empty entries. \"_en_\" entries show the English text and \"_params_\" entries the {placeholders} to keep; both may be deleted. Empty entries are spoken in English.";
+
+struct Args {
+    existing: Option<PathBuf>,
+    output: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args { existing: None, output: None };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--existing" => parsed.existing = Some(PathBuf::from(args.next().ok_or("--existing 缺少参数值")?)),
+            _ if arg.starts_with("--") => return Err(format!("未知参数: {}", arg)),
+            _ if parsed.output.is_none() => parsed.output = Some(PathBuf::from(arg)),
+            _ => return Err(format!("多余的参数: {}", arg)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn read_object(data: &str) -> Result<Map<String, Value>, String> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err("内容不是 JSON 对象".to_string()),
+        Err(e) => Err(format!("不是有效的 JSON: {}", e)),
+    }
+}
+
+// 一个条目的空白版本：复数条目保留英文的各个形式，由翻译者按该语言的规则增减
+fn blank(value: &Value) -> Value {
+    match value {
+        Value::Object(forms) => Value::Object(forms.keys().map(|form| (form.clone(), Value::String(String::new()))).collect()),
+        _ => Value::String(String::new()),
+    }
+}
+
+// 按条目名称排序写出，同一条目的说明与条目相邻。serde_json 的对象不保留顺序，所以逐行写出
+fn render(keys: &[String], reference: &Map<String, Value>, language_name: Option<&str>) -> String {
+    let json = |value: &Value| serde_json::to_string(value).unwrap_or_default();
+    let mut entries = vec![format!("\"_note\": {}", json(&Value::String(NOTE.to_string())))];
+    if let Some(name) = language_name {
+        entries.push(format!("\"{}\": {}", LANGUAGE_NAME_KEY, json(&Value::String(name.to_string()))));
+    }
+    for key in keys {
+        let Some(english) = reference.get(key) else { continue };
+        let mut lines = vec![format!("\"_en_{}\": {}", key, json(english))];
+        let params = locale_check::entry_placeholders(english).unwrap_or_default();
+        if !params.is_empty() {
+            let listed: Vec<String> = params.iter().map(|name| format!("{{{}}}", name)).collect();
+            lines.push(format!("\"_params_{}\": {}", key, json(&Value::String(listed.join(" ")))));
+        }
+        lines.push(format!("\"{}\": {}", key, json(&blank(english))));
+        entries.push(lines.join(",\n    "));
+    }
+    format!("{{\n    {}\n}}\n", entries.join(",\n\n    "))
+}
+
+fn run(args: Args) -> Result<usize, String> {
+    let reference = read_object(REFERENCE).map_err(|e| format!("内置的英文语言档案: {}", e))?;
+    let (keys, language_name) = match &args.existing {
+        Some(path) => {
+            let data = fs::read_to_string(path).map_err(|e| format!("{}: 无法读取: {}", path.display(), e))?;
+            let existing = read_object(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+            for problem in locale_check::compare(&reference, &existing) {
+                if !matches!(problem, locale_check::Problem::MissingKey(_)) {
+                    eprintln!("{}: {}", path.display(), problem);
+                }
+            }
+            let name = (!existing.contains_key(LANGUAGE_NAME_KEY)).then_some("");
+            (locale_check::missing_keys(&reference, &existing), name)
+        }
+        None => (locale_check::missing_keys(&reference, &Map::new()), Some("")),
+    };
+    let text = render(&keys, &reference, language_name);
+    match &args.output {
+        Some(path) => fs::write(path, text).map_err(|e| format!("{}: 无法写入: {}", path.display(), e))?,
+        None => print!("{}", text),
+    }
+    Ok(keys.len())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let output = args.output.clone();
+    match run(args) {
+        Ok(count) => {
+            if let Some(path) = output {
+                println!("已写入 {}，共 {} 个待翻译的条目。", path.display(), count);
+            } else {
+                eprintln!("共 {} 个待翻译的条目。", count);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -12,8 +12,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     for voice in voices {
         println!("  名称: {}", voice.name());
         println!("  语言: {}", voice.language());
+        if let Some(gender) = voice.gender() {
+            println!("  性别: {:?}", gender);
+        }
+        // config.json 中的 custom_voice 填写这个 id
+        println!("  ID:   {}", voice.id());
         println!("---------------------------------------------");
     }
 
     Ok(())
-}
\ No newline at end of file
+}
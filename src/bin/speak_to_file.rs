@@ -0,0 +1,65 @@
+// src/bin/speak_to_file.rs
+// 把一段文字合成为 WAV 文件，例如用来制作自己的提示音:
+//   speak_to_file --text "Battery low" --voice "Microsoft Zira" --rate 1.2 battery.wav
+
+#[path = "../wav_export.rs"]
+mod wav_export;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+use wav_export::WavOptions;
+
+const USAGE: &str = "用法: speak_to_file --text <文字> [--voice <语音名称或 ID>] [--rate <0.5-6.0>] <输出文件.wav>";
+
+fn parse_args() -> Result<(String, WavOptions, PathBuf), String> {
+    let mut text = None;
+    let mut options = WavOptions::default();
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--text" => text = Some(args.next().ok_or("--text 缺少参数值")?),
+            "--voice" => options.voice = Some(args.next().ok_or("--voice 缺少参数值")?),
+            "--rate" => {
+                let value = args.next().ok_or("--rate 缺少参数值")?;
+                let rate = value.parse::<f64>().map_err(|_| format!("无效的语速: {}", value))?;
+                if !(0.5..=6.0).contains(&rate) {
+                    return Err(format!("语速 {} 超出范围 (0.5 到 6.0)", rate));
+                }
+                options.rate = Some(rate);
+            }
+            _ if arg.starts_with("--") => return Err(format!("未知参数: {}", arg)),
+            _ if output.is_none() => output = Some(PathBuf::from(arg)),
+            _ => return Err(format!("多余的参数: {}", arg)),
+        }
+    }
+    let text = text.ok_or("缺少 --text")?;
+    let output = output.ok_or("缺少输出文件路径")?;
+    options.ssml = text.trim_start().starts_with("<speak");
+    Ok((text, options, output))
+}
+
+fn main() -> ExitCode {
+    let (text, options, output) = match parse_args() {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if let Err(e) = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.ok() {
+        eprintln!("COM 初始化失败: {}", e);
+        return ExitCode::FAILURE;
+    }
+    match wav_export::synthesize_to_file(&text, &options, &output) {
+        Ok(()) => {
+            println!("已写入 {}", output.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// src/break_reminder.rs
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use log::{info, error};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+use windows::Win32::System::SystemInformation::GetTickCount;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+use crate::mic_monitor::is_microphone_in_use;
+use crate::config::Config;
+use crate::event_monitor::{send_event, SystemEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Starts the break reminder thread. Does nothing unless enabled in the config.
+pub fn start_break_reminder(config: &Config, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    if !config.break_reminder_enabled { return; }
+    let work_minutes = config.break_reminder_work_minutes.max(1);
+    let work_duration = Duration::from_secs(u64::from(work_minutes) * 60);
+    let idle_gap = Duration::from_secs(u64::from(config.break_reminder_idle_gap_minutes.max(1)) * 60);
+    let hwnd_value = hwnd.0 as isize;
+
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("休息提醒线程 COM 初始化失败。");
+            return;
+        }
+        info!("休息提醒已启动: 连续活动 {} 分钟后提醒。", work_minutes);
+
+        // Start of the current stretch of continuous activity.
+        let mut active_since: Option<Instant> = None;
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            // An idle gap longer than the threshold counts as a break and resets the accumulator.
+            if idle_time() >= idle_gap {
+                active_since = None;
+                continue;
+            }
+            let started = *active_since.get_or_insert_with(Instant::now);
+            if started.elapsed() < work_duration { continue; }
+
+            // Don't interrupt a call; the nudge is deferred until the microphone is released.
+            if is_microphone_in_use() { continue; }
+
+            send_event(&sender, hwnd_value, SystemEvent::BreakReminder { minutes: work_minutes });
+            active_since = Some(Instant::now());
+        }
+    });
+}
+
+fn idle_time() -> Duration {
+    let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+    if !unsafe { GetLastInputInfo(&mut info) }.as_bool() {
+        return Duration::ZERO;
+    }
+    // Both are 32-bit tick counts; wrapping_sub keeps this correct across the 49-day rollover.
+    let idle_ms = unsafe { GetTickCount() }.wrapping_sub(info.dwTime);
+    Duration::from_millis(u64::from(idle_ms))
+}
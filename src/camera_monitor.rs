@@ -0,0 +1,93 @@
+// src/camera_monitor.rs
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::time::Duration;
+use log::info;
+use winreg::enums::{HKEY_CURRENT_USER, KEY_READ};
+use winreg::RegKey;
+use windows::Win32::Foundation::HWND;
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+const CONSENT_STORE_WEBCAM: &str = r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\webcam";
+// A change has to survive a second look this long after it was first seen,
+// so an app that briefly reopens the camera is announced only once.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Starts polling the camera consent store. Windows records a LastUsedTimeStop of 0
+/// for every app that currently holds the camera.
+pub fn start_camera_monitor(poll_interval_secs: u64, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let poll_interval = Duration::from_secs(poll_interval_secs.max(1));
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        info!("摄像头使用监控已启动，每 {} 秒检查一次。", poll_interval.as_secs());
+        // Apps already using the camera when we start are not news.
+        let mut announced = apps_using_camera();
+        loop {
+            std::thread::sleep(poll_interval);
+            let first_look = apps_using_camera();
+            if first_look == announced { continue; }
+
+            std::thread::sleep(COALESCE_WINDOW);
+            let second_look = apps_using_camera();
+            // Only apps whose state agreed in both looks count as changed.
+            let current: HashSet<String> = announced.iter()
+                .filter(|app| first_look.contains(*app) || second_look.contains(*app))
+                .chain(first_look.intersection(&second_look))
+                .cloned()
+                .collect();
+
+            let mut started: Vec<&String> = current.difference(&announced).collect();
+            started.sort();
+            for app in started {
+                info!("摄像头被 {} 占用。", app);
+                send_event(&sender, hwnd_value, SystemEvent::CameraInUse { app: app.clone() });
+            }
+            if current.is_empty() && !announced.is_empty() {
+                info!("摄像头已释放。");
+                send_event(&sender, hwnd_value, SystemEvent::CameraReleased);
+            }
+            announced = current;
+        }
+    });
+}
+
+fn apps_using_camera() -> HashSet<String> {
+    let mut apps = HashSet::new();
+    let Ok(store) = RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(CONSENT_STORE_WEBCAM, KEY_READ) else { return apps };
+
+    // Packaged apps are listed directly by package family name; desktop apps live under
+    // NonPackaged with the executable path as the key name ('\' replaced by '#').
+    for name in store.enum_keys().flatten() {
+        if name == "NonPackaged" {
+            let Ok(non_packaged) = store.open_subkey_with_flags(&name, KEY_READ) else { continue };
+            for exe_key in non_packaged.enum_keys().flatten() {
+                if is_in_use(&non_packaged, &exe_key) {
+                    apps.insert(desktop_app_name(&exe_key));
+                }
+            }
+        } else if is_in_use(&store, &name) {
+            apps.insert(packaged_app_name(&name));
+        }
+    }
+    apps
+}
+
+fn is_in_use(parent: &RegKey, name: &str) -> bool {
+    parent.open_subkey_with_flags(name, KEY_READ)
+        .and_then(|key| key.get_value::<u64, _>("LastUsedTimeStop"))
+        .is_ok_and(|stop| stop == 0)
+}
+
+/// "C:#Program Files#Zoom#bin#Zoom.exe" -> "Zoom"
+fn desktop_app_name(key_name: &str) -> String {
+    let file = key_name.rsplit('#').next().unwrap_or(key_name);
+    file.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(file).to_string()
+}
+
+/// "Microsoft.WindowsCamera_8wekyb3d8bbwe" -> "WindowsCamera"
+fn packaged_app_name(family_name: &str) -> String {
+    let name = family_name.split('_').next().unwrap_or(family_name);
+    name.rsplit('.').next().unwrap_or(name).to_string()
+}
@@ -0,0 +1,82 @@
+// src/clock_monitor.rs
+
+use std::time::{Instant, SystemTime};
+use log::info;
+use winreg::enums::HKEY_LOCAL_MACHINE;
+use winreg::RegKey;
+use windows::Win32::System::Time::{GetDynamicTimeZoneInformation, DYNAMIC_TIME_ZONE_INFORMATION};
+
+use crate::event_monitor::SystemEvent;
+
+// NTP corrections and small manual tweaks are not worth announcing.
+const MIN_ANNOUNCED_JUMP_SECS: i64 = 2 * 60;
+
+/// Remembers the time zone and the wall clock against the monotonic clock,
+/// so a WM_TIMECHANGE can be classified as a time zone change or a clock jump.
+pub struct ClockObserver {
+    time_zone_key: String,
+    wall_at_baseline: SystemTime,
+    instant_at_baseline: Instant,
+}
+
+impl ClockObserver {
+    pub fn new() -> Self {
+        ClockObserver {
+            time_zone_key: current_time_zone().0,
+            wall_at_baseline: SystemTime::now(),
+            instant_at_baseline: Instant::now(),
+        }
+    }
+
+    /// The monotonic clock is not trustworthy across sleep; call this after resuming.
+    pub fn rebaseline(&mut self) {
+        self.wall_at_baseline = SystemTime::now();
+        self.instant_at_baseline = Instant::now();
+    }
+
+    /// Called on WM_TIMECHANGE. Returns the events worth announcing, if any.
+    pub fn on_time_change(&mut self) -> Vec<SystemEvent> {
+        let mut events = Vec::new();
+
+        let (key, display_name) = current_time_zone();
+        if key != self.time_zone_key {
+            info!("时区已从 {} 变更为 {}", self.time_zone_key, key);
+            self.time_zone_key = key;
+            events.push(SystemEvent::TimeZoneChanged { name: display_name });
+        }
+
+        let expected = self.wall_at_baseline + self.instant_at_baseline.elapsed();
+        let now = SystemTime::now();
+        let delta_secs = match now.duration_since(expected) {
+            Ok(ahead) => ahead.as_secs() as i64,
+            Err(behind) => -(behind.duration().as_secs() as i64),
+        };
+        if delta_secs.abs() >= MIN_ANNOUNCED_JUMP_SECS {
+            info!("系统时钟跳变 {} 秒", delta_secs);
+            events.push(SystemEvent::ClockChanged { delta_minutes: (delta_secs as f64 / 60.0).round() as i64 });
+        }
+        self.rebaseline();
+
+        events
+    }
+}
+
+/// Returns the registry key name of the current time zone and its user-facing name,
+/// e.g. "(UTC+09:00) Osaka, Sapporo, Tokyo". Falls back to the standard name.
+fn current_time_zone() -> (String, String) {
+    let mut info = DYNAMIC_TIME_ZONE_INFORMATION::default();
+    unsafe { GetDynamicTimeZoneInformation(&mut info) };
+    let key = wide_to_string(&info.TimeZoneKeyName);
+    let standard_name = wide_to_string(&info.StandardName);
+
+    let display_name = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey(format!(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Time Zones\{}", key))
+        .and_then(|zone| zone.get_value::<String, _>("Display"))
+        .unwrap_or(standard_name);
+    (key, display_name)
+}
+
+fn wide_to_string(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
@@ -0,0 +1,131 @@
+// src/companion.rs
+//
+// 伴侣模式：把播报文本以单行 JSON 的形式通过 TCP 转发给另一台运行本程序的机器，
+// 或者以 --listen 模式接收这些文本并在本机播报。仅面向局域网，不加密，
+// 但可以通过共享令牌 (companion_token) 拒绝来历不明的连接。
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use log::{info, warn, error};
+use serde::{Deserialize, Serialize};
+use windows::Win32::Foundation::HWND;
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize)]
+struct CompanionMessage {
+    text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+/// 转发器句柄。实际的网络连接在后台线程中维护，forward() 永远不会阻塞调用者。
+pub struct Forwarder {
+    queue: mpsc::Sender<String>,
+}
+
+impl Forwarder {
+    pub fn start(target: String, token: Option<String>) -> Self {
+        let (queue, receiver) = mpsc::channel::<String>();
+        std::thread::spawn(move || forward_loop(target, token, receiver));
+        Forwarder { queue }
+    }
+
+    pub fn forward(&self, text: &str) {
+        self.queue.send(text.to_string()).ok();
+    }
+}
+
+fn forward_loop(target: String, token: Option<String>, receiver: mpsc::Receiver<String>) {
+    let mut stream: Option<TcpStream> = None;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut next_attempt = Instant::now();
+
+    for text in receiver {
+        if stream.is_none() {
+            // 处于退避期间的播报直接丢弃：过时的提示比没有提示更让人困惑
+            if Instant::now() < next_attempt { continue; }
+            match connect(&target) {
+                Ok(s) => {
+                    info!("伴侣模式: 已连接到 {}", target);
+                    stream = Some(s);
+                    backoff = INITIAL_BACKOFF;
+                }
+                Err(e) => {
+                    warn!("伴侣模式: 连接 {} 失败: {}，{} 秒后重试。", target, e, backoff.as_secs());
+                    next_attempt = Instant::now() + backoff;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            }
+        }
+
+        let message = CompanionMessage { text, token: token.clone() };
+        let mut line = match serde_json::to_string(&message) {
+            Ok(line) => line,
+            Err(e) => { error!("伴侣模式: 序列化播报失败: {}", e); continue; }
+        };
+        line.push('\n');
+
+        if let Some(s) = stream.as_mut() {
+            if let Err(e) = s.write_all(line.as_bytes()).and_then(|_| s.flush()) {
+                warn!("伴侣模式: 发送失败，连接已断开: {}", e);
+                stream = None;
+                next_attempt = Instant::now() + backoff;
+            }
+        }
+    }
+}
+
+fn connect(target: &str) -> std::io::Result<TcpStream> {
+    use std::net::ToSocketAddrs;
+    let mut last_error = std::io::Error::new(std::io::ErrorKind::NotFound, "无法解析目标地址");
+    for addr in target.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// 在指定端口上接收其他实例转发来的播报，并作为 RemoteAnnouncement 事件送入主循环。
+pub fn start_listener(port: u16, token: Option<String>, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        let listener = match TcpListener::bind(("0.0.0.0", port)) {
+            Ok(l) => l,
+            Err(e) => { error!("伴侣模式: 无法监听端口 {}: {}", port, e); return; }
+        };
+        info!("伴侣模式: 正在端口 {} 上等待转发的播报。", port);
+
+        for connection in listener.incoming() {
+            let Ok(connection) = connection else { continue };
+            let peer = connection.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+            info!("伴侣模式: 接受来自 {} 的连接。", peer);
+            let sender = sender.clone();
+            let token = token.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(connection).lines() {
+                    let Ok(line) = line else { break };
+                    match serde_json::from_str::<CompanionMessage>(&line) {
+                        Ok(message) if message.token == token => {
+                            send_event(&sender, hwnd_value, SystemEvent::RemoteAnnouncement { text: message.text });
+                        }
+                        Ok(_) => {
+                            warn!("伴侣模式: 来自 {} 的令牌不匹配，断开连接。", peer);
+                            break;
+                        }
+                        Err(e) => warn!("伴侣模式: 来自 {} 的消息无法解析: {}", peer, e),
+                    }
+                }
+            });
+        }
+    });
+}
@@ -1,21 +1,532 @@
 // src/config.rs
 
 use serde::{Deserialize, Serialize}; // --- 修改: 增加 Serialize ---
+use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
-use log::warn;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use log::{info, warn, LevelFilter};
+use once_cell::sync::{Lazy, OnceCell};
+use windows::Win32::System::Com::CoTaskMemFree;
+use windows::Win32::UI::Shell::{FOLDERID_RoamingAppData, SHGetKnownFolderPath, KF_FLAG_DEFAULT};
 
-// --- 新增: 帮助函数，用于定位配置文件 ---
-// 将配置文件放在 AppData 目录是更好的实践，但为了简单起见，我们暂时保留在程序目录
-fn get_config_path() -> PathBuf {
-    PathBuf::from("config.json")
+use crate::error::{AppError, AppResult};
+use crate::timers::TimerEntry;
+use crate::tts_engine::{InterruptedPolicy, SpeechPriority, VoiceDetail};
+
+// %APPDATA% 下存放配置和日志的文件夹名
+const DATA_DIR_NAME: &str = "co_mp_ut_er";
+// 程序目录下有这个文件时为便携模式，配置和日志仍放在程序目录
+const PORTABLE_FLAG: &str = "portable.flag";
+const CONFIG_FILE_NAME: &str = "config.json";
+// 数据目录下存放用户语言档案 ({locale}.custom.json) 的文件夹
+const CUSTOM_LOCALE_DIR_NAME: &str = "locales";
+const CUSTOM_LOCALE_SUFFIX: &str = ".custom.json";
+
+static DATA_DIR: Lazy<PathBuf> = Lazy::new(resolve_data_dir);
+static OVERRIDES: OnceCell<Overrides> = OnceCell::new();
+
+/// 命令行参数对本次运行的覆盖，例如 `--lang ja --paused`。不会写回 config.json
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    /// 代替默认位置的 config.json
+    pub config_path: Option<PathBuf>,
+    pub language: Option<String>,
+    /// 语音名称或 id
+    pub voice: Option<String>,
+    pub log_level: Option<LogLevel>,
+    /// 以暂停播报的状态启动
+    pub paused: bool,
+}
+
+/// 记下命令行参数，在读取配置之前调用一次
+pub fn set_overrides(overrides: Overrides) {
+    if OVERRIDES.set(overrides).is_err() {
+        warn!("命令行参数已经设置过，忽略重复的设置。");
+    }
+}
+
+pub fn overrides() -> Option<&'static Overrides> {
+    OVERRIDES.get()
+}
+
+/// 存放配置、日志等需要写入的文件的目录：通常是 %APPDATA%\co_mp_ut_er\，便携模式下是程序目录。
+/// 程序安装在 Program Files 下时，程序目录的写入会被虚拟化或拒绝
+pub fn data_dir() -> &'static Path {
+    &DATA_DIR
+}
+
+/// 用户语言档案，其中的条目优先于该语言的语言档案。放在数据目录下，更新程序时不会被覆盖
+pub fn custom_locale_path(locale: &str) -> PathBuf {
+    data_dir().join(CUSTOM_LOCALE_DIR_NAME).join(format!("{}{}", locale, CUSTOM_LOCALE_SUFFIX))
+}
+
+/// 所有用户语言档案：(语言代码, 路径)，按语言代码排序
+pub fn custom_locale_files() -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(data_dir().join(CUSTOM_LOCALE_DIR_NAME)) else { return Vec::new() };
+    let mut files: Vec<(String, PathBuf)> = entries.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        let locale = path.file_name()?.to_str()?.strip_suffix(CUSTOM_LOCALE_SUFFIX)?.to_string();
+        (!locale.is_empty()).then_some((locale, path))
+    }).collect();
+    files.sort();
+    files
+}
+
+/// 是否因为 portable.flag 而把配置放在程序目录
+pub fn is_portable() -> bool {
+    exe_dir().join(PORTABLE_FLAG).exists()
+}
+
+fn exe_dir() -> PathBuf {
+    env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// 日志此时还没有初始化，出错时只能静默回退到程序目录
+fn resolve_data_dir() -> PathBuf {
+    if is_portable() {
+        return exe_dir();
+    }
+    let Some(app_data) = roaming_app_data() else { return exe_dir() };
+    let dir = app_data.join(DATA_DIR_NAME);
+    match fs::create_dir_all(&dir) {
+        Ok(()) => dir,
+        Err(_) => exe_dir(),
+    }
+}
+
+fn roaming_app_data() -> Option<PathBuf> {
+    unsafe {
+        let path = SHGetKnownFolderPath(&FOLDERID_RoamingAppData, KF_FLAG_DEFAULT, None).ok()?;
+        let result = path.to_string().ok().map(PathBuf::from);
+        CoTaskMemFree(Some(path.0 as *const _));
+        result
+    }
+}
+
+// 导出的设置文件的格式版本。格式变化到旧版本无法正确读取时递增
+const SETTINGS_BUNDLE_VERSION: u32 = 2;
+const SETTINGS_BUNDLE_APP: &str = "co_mp_ut_er";
+
+/// 导出的设置文件：完整的配置（包括读音替换）和用户语言档案，外加用于校验的程序名和格式版本。
+/// 除了版本 2 加入的 custom_strings 外，字段都是必需的，缺少任何一项都视为损坏的文件
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsBundle {
+    app: String,
+    version: u32,
+    config: serde_json::Value,
+    /// 语言代码 -> {locale}.custom.json 的内容
+    #[serde(default)]
+    custom_strings: serde_json::Map<String, serde_json::Value>,
+}
+
+/// usb_debounce_ms 和 usb_coalesce_ms 的有效范围（毫秒）
+pub const USB_WINDOW_MS_RANGE: RangeInclusive<u64> = 100..=30_000;
+
+pub fn clamp_usb_window(name: &str, ms: u64) -> u64 {
+    let clamped = ms.clamp(*USB_WINDOW_MS_RANGE.start(), *USB_WINDOW_MS_RANGE.end());
+    if clamped != ms {
+        warn!("{} 的值 {} 超出范围 {}–{} 毫秒，改用 {}", name, ms, USB_WINDOW_MS_RANGE.start(), USB_WINDOW_MS_RANGE.end(), clamped);
+    }
+    clamped
+}
+
+/// 电量提醒阈值的有效范围（百分比）
+pub const BATTERY_THRESHOLD_RANGE: RangeInclusive<u8> = 1..=99;
+
+/// 整理电量提醒阈值：去掉超出范围的值和重复值，从高到低排列
+pub fn normalize_battery_thresholds(mut thresholds: Vec<u8>) -> Vec<u8> {
+    thresholds.retain(|value| {
+        let valid = BATTERY_THRESHOLD_RANGE.contains(value);
+        if !valid {
+            warn!("电量提醒阈值 {} 超出范围 {}–{}，已忽略", value, BATTERY_THRESHOLD_RANGE.start(), BATTERY_THRESHOLD_RANGE.end());
+        }
+        valid
+    });
+    thresholds.sort_unstable_by(|a, b| b.cmp(a));
+    thresholds.dedup();
+    thresholds
+}
+
+/// 设置窗口中填写的电量提醒阈值为什么无效
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatteryThresholdsError {
+    /// 这一项不是 1–99 的数字
+    Invalid(String),
+    /// 这个值填了不止一次
+    Duplicate(u8),
+}
+
+/// 解析设置窗口中以逗号分隔的阈值，例如 "20, 10, 5"。有无效或重复的项时返回第一个这样的项
+pub fn parse_battery_thresholds(text: &str) -> Result<Vec<u8>, BatteryThresholdsError> {
+    let mut thresholds = Vec::new();
+    for item in text.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let value = item.trim_end_matches('%').trim().parse::<u8>().ok()
+            .filter(|value| BATTERY_THRESHOLD_RANGE.contains(value))
+            .ok_or_else(|| BatteryThresholdsError::Invalid(item.to_string()))?;
+        if thresholds.contains(&value) {
+            return Err(BatteryThresholdsError::Duplicate(value));
+        }
+        thresholds.push(value);
+    }
+    Ok(normalize_battery_thresholds(thresholds))
+}
+
+/// 解析设置窗口中的 USB 重复通知时间窗口 (毫秒)。不是 USB_WINDOW_MS_RANGE 之内的整数时返回 None
+pub fn parse_usb_window(text: &str) -> Option<u64> {
+    text.trim().parse::<u64>().ok().filter(|ms| USB_WINDOW_MS_RANGE.contains(ms))
+}
+
+/// config.json 的位置；用 `--config` 指定时使用指定的文件
+pub fn config_path() -> PathBuf {
+    overrides().and_then(|o| o.config_path.clone()).unwrap_or_else(|| data_dir().join(CONFIG_FILE_NAME))
+}
+
+/// 上一次成功保存的配置，config.json 损坏时从这里恢复
+pub fn backup_path() -> PathBuf {
+    let mut path = config_path().into_os_string();
+    path.push(".bak");
+    PathBuf::from(path)
+}
+
+/// 写入文件的 JSON 文字。转换失败是程序的问题而不是文件损坏，以 ConfigSerialize 返回
+fn to_json<T: Serialize>(value: &T) -> AppResult<String> {
+    serde_json::to_string_pretty(value).map_err(AppError::ConfigSerialize)
+}
+
+/// 先写入同目录下的临时文件并刷新到磁盘，再替换目标文件。写入途中崩溃或断电时
+/// 目标文件要么是旧内容，要么是新内容，不会只剩一半
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp, path)
+}
+
+/// 旧版本把 config.json 放在程序目录。新位置还没有配置时复制一次过来，旧文件保留不动
+pub fn migrate_legacy_config() {
+    let legacy = exe_dir().join(CONFIG_FILE_NAME);
+    let current = config_path();
+    let redirected = overrides().is_some_and(|o| o.config_path.is_some());
+    if redirected || data_dir() == exe_dir() || !legacy.exists() || current.exists() { return; }
+    match fs::copy(&legacy, &current) {
+        Ok(_) => info!("已将程序目录下的配置文件 {} 迁移到 {}", legacy.display(), current.display()),
+        Err(e) => warn!("迁移配置文件 {} 到 {} 失败: {}", legacy.display(), current.display(), e),
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)] // 缺失的字段使用默认值，这样旧版本的 config.json 仍然可以正常加载
 pub struct Config {
+    /// 使用的语音 id（见 list_voices.exe）。旧版本保存的是语音名称，启动时会自动转换为 id
     pub custom_voice: Option<String>,
     pub auto_start: bool,
+    /// 登录时自动启动后先等待这么多秒再初始化语音，让音频和语音服务先准备好。手动启动时不等待
+    pub startup_delay_secs: u32,
     pub language: Option<String>, // --- 新增: 用于存储语言选择，例如 "en", "zh", "ja" ---
+    /// 耳机接入后若 Windows 仍从扬声器输出，是否播报提醒
+    pub warn_headphones_not_default: bool,
+    /// 接通充电器后是否播报估算的充电功率（台式机可关闭）
+    pub announce_charger_wattage: bool,
+    /// 电量降到这些百分比时播报，例如 [20, 10, 5]。为空时电量每变化 1% 都播报
+    pub battery_thresholds: Vec<u8>,
+    /// 电量回升到阈值以上这么多个百分点后，该阈值才会再次播报，避免在阈值附近来回跳动时反复提醒
+    pub battery_hysteresis: u8,
+    /// 伴侣模式: 把每条播报同时转发到另一台机器 (host:port)
+    pub forward_to: Option<String>,
+    /// 伴侣模式的共享令牌，转发端与 --listen 端必须一致
+    pub companion_token: Option<String>,
+    /// 需要播报启动/退出的程序名，例如 "obs64.exe"；为空时不启动进程监控
+    pub watched_processes: Vec<String>,
+    /// 用户设置的倒计时，保存在配置中以便程序重启后继续
+    pub timers: Vec<TimerEntry>,
+    /// 是否为倒计时播报“过半”和“最后一分钟”提醒
+    pub timer_warnings: bool,
+    /// 是否朗读收到的 Windows 通知（需要用户授予通知访问权限）
+    pub mirror_notifications: bool,
+    /// 只朗读这些应用的通知；为空表示不限制
+    pub notification_allow_apps: Vec<String>,
+    /// 永远不朗读这些应用的通知，优先于 notification_allow_apps
+    pub notification_deny_apps: Vec<String>,
+    /// 通知正文超过该字符数时截断，0 表示不截断
+    pub notification_max_chars: usize,
+    /// 连续使用电脑一段时间后提醒休息，默认关闭
+    pub break_reminder_enabled: bool,
+    /// 连续活动多少分钟后提醒休息
+    pub break_reminder_work_minutes: u32,
+    /// 超过多少分钟无键盘鼠标操作视为已经休息过
+    pub break_reminder_idle_gap_minutes: u32,
+    /// 有程序开始/停止使用麦克风时是否播报（视频会议时可能较频繁）
+    pub announce_microphone_use: bool,
+    /// 有程序开始/停止使用摄像头时是否播报
+    pub announce_camera_use: bool,
+    /// 检查摄像头使用状态的间隔（秒）
+    pub camera_poll_interval_secs: u64,
+    /// 显卡驱动停止响应并恢复 (事件 4101) 时是否播报
+    pub announce_display_driver_reset: bool,
+    /// 蓝牙、Wi-Fi 等无线电开关时是否播报
+    pub announce_radio_changes: bool,
+    /// 所有无线电同时切换时合并为一条“飞行模式”播报
+    pub announce_airplane_mode: bool,
+    /// 麦克风静音/取消静音（包括耳机上的硬件静音键）时是否播报
+    pub announce_mic_mute: bool,
+    /// 静音提示的音量（相对于正常音量的比例），避免在会议中过于响亮
+    pub mic_mute_announcement_volume: f32,
+    /// 同一设备在这么多毫秒内重复收到的接入（或断开）通知只处理一次 (100–30000)
+    pub usb_debounce_ms: u64,
+    /// 在这么多毫秒内接入（或断开）的 USB 设备合并为一条播报，例如连接扩展坞时 (100–30000)
+    pub usb_coalesce_ms: u64,
+    /// 旧版本以秒为单位的合并时间，加载时转换为 usb_coalesce_ms，不再写回
+    #[serde(skip_serializing)]
+    pub usb_coalesce_secs: Option<u64>,
+    /// 相同的播报内容在这么多秒内再次出现时不再播报，0 表示关闭
+    pub repeat_suppression_secs: u64,
+    /// 重复的播报平息后，说明上一条消息又重复了几次
+    pub announce_repeat_summary: bool,
+    /// 安静时段，例如 {"start": "22:30", "end": "07:00", "mode": "beep_only"}。None 表示不启用
+    pub quiet_hours: Option<QuietHours>,
+    /// 专注助手、演示模式或全屏应用期间暂缓播报 (紧急提醒除外)，结束后再合并播报期间的变化
+    pub respect_focus_assist: bool,
+    /// 屏幕阅读器运行时不播报 (紧急提醒除外)，避免与屏幕阅读器同时说话
+    pub mute_with_screen_reader: bool,
+    /// 读音替换，例如 [{"pattern": "CO/MP/UT/ER", "replacement": "computer"}]。按顺序匹配，不区分大小写，
+    /// 在播报前应用于最终的文本
+    pub pronunciations: Vec<Pronunciation>,
+    /// 改写播报的文字，键是语言档案中的条目名称，例如 {"system_online": "早上好，{user}"}。
+    /// 写成 "zh:system_online" 时只用于该语言；可以使用原文中的 {参数}
+    pub custom_messages: HashMap<String, String>,
+    /// 每种语言使用的语音 (语言代码 -> 语音 id)，切换语言时自动选用。
+    /// 也可以写成 voice_per_language；没有记录的语言使用 custom_voice 或第一个匹配该语言的语音
+    #[serde(alias = "voice_per_language")]
+    pub voice_by_language: HashMap<String, String>,
+    /// 按事件名称开关播报，例如 {"NetworkConnected": false}；没有列出的事件照常播报
+    pub enabled_events: HashMap<String, bool>,
+    /// 命名的设置方案，例如“家里”和“办公室”，可以从托盘菜单切换
+    pub profiles: HashMap<String, ProfileSettings>,
+    /// 当前使用的方案名称。为空时没有使用方案，直接使用上面的设置（旧版本的配置就是这样）
+    pub active_profile: String,
+    /// 不播报这些 USB 设备的接入/断开。填写设备实例 ID（如 "USB\\VID_046D&PID_C52B\\5&2A3B"），
+    /// 也可以只填前缀（如 "USB\\VID_046D&PID_C52B"）来忽略同一型号的所有设备
+    pub ignored_device_ids: Vec<String>,
+    /// 播报时临时降低其他程序（例如音乐播放器）的音量
+    pub duck_other_audio: bool,
+    /// 降低音量的百分比，播报结束后恢复原音量
+    pub duck_other_audio_percent: u32,
+    /// 启动时用用户名问候
+    pub greet_user: bool,
+    /// 启动问候中同时说出今天的日期和现在的时间
+    pub greet_with_date: bool,
+    /// 说出时刻时用 12 小时制还是 24 小时制："locale" 按语言的习惯，"12h" 或 "24h" 固定使用一种
+    pub clock_format: ClockFormat,
+    /// 启动时播报电量和供电方式
+    pub announce_battery_on_start: bool,
+    /// 启动时播报当前网络连接
+    pub announce_network_on_start: bool,
+    /// 当前用户发送到默认打印机的打印任务完成或出错时播报
+    pub announce_print_jobs: bool,
+    /// Defender 实时保护或 Windows 防火墙被关闭/重新开启时播报
+    pub announce_security_changes: bool,
+    /// 检查安全设置的间隔（秒）
+    pub security_poll_interval_secs: u64,
+    /// 以管理员身份运行时提醒一次（有意以管理员身份运行的用户可以关闭）
+    pub warn_if_elevated: bool,
+    /// 语速，单位与语音引擎相同（Windows 上为 0.5 到 6.0，1.0 为正常语速）。None 表示默认语速，
+    /// 超出范围的值会被限制在引擎支持的范围内
+    pub speech_rate: Option<f32>,
+    /// 播报音量（0.0 到 1.0），与系统音量无关。None 表示语音引擎的默认音量
+    pub volume: Option<f32>,
+    /// 音调（Windows 上为 0.0 到 2.0，1.0 为正常音调）。None 表示语音本身的音调
+    pub pitch: Option<f32>,
+    /// 最多排队等待的播报条数，超出时先丢弃最早的低优先级播报，避免积压成一分钟长的播报
+    pub max_queued_announcements: usize,
+    /// 被更高优先级播报打断的内容："requeue" 稍后重播，"drop" 直接丢弃
+    pub interrupted_announcements: InterruptedPolicy,
+    /// 按事件名称覆盖默认优先级，例如 {"UsbDevicesConnected": "normal", "BatteryLevelReport": "high"}。
+    /// 可选值为 "low"、"normal"、"high"、"critical"
+    pub announcement_priorities: HashMap<String, SpeechPriority>,
+    /// 立即停止播报的全局快捷键，例如 "Ctrl+Alt+S"。留空则不注册
+    pub stop_hotkey: String,
+    /// 播报使用的音频输出设备名称（与声音设置中显示的一致），例如始终从笔记本扬声器播报。
+    /// None 表示跟随系统默认设备；设备不存在时也会回退到默认设备
+    pub output_device: Option<String>,
+    /// 语音引擎出错时用蜂鸣代替播报（办公室环境可以关闭）
+    pub fallback_beeps: bool,
+    /// 按事件名称指定提示音 (WAV 文件)，例如 {"UsbDevicesConnected": {"file": "C:\\Sounds\\click.wav"}}。
+    /// 文件不存在或无法播放时改用语音播报
+    pub event_sounds: HashMap<String, EventSound>,
+    /// 每条播报前先响一下提示音，免得漏听开头的几个字。连续的播报只在开头响一次
+    pub pre_announcement_chime: bool,
+    /// 自定义的提示音 (WAV 文件)，None 表示使用默认提示音
+    pub pre_announcement_chime_file: Option<PathBuf>,
+    /// 手动修改 config.json 后自动重新加载时，是否播报“设置已重新加载”
+    pub announce_config_reload: bool,
+    /// 日志级别: off / error / warn / info / debug / trace
+    pub log_level: LogLevel,
+    /// 日志文件路径，未设置时为配置目录下的 advanced_beeper.log
+    pub log_file: Option<PathBuf>,
+    /// 日志文件超过这么多 KB 后轮换，0 表示不轮换
+    pub log_max_size_kb: u64,
+    /// 轮换后保留的旧日志文件数 (advanced_beeper.log.1、.2 …)
+    pub log_keep_files: usize,
+    /// 设置窗口上次关闭时的位置和大小 (屏幕坐标)，下次打开时放回原处。None 表示在鼠标所在的显示器上居中
+    pub settings_window_pos: Option<WindowRect>,
+    #[serde(skip)]
+    file_values: FileValues,
+}
+
+/// 被命令行参数覆盖之前文件中的设置，保存时写回这些值
+#[derive(Clone, Debug, Default)]
+struct FileValues {
+    language: Option<Option<String>>,
+    log_level: Option<LogLevel>,
+}
+
+/// 事件提示音如何与语音播报配合
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SoundMode {
+    /// 只播放提示音，不再播报语音
+    #[default]
+    Replace,
+    /// 先播放提示音，再播报语音
+    Before,
+}
+
+/// 说出时刻时使用的小时制
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClockFormat {
+    /// 按语言的习惯 (语言档案中的 time_hour_cycle)，例如英文 12 小时制、德文 24 小时制
+    #[default]
+    #[serde(rename = "locale")]
+    Locale,
+    /// 12 小时制，例如 "2:05 PM"
+    #[serde(rename = "12h")]
+    TwelveHour,
+    /// 24 小时制，例如 "14:05"
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EventSound {
+    pub file: PathBuf,
+    #[serde(default)]
+    pub mode: SoundMode,
+}
+
+/// 每天的安静时段。结束时间早于开始时间表示跨越午夜，例如 22:30 到次日 07:00
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct QuietHours {
+    /// 开始时间 "HH:MM" (本地时间)
+    pub start: String,
+    /// 结束时间 "HH:MM" (本地时间)
+    pub end: String,
+    #[serde(default)]
+    pub mode: QuietMode,
+}
+
+/// 窗口在屏幕上的矩形
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct WindowRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+/// 一个设置方案保存的内容。切换方案时这些值写入顶层的设置，切换前当前方案的值会先被保存回去
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ProfileSettings {
+    pub enabled_events: HashMap<String, bool>,
+    pub quiet_hours: Option<QuietHours>,
+    /// 当前语言使用的语音 id，None 表示沿用该语言已选的语音
+    pub voice: Option<String>,
+    pub speech_rate: Option<f32>,
+}
+
+impl ProfileSettings {
+    /// 取出顶层设置中属于方案的部分
+    pub fn capture(config: &Config, language: &str) -> Self {
+        Self {
+            enabled_events: config.enabled_events.clone(),
+            quiet_hours: config.quiet_hours.clone(),
+            voice: config.voice_for_language(language).cloned(),
+            speech_rate: config.speech_rate,
+        }
+    }
+}
+
+/// 写入日志的最低级别
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// 安静时段内如何处理播报
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietMode {
+    /// 除 Critical 级别的提醒外什么都不播报
+    #[default]
+    Silence,
+    /// 用与优先级对应的蜂鸣代替语音
+    BeepOnly,
+    /// 只播报 Critical 级别的提醒 (例如电量即将耗尽)
+    CriticalOnly,
+}
+
+impl QuietHours {
+    /// 开始和结束时间 (从午夜起的分钟数)；任一时间格式无效时返回 None
+    pub fn minutes(&self) -> Option<(u32, u32)> {
+        Some((parse_clock_time(&self.start)?, parse_clock_time(&self.end)?))
+    }
+}
+
+/// 一条读音替换规则
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Pronunciation {
+    pub pattern: String,
+    pub replacement: String,
+    /// pattern 是正则表达式 (replacement 中可以使用 $1 等分组)，否则按原文匹配
+    #[serde(default)]
+    pub regex: bool,
+}
+
+/// 解析 "HH:MM" 格式的时间，返回从午夜起的分钟数
+fn parse_clock_time(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
 }
 
 impl Default for Config {
@@ -23,7 +534,77 @@ impl Default for Config {
         Self {
             custom_voice: None,
             auto_start: false,
+            startup_delay_secs: 0,
             language: None, // --- 新增: 默认值为 None，表示“自动检测” ---
+            warn_headphones_not_default: true,
+            announce_charger_wattage: true,
+            battery_thresholds: vec![20, 10, 5],
+            battery_hysteresis: 3,
+            forward_to: None,
+            companion_token: None,
+            watched_processes: Vec::new(),
+            timers: Vec::new(),
+            timer_warnings: true,
+            mirror_notifications: false,
+            notification_allow_apps: Vec::new(),
+            notification_deny_apps: Vec::new(),
+            notification_max_chars: 200,
+            break_reminder_enabled: false,
+            break_reminder_work_minutes: 50,
+            break_reminder_idle_gap_minutes: 5,
+            announce_microphone_use: true,
+            announce_camera_use: true,
+            camera_poll_interval_secs: 3,
+            announce_display_driver_reset: true,
+            announce_radio_changes: true,
+            announce_airplane_mode: true,
+            announce_mic_mute: true,
+            mic_mute_announcement_volume: 0.5,
+            usb_debounce_ms: 3000,
+            usb_coalesce_ms: 2000,
+            usb_coalesce_secs: None,
+            repeat_suppression_secs: 10,
+            announce_repeat_summary: true,
+            quiet_hours: None,
+            respect_focus_assist: true,
+            mute_with_screen_reader: false,
+            pronunciations: Vec::new(),
+            custom_messages: HashMap::new(),
+            voice_by_language: HashMap::new(),
+            enabled_events: HashMap::new(),
+            profiles: HashMap::new(),
+            active_profile: String::new(),
+            ignored_device_ids: Vec::new(),
+            duck_other_audio: false,
+            duck_other_audio_percent: 60,
+            greet_user: true,
+            greet_with_date: false,
+            clock_format: ClockFormat::default(),
+            announce_battery_on_start: false,
+            announce_network_on_start: false,
+            announce_print_jobs: true,
+            announce_security_changes: false,
+            security_poll_interval_secs: 120,
+            warn_if_elevated: true,
+            speech_rate: None,
+            volume: None,
+            pitch: None,
+            max_queued_announcements: 10,
+            interrupted_announcements: InterruptedPolicy::default(),
+            announcement_priorities: HashMap::new(),
+            stop_hotkey: "Ctrl+Alt+S".to_string(),
+            output_device: None,
+            fallback_beeps: true,
+            event_sounds: HashMap::new(),
+            pre_announcement_chime: false,
+            pre_announcement_chime_file: None,
+            announce_config_reload: true,
+            log_level: LogLevel::Info,
+            log_file: None,
+            log_max_size_kb: 1024,
+            log_keep_files: 3,
+            settings_window_pos: None,
+            file_values: FileValues::default(),
         }
     }
 }
@@ -32,24 +613,419 @@ impl Default for Config {
 
 
 impl Config {
+    /// 该事件是否播报（enabled_events 中没有列出时播报）
+    pub fn event_enabled(&self, event_name: &str) -> bool {
+        self.enabled_events.get(event_name).copied().unwrap_or(true)
+    }
+
+    /// 把顶层设置保存回当前方案，例如在设置窗口修改了安静时段之后
+    pub fn capture_active_profile(&mut self, language: &str) {
+        let captured = ProfileSettings::capture(self, language);
+        if let Some(profile) = self.profiles.get_mut(&self.active_profile) {
+            *profile = captured;
+        }
+    }
+
+    /// 切换到名为 `name` 的方案后的配置，方案不存在时返回 None。当前方案先保存当前的值，
+    /// 这样切换回来时不会丢失期间的修改
+    pub fn with_profile(&self, name: &str, language: &str) -> Option<Self> {
+        let profile = self.profiles.get(name)?.clone();
+        let mut config = self.clone();
+        config.capture_active_profile(language);
+        config.enabled_events = profile.enabled_events;
+        config.quiet_hours = profile.quiet_hours;
+        config.speech_rate = profile.speech_rate;
+        if let Some(voice) = profile.voice {
+            config.voice_by_language.insert(language.to_string(), voice);
+        }
+        config.active_profile = name.to_string();
+        Some(config)
+    }
+
+    /// 方案名称，按字母顺序排列
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// 记录某种语言下成功使用的语音，下次切换到该语言时优先使用
+    pub fn remember_voice(&mut self, language: &str, voice_id: &str) {
+        self.voice_by_language.insert(language.to_string(), voice_id.to_string());
+    }
+
+    /// 该语言记住的语音 id。地区标签 (例如 "zh-TW") 没有自己的记录时使用该语言 ("zh") 的
+    pub fn voice_for_language(&self, language: &str) -> Option<&String> {
+        self.voice_by_language.get(language)
+            .or_else(|| self.voice_by_language.get(language.split(['-', '_']).next().unwrap_or(language)))
+    }
+
+    /// 为该语言选择语音时首先尝试的语音 id：该语言记住的语音，其次是旧版本的 custom_voice
+    pub fn mapped_voice(&self, language: &str) -> Option<&String> {
+        self.voice_for_language(language).or(self.custom_voice.as_ref())
+    }
+
+    /// 该语言上次使用的语音 id；只有当它仍在 `installed` 中时才返回
+    pub fn remembered_voice<'a>(&self, language: &str, mut installed: impl Iterator<Item = &'a str>) -> Option<String> {
+        let id = self.voice_for_language(language)?;
+        installed.any(|v| v == id).then(|| id.clone())
+    }
+
+    /// 把旧版本保存的语音名称转换为语音 id。名称对应多个语音时取第一个。
+    /// 返回是否有修改，调用者据此决定是否保存配置
+    pub fn migrate_voice_names(&mut self, voices: &[VoiceDetail]) -> bool {
+        let to_id = |key: &str| {
+            if voices.iter().any(|v| v.id == key) { return None; }
+            voices.iter().find(|v| v.name == key).map(|v| v.id.clone())
+        };
+        let mut changed = false;
+        if let Some(id) = self.custom_voice.as_deref().and_then(to_id) {
+            info!("将配置中的语音名称 '{}' 转换为 id '{}'", self.custom_voice.as_deref().unwrap_or_default(), id);
+            self.custom_voice = Some(id);
+            changed = true;
+        }
+        for voice in self.voice_by_language.values_mut() {
+            if let Some(id) = to_id(voice) {
+                *voice = id;
+                changed = true;
+            }
+        }
+        changed
+    }
+
     pub fn load() -> Self {
-        match fs::read_to_string(get_config_path()) {
-            Ok(content) => {
-                serde_json::from_str(&content).unwrap_or_else(|e| {
-                    warn!("警告: 解析 config.json 失败: {}. 将使用默认配置。", e);
-                    Config::default()
-                })
-            },
-            Err(_) => {
+        Self::load_or_recover(&config_path(), &backup_path())
+    }
+
+    fn load_or_recover(path: &Path, backup: &Path) -> Self {
+        match Self::load_from(path) {
+            Ok(config) => {
+                info!("已从 {} 读取设置。", path.display());
+                config
+            }
+            Err(AppError::ConfigIo(e)) if e.kind() == std::io::ErrorKind::NotFound => {
                 // 文件不存在是正常情况，直接返回默认值
                 Config::default()
+            },
+            Err(e) => {
+                // config.json 损坏 (例如保存时断电) 时改用上一次保存的备份，而不是悄悄丢掉所有设置
+                warn!("警告: 加载 {} 失败: {}。尝试使用备份。", path.display(), e);
+                match Self::load_from(backup) {
+                    Ok(config) => {
+                        warn!("已从备份 {} 恢复设置。", backup.display());
+                        config
+                    }
+                    Err(e) => {
+                        warn!("警告: 备份 {} 也无法加载: {}。将使用默认配置。", backup.display(), e);
+                        Config::default()
+                    }
+                }
+            }
+        }
+    }
+
+    /// 读取并解析配置文件，读写失败与格式错误分别以 ConfigIo / ConfigParse 返回。
+    pub fn try_load() -> AppResult<Self> {
+        Self::load_from(&config_path())
+    }
+
+    fn load_from(path: &Path) -> AppResult<Self> {
+        let content = fs::read_to_string(path).map_err(AppError::ConfigIo)?;
+        let mut config: Self = serde_json::from_str(&content).map_err(AppError::ConfigParse)?;
+        config.normalize();
+        config.apply_overrides();
+        Ok(config)
+    }
+
+    /// 把设置导出为一个文件，以便在其他电脑上导入。正在运行的倒计时只属于这台电脑，不导出
+    pub fn export(&self, path: &Path) -> AppResult<()> {
+        let mut config = self.for_file();
+        config.timers.clear();
+        // 格式有误的用户语言档案程序本身也不会使用，不导出
+        let custom_strings = custom_locale_files().into_iter().filter_map(|(locale, file)| {
+            let content = fs::read_to_string(&file).ok()?;
+            match serde_json::from_str::<serde_json::Value>(&content) {
+                Ok(strings @ serde_json::Value::Object(_)) => Some((locale, strings)),
+                _ => {
+                    warn!("用户语言档案 {} 格式有误，不导出。", file.display());
+                    None
+                }
+            }
+        }).collect();
+        let bundle = SettingsBundle {
+            app: SETTINGS_BUNDLE_APP.to_string(),
+            version: SETTINGS_BUNDLE_VERSION,
+            config: serde_json::to_value(&config).map_err(AppError::ConfigSerialize)?,
+            custom_strings,
+        };
+        let content = to_json(&bundle)?;
+        fs::write(path, content).map_err(AppError::ConfigIo)
+    }
+
+    /// 读取并完整校验导出的设置文件。任何一处有问题都返回错误，不会得到只读了一半的配置。
+    /// 校验通过后写入其中的用户语言档案，同名的档案被替换
+    pub fn import(path: &Path) -> AppResult<Self> {
+        let content = fs::read_to_string(path).map_err(AppError::ConfigIo)?;
+        let bundle: SettingsBundle = serde_json::from_str(&content).map_err(AppError::ConfigParse)?;
+        if bundle.app != SETTINGS_BUNDLE_APP {
+            return Err(AppError::SettingsBundleInvalid(format!("不是本程序导出的设置 ('{}')", bundle.app)));
+        }
+        if bundle.version == 0 || bundle.version > SETTINGS_BUNDLE_VERSION {
+            return Err(AppError::SettingsBundleInvalid(format!("不支持的格式版本 {} (支持 1–{})", bundle.version, SETTINGS_BUNDLE_VERSION)));
+        }
+        if !bundle.config.is_object() {
+            return Err(AppError::SettingsBundleInvalid("缺少配置内容".to_string()));
+        }
+        for (locale, strings) in &bundle.custom_strings {
+            if locale.is_empty() || !locale.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(AppError::SettingsBundleInvalid(format!("用户语言档案的语言代码 '{}' 无效", locale)));
             }
+            if !strings.is_object() {
+                return Err(AppError::SettingsBundleInvalid(format!("用户语言档案 '{}' 不是 JSON 对象", locale)));
+            }
+        }
+        let mut config: Self = serde_json::from_value(bundle.config).map_err(AppError::ConfigParse)?;
+        config.normalize();
+        config.apply_overrides();
+        for (locale, strings) in &bundle.custom_strings {
+            let file = custom_locale_path(locale);
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir).map_err(AppError::ConfigIo)?;
+            }
+            write_atomic(&file, to_json(strings)?.as_bytes()).map_err(AppError::ConfigIo)?;
+            info!("已导入用户语言档案 {}", file.display());
+        }
+        Ok(config)
+    }
+
+    /// 转换旧的设置项，并把超出范围的值限制在有效范围内
+    fn normalize(&mut self) {
+        if let Some(secs) = self.usb_coalesce_secs.take() {
+            self.usb_coalesce_ms = secs.saturating_mul(1000);
         }
+        self.usb_debounce_ms = clamp_usb_window("usb_debounce_ms", self.usb_debounce_ms);
+        self.usb_coalesce_ms = clamp_usb_window("usb_coalesce_ms", self.usb_coalesce_ms);
+        self.battery_thresholds = normalize_battery_thresholds(std::mem::take(&mut self.battery_thresholds));
+    }
+
+    /// 套用命令行参数，并记下文件中原来的值
+    fn apply_overrides(&mut self) {
+        let Some(overrides) = overrides() else { return };
+        if let Some(language) = &overrides.language {
+            self.file_values.language = Some(self.language.replace(language.clone()));
+        }
+        if let Some(level) = overrides.log_level {
+            self.file_values.log_level = Some(std::mem::replace(&mut self.log_level, level));
+        }
+    }
+
+    /// 写入文件的设置：仍是命令行参数指定的值时换回文件中原来的值，本次运行中改过的照常保存
+    fn for_file(&self) -> Config {
+        let mut config = self.clone();
+        let Some(overrides) = overrides() else { return config };
+        if let Some(language) = config.file_values.language.take() {
+            if config.language == overrides.language { config.language = language; }
+        }
+        if let Some(level) = config.file_values.log_level.take() {
+            if Some(config.log_level) == overrides.log_level { config.log_level = level; }
+        }
+        config
     }
 
     // --- 新增: 保存配置到文件的函数 ---
-    pub fn save(&self) -> Result<(), std::io::Error> {
-        let content = serde_json::to_string_pretty(self)?;
-        fs::write(get_config_path(), content)
+    /// 原子地写入 config.json。覆盖之前，把仍能正常读取的旧文件留作 config.json.bak
+    pub fn save(&self) -> AppResult<()> {
+        self.save_to(&config_path(), &backup_path())
+    }
+
+    fn save_to(&self, path: &Path, backup: &Path) -> AppResult<()> {
+        let content = to_json(&self.for_file())?;
+        if Self::load_from(path).is_ok() {
+            if let Err(e) = fs::copy(path, backup) {
+                warn!("备份 config.json 失败: {}", e);
+            }
+        }
+        write_atomic(path, content.as_bytes()).map_err(AppError::ConfigIo)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// 每个测试各用一个临时文件，测试结束时删除
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("co_mp_ut_er-{}-{}", std::process::id(), name));
+            fs::write(&path, content).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn missing_file_is_config_io() {
+        let path = std::env::temp_dir().join(format!("co_mp_ut_er-{}-missing.json", std::process::id()));
+        match Config::load_from(&path) {
+            Err(AppError::ConfigIo(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("应为 ConfigIo，实际为 {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn malformed_file_is_config_parse() {
+        let file = TempFile::new("malformed.json", "{\"language\": ");
+        assert!(matches!(Config::load_from(&file.0), Err(AppError::ConfigParse(_))));
+    }
+
+    #[test]
+    fn foreign_bundle_is_rejected() {
+        let file = TempFile::new("foreign.json", r#"{"app": "other", "version": 1, "config": {}}"#);
+        assert!(matches!(Config::import(&file.0), Err(AppError::SettingsBundleInvalid(_))));
+    }
+
+    #[test]
+    fn malformed_bundle_is_config_parse() {
+        let file = TempFile::new("bundle.json", "not json");
+        assert!(matches!(Config::import(&file.0), Err(AppError::ConfigParse(_))));
+    }
+
+    #[test]
+    fn serialization_failure_is_config_serialize() {
+        // JSON 对象的键只能是字符串
+        let value: HashMap<(u8, u8), u8> = HashMap::from([((1, 2), 3)]);
+        assert!(matches!(to_json(&value), Err(AppError::ConfigSerialize(_))));
+    }
+
+    fn with_language(language: &str) -> Config {
+        Config { language: Some(language.to_string()), ..Config::default() }
+    }
+
+    // 先后保存两次，config.json 是第二次的内容，备份是第一次的
+    fn saved_twice(name: &str) -> (TempFile, TempFile) {
+        let file = TempFile::new(name, "");
+        let backup = TempFile::new(&format!("{}.bak", name), "");
+        with_language("ja").save_to(&file.0, &backup.0).unwrap();
+        with_language("zh").save_to(&file.0, &backup.0).unwrap();
+        (file, backup)
+    }
+
+    fn truncate(path: &Path) {
+        let content = fs::read_to_string(path).unwrap();
+        fs::write(path, &content[..content.len() / 2]).unwrap();
+    }
+
+    #[test]
+    fn intact_file_is_loaded() {
+        let (file, backup) = saved_twice("intact.json");
+        assert_eq!(Config::load_or_recover(&file.0, &backup.0).language.as_deref(), Some("zh"));
+    }
+
+    #[test]
+    fn truncated_file_recovers_from_backup() {
+        let (file, backup) = saved_twice("truncated.json");
+        truncate(&file.0);
+        assert_eq!(Config::load_or_recover(&file.0, &backup.0).language.as_deref(), Some("ja"));
+    }
+
+    #[test]
+    fn truncated_file_without_usable_backup_uses_defaults() {
+        let (file, backup) = saved_twice("no_backup.json");
+        truncate(&file.0);
+        truncate(&backup.0);
+        assert_eq!(Config::load_or_recover(&file.0, &backup.0).language, Config::default().language);
+    }
+
+    #[test]
+    fn saving_over_a_damaged_file_keeps_the_backup() {
+        let (file, backup) = saved_twice("keep_backup.json");
+        truncate(&file.0);
+        with_language("en").save_to(&file.0, &backup.0).unwrap();
+        assert_eq!(Config::load_from(&backup.0).unwrap().language.as_deref(), Some("ja"));
+        assert_eq!(Config::load_from(&file.0).unwrap().language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn mapped_voice_prefers_language_then_region_then_custom_voice() {
+        let config = Config {
+            voice_by_language: HashMap::from([("zh".to_string(), "voice-zh".to_string()), ("zh-TW".to_string(), "voice-zh-tw".to_string())]),
+            custom_voice: Some("voice-custom".to_string()),
+            ..Config::default()
+        };
+        let cases = [("zh-TW", "voice-zh-tw"), ("zh-HK", "voice-zh"), ("zh", "voice-zh"), ("zh_CN", "voice-zh"), ("ja", "voice-custom")];
+        for (language, expected) in cases {
+            assert_eq!(config.mapped_voice(language).map(String::as_str), Some(expected), "{}", language);
+        }
+        let config = Config { custom_voice: None, ..config };
+        assert_eq!(config.mapped_voice("ja"), None);
+    }
+
+    #[test]
+    fn parses_battery_thresholds() {
+        let cases: [(&str, &[u8]); 7] = [
+            ("", &[]),
+            (" , ,", &[]),
+            ("20, 10, 5", &[20, 10, 5]),
+            ("5,20,10", &[20, 10, 5]),
+            ("20%, 10 %,5%", &[20, 10, 5]),
+            ("1, 99", &[99, 1]),
+            (" 15 ,", &[15]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_battery_thresholds(text).as_deref(), Ok(expected), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_battery_thresholds() {
+        let cases = [
+            ("0", "0"),
+            ("100", "100"),
+            ("20, 256", "256"),
+            ("-5", "-5"),
+            ("12.5", "12.5"),
+            ("abc, 200", "abc"),
+            ("%", "%"),
+            ("20 10", "20 10"),
+        ];
+        for (text, item) in cases {
+            assert_eq!(parse_battery_thresholds(text), Err(BatteryThresholdsError::Invalid(item.to_string())), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_battery_thresholds() {
+        assert_eq!(parse_battery_thresholds("20, 10, 20"), Err(BatteryThresholdsError::Duplicate(20)));
+        assert_eq!(parse_battery_thresholds("10%, 10"), Err(BatteryThresholdsError::Duplicate(10)));
+        // 无效的项在重复之前时报告无效的项
+        assert_eq!(parse_battery_thresholds("10, x, 10"), Err(BatteryThresholdsError::Invalid("x".to_string())));
+    }
+
+    #[test]
+    fn parses_usb_window_within_range() {
+        let (min, max) = (*USB_WINDOW_MS_RANGE.start(), *USB_WINDOW_MS_RANGE.end());
+        let cases = [
+            (min.to_string(), Some(min)),
+            (max.to_string(), Some(max)),
+            ((min - 1).to_string(), None),
+            ((max + 1).to_string(), None),
+            (" 500 ".to_string(), Some(500)),
+            (String::new(), None),
+            ("-1".to_string(), None),
+            ("1.5".to_string(), None),
+            ("500ms".to_string(), None),
+            ("abc".to_string(), None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_usb_window(&text), expected, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// src/config_watcher.rs
+
+use std::fs;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+use log::{error, info};
+use windows::Win32::Foundation::HWND;
+
+use crate::config::{self, Config};
+use crate::event_monitor::{send_event, SystemEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Polls config.json and the user locale files (`<locale>.custom.json`) for changes made outside
+/// the app (e.g. by hand in an editor) and sends the parsed config to the main thread, which
+/// re-reads the locale files, compares the config with the settings in use and applies the
+/// difference. A file that does not parse is reported once per change and otherwise ignored, so
+/// a half-saved edit never replaces the working settings.
+pub fn start_config_watcher(sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        let path = config::config_path();
+        let modified = |path: &std::path::Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        // 用户语言档案的修改时间，增加或删除档案也算修改
+        let custom_modified = || -> Vec<Option<SystemTime>> {
+            config::custom_locale_files().iter().map(|(_, file)| modified(file)).collect()
+        };
+        let mut last: Option<SystemTime> = modified(&path);
+        let mut last_custom = custom_modified();
+        info!("开始监视配置文件 {} 的修改。", path.display());
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = modified(&path);
+            let current_custom = custom_modified();
+            if current == last && current_custom == last_custom { continue; }
+            last = current;
+            last_custom = current_custom;
+            // 文件被删除时保留当前设置，重新创建后再加载
+            if current.is_none() { continue; }
+            match Config::try_load() {
+                Ok(config) => send_event(&sender, hwnd_value, SystemEvent::ConfigReloaded { config: Box::new(config) }),
+                Err(e) => error!("config.json 已修改但无法解析，继续使用当前设置: {}", e),
+            }
+        }
+    });
+}
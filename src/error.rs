@@ -0,0 +1,52 @@
+// src/error.rs
+
+use thiserror::Error;
+
+/// 本程序统一使用的错误类型。
+/// 调用者可以根据变体决定是否重试或回退，而不必匹配错误字符串。
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("TTS 引擎初始化失败: {0}")]
+    TtsInit(#[source] tts::Error),
+
+    #[error("TTS 引擎操作失败: {0}")]
+    Tts(#[from] tts::Error),
+
+    #[error("未找到名为 '{0}' 的语音")]
+    VoiceNotFound(String),
+
+    #[error("无法读取语言档案 '{locale}': {source}")]
+    LocaleMissing { locale: String, #[source] source: std::io::Error },
+
+    #[error("语言档案 '{locale}' 格式无效: {source}")]
+    LocaleInvalid { locale: String, #[source] source: serde_json::Error },
+
+    #[error("语言档案 '{locale}' 缺少必需的条目: {}", missing.join(", "))]
+    LocaleIncomplete { locale: String, missing: Vec<String> },
+
+    #[error("配置文件读写失败: {0}")]
+    ConfigIo(#[source] std::io::Error),
+
+    #[error("配置文件解析失败: {0}")]
+    ConfigParse(#[source] serde_json::Error),
+
+    #[error("配置无法转换为 JSON: {0}")]
+    ConfigSerialize(#[source] serde_json::Error),
+
+    #[error("设置文件无效: {0}")]
+    SettingsBundleInvalid(String),
+
+    #[error("访问注册表失败: {0}")]
+    RegistryAccess(#[source] std::io::Error),
+
+    #[error("无法确定程序本身的路径: {0}")]
+    ExecutablePath(#[source] std::io::Error),
+
+    #[error("Win32 调用失败: {0}")]
+    Win32(#[from] windows::core::Error),
+
+    #[error("导出 WAV 失败: {0}")]
+    WavExport(#[from] crate::wav_export::WavExportError),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
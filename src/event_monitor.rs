@@ -5,6 +5,7 @@ use log::{info, error};
 use windows::core::{IInspectable};
 use windows::Foundation::{TypedEventHandler, IReference};
 use windows::Devices::Power::Battery;
+use windows::System::Power::BatteryStatus;
 use windows::Networking::Connectivity::{NetworkInformation, NetworkStatusChangedEventHandler};
 use windows::Win32::Foundation::{HWND, WPARAM, LPARAM};
 use windows::Win32::UI::WindowsAndMessaging::PostMessageW;
@@ -13,12 +14,26 @@ use std::ffi::c_void;
 
 lazy_static::lazy_static! {
     pub static ref IS_SYSTEM_ASLEEP: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    static ref BATTERY_THRESHOLDS: Mutex<BatteryThresholds> = Mutex::new(BatteryThresholds::default());
 }
-use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED};
 use futures::executor::block_on;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::radio_monitor::RadioKind;
 
 const WM_APP_WAKEUP: u32 = 0x8000 + 2;
 
+// The charge rate reported right after plugging in ramps up over a few seconds while
+// USB-PD negotiates, so wait before sampling and keep the highest of several samples.
+const CHARGE_RATE_SETTLE_DELAY: Duration = Duration::from_secs(3);
+const CHARGE_RATE_SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+const CHARGE_RATE_SAMPLES: usize = 3;
+// When a connection drops, wait this long for another network to take over before
+// announcing a disconnect, so roaming between SSIDs is reported as a single switch.
+const NETWORK_SWITCH_GRACE: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConnectionType { Ethernet, WiFi, Cellular, Unknown }
 
@@ -26,26 +41,130 @@ pub enum ConnectionType { Ethernet, WiFi, Cellular, Unknown }
 pub enum SystemEvent {
     PowerSwitchedToAC, PowerSwitchedToBattery,
     BatteryLevelReport(u8),
-    UsbDeviceConnected, UsbDeviceDisconnected, SystemStartup,
+    UsbDevicesConnected { count: usize }, UsbDevicesDisconnected { count: usize }, SystemStartup,
+    RunningElevated,
+    /// The voice configured for the current language is not installed (any more); `fallback`
+    /// is the voice used instead.
+    ConfiguredVoiceMissing { name: String, fallback: String },
     BatteryInserted, BatteryRemoved,
     NetworkConnected { name: String, conn_type: ConnectionType },
     NetworkDisconnected,
+    NetworkSwitched { from: String, to: String },
     SystemGoingToSleep,
     SystemResumedFromSleep,
+    /// Focus Assist ended after announcements were held back; the net changes are summed up.
+    FocusAssistEnded,
+    BatterySaverChanged { enabled: bool },
+    HeadphonesNotDefault { current_device: String },
+    ChargerWattageReport { watts: u32 },
+    RemoteAnnouncement { text: String },
+    WatchedProcessStarted { name: String },
+    WatchedProcessExited { name: String, exit_code: Option<i32> },
+    TimerStarted { minutes: u32 },
+    TimerCancelled { minutes: u32 },
+    TimerHalfway { minutes: u32, remaining_minutes: u32 },
+    TimerOneMinuteLeft { minutes: u32 },
+    TimerElapsed { minutes: u32 },
+    NotificationReceived { app: String, text: String },
+    TimeZoneChanged { name: String },
+    ClockChanged { delta_minutes: i64 },
+    BreakReminder { minutes: u32 },
+    MicrophoneInUse { app: String },
+    MicrophoneReleased,
+    CameraInUse { app: String },
+    CameraReleased,
+    DisplayDriverReset { driver: String },
+    RadioStateChanged { kind: RadioKind, on: bool },
+    AirplaneModeChanged { on: bool },
+    MicrophoneMuteChanged { muted: bool },
+    /// The default audio output device changed. Not announced; the speech engine is rebuilt
+    /// because it may still be bound to the previous device.
+    DefaultAudioDeviceChanged,
+    /// config.json was changed outside the app; the main thread applies what differs.
+    ConfigReloaded { config: Box<Config> },
+    /// A subsystem that had been failing works again; `name` selects the `subsystem_<name>` text.
+    SubsystemRecovered { name: String },
+    /// The speech engine stopped working and could not be reinitialized; announcements are beeps.
+    TtsFailed,
+    /// The speech engine works again after it had been reported as failed or recovering.
+    TtsRecovered,
+    /// The user asked for a status summary from the tray menu. `battery` is the level and
+    /// whether external power is connected, `None` when there is no battery.
+    StatusRequest { battery: Option<(u8, bool)>, network: Option<(String, ConnectionType)> },
+    /// Battery and network state gathered right after startup; either part is `None` when it
+    /// is disabled, unavailable or (for the network) did not resolve in time.
+    StartupSummary { battery: Option<(u8, bool)>, network: Option<(String, ConnectionType)> },
+    /// `pages` is 0 when the driver did not report a page count.
+    PrintJobCompleted { document: String, pages: u32 },
+    PrintJobFailed { document: String },
+    RealtimeProtectionDisabled, RealtimeProtectionEnabled,
+    FirewallDisabled, FirewallEnabled,
+}
+
+impl SystemEvent {
+    /// The variant name, e.g. "UsbDevicesConnected", used as the key for per-event settings.
+    pub fn name(&self) -> String {
+        let debug = format!("{:?}", self);
+        debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default().to_string()
+    }
+
+    /// Events the user explicitly asked for are spoken even while announcements are paused.
+    pub fn bypasses_pause(&self) -> bool {
+        matches!(self,
+            SystemEvent::TimerStarted { .. } | SystemEvent::TimerCancelled { .. } |
+            SystemEvent::TimerHalfway { .. } | SystemEvent::TimerOneMinuteLeft { .. } |
+            SystemEvent::TimerElapsed { .. } | SystemEvent::StatusRequest { .. })
+    }
+}
+
+/// Events offered in the settings window for assigning a sound. config.json accepts any event name.
+pub const SOUND_EVENT_NAMES: &[&str] = &[
+    "UsbDevicesConnected", "UsbDevicesDisconnected",
+    "PowerSwitchedToAC", "PowerSwitchedToBattery", "BatteryLevelReport", "BatterySaverChanged",
+    "NetworkConnected", "NetworkDisconnected", "NetworkSwitched",
+    "SystemResumedFromSleep", "MicrophoneMuteChanged", "TimerElapsed",
+    "PrintJobCompleted", "PrintJobFailed", "NotificationReceived",
+];
+
+/// Events that can be switched off in the settings window, in the order they are listed there.
+/// Events the user asked for (timers, status) are always spoken and not listed, nor are
+/// internal events that are never announced.
+pub const TOGGLEABLE_EVENT_NAMES: &[&str] = &[
+    "SystemStartup", "StartupSummary", "RunningElevated", "ConfiguredVoiceMissing",
+    "PowerSwitchedToAC", "PowerSwitchedToBattery", "BatteryLevelReport", "BatteryInserted", "BatteryRemoved",
+    "BatterySaverChanged", "ChargerWattageReport",
+    "UsbDevicesConnected", "UsbDevicesDisconnected", "HeadphonesNotDefault", "DisplayDriverReset",
+    "NetworkConnected", "NetworkDisconnected", "NetworkSwitched", "RadioStateChanged", "AirplaneModeChanged",
+    "SystemGoingToSleep", "SystemResumedFromSleep", "FocusAssistEnded", "TimeZoneChanged", "ClockChanged",
+    "BreakReminder", "MicrophoneInUse", "MicrophoneReleased", "MicrophoneMuteChanged", "CameraInUse", "CameraReleased",
+    "WatchedProcessStarted", "WatchedProcessExited", "NotificationReceived", "RemoteAnnouncement",
+    "PrintJobCompleted", "PrintJobFailed",
+    "RealtimeProtectionDisabled", "RealtimeProtectionEnabled", "FirewallDisabled", "FirewallEnabled",
+    "SubsystemRecovered",
+];
+
+/// Sends an event to the main loop and wakes it up so the event is handled immediately.
+pub fn send_event(sender: &mpsc::Sender<SystemEvent>, hwnd_value: isize, event: SystemEvent) {
+    if sender.send(event).is_ok() {
+        let hwnd = HWND(hwnd_value as *mut c_void);
+        unsafe { PostMessageW(Some(hwnd), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+    }
 }
 
 // The public API still takes an HWND for clarity.
-pub fn start_monitoring(sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+pub fn start_monitoring(sender: mpsc::Sender<SystemEvent>, hwnd: HWND, config: &Config) {
     // --- CORE FIX: Cast the raw pointer (*mut c_void) to a pointer-sized integer (isize). ---
     // This is safe because isize is guaranteed to be large enough to hold a pointer.
     // The isize value is `Send` and can be moved to other threads.
     let hwnd_value = hwnd.0 as isize;
 
     let battery_sender = sender.clone();
+    let announce_charger_wattage = config.announce_charger_wattage;
+    set_battery_thresholds(&config.battery_thresholds, config.battery_hysteresis);
     std::thread::spawn(move || {
         if unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }.is_ok() {
             // Pass the isize value, not the HWND.
-            block_on(setup_battery_monitor(battery_sender, hwnd_value));
+            block_on(setup_battery_monitor(battery_sender, hwnd_value, announce_charger_wattage));
         }
     });
 
@@ -58,8 +177,46 @@ pub fn start_monitoring(sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
     });
 }
 
+/// Decides which battery level changes are announced. With thresholds configured only a drop to
+/// or below a threshold is; that threshold is announced again once the level has climbed more
+/// than `hysteresis` points above it. Without thresholds every change is announced.
+#[derive(Default)]
+struct BatteryThresholds {
+    thresholds: Vec<u8>,
+    hysteresis: u8,
+    announced: Vec<u8>,
+}
+
+impl BatteryThresholds {
+    /// Counts thresholds at or above the starting level as already announced, so starting the
+    /// app on a low battery doesn't report the next one-point drop.
+    fn prime(&mut self, level: u8) {
+        self.announced = self.thresholds.iter().copied().filter(|t| level <= *t).collect();
+    }
+
+    fn should_announce(&mut self, level: u8) -> bool {
+        if self.thresholds.is_empty() { return true; }
+        let hysteresis = self.hysteresis;
+        self.announced.retain(|t| level <= t.saturating_add(hysteresis));
+        let crossed: Vec<u8> = self.thresholds.iter().copied()
+            .filter(|t| level <= *t && !self.announced.contains(t))
+            .collect();
+        self.announced.extend(&crossed);
+        !crossed.is_empty()
+    }
+}
+
+/// Applies new battery thresholds (already normalized by Config) and forgets which ones were
+/// announced, so the new values take effect with the next level change.
+pub fn set_battery_thresholds(thresholds: &[u8], hysteresis: u8) {
+    let mut state = BATTERY_THRESHOLDS.lock().unwrap();
+    state.thresholds = thresholds.to_vec();
+    state.hysteresis = hysteresis;
+    state.announced.clear();
+}
+
 // This function correctly accepts the raw isize value.
-async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) {
+async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize, announce_charger_wattage: bool) {
     let aggregate_battery = match Battery::AggregateBattery() {
         Ok(b) => b,
         Err(_) => return
@@ -67,8 +224,10 @@ async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
 
     let last_present_state = Arc::new(Mutex::new(None::<bool>));
     let last_percentage = Arc::new(Mutex::new(None::<u8>));
+    let last_status = Arc::new(Mutex::new(None::<BatteryStatus>));
 
     if let Ok(report) = aggregate_battery.GetReport() {
+        *last_status.lock().unwrap() = report.Status().ok();
         let is_present = report.FullChargeCapacityInMilliwattHours()
             .and_then(|cap| cap.GetInt32())
             .map_or(false, |c| c > 0);
@@ -80,6 +239,7 @@ async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
                 if full > 0 {
                     let percentage = (rem as f64 / full as f64 * 100.0).round() as u8;
                     *last_percentage.lock().unwrap() = Some(percentage);
+                    BATTERY_THRESHOLDS.lock().unwrap().prime(percentage);
                 }
             }
         }
@@ -89,6 +249,7 @@ async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
         let sender_clone = sender.clone();
         let state_clone = last_present_state.clone();
         let percentage_clone = last_percentage.clone();
+        let status_clone = last_status.clone();
         let battery_clone = aggregate_battery.clone(); 
         
         move |_, _| {
@@ -96,6 +257,16 @@ async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
             
             let report = match battery_clone.GetReport() { Ok(r) => r, Err(_) => return Ok(()) };
 
+            // Charging only starts once a charger is plugged in; measure it once the rate has settled.
+            let status_now = report.Status().ok();
+            let previous_status = std::mem::replace(&mut *status_clone.lock().unwrap(), status_now);
+            if announce_charger_wattage
+                && status_now == Some(BatteryStatus::Charging)
+                && previous_status.is_some_and(|s| s != BatteryStatus::Charging)
+            {
+                spawn_charge_rate_sampler(sender_clone.clone(), hwnd_value);
+            }
+
             let is_present_now = report.FullChargeCapacityInMilliwattHours().and_then(|c| c.GetInt32()).map_or(false, |c| c > 0);
 
             let percentage_now = if let (Ok(rem_cap), Ok(full_cap)) = (report.RemainingCapacityInMilliwattHours(), report.FullChargeCapacityInMilliwattHours()) {
@@ -113,8 +284,10 @@ async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
                 event_to_send = Some(if is_present_now { SystemEvent::BatteryInserted } else { SystemEvent::BatteryRemoved });
                 *last_present_guard = Some(is_present_now);
                 *last_percentage_guard = percentage_now;
-            } else if is_present_now && *last_percentage_guard != percentage_now && percentage_now.is_some() {
-                event_to_send = Some(SystemEvent::BatteryLevelReport(percentage_now.unwrap()));
+            } else if let Some(level) = percentage_now.filter(|_| is_present_now && *last_percentage_guard != percentage_now) {
+                if BATTERY_THRESHOLDS.lock().unwrap().should_announce(level) {
+                    event_to_send = Some(SystemEvent::BatteryLevelReport(level));
+                }
                 *last_percentage_guard = percentage_now;
             }
 
@@ -135,17 +308,52 @@ async fn setup_battery_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
     }
 }
 
+/// Samples the aggregate battery's charge rate a few times once charging has started
+/// and reports the highest value as the charger's approximate wattage.
+fn spawn_charge_rate_sampler(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) {
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() { return; }
+        let Ok(battery) = Battery::AggregateBattery() else { return };
+
+        std::thread::sleep(CHARGE_RATE_SETTLE_DELAY);
+        let mut max_milliwatts = 0;
+        for sample in 0..CHARGE_RATE_SAMPLES {
+            if sample > 0 { std::thread::sleep(CHARGE_RATE_SAMPLE_INTERVAL); }
+            let rate = battery.GetReport()
+                .and_then(|report| report.ChargeRateInMilliwatts())
+                .and_then(|rate| rate.GetInt32());
+            if let Ok(milliwatts) = rate {
+                max_milliwatts = max_milliwatts.max(milliwatts);
+            }
+        }
+
+        if *IS_SYSTEM_ASLEEP.lock().unwrap() { return; }
+        if max_milliwatts > 0 {
+            let watts = ((max_milliwatts + 500) / 1000) as u32;
+            info!("Charger connected, charging at about {} W.", watts);
+            send_event(&sender, hwnd_value, SystemEvent::ChargerWattageReport { watts });
+        }
+    });
+}
+
 // This function correctly accepts the raw isize value.
+/// Name and type of the connection currently used for internet access.
+pub fn current_network_details() -> windows::core::Result<Option<(String, ConnectionType)>> {
+    let profile = NetworkInformation::GetInternetConnectionProfile()?;
+    let name = profile.ProfileName()?.to_string();
+    let iana_type = profile.NetworkAdapter()?.IanaInterfaceType()?;
+    let conn_type = match iana_type { 6 => ConnectionType::Ethernet, 71 => ConnectionType::WiFi, 243 | 244 => ConnectionType::Cellular, _ => ConnectionType::Unknown };
+    Ok(Some((name, conn_type)))
+}
+
 async fn setup_network_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) {
-    let get_details = || -> windows::core::Result<Option<(String, ConnectionType)>> {
-        let profile = NetworkInformation::GetInternetConnectionProfile()?;
-        let name = profile.ProfileName()?.to_string();
-        let iana_type = profile.NetworkAdapter()?.IanaInterfaceType()?;
-        let conn_type = match iana_type { 6 => ConnectionType::Ethernet, 71 => ConnectionType::WiFi, 243 | 244 => ConnectionType::Cellular, _ => ConnectionType::Unknown };
-        Ok(Some((name, conn_type)))
-    };
+    let get_details = current_network_details;
 
     let last_state = Arc::new(Mutex::new(get_details().ok().flatten()));
+    // A connection that has just dropped: (generation, name). The disconnect is only announced
+    // if no other network takes over within NETWORK_SWITCH_GRACE.
+    let pending_disconnect = Arc::new(Mutex::new(None::<(u64, String)>));
+    let generation = Arc::new(std::sync::atomic::AtomicU64::new(0));
     let handler = NetworkStatusChangedEventHandler::new({
         let sender_clone = sender.clone();
         let state_clone = last_state.clone();
@@ -157,19 +365,35 @@ async fn setup_network_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: is
             let mut last_details_guard = state_clone.lock().unwrap();
 
             if *last_details_guard != current_details {
-                // --- CORE FIX: Cast the isize back to a raw pointer and then create the HWND. ---
-                let hwnd = HWND(hwnd_value as *mut c_void);
+                let mut pending = pending_disconnect.lock().unwrap();
+                let previous_name = last_details_guard.as_ref().map(|(name, _)| name.clone())
+                    .or_else(|| pending.take().map(|(_, name)| name));
 
-                if last_details_guard.is_some() { 
-                    if sender_clone.send(SystemEvent::NetworkDisconnected).is_ok() {
-                        unsafe { PostMessageW(Some(hwnd), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+                match (&current_details, previous_name) {
+                    // Roaming between networks: one announcement instead of a disconnect and a connect.
+                    (Some((name, _)), Some(from)) if *name != from => {
+                        pending.take();
+                        send_event(&sender_clone, hwnd_value, SystemEvent::NetworkSwitched { from, to: name.clone() });
                     }
-                }
-                if let Some((name, conn_type)) = &current_details {
-                    let event = SystemEvent::NetworkConnected { name: name.clone(), conn_type: conn_type.clone() };
-                    if sender_clone.send(event).is_ok() {
-                        unsafe { PostMessageW(Some(hwnd), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+                    // The same network came back within the grace period: nothing to say.
+                    (Some(_), Some(_)) => { pending.take(); }
+                    (Some((name, conn_type)), None) => {
+                        send_event(&sender_clone, hwnd_value, SystemEvent::NetworkConnected { name: name.clone(), conn_type: conn_type.clone() });
+                    }
+                    (None, Some(from)) => {
+                        let this_generation = generation.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                        *pending = Some((this_generation, from));
+                        let (pending, sender) = (pending_disconnect.clone(), sender_clone.clone());
+                        std::thread::spawn(move || {
+                            std::thread::sleep(NETWORK_SWITCH_GRACE);
+                            let mut pending = pending.lock().unwrap();
+                            if pending.as_ref().is_some_and(|(g, _)| *g == this_generation) {
+                                pending.take();
+                                send_event(&sender, hwnd_value, SystemEvent::NetworkDisconnected);
+                            }
+                        });
                     }
+                    (None, None) => {}
                 }
                 *last_details_guard = current_details;
             }
@@ -0,0 +1,177 @@
+// src/format.rs
+//
+// Numbers, units, times and dates as they are read aloud in the active language. Unit words
+// come from the plural entries `unit_<name>` of the locale files, times and dates from the
+// patterns `time_format_spoken`, `time_format_spoken_12h` and `date_format_spoken`.
+
+use crate::config::ClockFormat;
+use crate::i18n::I18nManager;
+
+/// An integer with the language's digit grouping, e.g. "12,500".
+pub fn integer(i18n: &I18nManager, value: i64) -> String {
+    let separator = i18n.get_text("number_group_separator").unwrap_or_else(|| ",".to_string());
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 * separator.len() + 1);
+    if value < 0 {
+        grouped.push('-');
+    }
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push_str(&separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// A percentage, e.g. "80 percent" in English and "80%" in Chinese.
+pub fn percent(i18n: &I18nManager, value: impl Into<i64>) -> String {
+    let number = integer(i18n, value.into());
+    i18n.get_text_with_param("unit_percent", "value", &number).unwrap_or_else(|| format!("{}%", number))
+}
+
+/// A count followed by its unit word in the right plural form, e.g. "1 hour" or "2 hours".
+/// `unit` names the `unit_<unit>` entry.
+pub fn count_with_unit(i18n: &I18nManager, unit: &str, count: u64) -> String {
+    let number = integer(i18n, i64::try_from(count).unwrap_or(i64::MAX));
+    i18n.get_text_plural(&format!("unit_{}", unit), count, &[("count", &number)]).unwrap_or(number)
+}
+
+/// A duration given in minutes, e.g. "2 hours and 5 minutes" or "45 minutes".
+pub fn duration_minutes(i18n: &I18nManager, total_minutes: u64) -> String {
+    let (hours, minutes) = (total_minutes / 60, total_minutes % 60);
+    match (hours, minutes) {
+        (0, _) => count_with_unit(i18n, "minutes", minutes),
+        (_, 0) => count_with_unit(i18n, "hours", hours),
+        _ => i18n.get_text("duration_hours_minutes").unwrap_or_else(|| "{hours} {minutes}".to_string())
+            .replace("{hours}", &count_with_unit(i18n, "hours", hours))
+            .replace("{minutes}", &count_with_unit(i18n, "minutes", minutes)),
+    }
+}
+
+/// Whether times are read on the 12-hour clock: fixed by `clock`, or the language's habit
+/// (`time_hour_cycle`, "12" or "24") when it is left to the locale.
+pub fn uses_12_hour_clock(i18n: &I18nManager, clock: ClockFormat) -> bool {
+    match clock {
+        ClockFormat::TwelveHour => true,
+        ClockFormat::TwentyFourHour => false,
+        ClockFormat::Locale => i18n.get_text("time_hour_cycle").is_some_and(|cycle| cycle.trim() == "12"),
+    }
+}
+
+/// A time of day, e.g. "2:05 PM" in English, "14:05 Uhr" in German or "14時5分" in Japanese.
+/// The minute has two digits where the language writes it so (`time_minute_digits`).
+pub fn time_of_day(i18n: &I18nManager, clock: ClockFormat, hour: u16, minute: u16) -> String {
+    let twelve_hour = uses_12_hour_clock(i18n, clock);
+    let minute = match i18n.get_text("time_minute_digits").as_deref().map(str::trim) {
+        Some("1") => minute.to_string(),
+        _ => format!("{:02}", minute),
+    };
+    if !twelve_hour {
+        let hour = hour.to_string();
+        return i18n.get_text_with_params("time_format_spoken", &[("hour", &hour), ("minute", &minute)])
+            .unwrap_or_else(|| format!("{}:{}", hour, minute));
+    }
+    let period = i18n.get_text(if hour < 12 { "time_period_am" } else { "time_period_pm" }).unwrap_or_default();
+    let hour = match hour % 12 { 0 => 12, h => h }.to_string();
+    i18n.get_text_with_params("time_format_spoken_12h", &[("hour", &hour), ("minute", &minute), ("period", &period)])
+        .unwrap_or_else(|| format!("{}:{} {}", hour, minute, period))
+}
+
+/// A date with its weekday, e.g. "Friday, October 17". `weekday` counts from Sunday = 0 and
+/// `month` from January = 1, as in the system time.
+pub fn date(i18n: &I18nManager, year: u16, month: u16, day: u16, weekday: u16) -> String {
+    let (year, day) = (year.to_string(), day.to_string());
+    let weekday = i18n.get_text(&format!("weekday_{}", weekday)).unwrap_or_default();
+    let month = i18n.get_text(&format!("month_{}", month)).unwrap_or_else(|| month.to_string());
+    i18n.get_text_with_params("date_format_spoken", &[("weekday", &weekday), ("day", &day), ("month", &month), ("year", &year)])
+        .unwrap_or_else(|| format!("{}, {} {}", weekday, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each row is (locale, expected).
+    fn check(cases: &[(&str, &str)], format: impl Fn(&I18nManager) -> String) {
+        for (locale, expected) in cases {
+            assert_eq!(format(&I18nManager::for_tests(locale)), *expected, "locale {}", locale);
+        }
+    }
+
+    #[test]
+    fn groups_digits() {
+        let i18n = I18nManager::for_tests("en");
+        let cases = [(0, "0"), (999, "999"), (1000, "1,000"), (12500, "12,500"), (1234567, "1,234,567"), (-1000, "-1,000"), (i64::MIN, "-9,223,372,036,854,775,808")];
+        for (value, expected) in cases {
+            assert_eq!(integer(&i18n, value), expected);
+        }
+    }
+
+    #[test]
+    fn percent_per_locale() {
+        check(&[("en", "80 percent"), ("zh", "80%"), ("ja", "80%")], |i18n| percent(i18n, 80));
+    }
+
+    #[test]
+    fn plural_units_per_locale() {
+        check(&[("en", "1 hour"), ("zh", "1 小时"), ("ja", "1 時間")], |i18n| count_with_unit(i18n, "hours", 1));
+        check(&[("en", "2 hours"), ("zh", "2 小时"), ("ja", "2 時間")], |i18n| count_with_unit(i18n, "hours", 2));
+        check(&[("en", "1,500 minutes"), ("zh", "1,500 分钟"), ("ja", "1,500 分")], |i18n| count_with_unit(i18n, "minutes", 1500));
+    }
+
+    #[test]
+    fn durations_per_locale() {
+        check(&[("en", "45 minutes"), ("zh", "45 分钟"), ("ja", "45 分")], |i18n| duration_minutes(i18n, 45));
+        check(&[("en", "2 hours"), ("zh", "2 小时"), ("ja", "2 時間")], |i18n| duration_minutes(i18n, 120));
+        check(&[("en", "2 hours and 5 minutes"), ("zh", "2 小时 5 分钟"), ("ja", "2 時間 5 分")], |i18n| duration_minutes(i18n, 125));
+        check(&[("en", "1 hour and 1 minute"), ("zh", "1 小时 1 分钟"), ("ja", "1 時間 1 分")], |i18n| duration_minutes(i18n, 61));
+    }
+
+    #[test]
+    fn dates_per_locale() {
+        check(&[("en", "Friday, October 17"), ("zh", "10月17日，星期五"), ("ja", "10月17日金曜日")], |i18n| date(i18n, 2025, 10, 17, 5));
+    }
+
+    #[test]
+    fn hour_cycle_per_locale() {
+        let cases = [("en", true), ("zh", false), ("ja", false), ("de", false), ("fr", false), ("es", false), ("ko", true)];
+        for (locale, twelve_hour) in cases {
+            let i18n = I18nManager::for_tests(locale);
+            assert_eq!(uses_12_hour_clock(&i18n, ClockFormat::Locale), twelve_hour, "locale {}", locale);
+            assert!(uses_12_hour_clock(&i18n, ClockFormat::TwelveHour));
+            assert!(!uses_12_hour_clock(&i18n, ClockFormat::TwentyFourHour));
+        }
+    }
+
+    #[test]
+    fn time_of_day_per_locale() {
+        use ClockFormat::{Locale, TwelveHour, TwentyFourHour};
+        let cases = [
+            ("en", Locale, 14, 5, "2:05 PM"),
+            ("en", Locale, 0, 7, "12:07 AM"),
+            ("en", Locale, 12, 0, "12:00 PM"),
+            ("en", TwentyFourHour, 14, 5, "14:05"),
+            ("en", TwentyFourHour, 0, 7, "0:07"),
+            ("zh", Locale, 14, 5, "14点5分"),
+            ("zh", TwentyFourHour, 0, 7, "0点7分"),
+            ("zh", TwelveHour, 14, 5, "下午2点5分"),
+            ("zh", TwelveHour, 0, 7, "上午12点7分"),
+            ("ja", Locale, 14, 5, "14時5分"),
+            ("ja", TwelveHour, 14, 5, "午後2時5分"),
+            ("ja", TwelveHour, 11, 59, "午前11時59分"),
+            ("de", Locale, 14, 5, "14:05 Uhr"),
+            ("de", TwelveHour, 9, 30, "9:30 Uhr vormittags"),
+            ("fr", Locale, 14, 5, "14 h 05"),
+            ("fr", TwelveHour, 14, 5, "2 h 05 de l'après-midi"),
+            ("es", Locale, 14, 5, "14:05"),
+            ("es", TwelveHour, 14, 5, "2:05 de la tarde"),
+            ("ko", Locale, 14, 5, "오후 2시 5분"),
+            ("ko", TwentyFourHour, 14, 5, "14시 5분"),
+        ];
+        for (locale, clock, hour, minute, expected) in cases {
+            let i18n = I18nManager::for_tests(locale);
+            assert_eq!(time_of_day(&i18n, clock, hour, minute), expected, "{} {:?} {}:{}", locale, clock, hour, minute);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// src/gpu_monitor.rs
+
+use std::ffi::c_void;
+use std::sync::mpsc;
+use log::{info, warn};
+use windows::core::w;
+use windows::Win32::System::EventLog::{
+    EvtRender, EvtSubscribe, EvtRenderEventXml, EvtSubscribeActionDeliver, EvtSubscribeToFutureEvents,
+    EVT_HANDLE, EVT_SUBSCRIBE_NOTIFY_ACTION,
+};
+use windows::Win32::Foundation::HWND;
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+/// Passed to the subscription callback, which Windows invokes on its own thread pool.
+struct SubscriptionContext {
+    sender: mpsc::Sender<SystemEvent>,
+    hwnd_value: isize,
+}
+
+/// Subscribes to "Display driver stopped responding and has successfully recovered"
+/// (System log, Event ID 4101). If the subscription is refused, logs once and stays disabled.
+pub fn start_gpu_monitor(sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        let context = Box::new(SubscriptionContext { sender, hwnd_value });
+        let subscription = unsafe {
+            EvtSubscribe(
+                None,
+                None,
+                w!("System"),
+                w!("*[System[(EventID=4101)]]"),
+                None,
+                Some(&*context as *const SubscriptionContext as *const c_void),
+                Some(on_event),
+                EvtSubscribeToFutureEvents.0,
+            )
+        };
+        match subscription {
+            Ok(handle) => {
+                info!("显卡驱动重置监控已启动。");
+                // The subscription and its context must outlive every callback; keep both for the life of the process.
+                std::mem::forget(context);
+                let _subscription = handle;
+                loop { std::thread::park(); }
+            }
+            Err(e) => warn!("显卡驱动重置监控已禁用: 无法订阅系统事件日志: {}", e),
+        }
+    });
+}
+
+unsafe extern "system" fn on_event(action: EVT_SUBSCRIBE_NOTIFY_ACTION, user_context: *const c_void, event: EVT_HANDLE) -> u32 {
+    if action != EvtSubscribeActionDeliver || user_context.is_null() { return 0; }
+    let context = unsafe { &*(user_context as *const SubscriptionContext) };
+    let driver = render_event_xml(event).and_then(|xml| first_data_value(&xml)).unwrap_or_default();
+    warn!("检测到显卡驱动重置: {}", driver);
+    send_event(&context.sender, context.hwnd_value, SystemEvent::DisplayDriverReset { driver });
+    0
+}
+
+fn render_event_xml(event: EVT_HANDLE) -> Option<String> {
+    let (mut used, mut count) = (0u32, 0u32);
+    // The first call only reports the required buffer size (in bytes).
+    unsafe { EvtRender(None, event, EvtRenderEventXml.0, 0, None, &mut used, &mut count).ok() };
+    if used == 0 { return None; }
+    let mut buffer = vec![0u16; used as usize / 2 + 1];
+    unsafe {
+        EvtRender(None, event, EvtRenderEventXml.0, (buffer.len() * 2) as u32, Some(buffer.as_mut_ptr() as *mut c_void), &mut used, &mut count).ok()?;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
+/// Event 4101 carries the driver's service name (e.g. "nvlddmkm") as its first data item.
+fn first_data_value(xml: &str) -> Option<String> {
+    let tag = xml.find("<Data")?;
+    let open_end = tag + xml[tag..].find('>')?;
+    if xml[..open_end].ends_with('/') { return None; }
+    let start = open_end + 1;
+    let end = start + xml[start..].find("</Data>")?;
+    let value = xml[start..end].trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
@@ -0,0 +1,62 @@
+// src/hotkey.rs
+
+use log::{info, warn};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+};
+
+/// WM_HOTKEY id of the "stop speaking" shortcut.
+pub const HOTKEY_ID_STOP: i32 = 1;
+
+/// Parses a shortcut such as `"Ctrl+Alt+S"` or `"Win+Shift+F9"`. Modifiers are Ctrl, Alt,
+/// Shift and Win; the key is a letter, a digit or F1-F24. At least one modifier is required so
+/// that a plain key is never swallowed system-wide.
+pub fn parse_hotkey(text: &str) -> Option<(HOT_KEY_MODIFIERS, u32)> {
+    let mut modifiers = HOT_KEY_MODIFIERS(0);
+    let mut key = None;
+    for part in text.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= MOD_CONTROL,
+            "alt" => modifiers |= MOD_ALT,
+            "shift" => modifiers |= MOD_SHIFT,
+            "win" | "windows" => modifiers |= MOD_WIN,
+            _ if key.is_none() => key = Some(virtual_key(part)?),
+            _ => return None,
+        }
+    }
+    if modifiers.0 == 0 { return None; }
+    Some((modifiers | MOD_NOREPEAT, key?))
+}
+
+fn virtual_key(name: &str) -> Option<u32> {
+    let upper = name.to_ascii_uppercase();
+    let mut chars = upper.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => Some(c as u32),
+        (Some('F'), Some(_)) => match upper[1..].parse::<u32>() {
+            // VK_F1 is 0x70 and the function keys are consecutive up to VK_F24.
+            Ok(n @ 1..=24) => Some(0x6F + n),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Registers the "stop speaking" shortcut on the hidden window. An empty setting disables it.
+pub fn register_stop_hotkey(hwnd: HWND, shortcut: &str) {
+    if shortcut.trim().is_empty() { return; }
+    let Some((modifiers, key)) = parse_hotkey(shortcut) else {
+        warn!("无法识别停止播报的快捷键 '{}'，快捷键未启用。", shortcut);
+        return;
+    };
+    match unsafe { RegisterHotKey(Some(hwnd), HOTKEY_ID_STOP, modifiers, key) } {
+        Ok(()) => info!("已注册停止播报的快捷键: {}", shortcut),
+        // Usually another application already owns the same combination.
+        Err(e) => warn!("注册停止播报的快捷键 '{}' 失败: {}", shortcut, e),
+    }
+}
+
+pub fn unregister_stop_hotkey(hwnd: HWND) {
+    unsafe { let _ = UnregisterHotKey(Some(hwnd), HOTKEY_ID_STOP); }
+}
@@ -1,38 +1,927 @@
 // src/i18n.rs
 
-use serde_json::Value;
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use log::{debug, info, warn};
+
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::locale_check::{self, is_placeholder_name, placeholders, PLURAL_CATEGORIES};
+use crate::ssml;
+
+/// 编译进程序的语言档案。程序目录下的 locales/{locale}.json 存在时优先使用，
+/// 以便用户修改翻译或加入新的语言
+const EMBEDDED_LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("zh", include_str!("../locales/zh.json")),
+    ("ja", include_str!("../locales/ja.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("fr", include_str!("../locales/fr.json")),
+    ("es", include_str!("../locales/es.json")),
+    ("ko", include_str!("../locales/ko.json")),
+];
+
+const LOCALE_DIR: &str = "locales";
+/// 每个语言档案中该语言自己的名称，例如 "日本語"，显示在设置窗口的语言列表中
+const LANGUAGE_NAME_KEY: &str = "_language_name";
+
+/// 其他语言缺少的条目用这个语言补上
+const FALLBACK_LOCALE: &str = "en";
+
+/// 只写地区的语言标签所用的文字，例如台湾用繁体中文。`zh-TW` 因此依次尝试
+/// zh-TW、zh-Hant、zh 的语言档案
+const IMPLIED_SCRIPTS: &[(&str, &str)] = &[
+    ("zh-TW", "zh-Hant"),
+    ("zh-HK", "zh-Hant"),
+    ("zh-MO", "zh-Hant"),
+    ("zh-CN", "zh-Hans"),
+    ("zh-SG", "zh-Hans"),
+    ("zh-MY", "zh-Hans"),
+];
+
+/// 切换语言前必须存在的条目。缺少这些条目时托盘菜单或设置窗口会显示为空白。
+const CRITICAL_KEYS: &[&str] = &[
+    "system_online",
+    "menu_pause",
+    "menu_resume",
+    "menu_settings",
+    "menu_exit",
+    "settings_window_title",
+    "settings_label_voice",
+    "settings_label_language",
+    "settings_button_ok",
+    "settings_button_cancel",
+    "announcement_language_changed",
+];
+
+/// 只显示在界面上、不会被朗读的条目前缀，编辑播报文字时不列出
+const NON_SPOKEN_PREFIXES: &[&str] = &[
+    "_", "settings_", "about_", "log_viewer_", "menu_", "tray_", "tts_", "unit_", "number_", "duration_", "engine_health_",
+    "time_hour_", "time_minute_", "time_format_", "time_period_", "date_format_", "weekday_", "month_",
+];
+
+/// 条目的文字来自哪一层，按优先顺序排列
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextSource {
+    /// config.json 中的 custom_messages
+    CustomMessage,
+    /// 数据目录下的用户语言档案 {locale}.custom.json
+    CustomFile,
+    /// 该语言的语言档案
+    Locale,
+    /// 英文语言档案
+    Fallback,
+}
+
+impl fmt::Display for TextSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextSource::CustomMessage => "custom_messages",
+            TextSource::CustomFile => "用户语言档案",
+            TextSource::Locale => "语言档案",
+            TextSource::Fallback => "英文语言档案",
+        })
+    }
+}
 
 pub struct I18nManager {
+    // 语言标签，例如 "en"、"zh"、"zh-TW"
+    locale: String,
     translations: HashMap<String, String>,
+    // 英文语言档案，该语言缺少某个条目时使用。当前语言就是英文时为空
+    fallback: HashMap<String, String>,
+    // 用户语言档案 {locale}.custom.json 中的条目，优先于语言档案
+    custom_strings: HashMap<String, String>,
+    // 用户在 custom_messages 中改写的条目，优先于用户语言档案
+    overrides: HashMap<String, String>,
+    // 已经在日志中提示过缺少的条目，每个条目只提示一次
+    reported_missing: Mutex<HashSet<String>>,
 }
 
 impl I18nManager {
-    pub fn new(locale: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let path = format!("locales/{}.json", locale);
-        let data = fs::read_to_string(path)?;
-        let parsed: Value = serde_json::from_str(&data)?;
-        
-        let mut translations = HashMap::new();
-        if let Value::Object(map) = parsed {
-            for (key, value) in map {
-                if let Value::String(s) = value {
-                    translations.insert(key, s);
+    /// 加载语言标签的语言档案 (见 fallback_chain)，缺少的条目用英文补上。
+    /// 语言档案都不存在时只使用英文并记录警告
+    pub fn new(locale: &str) -> AppResult<Self> {
+        let mut manager = Self::new_from(Path::new(LOCALE_DIR), locale)?;
+        manager.reload_custom_strings();
+        Ok(manager)
+    }
+
+    // new 从 dir 而不是程序目录下的 locales 文件夹读取磁盘上的语言档案，且不读取用户语言档案
+    fn new_from(dir: &Path, locale: &str) -> AppResult<Self> {
+        let locale = normalize_tag(locale);
+        let locale = locale.as_str();
+        match read_locale_chain(dir, locale) {
+            Ok(translations) => Ok(Self::without_custom_strings(dir, locale, translations)),
+            Err(e @ AppError::LocaleMissing { .. }) if locale != FALLBACK_LOCALE => {
+                warn!("{}。只使用英文。", e);
+                Ok(Self::without_custom_strings(dir, FALLBACK_LOCALE, read_locale(dir, FALLBACK_LOCALE)?))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn with_fallback(dir: &Path, locale: &str, translations: HashMap<String, String>) -> Self {
+        let mut manager = Self::without_custom_strings(dir, locale, translations);
+        manager.reload_custom_strings();
+        manager
+    }
+
+    fn without_custom_strings(dir: &Path, locale: &str, translations: HashMap<String, String>) -> Self {
+        let fallback = if locale == FALLBACK_LOCALE {
+            HashMap::new()
+        } else {
+            read_locale(dir, FALLBACK_LOCALE).unwrap_or_else(|e| {
+                warn!("无法载入英文语言档案，缺少的条目将无法补上: {}", e);
+                HashMap::new()
+            })
+        };
+        I18nManager {
+            locale: locale.to_string(),
+            translations,
+            fallback,
+            custom_strings: HashMap::new(),
+            overrides: HashMap::new(),
+            reported_missing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 只使用代码库中的语言档案，不读取用户语言档案，测试结果不受本机设置影响
+    #[cfg(test)]
+    pub fn for_tests(locale: &str) -> Self {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(LOCALE_DIR);
+        Self::without_custom_strings(&dir, locale, read_locale_chain(&dir, locale).unwrap())
+    }
+
+    /// 重新读取用户语言档案 (不存在时不使用)。与语言档案一样按 fallback_chain 叠加，
+    /// 例如 zh-TW 也使用 zh.custom.json 中 zh-TW.custom.json 没有改写的条目。
+    /// 档案格式有误时记录警告并忽略，语言档案中没有的条目同样忽略
+    pub fn reload_custom_strings(&mut self) {
+        self.custom_strings.clear();
+        for code in locale_layers(&self.locale).iter().rev() {
+            let path = config::custom_locale_path(code);
+            let data = match fs::read_to_string(&path) {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    warn!("无法读取用户语言档案 {}，已忽略: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let strings = match parse_locale(code, &data) {
+                Ok(strings) => strings,
+                Err(e) => {
+                    warn!("用户语言档案 {} 格式有误，已忽略: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let mut used = 0;
+            for (key, text) in strings {
+                if self.translations.contains_key(&key) || self.fallback.contains_key(&key) {
+                    self.custom_strings.insert(key, text);
+                    used += 1;
+                } else {
+                    warn!("用户语言档案 {} 中的 '{}' 不是语言档案中的条目，不会被使用。", path.display(), key);
                 }
             }
+            info!("使用用户语言档案 {}，共 {} 条。", path.display(), used);
+        }
+    }
+
+    /// 使用 Config.custom_messages 中的改写。键为 "system_online" 时适用于所有语言，
+    /// 为 "zh:system_online" 时只适用于该语言 (以及 zh-TW 等地区) 并优先。文字中出现
+    /// 语言档案里没有的 `{参数}` 时照原样朗读，并在日志中提示。
+    pub fn set_overrides(&mut self, custom_messages: &HashMap<String, String>) {
+        self.overrides.clear();
+        let layers = locale_layers(&self.locale);
+        let keys: HashSet<&str> = custom_messages.keys()
+            .filter_map(|entry| match entry.split_once(':') {
+                Some((locale, key)) => layers.iter().any(|layer| layer == locale).then_some(key),
+                None => Some(entry.as_str()),
+            })
+            .collect();
+        for key in keys {
+            let Some(text) = custom_override(custom_messages, &self.locale, key) else { continue };
+            if !self.translations.contains_key(key) && !self.fallback.contains_key(key) {
+                warn!("custom_messages 中的 '{}' 不是语言档案 '{}' 中的条目，不会被使用。", key, self.locale);
+                continue;
+            }
+            let unknown = self.unknown_placeholders(key, text);
+            if !unknown.is_empty() {
+                warn!("自定义播报 '{}' 中的参数 {{{}}} 不存在，将照原样朗读。", key, unknown.join("}, {"));
+            }
+            let text = checked_ssml(text.clone(), || format!("自定义播报 '{}'", key));
+            self.overrides.insert(key.to_string(), text);
+        }
+        if !self.overrides.is_empty() {
+            info!("语言 '{}' 使用 {} 条自定义播报文字。", self.locale, self.overrides.len());
         }
+    }
+
+    /// 加载语言档案并确认关键条目齐全 (可以由英文补上)。用于运行时切换语言：
+    /// 与 new 不同，语言档案不存在时返回错误，调用者可以保持当前语言不变。
+    pub fn load_validated(locale: &str) -> AppResult<Self> {
+        Self::load_validated_from(Path::new(LOCALE_DIR), locale)
+    }
 
-        Ok(I18nManager { translations })
+    // load_validated 从 dir 而不是程序目录下的 locales 文件夹读取磁盘上的语言档案
+    fn load_validated_from(dir: &Path, locale: &str) -> AppResult<Self> {
+        let locale = normalize_tag(locale);
+        let manager = Self::with_fallback(dir, &locale, read_locale_chain(dir, &locale)?);
+        let missing: Vec<String> = CRITICAL_KEYS.iter()
+            .filter(|key| !manager.translations.contains_key(**key) && !manager.fallback.contains_key(**key))
+            .map(|key| key.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(AppError::LocaleIncomplete { locale, missing });
+        }
+        Ok(manager)
+    }
+
+    /// 当前语言代码
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// 条目的模板：custom_messages 优先，其次是用户语言档案、语言档案，最后是英文
+    fn template(&self, key: &str) -> Option<&String> {
+        self.own_template(key).or_else(|| self.fallback_template(key))
+    }
+
+    fn own_template(&self, key: &str) -> Option<&String> {
+        let text = self.overrides.get(key)
+            .or_else(|| self.custom_strings.get(key))
+            .or_else(|| self.translations.get(key));
+        if text.is_some() {
+            self.log_source(key);
+        }
+        text
+    }
+
+    fn fallback_template(&self, key: &str) -> Option<&String> {
+        let text = self.fallback.get(key);
+        if text.is_some() && self.reported_missing.lock().unwrap().insert(key.to_string()) {
+            warn!("语言档案 '{}' 缺少条目 '{}'，使用英文。", self.locale, key);
+        }
+        text
+    }
+
+    /// 条目当前的文字来自哪一层，不存在时为 None
+    pub fn source_of(&self, key: &str) -> Option<TextSource> {
+        if self.overrides.contains_key(key) {
+            Some(TextSource::CustomMessage)
+        } else if self.custom_strings.contains_key(key) {
+            Some(TextSource::CustomFile)
+        } else if self.translations.contains_key(key) {
+            Some(TextSource::Locale)
+        } else if self.fallback.contains_key(key) {
+            Some(TextSource::Fallback)
+        } else {
+            None
+        }
+    }
+
+    // 只记录用户改写过的条目，语言档案本身的条目太多
+    fn log_source(&self, key: &str) {
+        if !log::log_enabled!(log::Level::Debug) { return; }
+        if let Some(source @ (TextSource::CustomMessage | TextSource::CustomFile)) = self.source_of(key) {
+            debug!("条目 '{}' 使用{}中的文字", key, source);
+        }
     }
 
     pub fn get_text(&self, key: &str) -> Option<String> {
-        self.translations.get(key).cloned()
+        self.template(key).cloned()
     }
 
+    /// custom_messages 以外的文字：用户语言档案，其次是语言档案 (缺少时为英文)
+    pub fn base_text(&self, key: &str) -> Option<String> {
+        self.custom_strings.get(key).or_else(|| self.translations.get(key)).or_else(|| self.fallback.get(key)).cloned()
+    }
+
+    /// 条目在语言档案 (缺少时为英文) 中的 `{参数}`，按出现的顺序。改写的文字只能使用这些参数
+    pub fn placeholders_of(&self, key: &str) -> Vec<String> {
+        self.translations.get(key).or_else(|| self.fallback.get(key))
+            .map(|base| placeholders(base))
+            .unwrap_or_default()
+    }
+
+    /// 改写的文字中条目没有提供的 `{参数}`，朗读时会照原样读出
+    pub fn unknown_placeholders(&self, key: &str, text: &str) -> Vec<String> {
+        let known = self.placeholders_of(key);
+        placeholders(text).into_iter().filter(|name| !known.contains(name)).collect()
+    }
+
+    /// 会被朗读的条目，按名称排序，供设置窗口编辑播报文字
+    pub fn announcement_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.translations.keys().chain(self.fallback.keys())
+            .filter(|key| !NON_SPOKEN_PREFIXES.iter().any(|prefix| key.starts_with(prefix)))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// 替换条目中的 `{param_key}`，见 get_text_with_params。
     pub fn get_text_with_param(&self, key: &str, param_key: &str, param_value: &str) -> Option<String> {
-        self.translations.get(key).map(|s| {
-            s.replace(&format!("{{{}}}", param_key), param_value)
+        self.get_text_with_params(key, &[(param_key, param_value)])
+    }
+
+    /// 替换条目中所有的 `{名称}`，`{{` 和 `}}` 表示花括号本身。没有提供的参数照原样保留，
+    /// 并在日志中列出。条目是 SSML 时会对参数值做 XML 转义，例如含有 "&" 的网络名称不会破坏标记。
+    pub fn get_text_with_params(&self, key: &str, params: &[(&str, &str)]) -> Option<String> {
+        let template = self.template(key)?;
+        let (text, missing) = interpolate(template, params, ssml::is_ssml(template));
+        if !missing.is_empty() {
+            warn!("条目 '{}' 的参数 {{{}}} 没有提供，照原样保留。", key, missing.join("}, {"));
+        }
+        Some(text)
+    }
+
+    /// 按当前语言的复数规则选择条目的形式，例如 `{"one": "...", "other": "..."}` 中的一个，
+    /// 再替换其中的参数。`{count}` 没有在 params 中提供时替换为数量本身。
+    /// 缺少该形式时使用 "other"，条目是普通文字时直接使用。
+    pub fn get_text_plural(&self, key: &str, count: u64, params: &[(&str, &str)]) -> Option<String> {
+        // 先在本语言中找，只提供 "other" 的语言不会因为英文有 "one" 而改用英文
+        let own = |category: &str| self.own_template(&plural_key(key, category))
+            .or_else(|| self.own_template(&plural_key(key, "other")))
+            .or_else(|| self.own_template(key));
+        let fallback = |category: &str| self.fallback_template(&plural_key(key, category))
+            .or_else(|| self.fallback_template(&plural_key(key, "other")))
+            .or_else(|| self.fallback_template(key));
+        let template = own(plural_category(&self.locale, count))
+            .or_else(|| fallback(plural_category(FALLBACK_LOCALE, count)))?;
+        let count = count.to_string();
+        let mut params = params.to_vec();
+        if !params.iter().any(|(name, _)| *name == "count") {
+            params.push(("count", &count));
+        }
+        let (text, missing) = interpolate(template, &params, ssml::is_ssml(template));
+        if !missing.is_empty() {
+            warn!("条目 '{}' 的参数 {{{}}} 没有提供，照原样保留。", key, missing.join("}, {"));
+        }
+        Some(text)
+    }
+}
+
+/// 可以选择的语言：(语言代码, 该语言自己的名称)。内置的语言在前，其后是 locales 文件夹中
+/// 另外加入的语言。无法读取的档案记录警告后跳过，没有 `_language_name` 时以语言代码显示
+pub fn available_locales() -> Vec<(String, String)> {
+    locales_in(Path::new(LOCALE_DIR))
+}
+
+fn locales_in(dir: &Path) -> Vec<(String, String)> {
+    EMBEDDED_LOCALES.iter().map(|(code, _)| code.to_string())
+        .chain(on_disk_locales(dir))
+        .filter_map(|code| match read_locale(dir, &code) {
+            Ok(translations) => {
+                let name = translations.get(LANGUAGE_NAME_KEY).cloned().unwrap_or_else(|| code.clone());
+                Some((code, name))
+            }
+            Err(e) => {
+                warn!("跳过无法使用的语言档案 '{}': {}", code, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// 是否有语言标签可用的语言档案 (内置的或 locales 文件夹中的，较一般的 zh 对 zh-TW 也算)，
+/// 用于按系统语言选择默认语言。英文总是可用
+pub fn is_supported(tag: &str) -> bool {
+    locale_layers(tag).iter()
+        .any(|code| EMBEDDED_LOCALES.iter().any(|(embedded, _)| embedded == code) || locale_path(Path::new(LOCALE_DIR), code).exists())
+}
+
+/// 把语言标签整理成通常的写法：语言小写、文字首字母大写、地区大写，以 "-" 分隔，
+/// 例如 "zh_tw" → "zh-TW"、"ZH-hant-tw" → "zh-Hant-TW"
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split(['-', '_'])
+        .filter(|subtag| !subtag.is_empty())
+        .enumerate()
+        .map(|(i, subtag)| match subtag.len() {
+            _ if i == 0 => subtag.to_ascii_lowercase(),
+            2 => subtag.to_ascii_uppercase(),
+            4 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                let (first, rest) = subtag.split_at(1);
+                first.to_ascii_uppercase() + &rest.to_ascii_lowercase()
+            }
+            _ => subtag.to_ascii_lowercase(),
         })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 语言标签依次尝试的语言，从最具体的开始，最后是英文。每次去掉最后一个子标签，
+/// 只写地区时先尝试该地区所用的文字，例如 "zh-TW" → zh-TW、zh-Hant、zh、en，
+/// "pt-BR" → pt-BR、pt、en
+pub fn fallback_chain(tag: &str) -> Vec<String> {
+    let tag = normalize_tag(tag);
+    let subtags: Vec<&str> = tag.split('-').collect();
+    let mut chain: Vec<String> = Vec::new();
+    let mut push = |code: &str| {
+        if !code.is_empty() && !chain.iter().any(|known| known == code) {
+            chain.push(code.to_string());
+        }
+    };
+    for len in (1..=subtags.len()).rev() {
+        let code = subtags[..len].join("-");
+        push(&code);
+        if let Some((_, script)) = IMPLIED_SCRIPTS.iter().find(|(region, _)| *region == code) {
+            push(script);
+        }
+    }
+    push(FALLBACK_LOCALE);
+    chain
+}
+
+/// `codes` 中与语言标签最接近的一个的位置，按 fallback_chain 的顺序比较
+pub fn closest_locale(tag: &str, codes: &[String]) -> Option<usize> {
+    fallback_chain(tag).iter().find_map(|code| codes.iter().position(|known| known.eq_ignore_ascii_case(code)))
+}
+
+// 语言标签叠加使用的语言档案：fallback_chain 中除英文以外的部分 (英文另外作为 fallback)，
+// 本身是英文 (例如 en-GB) 时包括英文
+fn locale_layers(tag: &str) -> Vec<String> {
+    let mut layers = fallback_chain(tag);
+    if !primary_language(tag).eq_ignore_ascii_case(FALLBACK_LOCALE) {
+        layers.retain(|code| code != FALLBACK_LOCALE);
     }
-}
\ No newline at end of file
+    layers
+}
+
+fn primary_language(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap_or(tag)
+}
+
+/// dir 中内置语言以外的语言代码，按代码排序
+fn on_disk_locales(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut codes: Vec<String> = entries.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        if path.extension()? != "json" { return None; }
+        let code = path.file_stem()?.to_str()?;
+        // 带点的文件名 (例如 zh.custom.json) 不是语言档案
+        (!code.contains('.') && !EMBEDDED_LOCALES.iter().any(|(embedded, _)| *embedded == code)).then(|| code.to_string())
+    }).collect();
+    codes.sort();
+    codes
+}
+
+/// 读取语言标签的全部条目：按 locale_layers 从一般到具体叠加，较具体的档案 (例如 zh-TW.json)
+/// 只需写与 zh.json 不同的条目。一个档案都没有时返回最具体的那个不存在的错误
+fn read_locale_chain(dir: &Path, tag: &str) -> AppResult<HashMap<String, String>> {
+    let mut translations = HashMap::new();
+    let mut found = false;
+    let mut missing = None;
+    for code in locale_layers(tag).iter().rev() {
+        match read_locale(dir, code) {
+            Ok(layer) => {
+                // 较具体的档案本来就只有部分条目，只有最一般的档案与英文比较
+                if code != FALLBACK_LOCALE && !found {
+                    log_differences(dir, code);
+                }
+                translations.extend(layer);
+                found = true;
+            }
+            Err(e @ AppError::LocaleMissing { .. }) => missing = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    match missing {
+        Some(e) if !found => Err(e),
+        _ => {
+            if let Some(e) = missing {
+                debug!("{}，使用较一般的语言档案。", e);
+            }
+            Ok(translations)
+        }
+    }
+}
+
+/// 读取语言的全部条目：优先使用 dir 中的 {locale}.json，不存在时使用内置的档案。
+/// 磁盘上的档案格式有误而又有内置档案时，记录警告并使用内置档案
+fn read_locale(dir: &Path, locale: &str) -> AppResult<HashMap<String, String>> {
+    let embedded = EMBEDDED_LOCALES.iter().find(|(code, _)| *code == locale).map(|(_, data)| *data);
+    let path = locale_path(dir, locale);
+    match fs::read_to_string(&path) {
+        Ok(data) => match parse_locale(locale, &data) {
+            Ok(translations) => {
+                debug!("使用语言档案 {}", path.display());
+                return Ok(translations);
+            }
+            Err(e) if embedded.is_some() => warn!("{}。改用内置的语言档案。", e),
+            Err(e) => return Err(e),
+        },
+        Err(source) if embedded.is_none() => return Err(AppError::LocaleMissing { locale: locale.to_string(), source }),
+        Err(_) => {}
+    }
+    parse_locale(locale, embedded.unwrap_or_default())
+}
+
+fn locale_path(dir: &Path, locale: &str) -> PathBuf {
+    dir.join(format!("{}.json", locale))
+}
+
+/// 在调试日志中列出语言档案与英文的差异：缺少或多余的条目、参数不同的条目
+fn log_differences(dir: &Path, locale: &str) {
+    if !log::log_enabled!(log::Level::Debug) { return; }
+    let (Some(reference), Some(translation)) = (raw_locale(dir, FALLBACK_LOCALE), raw_locale(dir, locale)) else { return };
+    let problems = locale_check::compare(&reference, &translation);
+    for problem in &problems {
+        debug!("语言档案 '{}': {}", locale, problem);
+    }
+    debug!("语言档案 '{}' 与英文比较完毕，{} 处差异。", locale, problems.len());
+}
+
+// 语言档案未经转换的内容，与 read_locale 选择同一个档案
+fn raw_locale(dir: &Path, locale: &str) -> Option<Map<String, Value>> {
+    let embedded = EMBEDDED_LOCALES.iter().find(|(code, _)| *code == locale).map(|(_, data)| *data);
+    fs::read_to_string(locale_path(dir, locale)).ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .or_else(|| embedded.and_then(|data| serde_json::from_str(data).ok()))
+}
+
+fn parse_locale(locale: &str, data: &str) -> AppResult<HashMap<String, String>> {
+    let parsed: Value = serde_json::from_str(data)
+        .map_err(|source| AppError::LocaleInvalid { locale: locale.to_string(), source })?;
+
+    let mut translations = HashMap::new();
+    if let Value::Object(map) = parsed {
+        for (key, value) in map {
+            match value {
+                // 空的条目是尚未翻译的模板 (见 export_locale_template)，由英文补上
+                Value::String(s) if s.is_empty() => {}
+                Value::String(s) => {
+                    let s = checked_ssml(s, || format!("语言档案 '{}' 中的条目 '{}'", locale, key));
+                    translations.insert(key, s);
+                }
+                // 复数条目，每种形式保存为 "{key}_{形式}"
+                Value::Object(forms) => {
+                    if !forms.contains_key("other") {
+                        warn!("语言档案 '{}' 中的复数条目 '{}' 缺少 \"other\" 形式。", locale, key);
+                    }
+                    for (category, form) in forms {
+                        let Value::String(s) = form else { continue };
+                        if s.is_empty() { continue; }
+                        if !PLURAL_CATEGORIES.contains(&category.as_str()) {
+                            warn!("语言档案 '{}' 中的复数条目 '{}' 有未知的形式 '{}'，已忽略。", locale, key, category);
+                            continue;
+                        }
+                        let s = checked_ssml(s, || format!("语言档案 '{}' 中的条目 '{}' ({})", locale, key, category));
+                        translations.insert(plural_key(&key, &category), s);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(translations)
+}
+
+/// 复数条目的一种形式在 translations 中的键，例如 "unit_hours_one"
+fn plural_key(key: &str, category: &str) -> String {
+    format!("{}_{}", key, category)
+}
+
+/// 数量在该语言中的复数形式。英文等语言区分 "one" 和 "other"，法文的 0 也用 "one"，
+/// 中文、日文和韩文的名词没有单复数变化，总是 "other"
+fn plural_category(locale: &str, count: u64) -> &'static str {
+    match primary_language(locale) {
+        "zh" | "ja" | "ko" => "other",
+        "fr" if count <= 1 => "one",
+        _ if count == 1 => "one",
+        _ => "other",
+    }
+}
+
+/// custom_messages 中某个条目对指定语言生效的改写：该语言专用的优先 (zh-TW 也使用 "zh:" 的改写)，
+/// 其次是适用于所有语言的
+pub fn custom_override<'a>(custom_messages: &'a HashMap<String, String>, locale: &str, key: &str) -> Option<&'a String> {
+    locale_layers(locale).iter()
+        .find_map(|layer| custom_messages.get(&custom_message_key(layer, key)))
+        .or_else(|| custom_messages.get(key))
+}
+
+/// 只适用于一种语言的改写在 custom_messages 中的键，例如 "zh:system_online"
+pub fn custom_message_key(locale: &str, key: &str) -> String {
+    format!("{}:{}", locale, key)
+}
+
+/// 替换任意文字 (例如设置窗口中正在编辑的播报) 中的参数，规则与 get_text_with_params 相同。
+/// 没有提供的参数照原样保留
+pub fn fill_params(text: &str, params: &[(&str, &str)]) -> String {
+    let text = checked_ssml(text.to_string(), || "编辑中的播报文字".to_string());
+    interpolate(&text, params, ssml::is_ssml(&text)).0
+}
+
+/// 把 `{名称}` 换成参数值，`{{` / `}}` 换成花括号。参数值原样插入，其中的花括号不再替换。
+/// 返回结果和没有提供值的参数名称
+fn interpolate(template: &str, params: &[(&str, &str)], escape: bool) -> (String, Vec<String>) {
+    let mut result = String::with_capacity(template.len());
+    let mut missing = Vec::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            result.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let name = rest.strip_prefix('{')
+            .and_then(|inner| inner.find('}').map(|end| &inner[..end]))
+            .filter(|name| is_placeholder_name(name));
+        match name {
+            Some(name) => {
+                match params.iter().find(|(param, _)| *param == name) {
+                    Some((_, value)) if escape => result.push_str(&ssml::escape(value)),
+                    Some((_, value)) => result.push_str(value),
+                    None => {
+                        result.push_str(&rest[..name.len() + 2]);
+                        missing.push(name.to_string());
+                    }
+                }
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                result.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    (result, missing)
+}
+
+// 标记有误的 SSML 条目改为去掉标记的纯文本，至少还能正常朗读
+fn checked_ssml(s: String, describe: impl FnOnce() -> String) -> String {
+    if ssml::is_ssml(&s) && !ssml::is_well_formed(&s) {
+        warn!("{} 不是有效的 SSML，将去掉标记后朗读。", describe());
+        ssml::strip_tags(&s)
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // en.json 只有少数条目，用来检查无法由英文补上的缺失
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/locales")
+    }
+
+    // 只翻译了少数条目的 zh.json，英文使用内置的档案
+    fn incomplete_zh() -> I18nManager {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/incomplete_zh");
+        I18nManager::without_custom_strings(&dir, "zh", read_locale_chain(&dir, "zh").unwrap())
+    }
+
+    #[test]
+    fn malformed_locale_is_invalid() {
+        match I18nManager::load_validated_from(&fixtures(), "malformed") {
+            Err(AppError::LocaleInvalid { locale, .. }) => assert_eq!(locale, "malformed"),
+            other => panic!("应为 LocaleInvalid，实际为 {:?}", other.map(|m| m.locale)),
+        }
+    }
+
+    #[test]
+    fn locale_missing_critical_keys_is_incomplete() {
+        match I18nManager::load_validated_from(&fixtures(), "partial") {
+            Err(AppError::LocaleIncomplete { locale, missing }) => {
+                assert_eq!(locale, "partial");
+                // partial.json 或英文中有的条目不算缺少
+                assert!(!missing.iter().any(|key| ["menu_pause", "menu_exit", "system_online"].contains(&key.as_str())));
+                assert!(missing.contains(&"settings_window_title".to_string()));
+            }
+            other => panic!("应为 LocaleIncomplete，实际为 {:?}", other.map(|m| m.locale)),
+        }
+    }
+
+    #[test]
+    fn complete_locale_is_accepted() {
+        let manager = I18nManager::load_validated_from(&fixtures(), "zh").unwrap();
+        assert_eq!(manager.locale(), "zh");
+    }
+
+    #[test]
+    fn unknown_locale_is_missing() {
+        assert!(matches!(I18nManager::load_validated_from(&fixtures(), "xx"), Err(AppError::LocaleMissing { .. })));
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_english() {
+        let i18n = incomplete_zh();
+        assert_eq!(i18n.get_text("menu_exit").as_deref(), Some("退出"));
+        assert_eq!(i18n.source_of("menu_exit"), Some(TextSource::Locale));
+        assert_eq!(i18n.get_text("menu_pause").as_deref(), Some("Pause Announcing"));
+        assert_eq!(i18n.source_of("menu_pause"), Some(TextSource::Fallback));
+        assert_eq!(i18n.get_text("no_such_key"), None);
+    }
+
+    #[test]
+    fn untranslated_entries_fall_back_to_english() {
+        // 空的复数形式是尚未翻译的模板
+        let i18n = incomplete_zh();
+        assert_eq!(i18n.get_text_plural("unit_hours", 2, &[("count", "2")]).as_deref(), Some("2 hours"));
+    }
+
+    #[test]
+    fn missing_keys_are_reported_once() {
+        let i18n = incomplete_zh();
+        for _ in 0..3 {
+            i18n.get_text("menu_pause");
+            i18n.get_text_with_param("menu_resume", "unused", "");
+        }
+        i18n.get_text("menu_exit");
+        let reported = i18n.reported_missing.lock().unwrap();
+        let mut reported: Vec<&str> = reported.iter().map(String::as_str).collect();
+        reported.sort_unstable();
+        assert_eq!(reported, ["menu_pause", "menu_resume"]);
+    }
+
+    #[test]
+    fn missing_locale_degrades_to_english() {
+        let i18n = I18nManager::new_from(&fixtures(), "xx").unwrap();
+        assert_eq!(i18n.locale(), "en");
+        assert_eq!(i18n.get_text("system_online").as_deref(), Some("System online"));
+        assert_eq!(i18n.source_of("menu_exit"), Some(TextSource::Locale));
+    }
+
+    fn overrides() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/overrides")
+    }
+
+    fn embedded(locale: &str) -> HashMap<String, String> {
+        let data = EMBEDDED_LOCALES.iter().find(|(code, _)| *code == locale).unwrap().1;
+        parse_locale(locale, data).unwrap()
+    }
+
+    #[test]
+    fn embedded_locales_are_used_without_files_on_disk() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/no_such_dir");
+        for (code, _) in EMBEDDED_LOCALES {
+            assert_eq!(read_locale(&dir, code).unwrap(), embedded(code), "locale {}", code);
+        }
+    }
+
+    #[test]
+    fn file_on_disk_takes_precedence_over_embedded() {
+        let ja = read_locale(&overrides(), "ja").unwrap();
+        assert_eq!(ja.get("menu_exit").map(String::as_str), Some("おしまい"));
+        // 磁盘上的档案整个代替内置的档案，其中没有的条目由英文补上
+        assert!(!ja.contains_key("menu_pause"));
+    }
+
+    #[test]
+    fn malformed_file_on_disk_falls_back_to_embedded() {
+        assert_eq!(read_locale(&overrides(), "zh").unwrap(), embedded("zh"));
+    }
+
+    #[test]
+    fn specific_locale_is_layered_over_general_one() {
+        let zh_tw = read_locale_chain(&overrides(), "zh-TW").unwrap();
+        assert_eq!(zh_tw.get("menu_exit").map(String::as_str), Some("結束"));
+        assert_eq!(zh_tw.get("menu_pause"), embedded("zh").get("menu_pause"));
+    }
+
+    #[test]
+    fn extra_locales_on_disk_are_listed() {
+        let locales = locales_in(&overrides());
+        let codes: Vec<&str> = locales.iter().map(|(code, _)| code.as_str()).collect();
+        // 内置的语言在前，其后是磁盘上另外加入的语言；zh-TW 也是另外加入的语言
+        assert_eq!(codes, ["en", "zh", "ja", "de", "fr", "es", "ko", "eo", "zh-TW"]);
+        assert!(locales.contains(&("eo".to_string(), "Esperanto".to_string())));
+        assert!(locales.contains(&("ja".to_string(), "日本語 (カスタム)".to_string())));
+        assert!(locales.contains(&("zh-TW".to_string(), "zh-TW".to_string())));
+    }
+
+    // 只有给定条目、没有英文补充的语言
+    fn manager(locale: &str, entries: &[(&str, &str)]) -> I18nManager {
+        let translations = entries.iter().map(|(key, text)| (key.to_string(), text.to_string())).collect();
+        I18nManager {
+            locale: locale.to_string(),
+            translations,
+            fallback: HashMap::new(),
+            custom_strings: HashMap::new(),
+            overrides: HashMap::new(),
+            reported_missing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    #[test]
+    fn replaces_every_placeholder() {
+        let i18n = manager("en", &[("wifi", "Connected to {ssid}, signal {signal} percent. {ssid} is trusted.")]);
+        let text = i18n.get_text_with_params("wifi", &[("signal", "80"), ("ssid", "Home")]);
+        assert_eq!(text.as_deref(), Some("Connected to Home, signal 80 percent. Home is trusted."));
+    }
+
+    #[test]
+    fn single_parameter_wrapper_matches() {
+        let i18n = manager("en", &[("drive", "Drive {letter} is full.")]);
+        assert_eq!(i18n.get_text_with_param("drive", "letter", "C"), i18n.get_text_with_params("drive", &[("letter", "C")]));
+    }
+
+    #[test]
+    fn missing_parameters_are_kept_and_listed() {
+        let (text, missing) = interpolate("{app}: {text} ({app})", &[("app", "Mail")], false);
+        assert_eq!(text, "Mail: {text} (Mail)");
+        assert_eq!(missing, ["text"]);
+        let i18n = manager("en", &[("notification", "{app}: {text}")]);
+        assert_eq!(i18n.get_text_with_params("notification", &[]).as_deref(), Some("{app}: {text}"));
+    }
+
+    #[test]
+    fn values_containing_braces_are_not_replaced_again() {
+        let (text, missing) = interpolate("{app}: {text}", &[("app", "{text}"), ("text", "}{ {{x}}")], false);
+        assert_eq!(text, "{text}: }{ {{x}}");
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let (text, missing) = interpolate("{{name}} is {name}, {{{name}}}", &[("name", "x")], false);
+        assert_eq!(text, "{name} is x, {x}");
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn text_that_is_not_a_placeholder_is_kept() {
+        let (text, missing) = interpolate("{ not a name } {} { and }", &[("name", "x")], false);
+        assert_eq!(text, "{ not a name } {} { and }");
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn values_are_escaped_in_ssml() {
+        let i18n = manager("en", &[("wifi", "<speak>Connected to {ssid}</speak>")]);
+        let text = i18n.get_text_with_params("wifi", &[("ssid", "Tom & <Jerry>")]);
+        assert_eq!(text.as_deref(), Some("<speak>Connected to Tom &amp; &lt;Jerry&gt;</speak>"));
+    }
+
+    #[test]
+    fn plural_categories_per_language() {
+        let cases = [
+            ("en", [(0, "other"), (1, "one"), (2, "other"), (21, "other")]),
+            ("en-GB", [(0, "other"), (1, "one"), (2, "other"), (21, "other")]),
+            ("fr", [(0, "one"), (1, "one"), (2, "other"), (21, "other")]),
+            ("zh", [(0, "other"), (1, "other"), (2, "other"), (21, "other")]),
+            ("zh-TW", [(0, "other"), (1, "other"), (2, "other"), (21, "other")]),
+            ("ja", [(0, "other"), (1, "other"), (2, "other"), (21, "other")]),
+        ];
+        for (locale, counts) in cases {
+            for (count, expected) in counts {
+                assert_eq!(plural_category(locale, count), expected, "{} {}", locale, count);
+            }
+        }
+    }
+
+    #[test]
+    fn plural_forms_per_locale() {
+        let cases = [
+            ("en", 1, "New device detected. Analyzing signature."),
+            ("en", 3, "3 new devices detected. Analyzing signatures."),
+            ("zh", 1, "检测到 1 个新设备。正在分析签名。"),
+            ("zh", 3, "检测到 3 个新设备。正在分析签名。"),
+            ("ja", 1, "1 台の新しいデバイスを検出しました。署名を解析中。"),
+            ("ja", 3, "3 台の新しいデバイスを検出しました。署名を解析中。"),
+        ];
+        for (locale, count, expected) in cases {
+            let text = I18nManager::for_tests(locale).get_text_plural("usb_device_detected", count, &[]);
+            assert_eq!(text.as_deref(), Some(expected), "{} {}", locale, count);
+        }
+    }
+
+    #[test]
+    fn plural_entries_keep_other_parameters() {
+        let text = I18nManager::for_tests("en").get_text_plural("print_job_completed_pages", 14, &[("document", "report.pdf")]);
+        assert_eq!(text.as_deref(), Some("Your print job report.pdf has finished, 14 pages."));
+        // 提供了 {count} 时使用提供的文字，例如按语言分组的数字
+        let text = I18nManager::for_tests("en").get_text_plural("unit_minutes", 1500, &[("count", "1,500")]);
+        assert_eq!(text.as_deref(), Some("1,500 minutes"));
+    }
+
+    #[test]
+    fn missing_form_uses_other_and_plain_strings_still_work() {
+        let i18n = manager("en", &[("devices_other", "{count} devices"), ("plain", "{count} item(s)")]);
+        assert_eq!(i18n.get_text_plural("devices", 1, &[]).as_deref(), Some("1 devices"));
+        assert_eq!(i18n.get_text_plural("plain", 1, &[]).as_deref(), Some("1 item(s)"));
+        assert_eq!(i18n.get_text_plural("absent", 1, &[]), None);
+    }
+
+    #[test]
+    fn plural_objects_are_parsed_per_form() {
+        let translations = parse_locale("en", r#"{"devices": {"one": "a device", "other": "{count} devices", "few": "x", "many": ""}, "plain": "text"}"#).unwrap();
+        assert_eq!(translations.get("devices_one").map(String::as_str), Some("a device"));
+        assert_eq!(translations.get("devices_other").map(String::as_str), Some("{count} devices"));
+        assert_eq!(translations.get("devices_few").map(String::as_str), Some("x"));
+        assert!(!translations.contains_key("devices_many"));
+        assert_eq!(translations.get("plain").map(String::as_str), Some("text"));
+    }
+}
@@ -0,0 +1,167 @@
+// src/locale_check.rs
+//
+// Compares a translation with the English locale file: which entries are missing or extra, and
+// whether each entry uses the same `{placeholders}`. Used by the startup check in i18n.rs and by
+// the check_locales and export_locale_template tools, so it depends on nothing but serde_json.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use serde_json::{Map, Value};
+
+/// The forms a plural entry may have (CLDR categories).
+pub const PLURAL_CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// One difference between a translation and the reference file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// The reference has the entry, the translation does not or left it empty: English is
+    /// spoken instead.
+    MissingKey(String),
+    /// The translation has an entry the reference does not know; it is never used.
+    ExtraKey(String),
+    /// The value is neither a string nor an object of plural forms.
+    InvalidValue(String),
+    /// A plural entry has a form that is not a CLDR category.
+    UnknownPluralForm { key: String, form: String },
+    /// A plural entry lacks the "other" form used for every count without its own form.
+    MissingOtherForm(String),
+    /// The placeholders differ: `missing` are in the reference only and are left out of the
+    /// announcement, `unknown` are in the translation only and are read as written.
+    Placeholders { key: String, missing: Vec<String>, unknown: Vec<String> },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let braced = |names: &[String]| names.iter().map(|name| format!("{{{}}}", name)).collect::<Vec<_>>().join(", ");
+        match self {
+            Problem::MissingKey(key) => write!(f, "缺少条目 '{}'", key),
+            Problem::ExtraKey(key) => write!(f, "多余的条目 '{}' (英文中没有，不会被使用)", key),
+            Problem::InvalidValue(key) => write!(f, "条目 '{}' 既不是文字也不是复数形式", key),
+            Problem::UnknownPluralForm { key, form } => write!(f, "条目 '{}' 有未知的复数形式 '{}'", key, form),
+            Problem::MissingOtherForm(key) => write!(f, "复数条目 '{}' 缺少 \"other\" 形式", key),
+            Problem::Placeholders { key, missing, unknown } => {
+                write!(f, "条目 '{}' 的参数与英文不同:", key)?;
+                if !missing.is_empty() {
+                    write!(f, " 缺少 {}", braced(missing))?;
+                }
+                if !missing.is_empty() && !unknown.is_empty() {
+                    write!(f, ";")?;
+                }
+                if !unknown.is_empty() {
+                    write!(f, " 多出 {}", braced(unknown))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// All differences between `translation` and `reference`, in the order of the entries.
+/// Entries starting with "_" (e.g. `_language_name`) describe the file and are not compared.
+pub fn compare(reference: &Map<String, Value>, translation: &Map<String, Value>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for (key, value) in translation {
+        problems.extend(check_value(key, value));
+    }
+    let missing = missing_keys(reference, translation);
+    for (key, value) in reference {
+        if missing.contains(key) {
+            problems.push(Problem::MissingKey(key.clone()));
+            continue;
+        }
+        let Some(translated) = translation.get(key).filter(|_| !key.starts_with('_')) else { continue };
+        let (Some(expected), Some(found)) = (entry_placeholders(value), entry_placeholders(translated)) else { continue };
+        let missing: Vec<String> = expected.difference(&found).cloned().collect();
+        let unknown: Vec<String> = found.difference(&expected).cloned().collect();
+        if !missing.is_empty() || !unknown.is_empty() {
+            problems.push(Problem::Placeholders { key: key.clone(), missing, unknown });
+        }
+    }
+    for key in translation.keys() {
+        if !key.starts_with('_') && !reference.contains_key(key) {
+            problems.push(Problem::ExtraKey(key.clone()));
+        }
+    }
+    problems
+}
+
+/// The entries of `reference` that `translation` lacks or left empty, in the reference's order.
+pub fn missing_keys(reference: &Map<String, Value>, translation: &Map<String, Value>) -> Vec<String> {
+    reference.keys()
+        .filter(|key| !key.starts_with('_'))
+        .filter(|key| translation.get(*key).is_none_or(is_untranslated))
+        .cloned()
+        .collect()
+}
+
+/// An empty text, or plural forms that are all empty, as written by export_locale_template for
+/// the translator to fill in. Such entries are used in English.
+pub fn is_untranslated(value: &Value) -> bool {
+    match value {
+        Value::String(text) => text.is_empty(),
+        Value::Object(forms) => forms.values().all(|form| form.as_str().is_some_and(str::is_empty)),
+        _ => false,
+    }
+}
+
+fn check_value(key: &str, value: &Value) -> Vec<Problem> {
+    match value {
+        Value::String(_) => Vec::new(),
+        Value::Object(forms) => {
+            let mut problems: Vec<Problem> = forms.iter()
+                .filter(|(form, text)| !PLURAL_CATEGORIES.contains(&form.as_str()) || !text.is_string())
+                .map(|(form, text)| match text {
+                    Value::String(_) => Problem::UnknownPluralForm { key: key.to_string(), form: form.clone() },
+                    _ => Problem::InvalidValue(key.to_string()),
+                })
+                .collect();
+            if !forms.contains_key("other") {
+                problems.push(Problem::MissingOtherForm(key.to_string()));
+            }
+            problems
+        }
+        _ => vec![Problem::InvalidValue(key.to_string())],
+    }
+}
+
+/// The placeholders of all forms of an entry together: the English "one" form often spells out
+/// the number ("1 page") where the "other" form has {count}. `None` for an invalid value.
+pub fn entry_placeholders(value: &Value) -> Option<BTreeSet<String>> {
+    match value {
+        Value::String(text) => Some(placeholders(text).into_iter().collect()),
+        Value::Object(forms) => Some(forms.values().filter_map(Value::as_str).flat_map(placeholders).collect()),
+        _ => None,
+    }
+}
+
+/// The `{name}` placeholders of a text in order of appearance, without duplicates.
+/// `{{` and `}}` are literal braces.
+pub fn placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find(['{', '}']) {
+        rest = &rest[pos..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            rest = &rest[2..];
+            continue;
+        }
+        let name = rest.strip_prefix('{')
+            .and_then(|inner| inner.find('}').map(|end| &inner[..end]))
+            .filter(|name| is_placeholder_name(name));
+        match name {
+            Some(name) => {
+                if !names.iter().any(|known| known == name) {
+                    names.push(name.to_string());
+                }
+                rest = &rest[name.len() + 2..];
+            }
+            None => rest = &rest[1..],
+        }
+    }
+    names
+}
+
+/// Whether `{name}` is a placeholder rather than text that happens to be in braces.
+pub fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
@@ -0,0 +1,319 @@
+// src/log_viewer.rs
+// 日志查看窗口：显示正在写入的日志文件的最后约 500 行，打开期间每隔两秒刷新。
+// 读取文件在后台线程中进行 (日志很大或某一行很长时界面不会卡住)，结果放在共享的位置，
+// 再发 WM_APP_LOG_UPDATED 通知窗口取走。每次刷新都按路径重新打开文件，日志轮换后自动读到新文件
+
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use once_cell::sync::Lazy;
+
+use windows::core::{w, HSTRING};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::HFONT;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Controls::BST_CHECKED;
+use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, GetDlgItem, GetWindowLongPtrW, PostMessageW, SendMessageW, SetWindowLongPtrW,
+    SetWindowTextW, BM_GETCHECK, BS_AUTOCHECKBOX, BS_PUSHLIKE, CREATESTRUCTW, CW_USEDEFAULT, ES_AUTOHSCROLL, ES_AUTOVSCROLL,
+    ES_MULTILINE, ES_READONLY, GWLP_USERDATA, HMENU, WINDOW_STYLE, WM_APP, WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_DPICHANGED,
+    WM_SETFONT, WS_BORDER, WS_CAPTION, WS_CHILD, WS_EX_DLGMODALFRAME, WS_HSCROLL, WS_SYSMENU, WS_TABSTOP, WS_VISIBLE, WS_VSCROLL,
+};
+
+use crate::about_ui::copy_to_clipboard;
+use crate::logging;
+use crate::settings_ui::{
+    activate_dialog_window, control_text, create_dialog_font, delete_dialog_font, fit_to_dpi, handle_dpi_changed, record_layout,
+    register_dialog_class, register_dialog_window, unregister_dialog_window, window_dpi, window_text, BASE_DPI,
+};
+use crate::AppState;
+use log::{error, warn};
+
+const IDC_LOG_TEXT: i32 = 101;
+const IDC_LOG_PAUSE: i32 = 102;
+const IDC_LOG_COPY: i32 = 103;
+const IDC_LOG_OPEN: i32 = 104;
+const IDCANCEL: i32 = 2;
+// windows 0.61 没有导出编辑框的这几个消息
+const EM_SETSEL: u32 = 0x00B1;
+const EM_SCROLLCARET: u32 = 0x00B7;
+const EM_SETLIMITTEXT: u32 = 0x00C5;
+
+// 日志查看窗口在 96 DPI 下的大小
+const VIEWER_WIDTH: i32 = 900;
+const VIEWER_HEIGHT: i32 = 620;
+
+// 后台线程读到新的内容后发给窗口
+const WM_APP_LOG_UPDATED: u32 = WM_APP + 1;
+// 刷新间隔、显示的行数，以及最多从文件末尾读取的字节数 (够 500 行普通日志，又不会一次读入整个大文件)
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+const TAIL_LINES: usize = 500;
+const TAIL_BYTES: u64 = 256 * 1024;
+// 超过这么多字符的行截断显示，编辑框绘制极长的行很慢
+const MAX_LINE_CHARS: usize = 1000;
+
+static LOG_VIEWER_CLASS_NAME: Lazy<HSTRING> = Lazy::new(|| HSTRING::from("AdvancedBeeperLogViewerClass"));
+// 已经打开的窗口 (0 表示没有)
+static LOG_VIEWER_WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+/// 后台线程读取日志的结果
+#[derive(Clone, PartialEq)]
+enum LogTail {
+    Text(String),
+    // 日志已关闭 (log_level 为 off)，没有正在写入的文件
+    NoLog,
+    Failed(String),
+}
+
+/// 窗口和后台线程共享的状态
+#[derive(Default)]
+struct Shared {
+    // 窗口已关闭，后台线程结束
+    closed: AtomicBool,
+    // 暂停刷新，内容保持不变便于阅读和选取
+    paused: AtomicBool,
+    // 最近一次读到的、窗口还没有取走的结果
+    latest: Mutex<Option<LogTail>>,
+}
+
+struct LogViewerData {
+    shared: Arc<Shared>,
+    app_state: Arc<Mutex<AppState>>,
+    h_text: HWND,
+    h_font: HFONT,
+    dpi: u32,
+    layout: Vec<(HWND, RECT)>,
+}
+
+fn register_log_viewer_class() {
+    static REGISTER_ONCE: std::sync::Once = std::sync::Once::new();
+    REGISTER_ONCE.call_once(|| register_dialog_class(&LOG_VIEWER_CLASS_NAME, Some(log_viewer_wnd_proc)));
+}
+
+pub fn show(parent: HWND, app_state: Arc<Mutex<AppState>>) {
+    if activate_dialog_window(&LOG_VIEWER_WINDOW) { return; }
+    register_log_viewer_class();
+    let instance = unsafe { GetModuleHandleW(None).unwrap() };
+
+    let window_title = control_text(&app_state.lock().unwrap().i18n_manager, "log_viewer_title", "Log");
+    let shared = Arc::new(Shared::default());
+    let data = Box::new(LogViewerData {
+        shared: shared.clone(),
+        app_state,
+        h_text: HWND::default(),
+        h_font: HFONT::default(),
+        dpi: BASE_DPI,
+        layout: vec![],
+    });
+    let data_ptr = Box::into_raw(data);
+
+    let hwnd = match unsafe {
+        CreateWindowExW(
+            WS_EX_DLGMODALFRAME,
+            &*LOG_VIEWER_CLASS_NAME,
+            &HSTRING::from(window_title),
+            WS_CAPTION | WS_SYSMENU | WS_VISIBLE,
+            CW_USEDEFAULT, CW_USEDEFAULT, VIEWER_WIDTH, VIEWER_HEIGHT,
+            Some(parent),
+            None,
+            Some(instance.into()),
+            Some(data_ptr as *mut c_void),
+        )
+    } {
+        Ok(hwnd) => hwnd,
+        Err(e) => {
+            error!("创建日志查看窗口失败: {}", e);
+            unsafe { let _ = Box::from_raw(data_ptr); };
+            return;
+        }
+    };
+    register_dialog_window(&LOG_VIEWER_WINDOW, hwnd);
+    start_reader(hwnd, shared);
+
+    if let Ok(text) = unsafe { GetDlgItem(Some(hwnd), IDC_LOG_TEXT) } {
+        unsafe { let _ = SetFocus(Some(text)); };
+    }
+}
+
+// 每隔 REFRESH_INTERVAL 读取一次日志，内容有变化时通知窗口。窗口关闭后线程自行结束
+fn start_reader(hwnd: HWND, shared: Arc<Shared>) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        let mut last: Option<LogTail> = None;
+        let mut first = true;
+        while !shared.closed.load(Ordering::SeqCst) {
+            if first || !shared.paused.load(Ordering::SeqCst) {
+                first = false;
+                log::logger().flush();
+                let tail = match logging::active_path() {
+                    Some(path) => match read_tail(&path) {
+                        Ok(text) => LogTail::Text(text),
+                        Err(e) => LogTail::Failed(e.to_string()),
+                    },
+                    None => LogTail::NoLog,
+                };
+                // 内容没有变化时不刷新，免得打断正在进行的选取
+                if last.as_ref() != Some(&tail) {
+                    last = Some(tail.clone());
+                    *shared.latest.lock().unwrap() = Some(tail);
+                    let hwnd = HWND(hwnd_value as *mut c_void);
+                    if unsafe { PostMessageW(Some(hwnd), WM_APP_LOG_UPDATED, WPARAM(0), LPARAM(0)) }.is_err() {
+                        break;
+                    }
+                }
+            }
+            std::thread::sleep(REFRESH_INTERVAL);
+        }
+    });
+}
+
+// 读取日志文件末尾的 TAIL_LINES 行，换行改为编辑框需要的 "\r\n"
+fn read_tail(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+    let start = length.saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines: Vec<&str> = text.lines().collect();
+    // 从文件中间开始读时，第一行多半不完整
+    if start > 0 && !lines.is_empty() {
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(TAIL_LINES);
+    let shown: Vec<String> = lines[skip..].iter()
+        .map(|line| match line.char_indices().nth(MAX_LINE_CHARS) {
+            Some((cut, _)) => format!("{}…", &line[..cut]),
+            None => line.to_string(),
+        })
+        .collect();
+    Ok(shown.join("\r\n"))
+}
+
+extern "system" fn log_viewer_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_CREATE => {
+            let create_struct = unsafe { &*(lparam.0 as *const CREATESTRUCTW) };
+            let data_ptr = create_struct.lpCreateParams as *mut LogViewerData;
+            unsafe { SetWindowLongPtrW(hwnd, GWLP_USERDATA, data_ptr as isize) };
+            let data = unsafe { &mut *data_ptr };
+
+            data.dpi = window_dpi(hwnd);
+            data.h_font = create_dialog_font(data.dpi);
+            create_controls(hwnd, data);
+            data.layout = record_layout(hwnd);
+            fit_to_dpi(hwnd, &data.layout, VIEWER_WIDTH, VIEWER_HEIGHT, data.dpi);
+            LRESULT(0)
+        }
+        WM_DPICHANGED => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut LogViewerData };
+            if data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
+            let data = unsafe { &mut *data_ptr };
+            data.dpi = handle_dpi_changed(hwnd, wparam, lparam, &data.layout, &mut data.h_font);
+            LRESULT(0)
+        }
+        WM_APP_LOG_UPDATED => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut LogViewerData };
+            if !data_ptr.is_null() {
+                show_log_tail(unsafe { &*data_ptr });
+            }
+            LRESULT(0)
+        }
+        WM_COMMAND => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut LogViewerData };
+            if data_ptr.is_null() { return LRESULT(0); }
+            let data = unsafe { &*data_ptr };
+            match (wparam.0 & 0xFFFF) as i32 {
+                IDC_LOG_PAUSE => {
+                    // 按钮发来的 WM_COMMAND 中 lparam 是按钮本身
+                    let checked = unsafe { SendMessageW(HWND(lparam.0 as *mut c_void), BM_GETCHECK, None, None) }.0 as u32 == BST_CHECKED.0;
+                    data.shared.paused.store(checked, Ordering::SeqCst);
+                }
+                IDC_LOG_COPY => copy_to_clipboard(hwnd, &window_text(data.h_text)),
+                IDC_LOG_OPEN => crate::open_log_file(),
+                IDCANCEL => unsafe { let _ = DestroyWindow(hwnd); },
+                _ => {}
+            }
+            LRESULT(0)
+        }
+        WM_CLOSE => {
+            unsafe { let _ = DestroyWindow(hwnd); };
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            let data_ptr = unsafe { SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0) as *mut LogViewerData };
+            if !data_ptr.is_null() {
+                let data = unsafe { Box::from_raw(data_ptr) };
+                data.shared.closed.store(true, Ordering::SeqCst);
+                delete_dialog_font(data.h_font);
+            }
+            unregister_dialog_window(&LOG_VIEWER_WINDOW, hwnd);
+            LRESULT(0)
+        }
+        _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+    }
+}
+
+// 取走后台线程读到的内容，显示后滚动到最后一行
+fn show_log_tail(data: &LogViewerData) {
+    let Some(tail) = data.shared.latest.lock().unwrap().take() else { return };
+    let text = match tail {
+        LogTail::Text(text) => text,
+        LogTail::NoLog => control_text(&data.app_state.lock().unwrap().i18n_manager, "log_viewer_no_log", "Logging is turned off, so there is no log to show."),
+        LogTail::Failed(e) => {
+            warn!("读取日志文件失败: {}", e);
+            data.app_state.lock().unwrap().i18n_manager.get_text_with_param("log_viewer_read_failed", "error", &e)
+                .unwrap_or_else(|| format!("The log file could not be read: {}", e))
+        }
+    };
+    unsafe {
+        let _ = SetWindowTextW(data.h_text, &HSTRING::from(text));
+        SendMessageW(data.h_text, EM_SETSEL, Some(WPARAM(usize::MAX)), Some(LPARAM(-1)));
+        SendMessageW(data.h_text, EM_SCROLLCARET, None, None);
+    }
+}
+
+fn create_controls(parent: HWND, data: &mut LogViewerData) {
+    let instance = unsafe { GetModuleHandleW(None).unwrap() };
+    let h_font = data.h_font;
+    let texts: Vec<HSTRING> = {
+        let app_state = data.app_state.lock().unwrap();
+        [("log_viewer_pause", "Pause refresh"), ("log_viewer_copy", "Copy all"), ("log_viewer_open", "Open in editor"), ("log_viewer_close", "Close")]
+            .iter()
+            .map(|(key, fallback)| HSTRING::from(control_text(&app_state.i18n_manager, key, fallback)))
+            .collect()
+    };
+
+    unsafe {
+        let set_font = |hwnd: HWND| {
+            if !h_font.is_invalid() {
+                SendMessageW(hwnd, WM_SETFONT, Some(WPARAM(h_font.0 as usize)), Some(LPARAM(1)));
+            }
+        };
+
+        // 只读的多行编辑框，不自动换行，长行用水平滚动条查看
+        let h_text = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_BORDER.0 | WS_VSCROLL.0 | WS_HSCROLL.0 | (ES_MULTILINE as u32) | (ES_READONLY as u32) | (ES_AUTOVSCROLL as u32) | (ES_AUTOHSCROLL as u32)), 10, 10, 865, 520, Some(parent), Some(HMENU((IDC_LOG_TEXT as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_text);
+        // 多行编辑框默认最多约 32K 个字符，500 行日志可能超过
+        SendMessageW(h_text, EM_SETLIMITTEXT, Some(WPARAM(0)), None);
+        data.h_text = h_text;
+
+        let h_pause = CreateWindowExW(Default::default(), w!("BUTTON"), &texts[0], WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | (BS_AUTOCHECKBOX as u32) | (BS_PUSHLIKE as u32)), 10, 540, 150, 30, Some(parent), Some(HMENU((IDC_LOG_PAUSE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_pause);
+
+        let h_copy = CreateWindowExW(Default::default(), w!("BUTTON"), &texts[1], WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0), 170, 540, 120, 30, Some(parent), Some(HMENU((IDC_LOG_COPY as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_copy);
+
+        let h_open = CreateWindowExW(Default::default(), w!("BUTTON"), &texts[2], WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0), 300, 540, 150, 30, Some(parent), Some(HMENU((IDC_LOG_OPEN as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_open);
+
+        let h_close = CreateWindowExW(Default::default(), w!("BUTTON"), &texts[3], WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0), 775, 540, 100, 30, Some(parent), Some(HMENU((IDCANCEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_close);
+    }
+}
@@ -0,0 +1,143 @@
+// src/logging.rs
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use windows::Win32::System::SystemInformation::GetLocalTime;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+
+use crate::config::{self, Config};
+
+const LOG_FILE_NAME: &str = "advanced_beeper.log";
+
+/// Where and how much to log, taken from `Config`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogSettings {
+    pub path: PathBuf,
+    pub level: LevelFilter,
+    /// The file is rotated once it would grow past this size; 0 never rotates.
+    pub max_bytes: u64,
+    /// Rotated files kept next to the log as `<name>.1` (newest) to `<name>.<keep_files>`.
+    pub keep_files: usize,
+}
+
+impl LogSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            path: config.log_file.clone().unwrap_or_else(|| config::data_dir().join(LOG_FILE_NAME)),
+            level: config.log_level.filter(),
+            max_bytes: config.log_max_size_kb.saturating_mul(1024),
+            keep_files: config.log_keep_files,
+        }
+    }
+}
+
+struct Sink {
+    settings: LogSettings,
+    file: Option<File>,
+    written: u64,
+}
+
+/// Writes one line per record to the configured file, in the format
+/// `[YYYY-MM-DD hh:mm:ss.SSS] (<thread-id>) <level> <message>`, rotating by size.
+struct FileLogger {
+    sink: Mutex<Option<Sink>>,
+}
+
+static LOGGER: Lazy<FileLogger> = Lazy::new(|| FileLogger { sink: Mutex::new(None) });
+
+/// Installs the logger. Called once at startup, before the configuration is read.
+pub fn init(settings: LogSettings) -> Result<(), log::SetLoggerError> {
+    apply(settings);
+    log::set_logger(&*LOGGER)
+}
+
+/// Switches to new settings: the level takes effect at once, a new path from the next line on.
+pub fn apply(settings: LogSettings) {
+    log::set_max_level(settings.level);
+    let mut sink = LOGGER.sink.lock().unwrap();
+    if let Some(current) = sink.as_mut() {
+        if current.settings.path == settings.path {
+            current.settings = settings;
+            return;
+        }
+    }
+    let (file, written) = open(&settings.path);
+    *sink = Some(Sink { settings, file, written });
+}
+
+/// The file currently written to.
+pub fn active_path() -> Option<PathBuf> {
+    LOGGER.sink.lock().unwrap().as_ref().map(|sink| sink.settings.path.clone())
+}
+
+// 日志本身无法打开时无处报告错误，只能不写日志
+fn open(path: &Path) -> (Option<File>, u64) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => {
+            let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+            (Some(file), written)
+        }
+        Err(_) => (None, 0),
+    }
+}
+
+impl Sink {
+    fn rotate(&mut self) {
+        self.file = None;
+        let path = &self.settings.path;
+        let numbered = |n: usize| {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        if self.settings.keep_files == 0 {
+            fs::remove_file(path).ok();
+        } else {
+            fs::remove_file(numbered(self.settings.keep_files)).ok();
+            for n in (1..self.settings.keep_files).rev() {
+                fs::rename(numbered(n), numbered(n + 1)).ok();
+            }
+            fs::rename(path, numbered(1)).ok();
+        }
+        (self.file, self.written) = open(path);
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) { return; }
+        let now = unsafe { GetLocalTime() };
+        let line = format!(
+            "[{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}] ({:x}) {:6} {}\n",
+            now.wYear, now.wMonth, now.wDay, now.wHour, now.wMinute, now.wSecond, now.wMilliseconds,
+            unsafe { GetCurrentThreadId() }, record.level(), record.args()
+        );
+        let mut sink = self.sink.lock().unwrap();
+        let Some(sink) = sink.as_mut() else { return };
+        if sink.settings.max_bytes > 0 && sink.written > 0 && sink.written + line.len() as u64 > sink.settings.max_bytes {
+            sink.rotate();
+        }
+        if let Some(file) = sink.file.as_mut() {
+            if file.write_all(line.as_bytes()).is_ok() {
+                sink.written += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.sink.lock().unwrap().as_mut().and_then(|sink| sink.file.as_mut()) {
+            file.flush().ok();
+        }
+    }
+}
@@ -4,60 +4,244 @@
 
 mod tts_engine;
 mod i18n;
+mod locale_check;
 mod event_monitor;
 mod config;
 mod startup;
 mod settings_ui;
+mod about_ui;
+mod log_viewer;
+mod error;
+mod audio_monitor;
+mod companion;
+mod process_monitor;
+mod timers;
+mod notification_monitor;
+mod clock_monitor;
+mod break_reminder;
+mod mic_monitor;
+mod camera_monitor;
+mod gpu_monitor;
+mod radio_monitor;
+mod status;
+mod usb_debounce;
+mod audio_ducking;
+mod resume_summary;
+mod print_monitor;
+mod announce_policy;
+mod security_monitor;
+mod hotkey;
+mod output_device;
+mod sound;
+mod ssml;
+mod wav_export;
+mod repeat_guard;
+mod text_preprocess;
+mod format;
+mod config_watcher;
+mod logging;
 
 use log::{info, error, warn, debug};
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 
 use std::env;
 use std::ffi::c_void;
+use std::path::PathBuf;
 use std::error::Error;
 use std::sync::{mpsc, Arc, Mutex};
 use windows::core::{w, HSTRING, PCWSTR};
 use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::DataExchange::COPYDATASTRUCT;
 // --- FIX: 引入 COM 初始化相关的常量 ---
-use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
-use windows::Win32::UI::Shell::{Shell_NotifyIconW, NOTIFYICONDATAW, NIM_ADD, NIM_DELETE, NIF_ICON, NIF_MESSAGE, NIF_TIP};
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED};
+use windows::Win32::UI::Shell::{ShellExecuteW, Shell_NotifyIconW, NOTIFYICONDATAW, NIM_ADD, NIM_DELETE, NIM_MODIFY, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_INFO, NIIF_WARNING, NOTIFY_ICON_INFOTIP_FLAGS};
+use windows::Win32::UI::HiDpi::{GetDpiForWindow, GetSystemMetricsForDpi, SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
 use windows::Win32::UI::WindowsAndMessaging::{
-    DBT_DEVICEARRIVAL, DBT_DEVICEREMOVECOMPLETE, DEV_BROADCAST_HDR, GetMessageW, MSG, AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetCursorPos, GetWindowLongPtrW, LoadIconW, PostQuitMessage, RegisterClassW, RegisterDeviceNotificationW, SetForegroundWindow, SetWindowLongPtrW, TrackPopupMenu, TranslateMessage, CREATESTRUCTW, CW_USEDEFAULT, GWLP_USERDATA, IDI_APPLICATION, MF_STRING, TPM_BOTTOMALIGN, TPM_LEFTALIGN, WM_APP, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_POWERBROADCAST, WM_RBUTTONUP, WNDCLASSW, WS_OVERLAPPEDWINDOW, PBT_APMSUSPEND, PBT_APMRESUMEAUTOMATIC, PBT_POWERSETTINGCHANGE, REGISTER_NOTIFICATION_FLAGS, DEV_BROADCAST_DEVICEINTERFACE_W, DBT_DEVTYP_DEVICEINTERFACE, DEVICE_NOTIFY_WINDOW_HANDLE, WM_DEVICECHANGE,
-    PostMessageW,
+    DBT_DEVICEARRIVAL, DBT_DEVICEREMOVECOMPLETE, DEV_BROADCAST_HDR, GetMessageW, MSG, AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetCursorPos, GetWindowLongPtrW, LoadIconW, LoadImageW, HICON, IMAGE_ICON, LR_DEFAULTCOLOR, SM_CXSMICON, SM_CYSMICON, PostQuitMessage, RegisterClassW, RegisterDeviceNotificationW, UnregisterDeviceNotification, HDEVNOTIFY, SetForegroundWindow, SetWindowLongPtrW, TrackPopupMenu, TranslateMessage, CREATESTRUCTW, CW_USEDEFAULT, GWLP_USERDATA, IDI_APPLICATION, MF_STRING, TPM_BOTTOMALIGN, TPM_LEFTALIGN, WM_APP, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_POWERBROADCAST, WM_RBUTTONUP, WNDCLASSW, WS_OVERLAPPEDWINDOW, PBT_APMSUSPEND, PBT_APMRESUMEAUTOMATIC, PBT_POWERSETTINGCHANGE, REGISTER_NOTIFICATION_FLAGS, DEV_BROADCAST_DEVICEINTERFACE_W, DBT_DEVTYP_DEVICEINTERFACE, DEVICE_NOTIFY_WINDOW_HANDLE, WM_DEVICECHANGE,
+    PostMessageW, SendMessageW, FindWindowW, SetTimer, KillTimer, MF_POPUP, MF_SEPARATOR, WM_COPYDATA, WM_TIMER, WM_TIMECHANGE, MF_GRAYED, MF_CHECKED, WM_HOTKEY, SW_SHOWNORMAL, MessageBoxW, MB_OK, MB_ICONWARNING,
 };
-use windows::Win32::System::Power::{GetSystemPowerStatus, RegisterPowerSettingNotification, POWERBROADCAST_SETTING, SYSTEM_POWER_STATUS};
-use windows::Win32::System::SystemServices::{GUID_ACDC_POWER_SOURCE, GUID_CONSOLE_DISPLAY_STATE};
+use windows::Win32::System::Power::{GetSystemPowerStatus, RegisterPowerSettingNotification, UnregisterPowerSettingNotification, HPOWERNOTIFY, POWERBROADCAST_SETTING, SYSTEM_POWER_STATUS};
+use windows::Win32::System::SystemInformation::GetLocalTime;
+use windows::Win32::System::SystemServices::{GUID_ACDC_POWER_SOURCE, GUID_CONSOLE_DISPLAY_STATE, GUID_POWER_SAVING_STATUS};
 use windows::Win32::Devices::Usb::GUID_DEVINTERFACE_USB_DEVICE;
 use windows::Win32::System::WindowsProgramming::GetUserNameW;
 use windows::core::PWSTR;
 
 use crate::tts_engine::VoiceDetail;
-use crate::config::Config;
+use crate::config::{Config, SoundMode};
+use crate::clock_monitor::ClockObserver;
 use crate::event_monitor::{start_monitoring, SystemEvent, ConnectionType, IS_SYSTEM_ASLEEP};
 use crate::i18n::I18nManager;
-use crate::tts_engine::TtsEngine;
+use crate::tts_engine::{SpeechHandle, SpeechPriority, TtsEngine, UtteranceWaiter};
+use crate::status::{EngineHealth, SharedStatus, WM_APP_STATUS_CHANGED};
+use crate::error::AppError;
+use crate::companion::Forwarder;
+use crate::resume_summary::{PowerSnapshot, ResumeSummary, SleepChange};
+use crate::usb_debounce::{device_instance_id, is_ignored, UsbDebouncer, UsbReconciler};
+use crate::repeat_guard::RepeatGuard;
 
 const WM_APP_TRAY_MSG: u32 = WM_APP + 1;
 const WM_APP_WAKEUP: u32 = WM_APP + 2;
+// 告别语播完（或等待超时）后由等待线程发出，真正销毁窗口 (WM_APP + 3 由 status 模块使用)
+const WM_APP_EXIT: u32 = WM_APP + 4;
 const ID_MENU_PAUSE_RESUME: u32 = 1001;
 const ID_MENU_SETTINGS: u32 = 1002;
 const ID_MENU_EXIT: u32 = 1003;
+const ID_MENU_SKIP_PENDING: u32 = 1004;
+const ID_MENU_IGNORE_LAST_DEVICE: u32 = 1005;
+const ID_MENU_STATUS: u32 = 1006;
+const ID_MENU_STOP: u32 = 1007;
+const ID_MENU_OPEN_LOG: u32 = 1008;
+const ID_MENU_ABOUT: u32 = 1009;
+const ID_MENU_VIEW_LOG: u32 = 1010;
+// 计时器菜单项: 预设时长为 ID_MENU_TIMER_START_BASE + 下标，取消为 ID_MENU_TIMER_CANCEL_BASE + 计时器 id
+const ID_MENU_TIMER_START_BASE: u32 = 1100;
+const ID_MENU_TIMER_CANCEL_BASE: u32 = 1200;
+const ID_MENU_TIMER_CANCEL_LAST: u32 = 1999;
+// 设置方案菜单项: ID_MENU_PROFILE_BASE + 按名称排序后的下标
+const ID_MENU_PROFILE_BASE: u32 = 2000;
+const ID_MENU_PROFILE_LAST: u32 = 2099;
+const TIMER_ID_USER_TIMERS: usize = 1;
+const TIMER_ID_USB_CONNECTS: usize = 2;
+const TIMER_ID_USB_DISCONNECTS: usize = 3;
+const TIMER_ID_REPEAT_SUMMARY: usize = 4;
+// 每分钟刷新托盘提示，安静时段开始或结束时及时显示
+const TIMER_ID_TOOLTIP_REFRESH: usize = 5;
+// 暂缓播报期间定期检查专注助手是否已经关闭
+const TIMER_ID_FOCUS_ASSIST: usize = 6;
+const FOCUS_ASSIST_POLL_MS: u32 = 5000;
+// WM_COPYDATA 的 dwData，用于让第二个实例把 --timer 转交给正在运行的实例
+const COPYDATA_START_TIMER: usize = 1;
+const MAIN_WINDOW_CLASS: PCWSTR = w!("AdvancedPromptsHiddenWindowClass");
 
 struct WindowProcData {
     sender: mpsc::Sender<SystemEvent>,
     app_state: Arc<Mutex<AppState>>,
+    registrations: Option<Registrations>,
+    clock: Mutex<ClockObserver>,
+}
+
+/// 隐藏窗口注册的系统通知句柄。Drop 时自动注销，窗口被重建时不会重复注册或泄漏句柄。
+struct Registrations {
+    power: Vec<HPOWERNOTIFY>,
+    device: Vec<HDEVNOTIFY>,
+}
+
+impl Drop for Registrations {
+    fn drop(&mut self) {
+        for handle in self.power.drain(..) {
+            if let Err(e) = unsafe { UnregisterPowerSettingNotification(handle) } {
+                warn!("注销电源设置通知失败: {}", e);
+            }
+        }
+        for handle in self.device.drain(..) {
+            if let Err(e) = unsafe { UnregisterDeviceNotification(handle) } {
+                warn!("注销设备通知失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 为隐藏窗口注册电源设置与 USB 设备插拔通知。单项注册失败只记录日志，不影响其他项。
+fn register_system_notifications(window: HWND) -> Registrations {
+    let mut registrations = Registrations { power: Vec::new(), device: Vec::new() };
+
+    let power_settings = [
+        (&GUID_ACDC_POWER_SOURCE, "GUID_ACDC_POWER_SOURCE"),
+        (&GUID_CONSOLE_DISPLAY_STATE, "GUID_CONSOLE_DISPLAY_STATE"),
+        (&GUID_POWER_SAVING_STATUS, "GUID_POWER_SAVING_STATUS"),
+    ];
+    for (guid, name) in power_settings {
+        match unsafe { RegisterPowerSettingNotification(window.into(), guid, REGISTER_NOTIFICATION_FLAGS(0)) } {
+            Ok(handle) => registrations.power.push(handle),
+            Err(e) => error!("注册电源设置通知 {} ({:?}) 失败: {}", name, guid, e),
+        }
+    }
+
+    let mut filter = DEV_BROADCAST_DEVICEINTERFACE_W {
+        dbcc_size: std::mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32,
+        dbcc_devicetype: DBT_DEVTYP_DEVICEINTERFACE.0,
+        dbcc_classguid: GUID_DEVINTERFACE_USB_DEVICE,
+        ..Default::default()
+    };
+    match unsafe { RegisterDeviceNotificationW(window.into(), &mut filter as *mut _ as *mut c_void, DEVICE_NOTIFY_WINDOW_HANDLE) } {
+        Ok(handle) => registrations.device.push(handle),
+        Err(e) => error!("注册 USB 设备插拔通知 GUID_DEVINTERFACE_USB_DEVICE ({:?}) 失败: {}", GUID_DEVINTERFACE_USB_DEVICE, e),
+    }
+
+    registrations
 }
 
 struct AppState {
     is_paused: bool,
+    // 已点击“退出”，正在等待告别语播完
+    is_exiting: bool,
     tts_engine: TtsEngine,
+    // 播报队列的句柄，可以克隆出来在释放锁之后播报
+    speech: SpeechHandle,
     i18n_manager: I18nManager,
     username: String,
-    last_usb_connect_time: Option<Instant>,
-    last_usb_disconnect_time: Option<Instant>,
+    // 合并窗口内尚未播报的 USB 接入 / 断开次数
+    pending_usb_connects: usize,
+    pending_usb_disconnects: usize,
+    usb_reconciler: UsbReconciler,
+    usb_debouncer: UsbDebouncer,
+    // 短时间内重复的相同播报只说一次
+    repeat_guard: RepeatGuard,
+    // 本次运行中已经播报过恢复的子系统
+    announced_recoveries: HashSet<String>,
+    // 最近一次接入的 USB 设备实例 ID，供托盘菜单“忽略最近接入的设备”使用
+    last_connected_device: Option<String>,
+    // 睡眠前的状态和睡眠期间未播报的事件，唤醒后只播报净变化
+    resume_summary: ResumeSummary,
+    // 专注助手开启，事件暂缓播报中
+    focus_assist_holding: bool,
+    // 当前语言配置的语音已不在系统中时为该语音的 (id, 名称)。配置保持不变，重新安装后会自动恢复使用
+    missing_voice: Option<(String, String)>,
+    // 语音引擎的健康状况，收到 WM_APP_STATUS_CHANGED 时从 status 同步，供设置窗口显示
+    engine_health: EngineHealth,
     config: Config,
     available_voices: Vec<VoiceDetail>,
+    // 省电模式状态：None 表示尚未收到注册时系统推送的初始值
+    battery_saver_enabled: Option<bool>,
+    last_battery_level_report: Option<(u8, Instant)>,
+    last_battery_saver_change: Option<(bool, Instant)>,
+    forwarder: Option<Forwarder>,
+    status: Arc<SharedStatus>,
+    // 以管理员身份运行时，HKCU 可能不是当前用户的注册表，设置窗口写自启动项前需要提醒
+    is_elevated: bool,
+    // 语言已切换，托盘提示和打开着的设置窗口还没有改用新的语言
+    language_changed: bool,
+    // 启动时开启的监控，供“关于”窗口和诊断信息显示
+    running_monitors: Vec<&'static str>,
+}
+
+impl AppState {
+    /// 换用另一种语言。托盘提示等界面文字由 refresh_language 在主线程更新
+    fn switch_language(&mut self, manager: I18nManager) {
+        self.i18n_manager = manager;
+        self.language_changed = true;
+    }
+}
+
+/// 配置中的语言，未设置时按系统语言选择。保留完整的语言标签 (例如 "zh-TW")，
+/// 由 I18nManager 按 zh-TW、zh-Hant、zh 的顺序寻找语言档案
+fn effective_locale(config: &Config) -> String {
+    match &config.language {
+        Some(lang_code) => i18n::normalize_tag(lang_code),
+        None => {
+            match sys_locale::get_locale() {
+                Some(sys_lang) => {
+                    // 没有任何对应语言档案的系统语言 (例如 "pt-BR" 而没有 pt.json) 使用英文
+                    let tag = i18n::normalize_tag(&sys_lang);
+                    if i18n::is_supported(&tag) { tag } else { "en".to_string() }
+                },
+                None => "en".to_string()
+            }
+        }
+    }
 }
 
 fn set_working_directory() -> Result<(), Box<dyn Error>> {
@@ -70,16 +254,136 @@ fn set_working_directory() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// 取出形如 `--flag <value>` 的命令行参数值
+fn parse_arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+    let index = args.iter().position(|a| a == flag)?;
+    args.get(index + 1).cloned()
+}
+
+const USAGE: &str = "用法: COMPUTER.exe [--lang <语言代码>] [--voice <语音名称或 ID>] [--paused] [--config <config.json 路径>] \
+[--log-level <off|error|warn|info|debug|trace>] [--timer <分钟>] [--listen <端口>] [--export-wav <文件.wav> --text <文字>]";
+
+/// 检查全部命令行参数，并取出只在本次运行中有效的覆盖设置。
+/// `--listen`、`--timer`、`--export-wav` 和 `--text` 由各自的功能读取，这里只检查是否带了参数值
+fn parse_overrides() -> Result<config::Overrides, String> {
+    let mut overrides = config::Overrides::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} 缺少参数值", arg));
+        match arg.as_str() {
+            "--lang" => overrides.language = Some(value()?),
+            "--voice" => overrides.voice = Some(value()?),
+            "--config" => overrides.config_path = Some(PathBuf::from(value()?)),
+            "--log-level" => {
+                let level = value()?;
+                overrides.log_level = Some(serde_json::from_value(serde_json::Value::String(level.to_lowercase()))
+                    .map_err(|_| format!("无效的日志级别: {}", level))?);
+            }
+            "--paused" => overrides.paused = true,
+            // 由开机自启动项附加，见 startup::launched_by_autostart
+            "--autostart" => {}
+            "--listen" | "--timer" | "--export-wav" | "--text" => { value()?; }
+            _ => return Err(format!("未知参数: {}", arg)),
+        }
+    }
+    Ok(overrides)
+}
+
+/// 解析 `--listen <port>`：以伴侣模式接收其他实例转发的播报。
+fn parse_listen_port() -> Option<u16> {
+    match parse_arg_value("--listen").map(|p| p.parse::<u16>()) {
+        Some(Ok(port)) => Some(port),
+        None => None,
+        _ => {
+            warn!("--listen 参数需要一个有效的端口号，已忽略。");
+            None
+        }
+    }
+}
+
+/// 解析 `--timer <minutes>`：启动一个任意时长的倒计时。
+fn parse_timer_minutes() -> Option<u32> {
+    match parse_arg_value("--timer").map(|m| m.parse::<u32>()) {
+        Some(Ok(minutes)) if minutes > 0 => Some(minutes),
+        None => None,
+        _ => {
+            warn!("--timer 参数需要一个正整数分钟数，已忽略。");
+            None
+        }
+    }
+}
+
+/// 如果已有实例在运行，把倒计时请求转交给它。返回 true 表示已转交，本进程应直接退出。
+fn forward_timer_to_running_instance(minutes: u32) -> bool {
+    let Ok(existing) = (unsafe { FindWindowW(MAIN_WINDOW_CLASS, None) }) else { return false };
+    let copy_data = COPYDATASTRUCT {
+        dwData: COPYDATA_START_TIMER,
+        cbData: std::mem::size_of::<u32>() as u32,
+        lpData: &minutes as *const u32 as *mut c_void,
+    };
+    unsafe { SendMessageW(existing, WM_COPYDATA, Some(WPARAM(0)), Some(LPARAM(&copy_data as *const _ as isize))) };
+    info!("已将 {} 分钟倒计时转交给正在运行的实例。", minutes);
+    true
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     if let Err(e) = set_working_directory() {
         return Err(format!("设置工作目录失败: {}", e).into());
     }
     
-    simple_logging::log_to_file("advanced_beeper.log", log::LevelFilter::Info)?;
+    // 先按默认设置写日志，读取配置后再切换到配置中的级别和路径
+    logging::init(logging::LogSettings::from_config(&Config::default())).map_err(|e| format!("初始化日志失败: {}", e))?;
     info!("-----------------------------------------");
     info!("高级提示 (Advanced Beeper) 应用程式启动");
     info!("-----------------------------------------");
     info!("工作目录已设置为可执行文件所在目录。");
+    if config::is_portable() {
+        info!("便携模式: 配置和日志保存在程序目录 {}", config::data_dir().display());
+    } else {
+        info!("配置和日志保存在 {}", config::data_dir().display());
+    }
+    // 在创建任何窗口之前声明按显示器感知 DPI，设置窗口和托盘图标在高 DPI 显示器上不会被拉伸模糊
+    if let Err(e) = unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) } {
+        warn!("声明按显示器感知 DPI 失败: {}", e);
+    }
+    // 程序没有控制台，参数有误时用消息框说明用法
+    let overrides = match parse_overrides() {
+        Ok(overrides) => overrides,
+        Err(message) => {
+            error!("命令行参数有误: {}", message);
+            unsafe { MessageBoxW(None, &HSTRING::from(format!("{}\n\n{}", message, USAGE)), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING) };
+            return Ok(());
+        }
+    };
+    if let Some(path) = &overrides.config_path {
+        info!("命令行参数: 使用配置文件 {}", path.display());
+    }
+    let paused_at_start = overrides.paused;
+    let voice_override = overrides.voice.clone();
+    config::set_overrides(overrides);
+    config::migrate_legacy_config();
+
+    let requested_timer = parse_timer_minutes();
+    if let Some(minutes) = requested_timer {
+        if forward_timer_to_running_instance(minutes) {
+            return Ok(());
+        }
+    }
+
+    let mut config = Config::load();
+    let log_settings = logging::LogSettings::from_config(&config);
+    if logging::active_path().as_ref() != Some(&log_settings.path) {
+        info!("日志改为写入 {}", log_settings.path.display());
+    }
+    logging::apply(log_settings);
+    info!("配置文件 config.json 已加载: {:?}", config);
+
+    // 登录时音频和语音服务可能还没就绪，自动启动时先等待一会儿再初始化。此时还没有托盘图标
+    if config.startup_delay_secs > 0 && startup::launched_by_autostart() {
+        info!("由开机自启动项启动，延迟 {} 秒后再初始化。", config.startup_delay_secs);
+        std::thread::sleep(Duration::from_secs(config.startup_delay_secs.into()));
+    }
 
     // --- CORE FIX: 为主线程初始化 COM ---
     // 这对于所有使用 WinRT 的操作（如此处的 TTS）都是必需的。
@@ -91,48 +395,44 @@ fn main() -> Result<(), Box<dyn Error>> {
         return Err(Box::new(error));
     }
     info!("主线程 COM (STA) 初始化成功。");
+    // 上次运行若在播报途中退出，其他程序的音量可能仍处于降低状态
+    audio_ducking::restore_after_crash();
 
+    let effective_locale = effective_locale(&config);
 
-    let config = Config::load();
-    info!("配置文件 config.json 已加载: {:?}", config);
-
-    let effective_locale = match &config.language {
-        Some(lang_code) => lang_code.clone(),
-        None => {
-            match sys_locale::get_locale() {
-                Some(sys_lang) => {
-                    let lang_prefix = sys_lang.split('-').next().unwrap_or(&sys_lang);
-                    match lang_prefix {
-                        "zh" => "zh".to_string(),
-                        "ja" => "ja".to_string(),
-                        _ => "en".to_string()
-                    }
-                },
-                None => "en".to_string()
-            }
-        }
-    };
+    let is_elevated = startup::is_elevated();
+    if is_elevated {
+        warn!("程序正以管理员身份运行，开机自启动项可能会写入管理员账户而不是当前用户。");
+    } else {
+        info!("程序以普通用户权限运行。");
+    }
 
     if let Err(e) = startup::set_auto_start(config.auto_start) {
         error!("启动时同步开机自启动设置失败: {}", e);
     }
 
     let (sender, receiver) = mpsc::channel();
-    
-    let tts_engine = {
+    let status = Arc::new(SharedStatus::default());
+
+    let mut tts_engine = {
         let mut engine = None;
         for attempt in 1..=3 {
-            match TtsEngine::new(&config) {
+            match TtsEngine::new(&config, status.clone()) {
                 Ok(e) => {
                     info!("TTS 语音引擎在第 {} 次尝试时初始化成功。", attempt);
                     engine = Some(e);
                     break;
                 },
-                Err(e) => {
+                // 只有引擎本身初始化失败（通常是开机时语音服务尚未就绪）才值得重试
+                Err(e @ AppError::TtsInit(_)) => {
                     warn!("TTS 语音引擎初始化失败 (尝试 {}/3): {}", attempt, e);
                     if attempt < 3 {
                         std::thread::sleep(Duration::from_secs(3));
                     }
+                },
+                Err(e) => {
+                    error!("TTS 语音引擎初始化失败，且该错误无法通过重试解决: {}", e);
+                    return Err(Box::new(e));
                 }
             }
         }
@@ -152,30 +452,116 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
-    let i18n_manager = I18nManager::new(&effective_locale)?;
+    // 旧版本按名称保存语音，转换为不会重名的语音 id
+    if config.migrate_voice_names(&available_voices) {
+        if let Err(e) = config.save() {
+            warn!("保存迁移后的语音设置失败: {}", e);
+        }
+    }
+
+    // 按界面语言选择语音：该语言记住的语音优先，旧版本的 custom_voice 作为备选
+    let mapped_voice = config.mapped_voice(&effective_locale);
+    match tts_engine.select_voice_for_language(&effective_locale, mapped_voice.map(String::as_str)) {
+        Ok(Some(voice)) => info!("语言 '{}' 使用语音: {}", effective_locale, voice.name),
+        Ok(None) => {}
+        Err(e) => warn!("为语言 '{}' 选择语音失败: {}", effective_locale, e),
+    }
+    // `--voice` 只在本次运行中代替配置的语音
+    if let Some(requested) = &voice_override {
+        let found = available_voices.iter()
+            .find(|v| v.id.eq_ignore_ascii_case(requested) || v.name.eq_ignore_ascii_case(requested));
+        match found {
+            Some(voice) => match tts_engine.set_voice(&voice.id) {
+                Ok(()) => info!("命令行参数: 使用语音 {}", voice.name),
+                Err(e) => warn!("使用命令行指定的语音 '{}' 失败: {}", voice.name, e),
+            },
+            None => warn!("找不到命令行指定的语音 '{}'，使用配置的语音。", requested),
+        }
+    }
+
+    // `--export-wav <文件> --text <文字>`：用配置的语音和语速导出 WAV 后直接退出，不启动托盘
+    if let Some(path) = parse_arg_value("--export-wav") {
+        let text = parse_arg_value("--text").unwrap_or_default();
+        return match tts_engine.synthesize_to_file(&text, std::path::Path::new(&path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                error!("导出 WAV 失败: {}", e);
+                Err(Box::new(e))
+            }
+        };
+    }
+
+    let forwarder = config.forward_to.clone().map(|target| {
+        info!("伴侣模式: 播报将同时转发到 {}", target);
+        Forwarder::start(target, config.companion_token.clone())
+    });
+
+    let mut i18n_manager = match I18nManager::new(&effective_locale) {
+        Ok(manager) => manager,
+        // 语言档案不存在时 I18nManager 自己会改用英文，这里只处理格式有误的档案
+        Err(e @ AppError::LocaleInvalid { .. }) if effective_locale != "en" => {
+            warn!("载入语言档案失败: {}。回退到英文。", e);
+            I18nManager::new("en")?
+        },
+        Err(e) => return Err(Box::new(e)),
+    };
     info!("国际化语言档案 (locale: {}) 载入成功。", effective_locale);
+    i18n_manager.set_overrides(&config.custom_messages);
 
     let app_state = Arc::new(Mutex::new(AppState {
-        is_paused: false,
+        is_paused: paused_at_start,
+        is_exiting: false,
+        speech: tts_engine.speech(),
         tts_engine,
         i18n_manager,
         username: get_windows_username(),
-        last_usb_connect_time: None,
-        last_usb_disconnect_time: None,
+        pending_usb_connects: 0,
+        pending_usb_disconnects: 0,
+        usb_reconciler: UsbReconciler::default(),
+        usb_debouncer: UsbDebouncer::default(),
+        repeat_guard: RepeatGuard::default(),
+        announced_recoveries: HashSet::new(),
+        last_connected_device: None,
+        resume_summary: ResumeSummary::default(),
+        focus_assist_holding: false,
+        missing_voice: None,
+        engine_health: EngineHealth::Ok,
         config,
         available_voices,
+        battery_saver_enabled: None,
+        last_battery_level_report: None,
+        last_battery_saver_change: None,
+        forwarder,
+        status: status.clone(),
+        is_elevated,
+        language_changed: false,
+        running_monitors: Vec::new(),
     }));
 
-    if let Err(e) = sender.send(SystemEvent::SystemStartup) {
-        error!("在启动时发送 SystemStartup 事件失败: {}", e);
+    if app_state.lock().unwrap().config.greet_user {
+        if let Err(e) = sender.send(SystemEvent::SystemStartup) {
+            error!("在启动时发送 SystemStartup 事件失败: {}", e);
+        }
+    }
+    if let Some((name, fallback)) = check_configured_voice(&mut app_state.lock().unwrap()) {
+        if let Err(e) = sender.send(SystemEvent::ConfiguredVoiceMissing { name, fallback }) {
+            error!("发送 ConfiguredVoiceMissing 事件失败: {}", e);
+        }
+    }
+    if is_elevated && app_state.lock().unwrap().config.warn_if_elevated {
+        if let Err(e) = sender.send(SystemEvent::RunningElevated) {
+            error!("发送 RunningElevated 事件失败: {}", e);
+        }
     }
 
     let window_proc_data = Box::into_raw(Box::new(WindowProcData {
         sender: sender.clone(),
         app_state: app_state.clone(),
+        registrations: None,
+        clock: Mutex::new(ClockObserver::new()),
     }));
     
-    let class_name = w!("AdvancedPromptsHiddenWindowClass");
+    let class_name = MAIN_WINDOW_CLASS;
     let instance = unsafe { GetModuleHandleW(None)? };
     let wc = WNDCLASSW { lpfnWndProc: Some(wndproc), hInstance: instance.into(), lpszClassName: class_name, ..Default::default() };
     
@@ -189,26 +575,106 @@ fn main() -> Result<(), Box<dyn Error>> {
             None, None, Some(instance.into()), Some(window_proc_data as *mut c_void),
         )?
     };
+    status.attach_window(hwnd);
+    hotkey::register_stop_hotkey(hwnd, &app_state.lock().unwrap().config.stop_hotkey);
 
-    start_monitoring(sender, hwnd);
+    let config_snapshot = app_state.lock().unwrap().config.clone();
+    request_startup_summary(&config_snapshot, sender.clone(), hwnd);
+    // 电量和网络监控由 start_monitoring 在最后开启
+    let mut monitors = vec!["battery", "network", "audio devices"];
+    audio_monitor::start_audio_monitor(sender.clone(), hwnd, config_snapshot.warn_headphones_not_default);
+    if let Some(port) = parse_listen_port() {
+        companion::start_listener(port, config_snapshot.companion_token.clone(), sender.clone(), hwnd);
+        monitors.push("companion listener");
+    }
+    process_monitor::start_process_monitor(&config_snapshot.watched_processes, sender.clone(), hwnd);
+    if config_snapshot.watched_processes.iter().any(|name| !name.trim().is_empty()) {
+        monitors.push("processes");
+    }
+    if config_snapshot.mirror_notifications {
+        notification_monitor::start_notification_monitor(&config_snapshot, sender.clone(), hwnd);
+        monitors.push("notifications");
+    }
+    break_reminder::start_break_reminder(&config_snapshot, sender.clone(), hwnd);
+    if config_snapshot.break_reminder_enabled {
+        monitors.push("break reminder");
+    }
+    if config_snapshot.announce_microphone_use {
+        mic_monitor::start_mic_monitor(sender.clone(), hwnd);
+        monitors.push("microphone");
+    }
+    if config_snapshot.announce_mic_mute {
+        mic_monitor::start_mic_mute_monitor(sender.clone(), hwnd);
+        monitors.push("microphone mute");
+    }
+    if config_snapshot.announce_camera_use {
+        camera_monitor::start_camera_monitor(config_snapshot.camera_poll_interval_secs, sender.clone(), hwnd);
+        monitors.push("camera");
+    }
+    if config_snapshot.announce_display_driver_reset {
+        gpu_monitor::start_gpu_monitor(sender.clone(), hwnd);
+        monitors.push("display driver");
+    }
+    if config_snapshot.announce_security_changes {
+        security_monitor::start_security_monitor(config_snapshot.security_poll_interval_secs, sender.clone(), hwnd);
+        monitors.push("security");
+    }
+    if config_snapshot.announce_print_jobs {
+        let username = app_state.lock().unwrap().username.clone();
+        print_monitor::start_print_monitor(username, sender.clone(), hwnd);
+        monitors.push("print jobs");
+    }
+    if config_snapshot.announce_radio_changes {
+        radio_monitor::start_radio_monitor(config_snapshot.announce_airplane_mode, sender.clone(), hwnd);
+        monitors.push("radios");
+    }
+    if let Some(quiet_hours) = &config_snapshot.quiet_hours {
+        match quiet_hours.minutes() {
+            None => warn!("安静时段的时间 '{}' - '{}' 无效，应为 HH:MM 格式，安静时段不会生效。", quiet_hours.start, quiet_hours.end),
+            Some((start, end)) if start == end => {
+                warn!("安静时段的开始和结束时间都是 '{}'，安静时段不会生效。", quiet_hours.start);
+            }
+            Some(_) => {}
+        }
+    }
+    config_watcher::start_config_watcher(sender.clone(), hwnd);
+    monitors.push("config file");
+    app_state.lock().unwrap().running_monitors = monitors;
+    update_tray_tooltip(hwnd, &app_state.lock().unwrap());
+    unsafe { SetTimer(Some(hwnd), TIMER_ID_TOOLTIP_REFRESH, 60_000, None) };
+    if !config_snapshot.timers.is_empty() {
+        info!("从配置中恢复了 {} 个倒计时。", config_snapshot.timers.len());
+        unsafe { SetTimer(Some(hwnd), TIMER_ID_USER_TIMERS, 1000, None) };
+    }
+    if let Some(minutes) = requested_timer {
+        start_user_timer(hwnd, &sender, &app_state, minutes);
+    }
+    start_monitoring(sender, hwnd, &config_snapshot);
     info!("已分派背景事件监控线程。");
 
+    run_message_loop(&receiver, |event| handle_system_event(event, &app_state, hwnd));
+    Ok(())
+}
+
+/// 主消息循环，收到 WM_QUIT 时返回。每取一条消息前先处理通道中的事件 (send_event 会投递
+/// WM_APP_WAKEUP 唤醒这里)。设置等对话框是非模态的，它们的消息也在这里分派，打开期间照常播报
+fn run_message_loop(receiver: &mpsc::Receiver<SystemEvent>, mut handle_event: impl FnMut(SystemEvent)) {
     let mut msg = MSG::default();
     loop {
         while let Ok(event) = receiver.try_recv() {
-            handle_system_event(event, &app_state);
+            handle_event(event);
         }
 
         let result = unsafe { GetMessageW(&mut msg, None, 0, 0) };
         if !result.as_bool() { break; }
+        // 对话框的键盘导航也在这里处理
+        if settings_ui::is_dialog_message(&msg) { continue; }
 
         unsafe {
             TranslateMessage(&msg);
             DispatchMessageW(&msg);
         }
     }
-    
-    Ok(())
 }
 
 // ... wndproc 和其他函数保持不变 ...
@@ -218,23 +684,11 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
         let data_ptr = create_struct.lpCreateParams as *mut WindowProcData;
         unsafe { SetWindowLongPtrW(window, GWLP_USERDATA, data_ptr as isize); }
         add_tray_icon(window);
-        
-        if unsafe { RegisterPowerSettingNotification(window.into(), &GUID_ACDC_POWER_SOURCE, REGISTER_NOTIFICATION_FLAGS(0)) }.is_err() {
-            error!("注册 AC/DC 电源通知失败。");
-        }
-        if unsafe { RegisterPowerSettingNotification(window.into(), &GUID_CONSOLE_DISPLAY_STATE, REGISTER_NOTIFICATION_FLAGS(0)) }.is_err() {
-            error!("注册显示器状态通知失败。");
-        }
-        
-        let mut filter = DEV_BROADCAST_DEVICEINTERFACE_W {
-            dbcc_size: std::mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32,
-            dbcc_devicetype: DBT_DEVTYP_DEVICEINTERFACE.0,
-            dbcc_classguid: GUID_DEVINTERFACE_USB_DEVICE,
-            ..Default::default()
-        };
-        if unsafe { RegisterDeviceNotificationW(window.into(), &mut filter as *mut _ as *mut c_void, DEVICE_NOTIFY_WINDOW_HANDLE) }.is_err() {
-            error!("注册 USB 设备插拔通知失败。");
-        }
+
+        // 替换旧的注册时，旧句柄会随 Drop 一起注销
+        let data = unsafe { &mut *data_ptr };
+        data.registrations = None;
+        data.registrations = Some(register_system_notifications(window));
 
         return LRESULT(0);
     }
@@ -248,16 +702,17 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
     
     match message {
         WM_DEVICECHANGE => {
-            let event = match wparam.0 as u32 {
-                DBT_DEVICEARRIVAL => Some(SystemEvent::UsbDeviceConnected),
-                DBT_DEVICEREMOVECOMPLETE => Some(SystemEvent::UsbDeviceDisconnected),
+            let connected = match wparam.0 as u32 {
+                DBT_DEVICEARRIVAL => Some(true),
+                DBT_DEVICEREMOVECOMPLETE => Some(false),
                 _ => None
             };
-            if let Some(event) = event {
+            if let Some(connected) = connected {
                 if lparam.0 != 0 {
                     let hdr = unsafe { &*(lparam.0 as *const DEV_BROADCAST_HDR) };
                     if hdr.dbch_devicetype == DBT_DEVTYP_DEVICEINTERFACE {
-                        handle_debounced_usb_event(event, sender, app_state_arc, window);
+                        let device_path = unsafe { device_interface_path(lparam.0 as *const DEV_BROADCAST_DEVICEINTERFACE_W) };
+                        queue_usb_change(connected, &device_path, app_state_arc, window);
                     }
                 }
             }
@@ -268,12 +723,14 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
             match wparam.0 as u32 {
                 PBT_APMSUSPEND => {
                     *IS_SYSTEM_ASLEEP.lock().unwrap() = true;
+                    app_state_arc.lock().unwrap().resume_summary.on_sleep(PowerSnapshot::read());
                     if sender.send(SystemEvent::SystemGoingToSleep).is_ok() {
                         unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
                     }
                 }
                 PBT_APMRESUMEAUTOMATIC => {
                     *IS_SYSTEM_ASLEEP.lock().unwrap() = false;
+                    data.clock.lock().unwrap().rebaseline();
                     if sender.send(SystemEvent::SystemResumedFromSleep).is_ok() {
                         unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
                     }
@@ -289,6 +746,14 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
                             }
                         }
                     } 
+                    else if pbs.PowerSetting == GUID_POWER_SAVING_STATUS {
+                        if !*IS_SYSTEM_ASLEEP.lock().unwrap() {
+                            let enabled = unsafe { *(pbs.Data.as_ptr() as *const u32) } != 0;
+                            if sender.send(SystemEvent::BatterySaverChanged { enabled }).is_ok() {
+                                unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+                            }
+                        }
+                    }
                     else if pbs.PowerSetting == GUID_CONSOLE_DISPLAY_STATE {
                         let display_state = unsafe { *(pbs.Data.as_ptr() as *const u32) };
                         let mut is_asleep_guard = IS_SYSTEM_ASLEEP.lock().unwrap();
@@ -318,19 +783,79 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
 
         WM_APP_WAKEUP => LRESULT(0),
 
+        WM_APP_STATUS_CHANGED => {
+            let mut app_state = app_state_arc.lock().unwrap();
+            update_tray_tooltip(window, &app_state);
+            let recoveries = app_state.status.take_recoveries();
+            let health = app_state.status.engine_health();
+            let previous_health = std::mem::replace(&mut app_state.engine_health, health);
+            drop(app_state);
+            let mut events: Vec<SystemEvent> = recoveries.into_iter().map(|(name, detail)| {
+                info!("子系统 '{}' 已恢复: {}", name, detail);
+                SystemEvent::SubsystemRecovered { name }
+            }).collect();
+            // 只在“彻底失败”和“从失败中恢复”时提醒，重新初始化一次就成功的情况不打扰用户
+            match (previous_health, health) {
+                (EngineHealth::Failed, EngineHealth::Failed) => {}
+                (_, EngineHealth::Failed) => events.push(SystemEvent::TtsFailed),
+                (EngineHealth::Failed, EngineHealth::Ok) => events.push(SystemEvent::TtsRecovered),
+                _ => {}
+            }
+            for event in events {
+                if sender.send(event).is_ok() {
+                    unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+                }
+            }
+            LRESULT(0)
+        }
+
         WM_APP_TRAY_MSG => {
             if (lparam.0 as u32 & 0xFFFF) == WM_RBUTTONUP {
                 let menu = unsafe { CreatePopupMenu().unwrap() };
-                let app_state = app_state_arc.lock().unwrap();
-                let i18n = &app_state.i18n_manager;
-                let pause_resume_text_key = if app_state.is_paused { "menu_resume" } else { "menu_pause" };
-                let pause_resume_text = i18n.get_text(pause_resume_text_key).unwrap_or_else(|| "Pause/Resume".to_string());
-                let settings_text = i18n.get_text("menu_settings").unwrap_or_else(|| "Settings...".to_string());
-                let exit_text = i18n.get_text("menu_exit").unwrap_or_else(|| "Exit".to_string());
+                // TrackPopupMenu 会进入模态消息循环，其间的计时器和状态消息都要锁 app_state，
+                // 所以菜单建好后先释放锁
+                {
+                    let app_state = app_state_arc.lock().unwrap();
+                    let text = |key: &str| HSTRING::from(tray_text(&app_state.i18n_manager, key));
+                    let pause_resume_text = text(pause_menu_key(app_state.is_paused));
+                    let settings_text = text("menu_settings");
+                    let open_log_text = text("menu_open_log");
+                    let view_log_text = text("menu_view_log");
+                    let about_text = text("menu_about");
+                    let exit_text = text("menu_exit");
+                    let status_text = text("menu_status");
+                    let timer_menu = build_timer_menu(&app_state);
+                    let profile_menu = build_profile_menu(&app_state);
+                    let profile_text = text("menu_profile");
+                    let timer_text = text("menu_timer");
+                    let stop_text = text("menu_stop");
+                    let skip_text = text("menu_skip_pending");
+                    let skip_flags = if app_state.status.queued() > 0 { MF_STRING } else { MF_STRING | MF_GRAYED };
+                    let ignore_text = text("menu_ignore_last_device");
+                    let ignore_flags = if app_state.last_connected_device.is_some() { MF_STRING } else { MF_STRING | MF_GRAYED };
+                    unsafe {
+                        // 提示用户为什么现在没有播报，仅作说明，不能点击
+                        if app_state.focus_assist_holding {
+                            AppendMenuW(menu, MF_STRING | MF_GRAYED, 0, &text("menu_focus_assist_holding")).ok();
+                            AppendMenuW(menu, MF_SEPARATOR, 0, None).ok();
+                        }
+                        AppendMenuW(menu, MF_STRING, ID_MENU_STATUS as usize, &status_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_PAUSE_RESUME as usize, &pause_resume_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_STOP as usize, &stop_text).ok();
+                        AppendMenuW(menu, skip_flags, ID_MENU_SKIP_PENDING as usize, &skip_text).ok();
+                        AppendMenuW(menu, MF_POPUP, timer_menu.0 as usize, &timer_text).ok();
+                        if let Some(profile_menu) = profile_menu {
+                            AppendMenuW(menu, MF_POPUP, profile_menu.0 as usize, &profile_text).ok();
+                        }
+                        AppendMenuW(menu, ignore_flags, ID_MENU_IGNORE_LAST_DEVICE as usize, &ignore_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_SETTINGS as usize, &settings_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_VIEW_LOG as usize, &view_log_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_OPEN_LOG as usize, &open_log_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_ABOUT as usize, &about_text).ok();
+                        AppendMenuW(menu, MF_STRING, ID_MENU_EXIT as usize, &exit_text).ok();
+                    }
+                }
                 unsafe {
-                    AppendMenuW(menu, MF_STRING, ID_MENU_PAUSE_RESUME as usize, &HSTRING::from(pause_resume_text)).ok();
-                    AppendMenuW(menu, MF_STRING, ID_MENU_SETTINGS as usize, &HSTRING::from(settings_text)).ok();
-                    AppendMenuW(menu, MF_STRING, ID_MENU_EXIT as usize, &HSTRING::from(exit_text)).ok();
                     let mut point = Default::default();
                     GetCursorPos(&mut point).ok();
                     SetForegroundWindow(window);
@@ -347,27 +872,116 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
                     app_state.is_paused = !app_state.is_paused;
                     let announcement_key = if app_state.is_paused { "announcement_paused" } else { "announcement_resumed" };
                     if let Some(text) = app_state.i18n_manager.get_text(announcement_key) {
-                        app_state.tts_engine.speak(&text).ok();
+                        app_state.speech.speak_user_triggered(&text).ok();
+                    }
+                    update_tray_tooltip(window, &app_state);
+                }
+                ID_MENU_SKIP_PENDING => {
+                    let app_state = app_state_arc.lock().unwrap();
+                    match app_state.speech.flush() {
+                        Ok(skipped) if skipped > 0 => {
+                            if let Some(text) = app_state.i18n_manager.get_text_with_param("announcement_skipped", "count", &skipped.to_string()) {
+                                app_state.speech.speak_user_triggered(&text).ok();
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => error!("跳过待播报内容失败: {}", e),
                     }
                 }
+                ID_MENU_STOP => stop_speaking(app_state_arc),
+                ID_MENU_STATUS => request_status(sender.clone(), window),
+                ID_MENU_IGNORE_LAST_DEVICE => ignore_last_connected_device(app_state_arc),
                 ID_MENU_SETTINGS => settings_ui::show(window, app_state_arc.clone()),
+                ID_MENU_OPEN_LOG => open_log_file(),
+                ID_MENU_VIEW_LOG => log_viewer::show(window, app_state_arc.clone()),
+                ID_MENU_ABOUT => about_ui::show(window, app_state_arc.clone()),
+                id if (ID_MENU_TIMER_START_BASE..ID_MENU_TIMER_START_BASE + timers::PRESET_MINUTES.len() as u32).contains(&id) => {
+                    let minutes = timers::PRESET_MINUTES[(id - ID_MENU_TIMER_START_BASE) as usize];
+                    start_user_timer(window, sender, app_state_arc, minutes);
+                }
+                id if (ID_MENU_PROFILE_BASE..=ID_MENU_PROFILE_LAST).contains(&id) => {
+                    switch_profile(window, app_state_arc, (id - ID_MENU_PROFILE_BASE) as usize);
+                }
+                id if (ID_MENU_TIMER_CANCEL_BASE..=ID_MENU_TIMER_CANCEL_LAST).contains(&id) => {
+                    cancel_user_timer(window, sender, app_state_arc, id - ID_MENU_TIMER_CANCEL_BASE);
+                }
                 ID_MENU_EXIT => {
-                    {
-                        let mut app_state = app_state_arc.lock().unwrap();
-                        if let Some(text) = app_state.i18n_manager.get_text("announcement_exit") {
-                           app_state.tts_engine.speak(&text).ok();
-                        }
+                    let mut guard = app_state_arc.lock().unwrap();
+                    let app_state = &mut *guard;
+                    if !app_state.is_exiting {
+                        app_state.is_exiting = true;
+                        let farewell = app_state.i18n_manager.get_text("announcement_exit")
+                            .and_then(|text| app_state.speech.speak_user_triggered(&text).ok())
+                            .map(|id| app_state.speech.waiter(id));
+                        drop(guard);
+                        exit_after_farewell(window, farewell);
                     }
-                    std::thread::sleep(std::time::Duration::from_secs(5));
-                    unsafe { DestroyWindow(window) };
                 }
                 _ => {}
             }
             LRESULT(0)
         }
+        WM_APP_EXIT => {
+            unsafe { DestroyWindow(window).ok() };
+            LRESULT(0)
+        }
+
+        WM_HOTKEY if wparam.0 as i32 == hotkey::HOTKEY_ID_STOP => {
+            stop_speaking(app_state_arc);
+            LRESULT(0)
+        }
+
+        WM_TIMER if wparam.0 == TIMER_ID_USER_TIMERS => {
+            check_user_timers(window, sender, app_state_arc);
+            LRESULT(0)
+        }
+
+        WM_TIMER if wparam.0 == TIMER_ID_USB_CONNECTS || wparam.0 == TIMER_ID_USB_DISCONNECTS => {
+            flush_usb_changes(wparam.0 == TIMER_ID_USB_CONNECTS, sender, app_state_arc, window);
+            LRESULT(0)
+        }
+
+        WM_TIMER if wparam.0 == TIMER_ID_FOCUS_ASSIST => {
+            check_focus_assist_ended(sender, app_state_arc, window);
+            LRESULT(0)
+        }
+
+        WM_TIMER if wparam.0 == TIMER_ID_TOOLTIP_REFRESH => {
+            update_tray_tooltip(window, &app_state_arc.lock().unwrap());
+            LRESULT(0)
+        }
+
+        WM_TIMER if wparam.0 == TIMER_ID_REPEAT_SUMMARY => {
+            announce_repeat_summaries(app_state_arc, window);
+            LRESULT(0)
+        }
+
+        WM_TIMECHANGE => {
+            for event in data.clock.lock().unwrap().on_time_change() {
+                if sender.send(event).is_ok() {
+                    unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+                }
+            }
+            LRESULT(0)
+        }
+
+        WM_COPYDATA => {
+            let copy_data = unsafe { &*(lparam.0 as *const COPYDATASTRUCT) };
+            if copy_data.dwData == COPYDATA_START_TIMER && copy_data.cbData as usize == std::mem::size_of::<u32>() {
+                let minutes = unsafe { *(copy_data.lpData as *const u32) };
+                if minutes > 0 {
+                    start_user_timer(window, sender, app_state_arc, minutes);
+                    return LRESULT(1);
+                }
+            }
+            LRESULT(0)
+        }
+
         WM_DESTROY => {
             remove_tray_icon(window);
-            let _ = unsafe { Box::from_raw(SetWindowLongPtrW(window, GWLP_USERDATA, 0) as *mut WindowProcData) };
+            hotkey::unregister_stop_hotkey(window);
+            let mut data = unsafe { Box::from_raw(SetWindowLongPtrW(window, GWLP_USERDATA, 0) as *mut WindowProcData) };
+            drop(data.registrations.take());
             unsafe { PostQuitMessage(0) };
             LRESULT(0)
         }
@@ -375,20 +989,161 @@ extern "system" fn wndproc(window: HWND, message: u32, wparam: WPARAM, lparam: L
     }
 }
 
-fn handle_system_event(event: SystemEvent, app_state_arc: &Arc<Mutex<AppState>>) {
-    if *IS_SYSTEM_ASLEEP.lock().unwrap() && !matches!(event, SystemEvent::SystemResumedFromSleep) { return; }
+fn handle_system_event(event: SystemEvent, app_state_arc: &Arc<Mutex<AppState>>, window: HWND) {
     if matches!(event, SystemEvent::SystemGoingToSleep) { return; }
     let mut app_state = app_state_arc.lock().unwrap();
-    if app_state.is_paused { return; }
-    
+    // 拔掉 USB 声卡等情况下，语音引擎可能仍绑定在已经不存在的设备上
+    if matches!(event, SystemEvent::DefaultAudioDeviceChanged) {
+        info!("默认音频输出设备已变化，重新初始化语音引擎。");
+        app_state.tts_engine.reinitialize(&app_state.config);
+        return;
+    }
+    if let SystemEvent::ConfigReloaded { config } = event {
+        if apply_reloaded_config(&mut app_state, *config) && app_state.config.announce_config_reload {
+            if let Some(text) = app_state.i18n_manager.get_text("announcement_config_reloaded") {
+                if let Err(e) = app_state.speech.speak(&text) {
+                    error!("播报设置重新加载提示失败: {}", e);
+                }
+            }
+        }
+        update_tray_tooltip(window, &app_state);
+        refresh_language(window, &mut app_state);
+        return;
+    }
+    // 语音引擎失效时说不出话，只能用托盘气泡提示（播报线程已经响过蜂鸣）
+    if matches!(event, SystemEvent::TtsFailed | SystemEvent::TtsRecovered) {
+        let failed = matches!(event, SystemEvent::TtsFailed);
+        let i18n = &app_state.i18n_manager;
+        let title = i18n.get_text("tts_balloon_title").unwrap_or_else(|| "Speech output".to_string());
+        let text = if failed {
+            i18n.get_text("tts_failed_balloon").unwrap_or_else(|| "Speech output stopped working. Announcements are beeps for now.".to_string())
+        } else {
+            i18n.get_text("tts_recovered_balloon").unwrap_or_else(|| "Speech output works again.".to_string())
+        };
+        show_tray_balloon(window, &title, &text, if failed { NIIF_WARNING } else { NIIF_INFO });
+        return;
+    }
+    // 睡眠期间的事件先记下来，唤醒后与睡眠前的状态比较，只播报净变化
+    if *IS_SYSTEM_ASLEEP.lock().unwrap() && !matches!(event, SystemEvent::SystemResumedFromSleep) {
+        app_state.resume_summary.record(event);
+        return;
+    }
+    // 用户自己要求的播报（倒计时、状态）不受事件开关影响
+    if !event.bypasses_pause() && !app_state.config.event_enabled(&event.name()) {
+        debug!("事件 {} 的播报已关闭。", event.name());
+        return;
+    }
+    let priority = speech_priority(&event, &app_state.config.announcement_priorities);
+    let (focus_assist, fullscreen) = if app_state.config.respect_focus_assist { announce_policy::busy_state() } else { (false, false) };
+    let suppressors = announce_policy::Suppressors {
+        paused: app_state.is_paused,
+        quiet_hours: announce_policy::active_quiet_mode(app_state.config.quiet_hours.as_ref()),
+        focus_assist,
+        fullscreen,
+        screen_reader_muted: app_state.config.mute_with_screen_reader && announce_policy::is_screen_reader_active(),
+    };
+    let decision = announce_policy::decide(priority, event.bypasses_pause(), &suppressors);
+    // 专注助手开启期间同样先记下事件，关闭后只播报净变化
+    if decision == announce_policy::Decision::Defer {
+        if !app_state.focus_assist_holding {
+            info!("专注助手已开启，暂缓播报。");
+            app_state.focus_assist_holding = true;
+            app_state.resume_summary.begin_hold(PowerSnapshot::read());
+            unsafe { SetTimer(Some(window), TIMER_ID_FOCUS_ASSIST, FOCUS_ASSIST_POLL_MS, None) };
+        }
+        debug!("专注助手开启中，事件 {:?} 暂缓播报", event);
+        app_state.resume_summary.record(event);
+        return;
+    }
+    app_state.resume_summary.observe(&event);
+    let (sleep_changes, asleep_since) = if matches!(event, SystemEvent::SystemResumedFromSleep | SystemEvent::FocusAssistEnded) {
+        let since = app_state.resume_summary.since();
+        (app_state.resume_summary.on_resume(PowerSnapshot::read()), since)
+    } else {
+        (Vec::new(), None)
+    };
+    match decision {
+        announce_policy::Decision::Speak | announce_policy::Decision::Defer => {}
+        announce_policy::Decision::Beep => {
+            debug!("安静时段内，事件 {:?} 只响蜂鸣", event);
+            app_state.speech.beep(priority);
+            return;
+        }
+        announce_policy::Decision::Suppress(reason) => {
+            debug!("事件 {:?} 未播报 ({})", event, reason);
+            return;
+        }
+    }
+
+    // 省电模式通常在电量降到 20% 时自动开启，与电量播报几乎同时到达。
+    // 记录两者的时间，若在 BATTERY_MERGE_WINDOW 内相继到达，则合并为一句并打断前一句。
+    let now = Instant::now();
+    match &event {
+        SystemEvent::BatterySaverChanged { enabled } => {
+            let previous = app_state.battery_saver_enabled.replace(*enabled);
+            // 注册通知时系统会立即推送一次当前状态，这不是用户关心的变化
+            if previous.is_none() || previous == Some(*enabled) { return; }
+            app_state.last_battery_saver_change = Some((*enabled, now));
+        }
+        SystemEvent::BatteryLevelReport(level) => {
+            app_state.last_battery_level_report = Some((*level, now));
+        }
+        // 每个子系统每次运行只播报一次恢复，反复出错的子系统不会一直打扰用户
+        SystemEvent::SubsystemRecovered { name } if !app_state.announced_recoveries.insert(name.clone()) => {
+            info!("子系统 '{}' 的恢复本次运行已播报过，不再重复播报。", name);
+            return;
+        }
+        _ => {}
+    }
+    let recent_level = app_state.last_battery_level_report
+        .filter(|(_, at)| now.duration_since(*at) < BATTERY_MERGE_WINDOW)
+        .map(|(level, _)| level);
+    let recent_saver_change = app_state.last_battery_saver_change
+        .filter(|(_, at)| now.duration_since(*at) < BATTERY_MERGE_WINDOW)
+        .map(|(enabled, _)| enabled);
+    let mut interrupt = false;
+    // 需要在主播报之后依次播报的句子，每句单独排队，避免长句被截断
+    let mut follow_up: Vec<String> = Vec::new();
+
+    // 睡眠期间可能安装或卸载了语音包
+    let voice_missing = if matches!(event, SystemEvent::SystemResumedFromSleep) {
+        check_configured_voice(&mut app_state)
+    } else {
+        None
+    };
+
     let i18n = &app_state.i18n_manager;
     let text_to_speak = match &event {
+        SystemEvent::SystemStartup if app_state.config.greet_with_date => {
+            let now = unsafe { GetLocalTime() };
+            let time = format::time_of_day(i18n, app_state.config.clock_format, now.wHour, now.wMinute);
+            let date = format::date(i18n, now.wYear, now.wMonth, now.wDay, now.wDayOfWeek);
+            i18n.get_text_with_params("system_online_with_date", &[("user", &app_state.username), ("time", &time), ("date", &date)])
+        }
         SystemEvent::SystemStartup => i18n.get_text_with_param("system_online", "user", &app_state.username),
+        SystemEvent::RunningElevated => i18n.get_text("running_elevated"),
+        SystemEvent::ConfiguredVoiceMissing { name, fallback } => voice_missing_text(i18n, name, fallback),
         SystemEvent::PowerSwitchedToAC => i18n.get_text("external_power_connected"),
         SystemEvent::PowerSwitchedToBattery => i18n.get_text("switched_to_battery"),
-        SystemEvent::BatteryLevelReport(level) => i18n.get_text_with_param("battery_level_report", "level", &level.to_string()),
-        SystemEvent::UsbDeviceConnected => i18n.get_text("usb_device_detected"),
-        SystemEvent::UsbDeviceDisconnected => i18n.get_text("usb_device_disconnected"),
+        SystemEvent::BatteryLevelReport(_) if priority == SpeechPriority::Critical => i18n.get_text("battery_level_critical"),
+        SystemEvent::BatteryLevelReport(level) => match recent_saver_change {
+            Some(enabled) => {
+                interrupt = true;
+                let key = if enabled { "battery_saver_on_with_level" } else { "battery_saver_off_with_level" };
+                i18n.get_text_with_param(key, "level", &format::percent(i18n, *level))
+            }
+            None => i18n.get_text_with_param("battery_level_report", "level", &format::percent(i18n, *level)),
+        },
+        SystemEvent::BatterySaverChanged { enabled } => match recent_level {
+            Some(level) => {
+                interrupt = true;
+                let key = if *enabled { "battery_saver_on_with_level" } else { "battery_saver_off_with_level" };
+                i18n.get_text_with_param(key, "level", &format::percent(i18n, level))
+            }
+            None => i18n.get_text(if *enabled { "battery_saver_on" } else { "battery_saver_off" }),
+        },
+        SystemEvent::UsbDevicesConnected { count } => i18n.get_text_plural("usb_device_detected", *count as u64, &[]),
+        SystemEvent::UsbDevicesDisconnected { count } => i18n.get_text_plural("usb_device_disconnected", *count as u64, &[]),
         SystemEvent::BatteryInserted => i18n.get_text("battery_inserted"),
         SystemEvent::BatteryRemoved => i18n.get_text("battery_removed"),
         SystemEvent::NetworkConnected { name, conn_type } => match conn_type {
@@ -396,40 +1151,637 @@ fn handle_system_event(event: SystemEvent, app_state_arc: &Arc<Mutex<AppState>>)
             _ => i18n.get_text("network_connected_ethernet"),
         },
         SystemEvent::NetworkDisconnected => i18n.get_text("network_disconnected"),
-        SystemEvent::SystemResumedFromSleep => i18n.get_text("system_resumed_from_sleep"),
+        SystemEvent::NetworkSwitched { from, to } => i18n.get_text_with_params("network_switched", &[("from", from), ("to", to)]),
+        SystemEvent::SystemResumedFromSleep => {
+            // 知道何时进入睡眠时说“从 23:30 开始的睡眠期间”
+            let since = asleep_since.map(|(hour, minute)| format::time_of_day(i18n, app_state.config.clock_format, hour, minute));
+            let template_key = if since.is_some() { "sleep_changes_since" } else { "sleep_changes" };
+            follow_up.extend(format_sleep_changes(i18n, template_key, &sleep_changes, since.as_deref()));
+            follow_up.extend(voice_missing.and_then(|(name, fallback)| voice_missing_text(i18n, &name, &fallback)));
+            i18n.get_text("system_resumed_from_sleep")
+        }
+        SystemEvent::FocusAssistEnded => format_sleep_changes(i18n, "focus_assist_changes", &sleep_changes, None),
+        SystemEvent::ChargerWattageReport { watts } => i18n.get_text_with_param("charger_wattage_report", "watts", &format::integer(i18n, i64::from(*watts))),
+        SystemEvent::HeadphonesNotDefault { current_device } => i18n.get_text_with_param("headphones_not_default", "device", current_device),
+        SystemEvent::RemoteAnnouncement { text } => Some(text.clone()),
+        SystemEvent::TimerStarted { minutes } => i18n.get_text_with_param("timer_started", "minutes", &minutes.to_string()),
+        SystemEvent::TimerCancelled { minutes } => i18n.get_text_with_param("timer_cancelled", "minutes", &minutes.to_string()),
+        SystemEvent::TimerHalfway { minutes, remaining_minutes } => i18n.get_text_with_params("timer_halfway", &[
+            ("minutes", &minutes.to_string()),
+            ("remaining", &format::duration_minutes(i18n, u64::from(*remaining_minutes))),
+        ]),
+        SystemEvent::TimerOneMinuteLeft { minutes } => i18n.get_text_with_param("timer_one_minute_left", "minutes", &minutes.to_string()),
+        SystemEvent::TimerElapsed { minutes } => i18n.get_text_with_param("timer_elapsed", "minutes", &minutes.to_string()),
+        SystemEvent::NotificationReceived { app, text } => i18n.get_text_with_params("notification_received", &[("app", app), ("text", text)]),
+        SystemEvent::TimeZoneChanged { name } => i18n.get_text_with_param("time_zone_changed", "name", name),
+        SystemEvent::ClockChanged { delta_minutes } => {
+            let key = if *delta_minutes > 0 { "clock_moved_forward" } else { "clock_moved_backward" };
+            let duration = format::duration_minutes(i18n, delta_minutes.unsigned_abs());
+            i18n.get_text_with_param(key, "duration", &duration)
+        }
+        SystemEvent::BreakReminder { minutes } => {
+            let duration = format::duration_minutes(i18n, u64::from(*minutes));
+            i18n.get_text_with_param("break_reminder", "duration", &duration)
+        }
+        SystemEvent::MicrophoneInUse { app } => i18n.get_text_with_param("microphone_in_use", "app", app),
+        SystemEvent::MicrophoneReleased => i18n.get_text("microphone_released"),
+        SystemEvent::MicrophoneMuteChanged { muted } => i18n.get_text(if *muted { "microphone_muted" } else { "microphone_live" }),
+        SystemEvent::CameraInUse { app } => i18n.get_text_with_param("camera_in_use", "app", app),
+        SystemEvent::CameraReleased => i18n.get_text("camera_released"),
+        SystemEvent::DisplayDriverReset { driver } => if driver.is_empty() {
+            i18n.get_text("display_driver_reset")
+        } else {
+            i18n.get_text_with_param("display_driver_reset_named", "driver", driver)
+        },
+        SystemEvent::RadioStateChanged { kind, on } => {
+            let radio = i18n.get_text(kind.name_key()).unwrap_or_else(|| format!("{:?}", kind));
+            i18n.get_text_with_param(if *on { "radio_turned_on" } else { "radio_turned_off" }, "radio", &radio)
+        }
+        SystemEvent::AirplaneModeChanged { on } => i18n.get_text(if *on { "airplane_mode_on" } else { "airplane_mode_off" }),
+        // 没有电池时不播报电源，没有网络时不播报网络，只说有内容的部分
+        SystemEvent::StartupSummary { battery, network } => {
+            follow_up.extend(network.as_ref().and_then(|n| network_status_text(i18n, Some(n))));
+            battery.and_then(|b| battery_status_text(i18n, Some(b)))
+        }
+        SystemEvent::PrintJobCompleted { document, pages } => if *pages == 0 {
+            i18n.get_text_with_param("print_job_completed", "document", document)
+        } else {
+            i18n.get_text_plural("print_job_completed_pages", u64::from(*pages), &[("document", document)])
+        },
+        SystemEvent::RealtimeProtectionDisabled => i18n.get_text("realtime_protection_disabled"),
+        SystemEvent::RealtimeProtectionEnabled => i18n.get_text("realtime_protection_enabled"),
+        SystemEvent::FirewallDisabled => i18n.get_text("firewall_disabled"),
+        SystemEvent::FirewallEnabled => i18n.get_text("firewall_enabled"),
+        SystemEvent::PrintJobFailed { document } => i18n.get_text_with_param("print_job_failed", "document", document),
+        SystemEvent::StatusRequest { battery, network } => Some(format_status(i18n, *battery, network.as_ref())),
+        SystemEvent::SubsystemRecovered { name } => {
+            let subsystem = i18n.get_text(&format!("subsystem_{}", name)).unwrap_or_else(|| name.clone());
+            i18n.get_text_with_param("subsystem_recovered", "name", &subsystem)
+        }
+        SystemEvent::WatchedProcessStarted { name } => i18n.get_text_with_param("watched_process_started", "name", name),
+        SystemEvent::WatchedProcessExited { name, exit_code } => match exit_code {
+            Some(code) if *code != 0 => i18n.get_text_with_params("watched_process_exited_with_code", &[("name", name), ("code", &code.to_string())]),
+            _ => i18n.get_text_with_param("watched_process_exited", "name", name),
+        },
         _ => None, 
     };
     
-    if let Some(text) = text_to_speak {
-        app_state.tts_engine.speak(&text).ok();
+    // 为该事件配置的提示音：Replace 模式下代替语音，Before 模式下在第一句之前播放。
+    // 文件无法播放时照常播报语音
+    let mut sound_before = None;
+    let mut speak = true;
+    if let Some(event_sound) = app_state.config.event_sounds.get(&event.name()) {
+        match event_sound.mode {
+            SoundMode::Replace => speak = !sound::play_async(&event_sound.file),
+            SoundMode::Before => sound_before = sound::is_playable(&event_sound.file).then(|| event_sound.file.clone()),
+        }
+    }
+
+    for text in text_to_speak.into_iter().chain(follow_up) {
+        // 远端转发来的播报不再继续转发，避免两台机器互相转发形成回环
+        if !matches!(event, SystemEvent::RemoteAnnouncement { .. }) {
+            if let Some(forwarder) = &app_state.forwarder {
+                forwarder.forward(&text);
+            }
+        }
+        if !speak { continue; }
+        // 网络反复断开重连等情况下，相同的句子在窗口内只播报一次。
+        // 用户主动请求的状态和 Critical 提醒不受影响
+        let repeat_window = Duration::from_secs(app_state.config.repeat_suppression_secs);
+        let guarded = !repeat_window.is_zero() && priority != SpeechPriority::Critical
+            && !matches!(event, SystemEvent::StatusRequest { .. });
+        if guarded && !app_state.repeat_guard.accept(&text, repeat_window) {
+            unsafe { SetTimer(Some(window), TIMER_ID_REPEAT_SUMMARY, repeat_window.as_millis() as u32, None) };
+            continue;
+        }
+        if let Some(sound_file) = sound_before.take() {
+            app_state.speech.speak_after_sound(&text, priority, sound_file).ok();
+        } else if interrupt {
+            app_state.speech.speak_interrupting(&text).ok();
+        } else if matches!(event, SystemEvent::StatusRequest { .. }) {
+            // 用户刚从菜单请求的状态播报
+            app_state.speech.speak_user_triggered(&text).ok();
+        } else if matches!(event, SystemEvent::MicrophoneMuteChanged { .. }) {
+            // 此时多半在开会，提示音量要低一些，免得被麦克风收进去
+            let volume_scale = app_state.config.mic_mute_announcement_volume;
+            app_state.speech.speak_scaled(&text, priority, volume_scale).ok();
+        } else {
+            app_state.speech.speak_with_priority(&text, priority).ok();
+        }
     }
 }
 
-const USB_DEBOUNCE_DURATION: Duration = Duration::from_secs(2);
+/// 专注助手关闭后停止轮询，并让暂缓期间的变化作为一条事件播报
+fn check_focus_assist_ended(sender: &mpsc::Sender<SystemEvent>, app_state_arc: &Arc<Mutex<AppState>>, window: HWND) {
+    if announce_policy::is_focus_assist_active() { return; }
+    unsafe { KillTimer(Some(window), TIMER_ID_FOCUS_ASSIST).ok() };
+    app_state_arc.lock().unwrap().focus_assist_holding = false;
+    info!("专注助手已关闭，恢复播报。");
+    if sender.send(SystemEvent::FocusAssistEnded).is_ok() {
+        unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+    }
+}
 
-fn handle_debounced_usb_event(
-    event: SystemEvent, 
-    sender: &mpsc::Sender<SystemEvent>, 
-    app_state_arc: &Arc<Mutex<AppState>>,
-    window: HWND,
-) {
+/// 重复播报平息后，按配置说明上一句又重复了几次。仍在重复的句子稍后再检查
+fn announce_repeat_summaries(app_state_arc: &Arc<Mutex<AppState>>, window: HWND) {
+    unsafe { KillTimer(Some(window), TIMER_ID_REPEAT_SUMMARY).ok() };
     let mut app_state = app_state_arc.lock().unwrap();
-    let now = Instant::now();
-    let should_send = match event {
-        SystemEvent::UsbDeviceConnected => {
-            let last_time = app_state.last_usb_connect_time.get_or_insert(now);
-            if now.duration_since(*last_time) < USB_DEBOUNCE_DURATION && *last_time != now { false }
-            else { *last_time = now; true }
-        }
-        SystemEvent::UsbDeviceDisconnected => {
-            let last_time = app_state.last_usb_disconnect_time.get_or_insert(now);
-            if now.duration_since(*last_time) < USB_DEBOUNCE_DURATION && *last_time != now { false }
-            else { *last_time = now; true }
-        }
-        _ => true,
+    let repeat_window = Duration::from_secs(app_state.config.repeat_suppression_secs.max(1));
+    let finished = app_state.repeat_guard.take_finished(repeat_window);
+    if app_state.repeat_guard.has_pending() {
+        unsafe { SetTimer(Some(window), TIMER_ID_REPEAT_SUMMARY, repeat_window.as_millis() as u32, None) };
+    }
+    if !app_state.config.announce_repeat_summary || app_state.is_paused { return; }
+    if announce_policy::active_quiet_mode(app_state.config.quiet_hours.as_ref()).is_some() { return; }
+    for (text, count) in finished {
+        info!("播报 '{}' 被跳过了 {} 次。", text, count);
+        if let Some(summary) = app_state.i18n_manager.get_text_plural("announcement_repeated", count as u64, &[]) {
+            app_state.speech.speak_with_priority(&summary, SpeechPriority::Low).ok();
+        }
+    }
+}
+
+/// 播报排队时的优先级：用户主动设置的提醒和电量不足优先，转发、通知和 USB 这类可能成批到达的内容靠后。
+/// 除了会导致断电的 Critical 提醒外，都可以用 `announcement_priorities` 按事件名称覆盖。
+fn speech_priority(event: &SystemEvent, overrides: &HashMap<String, SpeechPriority>) -> SpeechPriority {
+    let on_battery = || PowerSnapshot::read().is_some_and(|power| !power.on_ac);
+    let default = match event {
+        // 只靠电池供电时，电量严重不足或电池被拔出都可能让电脑随时断电
+        SystemEvent::BatteryLevelReport(level) if *level <= CRITICAL_BATTERY_LEVEL && on_battery() => return SpeechPriority::Critical,
+        SystemEvent::BatteryRemoved if on_battery() => return SpeechPriority::Critical,
+        SystemEvent::BatteryLevelReport(level) if *level <= LOW_BATTERY_LEVEL && on_battery() => SpeechPriority::High,
+        SystemEvent::TimerElapsed { .. } | SystemEvent::TimerOneMinuteLeft { .. } |
+        SystemEvent::StatusRequest { .. } |
+        SystemEvent::RealtimeProtectionDisabled | SystemEvent::FirewallDisabled => SpeechPriority::High,
+        SystemEvent::RemoteAnnouncement { .. } | SystemEvent::NotificationReceived { .. } |
+        SystemEvent::UsbDevicesConnected { .. } | SystemEvent::UsbDevicesDisconnected { .. } |
+        SystemEvent::SubsystemRecovered { .. } => SpeechPriority::Low,
+        _ => SpeechPriority::Normal,
     };
+    overrides.get(&event.name()).copied().unwrap_or(default)
+}
+
+const BATTERY_MERGE_WINDOW: Duration = Duration::from_secs(1);
+const CRITICAL_BATTERY_LEVEL: u8 = 5;
+const LOW_BATTERY_LEVEL: u8 = 20;
+const STARTUP_NETWORK_TIMEOUT: Duration = Duration::from_secs(2);
+const EXIT_SPEECH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 在后台线程收集电池和网络状态，避免查询网络时阻塞托盘菜单，收集完成后作为事件播报
+fn request_status(sender: mpsc::Sender<SystemEvent>, window: HWND) {
+    let hwnd_value = window.0 as isize;
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("状态查询线程 COM 初始化失败。");
+            return;
+        }
+        let battery = read_battery_status();
+        let network = event_monitor::current_network_details().ok().flatten();
+        event_monitor::send_event(&sender, hwnd_value, SystemEvent::StatusRequest { battery, network });
+    });
+}
+
+/// 当前电量以及是否接通了外部电源；没有电池或电量未知时返回 None
+fn read_battery_status() -> Option<(u8, bool)> {
+    let mut power_status = SYSTEM_POWER_STATUS::default();
+    // BatteryFlag 128 表示没有电池，BatteryLifePercent 255 表示电量未知
+    match unsafe { GetSystemPowerStatus(&mut power_status) } {
+        Ok(()) if power_status.BatteryFlag & 128 == 0 && power_status.BatteryLifePercent <= 100 =>
+            Some((power_status.BatteryLifePercent, power_status.ACLineStatus == 1)),
+        Ok(()) => None,
+        Err(e) => {
+            warn!("读取电源状态失败: {}", e);
+            None
+        }
+    }
+}
+
+/// 启动问候之后播报电量和网络。网络查询放在单独的线程里，超过 STARTUP_NETWORK_TIMEOUT 仍无结果就不播报网络，
+/// 以免拖慢启动。
+fn request_startup_summary(config: &Config, sender: mpsc::Sender<SystemEvent>, window: HWND) {
+    let (include_battery, include_network) = (config.announce_battery_on_start, config.announce_network_on_start);
+    if !include_battery && !include_network { return; }
+    let hwnd_value = window.0 as isize;
+    std::thread::spawn(move || {
+        let battery = if include_battery { read_battery_status() } else { None };
+        let network = if include_network {
+            let (tx, rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok() {
+                    tx.send(event_monitor::current_network_details().ok().flatten()).ok();
+                }
+            });
+            rx.recv_timeout(STARTUP_NETWORK_TIMEOUT).unwrap_or_else(|_| {
+                warn!("启动时查询网络状态超过 {} 秒，跳过网络播报。", STARTUP_NETWORK_TIMEOUT.as_secs());
+                None
+            })
+        } else {
+            None
+        };
+        event_monitor::send_event(&sender, hwnd_value, SystemEvent::StartupSummary { battery, network });
+    });
+}
+
+/// 把电池和网络状态组合成一句话，例如“电池电量 67%，正在充电。已通过 Wi-Fi 连接到 HomeWifi。”
+fn format_status(i18n: &I18nManager, battery: Option<(u8, bool)>, network: Option<&(String, ConnectionType)>) -> String {
+    [battery_status_text(i18n, battery), network_status_text(i18n, network)].into_iter().flatten().collect::<Vec<_>>().join(" ")
+}
+
+/// 把睡眠期间的变化合并成一句，例如“睡眠期间，电源已断开，电池电量降至 60%。”
+/// `template_key` 是包含 {changes} 的整句，专注助手结束时使用另一种说法；`since` 是进入睡眠的时刻，
+/// 用于 sleep_changes_since 中的 {time}
+fn format_sleep_changes(i18n: &I18nManager, template_key: &str, changes: &[SleepChange], since: Option<&str>) -> Option<String> {
+    let parts: Vec<String> = changes.iter().filter_map(|change| match change {
+        SleepChange::PowerConnected => i18n.get_text("sleep_change_power_connected"),
+        SleepChange::PowerDisconnected => i18n.get_text("sleep_change_power_disconnected"),
+        SleepChange::BatteryDropped { level } => i18n.get_text_with_param("sleep_change_battery_dropped", "level", &format::percent(i18n, *level)),
+        SleepChange::BatteryRose { level } => i18n.get_text_with_param("sleep_change_battery_rose", "level", &format::percent(i18n, *level)),
+        SleepChange::NetworkLost => i18n.get_text("sleep_change_network_lost"),
+        SleepChange::NetworkChanged { name } => i18n.get_text_with_param("sleep_change_network_changed", "name", name),
+    }).collect();
+    if parts.is_empty() { return None; }
+    let separator = i18n.get_text("sleep_change_separator").unwrap_or_else(|| ", ".to_string());
+    let changes = parts.join(&separator);
+    let mut params = vec![("changes", changes.as_str())];
+    if let Some(time) = since {
+        params.push(("time", time));
+    }
+    i18n.get_text_with_params(template_key, &params)
+}
+
+/// 检查当前语言配置的语音是否仍已安装，并重新读取语音列表。语音缺失或重新安装时重新选择语音；
+/// 只有新发现缺失时才返回 (语音名称, 改用的语音名称)，同一个缺失的语音不会反复提醒
+fn check_configured_voice(app_state: &mut AppState) -> Option<(String, String)> {
+    let language = app_state.i18n_manager.locale().to_string();
+    let configured = app_state.config.mapped_voice(&language).cloned();
+    // 运行期间被卸载的语音，名称还在旧的列表里
+    let known_name = configured.as_ref()
+        .and_then(|id| app_state.available_voices.iter().find(|v| v.id == *id))
+        .map(|v| v.name.clone());
+    if app_state.tts_engine.refresh_voices() {
+        app_state.available_voices = app_state.tts_engine.list_available_voices().unwrap_or_default();
+    }
+    let missing = configured.clone()
+        .filter(|id| !app_state.available_voices.iter().any(|v| v.id == *id))
+        .map(|id| { let name = known_name.unwrap_or_else(|| voice_display_name(&id)); (id, name) });
+    let previously_missing = std::mem::replace(&mut app_state.missing_voice, missing.clone());
+    if previously_missing.map(|(id, _)| id) == missing.as_ref().map(|(id, _)| id.clone()) { return None; }
+    match app_state.tts_engine.select_voice_for_language(&language, configured.as_deref()) {
+        Ok(Some(voice)) if missing.is_none() => info!("配置的语音 '{}' 已重新安装，恢复使用。", voice.name),
+        Ok(_) => {}
+        Err(e) => warn!("重新选择语言 '{}' 的语音失败: {}", language, e),
+    }
+    let (id, name) = missing?;
+    let fallback = app_state.tts_engine.voice().map(|v| v.name).unwrap_or_default();
+    warn!("配置的语音 '{}' ({}) 未安装，改用 '{}'。配置保持不变，重新安装该语音后会自动恢复。", name, id, fallback);
+    Some((name, fallback))
+}
+
+// 只在启动时读取的设置，修改后需要重新启动程序才会生效
+const RESTART_REQUIRED_SETTINGS: &[&str] = &[
+    "forward_to", "companion_token", "watched_processes", "mirror_notifications", "notification_allow_apps",
+    "notification_deny_apps", "notification_max_chars", "break_reminder_enabled", "break_reminder_work_minutes",
+    "break_reminder_idle_gap_minutes", "announce_microphone_use", "announce_mic_mute", "announce_camera_use",
+    "camera_poll_interval_secs", "announce_display_driver_reset", "announce_security_changes",
+    "security_poll_interval_secs", "announce_print_jobs", "announce_radio_changes", "announce_airplane_mode",
+    "stop_hotkey", "duck_other_audio", "duck_other_audio_percent", "fallback_beeps",
+    "pre_announcement_chime", "pre_announcement_chime_file", "max_queued_announcements", "interrupted_announcements",
+];
+
+/// 应用在外部修改后重新读取的配置。与当前设置相同（例如本程序自己刚保存过）时返回 false。
+/// 语言、语音、语速/音量/音调、输出设备、开机自启动和读音替换立即生效，其他设置在使用时读取新值
+fn apply_reloaded_config(app_state: &mut AppState, mut config: Config) -> bool {
+    // 用户语言档案不在 config.json 中，每次重新加载配置时都重新读取
+    app_state.i18n_manager.reload_custom_strings();
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(&app_state.config), serde_json::to_value(&config)) else { return false };
+    let changed: Vec<&str> = new.iter().filter(|(key, value)| old.get(*key) != Some(value)).map(|(key, _)| key.as_str()).collect();
+    if changed.is_empty() { return false; }
+    info!("config.json 已在外部修改，重新加载。变化的设置: {}", changed.join(", "));
 
-    if should_send {
+    let old_locale = app_state.i18n_manager.locale().to_string();
+    let locale = effective_locale(&config);
+    if locale != old_locale {
+        match I18nManager::load_validated(&locale) {
+            Ok(manager) => {
+                info!("语言已从 '{}' 切换为 '{}'", old_locale, locale);
+                app_state.switch_language(manager);
+            }
+            Err(e) => {
+                error!("切换语言到 '{}' 失败，保持当前语言: {}", locale, e);
+                config.language = app_state.config.language.clone();
+            }
+        }
+    }
+    let language = app_state.i18n_manager.locale().to_string();
+    if language != old_locale || changed.contains(&"custom_messages") {
+        app_state.i18n_manager.set_overrides(&config.custom_messages);
+    }
+    let mapped_voice = |config: &Config| config.mapped_voice(&language).cloned();
+    if language != old_locale || mapped_voice(&config) != mapped_voice(&app_state.config) {
+        match app_state.tts_engine.select_voice_for_language(&language, mapped_voice(&config).as_deref()) {
+            Ok(Some(voice)) => info!("语言 '{}' 使用语音: {}", language, voice.name),
+            Ok(None) => {}
+            Err(e) => warn!("为语言 '{}' 选择语音失败: {}", language, e),
+        }
+    }
+    if config.speech_rate != app_state.config.speech_rate {
+        app_state.tts_engine.set_rate(config.speech_rate);
+    }
+    if config.volume != app_state.config.volume {
+        app_state.tts_engine.set_volume(config.volume);
+    }
+    if config.pitch != app_state.config.pitch {
+        app_state.tts_engine.set_pitch(config.pitch);
+    }
+    if config.output_device != app_state.config.output_device {
+        app_state.tts_engine.set_output_device(config.output_device.as_deref());
+    }
+    if config.auto_start != app_state.config.auto_start {
+        if let Err(e) = startup::set_auto_start(config.auto_start) {
+            error!("同步开机自启动设置失败: {}", e);
+        }
+    }
+    if changed.contains(&"pronunciations") {
+        app_state.speech.set_pronunciations(&config.pronunciations);
+    }
+    if changed.iter().any(|key| key.starts_with("battery_")) {
+        event_monitor::set_battery_thresholds(&config.battery_thresholds, config.battery_hysteresis);
+    }
+    if changed.iter().any(|key| key.starts_with("log_")) {
+        logging::apply(logging::LogSettings::from_config(&config));
+    }
+    let needs_restart: Vec<&str> = changed.iter().copied().filter(|key| RESTART_REQUIRED_SETTINGS.contains(key)).collect();
+    if !needs_restart.is_empty() {
+        warn!("以下设置需要重新启动程序后才会生效: {}", needs_restart.join(", "));
+    }
+    app_state.config = config;
+    true
+}
+
+/// 语音 id 是注册表路径，例如 ...\Voices\Tokens\MSTTS_V110_enUS_ZiraM，取最后一段作为名称
+fn voice_display_name(id: &str) -> String {
+    id.rsplit('\\').next().unwrap_or(id).to_string()
+}
+
+fn voice_missing_text(i18n: &I18nManager, name: &str, fallback: &str) -> Option<String> {
+    i18n.get_text_with_params("configured_voice_missing", &[("name", name), ("fallback", fallback)])
+}
+
+fn battery_status_text(i18n: &I18nManager, battery: Option<(u8, bool)>) -> Option<String> {
+    match battery {
+        Some((level, true)) => i18n.get_text_with_param("status_battery_charging", "level", &format::percent(i18n, level)),
+        Some((level, false)) => i18n.get_text_with_param("status_battery_discharging", "level", &format::percent(i18n, level)),
+        None => i18n.get_text("status_no_battery"),
+    }
+}
+
+fn network_status_text(i18n: &I18nManager, network: Option<&(String, ConnectionType)>) -> Option<String> {
+    match network {
+        Some((name, conn_type)) => {
+            let key = match conn_type {
+                ConnectionType::WiFi => "status_network_wifi",
+                ConnectionType::Ethernet => "status_network_ethernet",
+                ConnectionType::Cellular => "status_network_cellular",
+                ConnectionType::Unknown => "status_network_other",
+            };
+            i18n.get_text_with_param(key, "name", name)
+        }
+        None => i18n.get_text("status_offline"),
+    }
+}
+
+/// 在后台线程等待告别语播完（最多 EXIT_SPEECH_TIMEOUT），然后通知窗口退出。
+/// 等待期间消息循环照常运行，托盘图标不会失去响应。
+fn exit_after_farewell(window: HWND, farewell: Option<UtteranceWaiter>) {
+    let hwnd_value = window.0 as isize;
+    std::thread::spawn(move || {
+        if let Some(waiter) = farewell {
+            if !waiter.wait(EXIT_SPEECH_TIMEOUT) {
+                warn!("告别语在 {} 秒内未播完，直接退出。", EXIT_SPEECH_TIMEOUT.as_secs());
+            }
+        }
+        unsafe { PostMessageW(Some(HWND(hwnd_value as *mut c_void)), WM_APP_EXIT, WPARAM(0), LPARAM(0)).ok() };
+    });
+}
+
+/// 托盘菜单“打开日志文件”：用默认程序打开当前正在写入的日志
+fn open_log_file() {
+    let Some(path) = logging::active_path() else { return };
+    log::logger().flush();
+    shell_open(path);
+}
+
+/// 用关联的程序打开文件，或在资源管理器中打开文件夹。关联的程序可能要过一会儿才启动，
+/// ShellExecuteW 在单独的线程中调用，不阻塞托盘和设置窗口
+fn shell_open(path: PathBuf) {
+    std::thread::spawn(move || {
+        // ShellExecuteW 可能用到 COM 扩展，需要单线程套间
+        let com_ready = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }.is_ok();
+        let result = unsafe { ShellExecuteW(None, w!("open"), &HSTRING::from(path.as_os_str()), None, None, SW_SHOWNORMAL) };
+        // 返回值不大于 32 表示失败
+        if result.0 as isize <= 32 {
+            error!("无法打开 {}", path.display());
+        }
+        if com_ready {
+            unsafe { CoUninitialize() };
+        }
+    });
+}
+
+/// 托盘菜单或快捷键“停止播报”：立即安静下来，但不改变暂停状态
+fn stop_speaking(app_state_arc: &Arc<Mutex<AppState>>) {
+    // 句柄取出后就释放锁，停止播报不必等待其他事件处理
+    let speech = app_state_arc.lock().unwrap().speech.clone();
+    if let Err(e) = speech.stop() {
+        error!("停止播报失败: {}", e);
+    }
+}
+
+/// 把最近接入的设备加入忽略列表并保存配置，之后该设备的接入/断开都不再播报
+fn ignore_last_connected_device(app_state_arc: &Arc<Mutex<AppState>>) {
+    let mut app_state = app_state_arc.lock().unwrap();
+    let Some(instance_id) = app_state.last_connected_device.take() else { return };
+    if !is_ignored(&app_state.config.ignored_device_ids, &instance_id) {
+        app_state.config.ignored_device_ids.push(instance_id.clone());
+    }
+    info!("已将 USB 设备 {} 加入忽略列表。", instance_id);
+    if let Err(e) = app_state.config.save() {
+        error!("保存忽略设备列表失败: {}", e);
+    }
+    if let Some(text) = app_state.i18n_manager.get_text("announcement_device_ignored") {
+        app_state.speech.speak(&text).ok();
+    }
+}
+
+/// 记录一次 USB 接入或断开。合并窗口内的第一次变化启动计时器，
+/// 窗口结束时由 flush_usb_changes 一次性播报总数。
+fn queue_usb_change(connected: bool, device_path: &str, app_state_arc: &Arc<Mutex<AppState>>, window: HWND) {
+    let mut app_state = app_state_arc.lock().unwrap();
+    let debounce = Duration::from_millis(app_state.config.usb_debounce_ms);
+    if !app_state.usb_reconciler.accept(device_path, connected, debounce, Instant::now()) { return; }
+    let instance_id = device_instance_id(device_path);
+    if is_ignored(&app_state.config.ignored_device_ids, &instance_id) {
+        info!("USB 设备 {} 在忽略列表中，不播报。", instance_id);
+        return;
+    }
+    if connected {
+        app_state.last_connected_device = Some(instance_id);
+    }
+    if !app_state.usb_debouncer.should_announce(device_path) { return; }
+    let window_ms = app_state.config.usb_coalesce_ms as u32;
+    let (pending, timer_id) = if connected {
+        (&mut app_state.pending_usb_connects, TIMER_ID_USB_CONNECTS)
+    } else {
+        (&mut app_state.pending_usb_disconnects, TIMER_ID_USB_DISCONNECTS)
+    };
+    *pending += 1;
+    if *pending == 1 {
+        unsafe { SetTimer(Some(window), timer_id, window_ms, None) };
+    }
+}
+
+/// 读取 DEV_BROADCAST_DEVICEINTERFACE_W 末尾以 NUL 结尾的设备接口路径 (dbcc_name)
+unsafe fn device_interface_path(broadcast: *const DEV_BROADCAST_DEVICEINTERFACE_W) -> String {
+    let name = unsafe { std::ptr::addr_of!((*broadcast).dbcc_name) } as *const u16;
+    let max_len = unsafe { ((*broadcast).dbcc_size as usize).saturating_sub(std::mem::offset_of!(DEV_BROADCAST_DEVICEINTERFACE_W, dbcc_name)) / 2 };
+    let mut len = 0;
+    while len < max_len && unsafe { *name.add(len) } != 0 { len += 1; }
+    String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(name, len) })
+}
+
+fn flush_usb_changes(connected: bool, sender: &mpsc::Sender<SystemEvent>, app_state_arc: &Arc<Mutex<AppState>>, window: HWND) {
+    let timer_id = if connected { TIMER_ID_USB_CONNECTS } else { TIMER_ID_USB_DISCONNECTS };
+    unsafe { KillTimer(Some(window), timer_id).ok() };
+
+    let count = {
+        let mut app_state = app_state_arc.lock().unwrap();
+        let pending = if connected { &mut app_state.pending_usb_connects } else { &mut app_state.pending_usb_disconnects };
+        std::mem::take(pending)
+    };
+    if count == 0 { return; }
+
+    let event = if connected { SystemEvent::UsbDevicesConnected { count } } else { SystemEvent::UsbDevicesDisconnected { count } };
+    if sender.send(event).is_ok() {
+        unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+    }
+}
+
+fn build_timer_menu(app_state: &AppState) -> windows::Win32::UI::WindowsAndMessaging::HMENU {
+    let submenu = unsafe { CreatePopupMenu().unwrap() };
+    let i18n = &app_state.i18n_manager;
+    for (index, minutes) in timers::PRESET_MINUTES.iter().enumerate() {
+        let text = i18n.get_text_with_param("menu_timer_minutes", "minutes", &minutes.to_string())
+            .unwrap_or_else(|| format!("{} min", minutes));
+        unsafe { AppendMenuW(submenu, MF_STRING, (ID_MENU_TIMER_START_BASE + index as u32) as usize, &HSTRING::from(text)).ok() };
+    }
+
+    let now = timers::now_secs();
+    let active = &app_state.config.timers;
+    if !active.is_empty() {
+        unsafe { AppendMenuW(submenu, MF_SEPARATOR, 0, None).ok() };
+    }
+    for timer in active {
+        let remaining = timers::format_remaining(timer.remaining_secs(now));
+        let text = i18n.get_text_with_params("menu_timer_cancel", &[("minutes", &timer.minutes.to_string()), ("remaining", &remaining)])
+            .unwrap_or_else(|| format!("Cancel {} min timer ({})", timer.minutes, remaining));
+        unsafe { AppendMenuW(submenu, MF_STRING, (ID_MENU_TIMER_CANCEL_BASE + timer.id) as usize, &HSTRING::from(text)).ok() };
+    }
+    submenu
+}
+
+/// 设置方案子菜单，当前方案前打勾。没有定义任何方案时返回 None，菜单中不显示这一项
+fn build_profile_menu(app_state: &AppState) -> Option<windows::Win32::UI::WindowsAndMessaging::HMENU> {
+    let names = app_state.config.profile_names();
+    if names.is_empty() { return None; }
+    let submenu = unsafe { CreatePopupMenu().unwrap() };
+    let count = (ID_MENU_PROFILE_LAST - ID_MENU_PROFILE_BASE + 1) as usize;
+    for (index, name) in names.iter().enumerate().take(count) {
+        let flags = if *name == app_state.config.active_profile { MF_STRING | MF_CHECKED } else { MF_STRING };
+        unsafe { AppendMenuW(submenu, flags, (ID_MENU_PROFILE_BASE + index as u32) as usize, &HSTRING::from(name.as_str())).ok() };
+    }
+    Some(submenu)
+}
+
+/// 托盘菜单中选择了一个设置方案：按重新加载配置的方式立即应用、保存并播报
+fn switch_profile(window: HWND, app_state_arc: &Arc<Mutex<AppState>>, index: usize) {
+    let mut app_state = app_state_arc.lock().unwrap();
+    let Some(name) = app_state.config.profile_names().into_iter().nth(index) else { return };
+    if name == app_state.config.active_profile { return; }
+    let language = app_state.i18n_manager.locale().to_string();
+    let Some(config) = app_state.config.with_profile(&name, &language) else { return };
+    info!("切换到设置方案 '{}'", name);
+    apply_reloaded_config(&mut app_state, config);
+    if let Err(e) = app_state.config.save() {
+        error!("保存设置方案的切换失败: {}", e);
+    }
+    update_tray_tooltip(window, &app_state);
+    refresh_language(window, &mut app_state);
+    if let Some(text) = app_state.i18n_manager.get_text_with_param("announcement_profile_switched", "name", &name) {
+        if let Err(e) = app_state.speech.speak_user_triggered(&text) {
+            error!("播报设置方案切换失败: {}", e);
+        }
+    }
+}
+
+fn save_timers(app_state: &AppState) {
+    if let Err(e) = app_state.config.save() {
+        error!("保存倒计时到 config.json 失败: {}", e);
+    }
+}
+
+fn start_user_timer(window: HWND, sender: &mpsc::Sender<SystemEvent>, app_state_arc: &Arc<Mutex<AppState>>, minutes: u32) {
+    {
+        let mut app_state = app_state_arc.lock().unwrap();
+        let used_ids: Vec<u32> = app_state.config.timers.iter().map(|t| t.id).collect();
+        let max_id = ID_MENU_TIMER_CANCEL_LAST - ID_MENU_TIMER_CANCEL_BASE;
+        let Some(id) = (0..=max_id).find(|id| !used_ids.contains(id)) else {
+            warn!("同时运行的倒计时过多，忽略新的 {} 分钟倒计时。", minutes);
+            return;
+        };
+        app_state.config.timers.push(timers::TimerEntry::new(id, minutes));
+        save_timers(&app_state);
+    }
+    info!("已启动 {} 分钟倒计时。", minutes);
+    unsafe { SetTimer(Some(window), TIMER_ID_USER_TIMERS, 1000, None) };
+    if sender.send(SystemEvent::TimerStarted { minutes }).is_ok() {
+        unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+    }
+}
+
+fn cancel_user_timer(window: HWND, sender: &mpsc::Sender<SystemEvent>, app_state_arc: &Arc<Mutex<AppState>>, id: u32) {
+    let cancelled = {
+        let mut app_state = app_state_arc.lock().unwrap();
+        let position = app_state.config.timers.iter().position(|t| t.id == id);
+        let cancelled = position.map(|index| app_state.config.timers.remove(index));
+        if cancelled.is_some() { save_timers(&app_state); }
+        cancelled
+    };
+    if let Some(timer) = cancelled {
+        info!("已取消 {} 分钟倒计时。", timer.minutes);
+        if sender.send(SystemEvent::TimerCancelled { minutes: timer.minutes }).is_ok() {
+            unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
+        }
+    }
+}
+
+fn check_user_timers(window: HWND, sender: &mpsc::Sender<SystemEvent>, app_state_arc: &Arc<Mutex<AppState>>) {
+    let notices = {
+        let mut app_state = app_state_arc.lock().unwrap();
+        let warnings = app_state.config.timer_warnings;
+        let notices = timers::collect_due(&mut app_state.config.timers, timers::now_secs(), warnings);
+        if !notices.is_empty() { save_timers(&app_state); }
+        if app_state.config.timers.is_empty() {
+            unsafe { KillTimer(Some(window), TIMER_ID_USER_TIMERS).ok() };
+        }
+        notices
+    };
+    for notice in notices {
+        let event = match notice {
+            timers::TimerNotice::Halfway { minutes, remaining_minutes } => SystemEvent::TimerHalfway { minutes, remaining_minutes },
+            timers::TimerNotice::OneMinuteLeft { minutes } => SystemEvent::TimerOneMinuteLeft { minutes },
+            timers::TimerNotice::Elapsed { minutes } => SystemEvent::TimerElapsed { minutes },
+        };
         if sender.send(event).is_ok() {
             unsafe { PostMessageW(Some(window), WM_APP_WAKEUP, WPARAM(0), LPARAM(0)).ok(); }
         }
@@ -457,7 +1809,13 @@ fn add_tray_icon(hwnd: HWND) {
     nid.uCallbackMessage = WM_APP_TRAY_MSG;
     unsafe {
         let instance = GetModuleHandleW(None).unwrap();
-        nid.hIcon = LoadIconW(Some(instance.into()), PCWSTR(1 as *const u16)).unwrap_or_else(|_| LoadIconW(None, IDI_APPLICATION).unwrap());
+        // 按托盘所在显示器的 DPI 载入对应大小的小图标，而不是把 32x32 的图标缩小
+        let dpi = GetDpiForWindow(hwnd);
+        let (cx, cy) = (GetSystemMetricsForDpi(SM_CXSMICON, dpi), GetSystemMetricsForDpi(SM_CYSMICON, dpi));
+        nid.hIcon = LoadImageW(Some(instance.into()), PCWSTR(1 as *const u16), IMAGE_ICON, cx, cy, LR_DEFAULTCOLOR)
+            .map(|icon| HICON(icon.0))
+            .or_else(|_| LoadIconW(None, IDI_APPLICATION))
+            .unwrap();
     }
     let tip = w!("CO/MP/UT/ER");
     let tip_wide = unsafe { tip.as_wide() };
@@ -465,10 +1823,168 @@ fn add_tray_icon(hwnd: HWND) {
     unsafe { Shell_NotifyIconW(NIM_ADD, &nid) };
 }
 
+/// 托盘菜单和托盘提示中的文字在语言档案中找不到时使用的英文
+const TRAY_FALLBACK_TEXTS: &[(&str, &str)] = &[
+    ("menu_pause", "Pause Announcing"),
+    ("menu_resume", "Resume Announcing"),
+    ("menu_settings", "Settings..."),
+    ("menu_open_log", "Open log file"),
+    ("menu_view_log", "View log"),
+    ("menu_about", "About..."),
+    ("menu_exit", "Exit"),
+    ("menu_status", "Announce system status"),
+    ("menu_profile", "Profile"),
+    ("menu_timer", "Timer"),
+    ("menu_stop", "Stop speaking"),
+    ("menu_skip_pending", "Skip pending announcements"),
+    ("menu_ignore_last_device", "Ignore last connected device"),
+    ("menu_focus_assist_holding", "Focus Assist: announcements held"),
+    ("tray_tooltip_title", "CO/MP/UT/ER"),
+    ("tray_tooltip_paused", "announcements paused"),
+];
+
+/// 托盘菜单和托盘提示中的一条文字。托盘菜单和语言切换后的刷新都经过这里，两处的文字不会不同
+fn tray_text(i18n: &I18nManager, key: &str) -> String {
+    i18n.get_text(key).unwrap_or_else(|| {
+        TRAY_FALLBACK_TEXTS.iter().find(|(fallback_key, _)| *fallback_key == key)
+            .map_or_else(|| key.to_string(), |(_, text)| text.to_string())
+    })
+}
+
+/// 托盘菜单中暂停/恢复一项的条目：暂停中显示“恢复播报”
+fn pause_menu_key(paused: bool) -> &'static str {
+    if paused { "menu_resume" } else { "menu_pause" }
+}
+
+/// 语言切换后更新托盘提示和打开着的设置窗口，暂停中时用新的语言说明仍在暂停。
+/// 托盘菜单每次打开时重新生成，不需要更新
+fn refresh_language(window: HWND, app_state: &mut AppState) {
+    if !std::mem::take(&mut app_state.language_changed) { return; }
+    info!("界面文字改用语言 '{}'", app_state.i18n_manager.locale());
+    update_tray_tooltip(window, app_state);
+    settings_ui::refresh_texts(&app_state.i18n_manager, app_state.engine_health);
+    if app_state.is_paused {
+        if let Some(text) = app_state.i18n_manager.get_text("announcement_paused") {
+            if let Err(e) = app_state.speech.speak_user_triggered(&text) {
+                error!("播报暂停状态失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 托盘提示显示程序名，暂停中附加“播报已暂停”，处于安静时段时附加“安静时段，至 07:00”，
+/// 有积压的播报时附加“{n} 条待播报”
+fn update_tray_tooltip(hwnd: HWND, app_state: &AppState) {
+    let i18n = &app_state.i18n_manager;
+    let mut tip = tray_text(i18n, "tray_tooltip_title");
+    if app_state.is_paused {
+        tip = format!("{} - {}", tip, tray_text(i18n, "tray_tooltip_paused"));
+    }
+    if let Some(quiet_hours) = app_state.config.quiet_hours.as_ref().filter(|q| announce_policy::is_quiet_now(q)) {
+        let end = quiet_hours.minutes()
+            .map(|(_, end)| format::time_of_day(i18n, app_state.config.clock_format, (end / 60) as u16, (end % 60) as u16))
+            .unwrap_or_else(|| quiet_hours.end.clone());
+        let suffix = i18n.get_text_with_param("tray_tooltip_quiet_hours", "end", &end)
+            .unwrap_or_else(|| format!("quiet hours until {}", end));
+        tip = format!("{} - {}", tip, suffix);
+    }
+    let queued = app_state.status.queued();
+    if queued > 0 {
+        let suffix = i18n.get_text_with_param("tray_tooltip_queued", "count", &queued.to_string())
+            .unwrap_or_else(|| format!("{} queued", queued));
+        tip = format!("{} - {}", tip, suffix);
+    }
+
+    let mut nid = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: 1,
+        uFlags: NIF_TIP,
+        ..Default::default()
+    };
+    let tip_wide: Vec<u16> = tip.encode_utf16().take(nid.szTip.len() - 1).collect();
+    nid.szTip[..tip_wide.len()].copy_from_slice(&tip_wide);
+    let _ = unsafe { Shell_NotifyIconW(NIM_MODIFY, &nid) };
+}
+
+/// 在托盘图标上显示气泡通知
+fn show_tray_balloon(hwnd: HWND, title: &str, text: &str, icon: NOTIFY_ICON_INFOTIP_FLAGS) {
+    let mut nid = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: 1,
+        uFlags: NIF_INFO,
+        dwInfoFlags: icon,
+        ..Default::default()
+    };
+    let title_wide: Vec<u16> = title.encode_utf16().take(nid.szInfoTitle.len() - 1).collect();
+    nid.szInfoTitle[..title_wide.len()].copy_from_slice(&title_wide);
+    let text_wide: Vec<u16> = text.encode_utf16().take(nid.szInfo.len() - 1).collect();
+    nid.szInfo[..text_wide.len()].copy_from_slice(&text_wide);
+    let _ = unsafe { Shell_NotifyIconW(NIM_MODIFY, &nid) };
+}
+
 fn remove_tray_icon(hwnd: HWND) {
     let mut nid = NOTIFYICONDATAW::default();
     nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
     nid.hWnd = hwnd;
     nid.uID = 1;
     unsafe { Shell_NotifyIconW(NIM_DELETE, &nid) };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicIsize;
+    use windows::Win32::UI::WindowsAndMessaging::{HWND_MESSAGE, WINDOW_STYLE, WM_KEYDOWN, WM_NULL, WS_CAPTION};
+
+    // 用系统的 STATIC 窗口代替主窗口和对话框，它们不处理 WM_APP 消息
+    fn static_window(parent: Option<HWND>, style: WINDOW_STYLE) -> HWND {
+        unsafe { CreateWindowExW(Default::default(), w!("STATIC"), w!(""), style, 0, 0, 100, 100, parent, None, None, None) }.unwrap()
+    }
+
+    #[test]
+    fn events_are_handled_while_a_dialog_is_open() {
+        const EVENTS: usize = 20;
+        let (done_tx, done_rx) = mpsc::channel();
+        // GetMessageW 只取本线程的消息，窗口和消息循环都在同一个线程
+        std::thread::spawn(move || {
+            let main_window = static_window(Some(HWND_MESSAGE), WINDOW_STYLE(0));
+            let dialog = static_window(None, WS_CAPTION);
+            let slot = AtomicIsize::new(0);
+            settings_ui::register_dialog_window(&slot, dialog);
+
+            let (sender, receiver) = mpsc::channel();
+            let (main_value, dialog_value) = (main_window.0 as isize, dialog.0 as isize);
+            std::thread::spawn(move || {
+                for _ in 0..EVENTS {
+                    let dialog = HWND(dialog_value as *mut c_void);
+                    // 对话框同时也在收消息，例如用户正在按键
+                    unsafe {
+                        PostMessageW(Some(dialog), WM_KEYDOWN, WPARAM(0x41), LPARAM(0)).ok();
+                        PostMessageW(Some(dialog), WM_NULL, WPARAM(0), LPARAM(0)).ok();
+                    }
+                    event_monitor::send_event(&sender, main_value, SystemEvent::PowerSwitchedToAC);
+                }
+            });
+
+            let mut handled = 0;
+            run_message_loop(&receiver, |event| {
+                assert!(matches!(event, SystemEvent::PowerSwitchedToAC));
+                handled += 1;
+                if handled == EVENTS {
+                    unsafe { PostQuitMessage(0) };
+                }
+            });
+
+            settings_ui::unregister_dialog_window(&slot, dialog);
+            unsafe {
+                let _ = DestroyWindow(dialog);
+                let _ = DestroyWindow(main_window);
+            }
+            done_tx.send(handled).unwrap();
+        });
+        assert_eq!(done_rx.recv_timeout(Duration::from_secs(10)), Ok(EVENTS));
+    }
+}
+
@@ -0,0 +1,314 @@
+// src/mic_monitor.rs
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use log::{info, error};
+use windows::core::{implement, Interface, Ref, PCWSTR, PWSTR, GUID, BOOL};
+use windows::Win32::Foundation::{CloseHandle, HWND, PROPERTYKEY};
+use windows::Win32::Media::Audio::{
+    eCapture, eConsole, AudioSessionDisconnectReason, AudioSessionState, AudioSessionStateActive,
+    EDataFlow, ERole, IAudioSessionControl, IAudioSessionControl2, IAudioSessionEvents, IAudioSessionEvents_Impl,
+    IAudioSessionManager2, IAudioSessionNotification, IAudioSessionNotification_Impl, IMMDeviceEnumerator,
+    IMMNotificationClient, IMMNotificationClient_Impl, MMDeviceEnumerator, AUDIO_VOLUME_NOTIFICATION_DATA, DEVICE_STATE,
+};
+use windows::Win32::Media::Audio::Endpoints::{IAudioEndpointVolume, IAudioEndpointVolumeCallback, IAudioEndpointVolumeCallback_Impl};
+use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_ALL, COINIT_MULTITHREADED};
+use windows::Win32::System::Threading::{GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION};
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+// Conferencing apps often open and close the microphone several times while joining a call.
+const MIC_DEBOUNCE: Duration = Duration::from_millis(1500);
+// Safety net in case a state change notification is missed.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+// Mashing the mute button should produce one announcement for the final state.
+const MUTE_DEBOUNCE: Duration = Duration::from_millis(700);
+
+/// Wakes the monitor thread whenever a capture session appears or changes state.
+#[implement(IAudioSessionNotification)]
+struct SessionCreatedClient {
+    changed: mpsc::Sender<()>,
+}
+
+impl IAudioSessionNotification_Impl for SessionCreatedClient_Impl {
+    fn OnSessionCreated(&self, _new_session: Ref<'_, IAudioSessionControl>) -> windows::core::Result<()> {
+        self.changed.send(()).ok();
+        Ok(())
+    }
+}
+
+#[implement(IAudioSessionEvents)]
+struct SessionStateClient {
+    changed: mpsc::Sender<()>,
+}
+
+impl IAudioSessionEvents_Impl for SessionStateClient_Impl {
+    fn OnDisplayNameChanged(&self, _name: &PCWSTR, _context: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnIconPathChanged(&self, _path: &PCWSTR, _context: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnSimpleVolumeChanged(&self, _volume: f32, _mute: BOOL, _context: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnChannelVolumeChanged(&self, _count: u32, _volumes: *const f32, _channel: u32, _context: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnGroupingParamChanged(&self, _param: *const GUID, _context: *const GUID) -> windows::core::Result<()> { Ok(()) }
+
+    fn OnStateChanged(&self, _new_state: AudioSessionState) -> windows::core::Result<()> {
+        self.changed.send(()).ok();
+        Ok(())
+    }
+
+    fn OnSessionDisconnected(&self, _reason: AudioSessionDisconnectReason) -> windows::core::Result<()> {
+        self.changed.send(()).ok();
+        Ok(())
+    }
+}
+
+/// Starts watching capture sessions on the default microphone on its own MTA thread.
+pub fn start_mic_monitor(sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("麦克风监控线程 COM 初始化失败。");
+            return;
+        }
+        if let Err(e) = run_mic_monitor(sender, hwnd_value) {
+            error!("麦克风使用监控启动失败: {}", e);
+        }
+    });
+}
+
+fn run_mic_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) -> windows::core::Result<()> {
+    let manager = default_capture_session_manager()?;
+    let (changed_tx, changed_rx) = mpsc::channel();
+    let created_client: IAudioSessionNotification = SessionCreatedClient { changed: changed_tx.clone() }.into();
+    // OnSessionCreated only fires once the session list has been enumerated at least once.
+    unsafe {
+        manager.GetSessionEnumerator()?;
+        manager.RegisterSessionNotification(&created_client)?;
+    }
+    info!("麦克风使用监控已启动。");
+
+    let mut hooked: HashMap<String, (IAudioSessionControl, IAudioSessionEvents)> = HashMap::new();
+    let mut announced: HashSet<String> = active_capture_apps(&manager, &mut hooked, &changed_tx);
+    let mut settle_deadline: Option<Instant> = None;
+
+    loop {
+        let timeout = settle_deadline.map(|d| d.saturating_duration_since(Instant::now())).unwrap_or(RESCAN_INTERVAL);
+        match changed_rx.recv_timeout(timeout) {
+            Ok(()) => {
+                settle_deadline = Some(Instant::now() + MIC_DEBOUNCE);
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => settle_deadline = None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let active = active_capture_apps(&manager, &mut hooked, &changed_tx);
+        let mut started: Vec<&String> = active.difference(&announced).collect();
+        started.sort();
+        for app in started {
+            info!("麦克风被 {} 占用。", app);
+            send_event(&sender, hwnd_value, SystemEvent::MicrophoneInUse { app: app.clone() });
+        }
+        if active.is_empty() && !announced.is_empty() {
+            info!("麦克风已释放。");
+            send_event(&sender, hwnd_value, SystemEvent::MicrophoneReleased);
+        }
+        announced = active;
+    }
+
+    for (control, events) in hooked.values() {
+        unsafe { control.UnregisterAudioSessionNotification(events).ok() };
+    }
+    unsafe { manager.UnregisterSessionNotification(&created_client).ok() };
+    Ok(())
+}
+
+/// Whether any application is currently capturing from the default microphone.
+/// Must be called from a thread with COM initialized.
+pub fn is_microphone_in_use() -> bool {
+    let Ok(manager) = default_capture_session_manager() else { return false };
+    let Ok(sessions) = (unsafe { manager.GetSessionEnumerator() }) else { return false };
+    let count = unsafe { sessions.GetCount() }.unwrap_or(0);
+    (0..count).any(|i| unsafe {
+        sessions.GetSession(i).and_then(|session| session.GetState()).is_ok_and(|state| state == AudioSessionStateActive)
+    })
+}
+
+fn default_capture_session_manager() -> windows::core::Result<IAudioSessionManager2> {
+    unsafe {
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let device = enumerator.GetDefaultAudioEndpoint(eCapture, eConsole)?;
+        device.Activate::<IAudioSessionManager2>(CLSCTX_ALL, None)
+    }
+}
+
+/// Returns the executable names of all processes with an active capture session,
+/// subscribing to state changes of any session not seen before.
+fn active_capture_apps(
+    manager: &IAudioSessionManager2,
+    hooked: &mut HashMap<String, (IAudioSessionControl, IAudioSessionEvents)>,
+    changed: &mpsc::Sender<()>,
+) -> HashSet<String> {
+    let mut active = HashSet::new();
+    let Ok(sessions) = (unsafe { manager.GetSessionEnumerator() }) else { return active };
+    let own_pid = unsafe { GetCurrentProcessId() };
+    let count = unsafe { sessions.GetCount() }.unwrap_or(0);
+    let mut present = HashSet::new();
+
+    for i in 0..count {
+        let Ok(control) = (unsafe { sessions.GetSession(i) }) else { continue };
+        let Ok(control2) = control.cast::<IAudioSessionControl2>() else { continue };
+
+        if let Some(instance_id) = session_instance_id(&control2) {
+            present.insert(instance_id.clone());
+            hooked.entry(instance_id).or_insert_with(|| {
+                let events: IAudioSessionEvents = SessionStateClient { changed: changed.clone() }.into();
+                unsafe { control.RegisterAudioSessionNotification(&events).ok() };
+                (control.clone(), events)
+            });
+        }
+
+        if unsafe { control.GetState() }.ok() != Some(AudioSessionStateActive) { continue; }
+        // PID 0 is the system sounds session; sessions spanning several processes report an error.
+        let Ok(pid) = (unsafe { control2.GetProcessId() }) else { continue };
+        if pid == 0 || pid == own_pid { continue; }
+        active.insert(process_name(pid).unwrap_or_else(|| format!("PID {}", pid)));
+    }
+
+    // Sessions that have gone away no longer need their state callbacks.
+    hooked.retain(|id, (control, events)| {
+        let keep = present.contains(id);
+        if !keep { unsafe { control.UnregisterAudioSessionNotification(&*events).ok() }; }
+        keep
+    });
+    active
+}
+
+fn session_instance_id(control: &IAudioSessionControl2) -> Option<String> {
+    unsafe {
+        let id: PWSTR = control.GetSessionInstanceIdentifier().ok()?;
+        let result = id.to_string().ok();
+        CoTaskMemFree(Some(id.0 as *const _));
+        result
+    }
+}
+
+/// Executable file name without extension, e.g. "Teams" for Teams.exe.
+fn process_name(pid: u32) -> Option<String> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut buffer = [0u16; 260];
+        let mut size = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &mut size);
+        CloseHandle(handle).ok();
+        result.ok()?;
+        let path = String::from_utf16_lossy(&buffer[..size as usize]);
+        std::path::Path::new(&path).file_stem().map(|stem| stem.to_string_lossy().into_owned())
+    }
+}
+
+/// Messages from the COM callbacks to the mute monitor thread.
+enum MuteNotification {
+    DefaultCaptureChanged,
+    MuteChanged(bool),
+}
+
+#[implement(IMMNotificationClient)]
+struct DefaultCaptureClient {
+    notifications: mpsc::Sender<MuteNotification>,
+}
+
+impl IMMNotificationClient_Impl for DefaultCaptureClient_Impl {
+    fn OnDeviceStateChanged(&self, _device_id: &PCWSTR, _new_state: DEVICE_STATE) -> windows::core::Result<()> { Ok(()) }
+    fn OnDeviceAdded(&self, _device_id: &PCWSTR) -> windows::core::Result<()> { Ok(()) }
+    fn OnDeviceRemoved(&self, _device_id: &PCWSTR) -> windows::core::Result<()> { Ok(()) }
+    fn OnPropertyValueChanged(&self, _device_id: &PCWSTR, _key: &PROPERTYKEY) -> windows::core::Result<()> { Ok(()) }
+
+    fn OnDefaultDeviceChanged(&self, flow: EDataFlow, role: ERole, _default_device_id: &PCWSTR) -> windows::core::Result<()> {
+        if flow == eCapture && role == eConsole {
+            self.notifications.send(MuteNotification::DefaultCaptureChanged).ok();
+        }
+        Ok(())
+    }
+}
+
+#[implement(IAudioEndpointVolumeCallback)]
+struct MuteCallback {
+    notifications: mpsc::Sender<MuteNotification>,
+}
+
+impl IAudioEndpointVolumeCallback_Impl for MuteCallback_Impl {
+    fn OnNotify(&self, data: *mut AUDIO_VOLUME_NOTIFICATION_DATA) -> windows::core::Result<()> {
+        if let Some(data) = unsafe { data.as_ref() } {
+            self.notifications.send(MuteNotification::MuteChanged(data.bMuted.as_bool())).ok();
+        }
+        Ok(())
+    }
+}
+
+/// Starts watching the mute state of the default microphone, including hardware
+/// mute buttons on headsets that report through the endpoint volume.
+pub fn start_mic_mute_monitor(sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("麦克风静音监控线程 COM 初始化失败。");
+            return;
+        }
+        if let Err(e) = run_mic_mute_monitor(sender, hwnd_value) {
+            error!("麦克风静音监控启动失败: {}", e);
+        }
+    });
+}
+
+fn run_mic_mute_monitor(sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) -> windows::core::Result<()> {
+    let enumerator: IMMDeviceEnumerator = unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+    let (notification_tx, notification_rx) = mpsc::channel();
+    let default_client: IMMNotificationClient = DefaultCaptureClient { notifications: notification_tx.clone() }.into();
+    unsafe { enumerator.RegisterEndpointNotificationCallback(&default_client)? };
+    let mute_callback: IAudioEndpointVolumeCallback = MuteCallback { notifications: notification_tx }.into();
+
+    let mut endpoint = attach_mute_callback(&enumerator, &mute_callback);
+    let mut announced = endpoint.as_ref().and_then(|volume| unsafe { volume.GetMute() }.ok()).map(|m| m.as_bool());
+    info!("麦克风静音监控已启动，当前状态: {:?}", announced);
+
+    // Latest reported state, announced once it has been stable for MUTE_DEBOUNCE.
+    let mut pending: Option<(bool, Instant)> = None;
+    loop {
+        let timeout = pending.map(|(_, at)| (at + MUTE_DEBOUNCE).saturating_duration_since(Instant::now())).unwrap_or(Duration::from_secs(3600));
+        match notification_rx.recv_timeout(timeout) {
+            Ok(MuteNotification::MuteChanged(muted)) => pending = Some((muted, Instant::now())),
+            Ok(MuteNotification::DefaultCaptureChanged) => {
+                // A different microphone has its own state; switching devices is not a mute toggle.
+                if let Some(volume) = endpoint.take() {
+                    unsafe { volume.UnregisterControlChangeNotify(&mute_callback).ok() };
+                }
+                endpoint = attach_mute_callback(&enumerator, &mute_callback);
+                announced = endpoint.as_ref().and_then(|volume| unsafe { volume.GetMute() }.ok()).map(|m| m.as_bool());
+                pending = None;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let Some((muted, _)) = pending.take() else { continue };
+                if announced == Some(muted) { continue; }
+                announced = Some(muted);
+                info!("麦克风{}。", if muted { "已静音" } else { "已取消静音" });
+                send_event(&sender, hwnd_value, SystemEvent::MicrophoneMuteChanged { muted });
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if let Some(volume) = endpoint {
+        unsafe { volume.UnregisterControlChangeNotify(&mute_callback).ok() };
+    }
+    unsafe { enumerator.UnregisterEndpointNotificationCallback(&default_client).ok() };
+    Ok(())
+}
+
+fn attach_mute_callback(enumerator: &IMMDeviceEnumerator, callback: &IAudioEndpointVolumeCallback) -> Option<IAudioEndpointVolume> {
+    unsafe {
+        let device = enumerator.GetDefaultAudioEndpoint(eCapture, eConsole).ok()?;
+        let volume = device.Activate::<IAudioEndpointVolume>(CLSCTX_ALL, None).ok()?;
+        volume.RegisterControlChangeNotify(callback).ok()?;
+        Some(volume)
+    }
+}
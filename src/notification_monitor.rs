@@ -0,0 +1,138 @@
+// src/notification_monitor.rs
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::time::Duration;
+use log::{info, warn, error};
+use windows::Foundation::TypedEventHandler;
+use windows::UI::Notifications::Management::{UserNotificationListener, UserNotificationListenerAccessStatus};
+use windows::UI::Notifications::{KnownNotificationBindings, NotificationKinds, UserNotification, UserNotificationChangedEventArgs};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+
+use crate::config::Config;
+use crate::event_monitor::{send_event, SystemEvent};
+
+// Unpackaged apps are not always allowed to subscribe to NotificationChanged;
+// in that case we fall back to polling the notification list at this interval.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// With a working subscription the periodic rescan is only a safety net.
+const SUBSCRIBED_RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Which notifications to read aloud, taken from the config at startup.
+struct NotificationFilter {
+    allow_apps: Vec<String>,
+    deny_apps: Vec<String>,
+    max_chars: usize,
+}
+
+impl NotificationFilter {
+    fn from_config(config: &Config) -> Self {
+        let normalize = |apps: &[String]| apps.iter().map(|a| a.trim().to_lowercase()).filter(|a| !a.is_empty()).collect();
+        NotificationFilter {
+            allow_apps: normalize(&config.notification_allow_apps),
+            deny_apps: normalize(&config.notification_deny_apps),
+            max_chars: config.notification_max_chars,
+        }
+    }
+
+    /// An empty allow-list admits every app; the deny-list always wins.
+    fn admits(&self, app: &str) -> bool {
+        let app = app.to_lowercase();
+        if self.deny_apps.contains(&app) { return false; }
+        self.allow_apps.is_empty() || self.allow_apps.contains(&app)
+    }
+
+    fn truncate(&self, text: &str) -> String {
+        if self.max_chars == 0 || text.chars().count() <= self.max_chars {
+            return text.to_string();
+        }
+        let mut truncated: String = text.chars().take(self.max_chars).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Starts mirroring toast notifications on its own MTA thread.
+/// If the user has not granted notification access, logs once and stays disabled.
+pub fn start_notification_monitor(config: &Config, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let filter = NotificationFilter::from_config(config);
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("通知监控线程 COM 初始化失败。");
+            return;
+        }
+        if let Err(e) = run_notification_monitor(filter, sender, hwnd_value) {
+            warn!("通知朗读已禁用: {}", e);
+        }
+    });
+}
+
+fn run_notification_monitor(filter: NotificationFilter, sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) -> windows::core::Result<()> {
+    let listener = UserNotificationListener::Current()?;
+    let status = listener.RequestAccessAsync()?.get()?;
+    if status != UserNotificationListenerAccessStatus::Allowed {
+        warn!("通知朗读已禁用: 未获得读取通知的权限 (状态 {})。", status.0);
+        return Ok(());
+    }
+
+    let (changed_tx, changed_rx) = mpsc::channel::<()>();
+    let handler = TypedEventHandler::<UserNotificationListener, UserNotificationChangedEventArgs>::new(move |_, _| {
+        changed_tx.send(()).ok();
+        Ok(())
+    });
+    let (token, wait_interval) = match listener.NotificationChanged(&handler) {
+        Ok(token) => (Some(token), SUBSCRIBED_RESCAN_INTERVAL),
+        Err(e) => {
+            info!("无法订阅通知变更 ({})，改为每 {} 秒轮询。", e, POLL_INTERVAL.as_secs());
+            (None, POLL_INTERVAL)
+        }
+    };
+    info!("通知朗读已启动。");
+
+    // Notifications already in the action center when we start are not news.
+    let mut seen: HashSet<u32> = current_notifications(&listener)?.iter().filter_map(|n| n.Id().ok()).collect();
+    // Rescan on every change notification and periodically; stop once the handler is gone.
+    while let Ok(()) | Err(mpsc::RecvTimeoutError::Timeout) = changed_rx.recv_timeout(wait_interval) {
+        let notifications = match current_notifications(&listener) {
+            Ok(n) => n,
+            Err(e) => { warn!("读取通知列表失败: {}", e); continue; }
+        };
+        let mut still_present = HashSet::new();
+        for notification in &notifications {
+            let Ok(id) = notification.Id() else { continue };
+            still_present.insert(id);
+            if !seen.insert(id) { continue; }
+
+            let Some((app, text)) = describe(notification) else { continue };
+            if !filter.admits(&app) { continue; }
+            send_event(&sender, hwnd_value, SystemEvent::NotificationReceived { app, text: filter.truncate(&text) });
+        }
+        // Forget dismissed notifications so the set does not grow forever.
+        seen.retain(|id| still_present.contains(id));
+    }
+
+    if let Some(token) = token {
+        listener.RemoveNotificationChanged(token).ok();
+    }
+    Ok(())
+}
+
+fn current_notifications(listener: &UserNotificationListener) -> windows::core::Result<Vec<UserNotification>> {
+    let list = listener.GetNotificationsAsync(NotificationKinds::Toast)?.get()?;
+    Ok(list.into_iter().collect())
+}
+
+/// Returns the app's display name and the first line of text of a toast.
+fn describe(notification: &UserNotification) -> Option<(String, String)> {
+    let app = notification.AppInfo().ok()?.DisplayInfo().ok()?.DisplayName().ok()?.to_string();
+    let binding = notification.Notification().ok()?.Visual().ok()?
+        .GetBinding(&KnownNotificationBindings::ToastGeneric().ok()?).ok()?;
+    let text = binding.GetTextElements().ok()?
+        .into_iter()
+        .filter_map(|element| element.Text().ok())
+        .map(|text| text.to_string())
+        .find(|text| !text.trim().is_empty())?;
+    Some((app, text))
+}
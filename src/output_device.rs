@@ -0,0 +1,128 @@
+// src/output_device.rs
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use log::{info, warn};
+use windows::core::{IInspectable, HSTRING};
+use windows::Devices::Enumeration::{DeviceClass, DeviceInformation};
+use windows::Foundation::TypedEventHandler;
+use windows::Media::Core::MediaSource;
+use windows::Media::Playback::{MediaPlaybackState, MediaPlayer, MediaPlayerFailedEventArgs};
+use windows::Media::SpeechSynthesis::SpeechSynthesizer;
+
+/// Windows 声音设置中列出的一个音频输出设备
+#[derive(Clone, Debug)]
+pub struct OutputDevice {
+    pub id: String,
+    pub name: String,
+}
+
+/// 所有已启用的音频输出设备
+pub fn list_output_devices() -> windows::core::Result<Vec<OutputDevice>> {
+    Ok(enabled_render_devices()?
+        .into_iter()
+        .filter_map(|device| Some(OutputDevice { id: device.Id().ok()?.to_string(), name: device.Name().ok()?.to_string() }))
+        .collect())
+}
+
+fn enabled_render_devices() -> windows::core::Result<Vec<DeviceInformation>> {
+    let devices = DeviceInformation::FindAllAsyncDeviceClass(DeviceClass::AudioRender)?.get()?;
+    Ok(devices.into_iter().filter(|device| device.IsEnabled().unwrap_or(false)).collect())
+}
+
+/// 通过指定的输出设备播报，未指定时使用默认设备。`tts` crate 只能在默认设备上播放纯文本，
+/// 所以这里先把文本合成为音频流，再用 MediaPlayer 播放；播报 SSML 也只能走这条路
+pub struct DeviceSpeaker {
+    // 配置中的设备名称或 ID，`None` 表示默认设备
+    device: Option<String>,
+    synthesizer: SpeechSynthesizer,
+    player: MediaPlayer,
+    // 设备不存在期间置位，回退到默认设备只记录一次日志
+    missing: AtomicBool,
+}
+
+impl DeviceSpeaker {
+    /// 播放结束或失败时调用 `on_finished`，用法与 `tts` 的朗读回调相同
+    pub fn new(device: Option<&str>, on_finished: impl Fn() + Send + Sync + 'static) -> windows::core::Result<Self> {
+        let synthesizer = SpeechSynthesizer::new()?;
+        let player = MediaPlayer::new()?;
+        let on_finished = Arc::new(on_finished);
+        let on_ended = on_finished.clone();
+        player.MediaEnded(&TypedEventHandler::<MediaPlayer, IInspectable>::new(move |_, _| {
+            on_ended();
+            Ok(())
+        }))?;
+        player.MediaFailed(&TypedEventHandler::<MediaPlayer, MediaPlayerFailedEventArgs>::new(move |_, args| {
+            if let Some(args) = args.as_ref() {
+                warn!("输出设备播放失败: {}", args.ErrorMessage().unwrap_or_default());
+            }
+            on_finished();
+            Ok(())
+        }))?;
+        Ok(DeviceSpeaker { device: device.map(str::to_string), synthesizer, player, missing: AtomicBool::new(false) })
+    }
+
+    /// 用给定的语音和设置在配置的设备上开始播报 `text` (`ssml` 为真时按 SSML 处理)。
+    /// 设备当前不可用时不播报并返回 `Ok(false)`，由调用方回退到默认设备。SSML 无效时返回错误
+    pub fn speak(&self, text: &str, ssml: bool, voice_id: Option<&str>, rate: f32, pitch: f32, volume: f32) -> windows::core::Result<bool> {
+        let device = match &self.device {
+            Some(name) => {
+                let Some(device) = self.find_device(name)? else {
+                    if !self.missing.swap(true, Ordering::Relaxed) {
+                        warn!("找不到输出设备 '{}'，改用默认设备播报。", name);
+                    }
+                    return Ok(false);
+                };
+                if self.missing.swap(false, Ordering::Relaxed) {
+                    info!("输出设备 '{}' 已重新可用。", name);
+                }
+                Some(device)
+            }
+            None => None,
+        };
+
+        // 沿用当前应用在 `tts` 上的语音和设置
+        if let Some(id) = voice_id {
+            let voice = SpeechSynthesizer::AllVoices()?.into_iter().find(|v| v.Id().is_ok_and(|v_id| v_id == id));
+            if let Some(voice) = voice {
+                self.synthesizer.SetVoice(&voice)?;
+            }
+        }
+        let options = self.synthesizer.Options()?;
+        options.SetSpeakingRate(rate as f64)?;
+        options.SetAudioPitch(pitch as f64)?;
+        options.SetAudioVolume(volume as f64)?;
+
+        let stream = if ssml {
+            self.synthesizer.SynthesizeSsmlToStreamAsync(&HSTRING::from(text))?.get()?
+        } else {
+            self.synthesizer.SynthesizeTextToStreamAsync(&HSTRING::from(text))?.get()?
+        };
+        let source = MediaSource::CreateFromStream(&stream, &stream.ContentType()?)?;
+        if let Some(device) = &device {
+            self.player.SetAudioDevice(device)?;
+        }
+        self.player.SetSource(&source)?;
+        self.player.Play()?;
+        Ok(true)
+    }
+
+    /// 停止播放，返回是否有正在播放的内容。此时不会触发结束回调，调用方需要自己把这次朗读当作已结束
+    pub fn stop(&self) -> bool {
+        let playing = self.player.PlaybackSession()
+            .and_then(|session| session.PlaybackState())
+            .is_ok_and(|state| state == MediaPlaybackState::Playing || state == MediaPlaybackState::Opening || state == MediaPlaybackState::Buffering);
+        if playing {
+            self.player.Pause().ok();
+        }
+        playing
+    }
+
+    /// 按名称 (不区分大小写) 或 ID 匹配到的配置设备
+    fn find_device(&self, configured: &str) -> windows::core::Result<Option<DeviceInformation>> {
+        Ok(enabled_render_devices()?.into_iter().find(|device| {
+            device.Name().is_ok_and(|name| name.to_string().eq_ignore_ascii_case(configured))
+                || device.Id().is_ok_and(|id| id == configured)
+        }))
+    }
+}
@@ -0,0 +1,130 @@
+// src/print_monitor.rs
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Duration;
+use log::{info, warn};
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::Printing::{
+    ClosePrinter, EnumJobsW, GetDefaultPrinterW, OpenPrinterW, JOB_INFO_1W, PRINTER_HANDLE,
+    JOB_STATUS_DELETED, JOB_STATUS_DELETING, JOB_STATUS_ERROR, JOB_STATUS_PAPEROUT, JOB_STATUS_USER_INTERVENTION,
+};
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const FAILED_STATUS: u32 = JOB_STATUS_ERROR | JOB_STATUS_PAPEROUT | JOB_STATUS_USER_INTERVENTION;
+const CANCELLED_STATUS: u32 = JOB_STATUS_DELETING | JOB_STATUS_DELETED;
+
+/// A queued job as (job id, owner, document name, status flags, page count).
+type JobEntry = (u32, String, String, u32, u32);
+
+struct TrackedJob {
+    document: String,
+    pages: u32,
+    status: u32,
+    failure_announced: bool,
+}
+
+/// Polls the default printer's queue and reports when the current user's jobs finish or fail.
+/// The printer is reopened on every poll, so a restarted spooler or a new default printer is
+/// picked up without special handling.
+pub fn start_print_monitor(username: String, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        info!("打印任务监控已启动。");
+        let mut printer = String::new();
+        let mut tracked: HashMap<u32, TrackedJob> = HashMap::new();
+        let mut spooler_available = true;
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let Some(default_printer) = default_printer_name() else { continue };
+            if default_printer != printer {
+                printer = default_printer;
+                tracked.clear();
+            }
+            let jobs = match enum_jobs(&printer) {
+                Ok(jobs) => {
+                    if !spooler_available {
+                        // Jobs that vanished while the spooler was down may have been purged
+                        // rather than printed, so start tracking afresh.
+                        info!("打印后台处理程序已恢复。");
+                        spooler_available = true;
+                        tracked.clear();
+                    }
+                    jobs
+                }
+                Err(e) => {
+                    // While the spooler is restarting jobs cannot be seen, which must not be
+                    // mistaken for them having finished.
+                    if spooler_available {
+                        warn!("读取打印队列失败，稍后重试: {}", e);
+                        spooler_available = false;
+                    }
+                    continue;
+                }
+            };
+
+            let mut seen = Vec::with_capacity(jobs.len());
+            for (id, user, document, status, pages) in jobs {
+                if !user.eq_ignore_ascii_case(&username) { continue; }
+                seen.push(id);
+                let job = tracked.entry(id).or_insert_with(|| TrackedJob { document: document.clone(), pages, status, failure_announced: false });
+                job.pages = job.pages.max(pages);
+                job.status = status;
+                if status & FAILED_STATUS != 0 && !job.failure_announced {
+                    job.failure_announced = true;
+                    info!("打印任务 '{}' 出错，状态 0x{:x}。", job.document, status);
+                    send_event(&sender, hwnd_value, SystemEvent::PrintJobFailed { document: job.document.clone() });
+                }
+            }
+
+            let finished: Vec<u32> = tracked.keys().filter(|id| !seen.contains(id)).copied().collect();
+            for id in finished {
+                let Some(job) = tracked.remove(&id) else { continue };
+                if job.status & CANCELLED_STATUS != 0 || job.failure_announced {
+                    info!("打印任务 '{}' 已取消或失败，不播报完成。", job.document);
+                    continue;
+                }
+                info!("打印任务 '{}' 已完成，共 {} 页。", job.document, job.pages);
+                send_event(&sender, hwnd_value, SystemEvent::PrintJobCompleted { document: job.document, pages: job.pages });
+            }
+        }
+    });
+}
+
+fn default_printer_name() -> Option<String> {
+    let mut len = 0u32;
+    unsafe { let _ = GetDefaultPrinterW(None, &mut len); }
+    if len == 0 { return None; }
+    let mut buffer = vec![0u16; len as usize];
+    if !unsafe { GetDefaultPrinterW(Some(PWSTR(buffer.as_mut_ptr())), &mut len) }.as_bool() { return None; }
+    Some(String::from_utf16_lossy(&buffer[..len.saturating_sub(1) as usize]))
+}
+
+/// Every job in the queue.
+fn enum_jobs(printer: &str) -> windows::core::Result<Vec<JobEntry>> {
+    let name: Vec<u16> = printer.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut handle = PRINTER_HANDLE::default();
+    unsafe { OpenPrinterW(PCWSTR(name.as_ptr()), &mut handle, None)? };
+
+    let result = (|| {
+        let (mut needed, mut returned) = (0u32, 0u32);
+        // The first call only reports the buffer size and is expected to fail.
+        unsafe { let _ = EnumJobsW(handle, 0, u32::MAX, 1, None, &mut needed, &mut returned); }
+        if needed == 0 { return Ok(Vec::new()); }
+        // JOB_INFO_1W contains pointers, so the buffer has to be suitably aligned.
+        let mut buffer = vec![0u64; (needed as usize).div_ceil(8)];
+        let bytes = unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, needed as usize) };
+        unsafe { EnumJobsW(handle, 0, u32::MAX, 1, Some(bytes), &mut needed, &mut returned)? };
+        let infos = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const JOB_INFO_1W, returned as usize) };
+        Ok(infos.iter().map(|info| {
+            let text = |p: PWSTR| if p.is_null() { String::new() } else { unsafe { p.to_string() }.unwrap_or_default() };
+            (info.JobId, text(info.pUserName), text(info.pDocument), info.Status, info.TotalPages.max(info.PagesPrinted))
+        }).collect())
+    })();
+
+    unsafe { ClosePrinter(handle).ok() };
+    result
+}
@@ -0,0 +1,106 @@
+// src/process_monitor.rs
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Duration;
+use log::{info, warn};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, HWND};
+use windows::Win32::System::Diagnostics::ToolHelp::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS};
+use windows::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// GetExitCodeProcess reports this while the process is still running.
+const STILL_ACTIVE: u32 = 259;
+
+/// A running instance of a watched executable. The handle keeps the exit code
+/// retrievable after the process is gone from the snapshot.
+struct TrackedProcess {
+    name: String,
+    handle: Option<HANDLE>,
+}
+
+/// Starts polling for the configured executables. Does nothing when the list is empty.
+pub fn start_process_monitor(watched: &[String], sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    // Keep the user's spelling for announcements, but match case-insensitively.
+    let watched: HashMap<String, String> = watched.iter()
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| (normalize(name), name.trim().to_string()))
+        .collect();
+    if watched.is_empty() { return; }
+
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        info!("Watching {} application(s) for start/exit.", watched.len());
+        let mut tracked: HashMap<u32, TrackedProcess> = HashMap::new();
+        let mut instance_counts: HashMap<String, usize> = HashMap::new();
+        let mut first_scan = true;
+
+        loop {
+            let snapshot = match running_processes() {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("Failed to enumerate processes: {}", e);
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+            };
+
+            for (pid, exe) in &snapshot {
+                if tracked.contains_key(pid) { continue; }
+                let Some(display_name) = watched.get(&normalize(exe)) else { continue };
+                let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, *pid) }.ok();
+                tracked.insert(*pid, TrackedProcess { name: display_name.clone(), handle });
+
+                let count = instance_counts.entry(display_name.clone()).or_insert(0);
+                *count += 1;
+                // Applications already running when we start are not news.
+                if *count == 1 && !first_scan {
+                    send_event(&sender, hwnd_value, SystemEvent::WatchedProcessStarted { name: display_name.clone() });
+                }
+            }
+
+            let exited: Vec<u32> = tracked.keys().filter(|pid| !snapshot.contains_key(pid)).copied().collect();
+            for pid in exited {
+                let Some(process) = tracked.remove(&pid) else { continue };
+                let exit_code = process.handle.and_then(|handle| {
+                    let mut code = 0u32;
+                    let result = unsafe { GetExitCodeProcess(handle, &mut code) }.ok().map(|_| code);
+                    unsafe { CloseHandle(handle).ok() };
+                    result.filter(|&c| c != STILL_ACTIVE).map(|c| c as i32)
+                });
+
+                let count = instance_counts.entry(process.name.clone()).or_insert(1);
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    send_event(&sender, hwnd_value, SystemEvent::WatchedProcessExited { name: process.name, exit_code });
+                }
+            }
+
+            first_scan = false;
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    name.strip_suffix(".exe").map(str::to_string).unwrap_or(name)
+}
+
+fn running_processes() -> windows::core::Result<HashMap<u32, String>> {
+    let mut processes = HashMap::new();
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)?;
+        let mut entry = PROCESSENTRY32W { dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32, ..Default::default() };
+        let mut next = Process32FirstW(snapshot, &mut entry);
+        while next.is_ok() {
+            let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+            processes.insert(entry.th32ProcessID, String::from_utf16_lossy(&entry.szExeFile[..len]));
+            next = Process32NextW(snapshot, &mut entry);
+        }
+        CloseHandle(snapshot).ok();
+    }
+    Ok(processes)
+}
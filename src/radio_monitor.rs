@@ -0,0 +1,129 @@
+// src/radio_monitor.rs
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Duration;
+use log::{info, warn, error};
+use windows::core::IInspectable;
+use windows::Devices::Radios::{Radio, RadioAccessStatus, RadioKind as WinRadioKind, RadioState};
+use windows::Foundation::TypedEventHandler;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+// Airplane mode flips every radio within a few hundred milliseconds.
+const BATCH_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RadioKind {
+    Bluetooth,
+    WiFi,
+    MobileBroadband,
+}
+
+impl RadioKind {
+    fn from_windows(kind: WinRadioKind) -> Option<Self> {
+        match kind {
+            WinRadioKind::Bluetooth => Some(RadioKind::Bluetooth),
+            WinRadioKind::WiFi => Some(RadioKind::WiFi),
+            WinRadioKind::MobileBroadband => Some(RadioKind::MobileBroadband),
+            _ => None,
+        }
+    }
+
+    /// Locale key for the radio's spoken name.
+    pub fn name_key(self) -> &'static str {
+        match self {
+            RadioKind::Bluetooth => "radio_bluetooth",
+            RadioKind::WiFi => "radio_wifi",
+            RadioKind::MobileBroadband => "radio_mobile_broadband",
+        }
+    }
+}
+
+/// Starts watching the Bluetooth / Wi-Fi / cellular radios on its own MTA thread.
+/// With `report_airplane_mode`, all radios switching together is reported as one
+/// airplane mode event instead of one announcement per radio.
+pub fn start_radio_monitor(report_airplane_mode: bool, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("无线电监控线程 COM 初始化失败。");
+            return;
+        }
+        if let Err(e) = run_radio_monitor(report_airplane_mode, sender, hwnd_value) {
+            warn!("无线电状态监控已禁用: {}", e);
+        }
+    });
+}
+
+fn run_radio_monitor(report_airplane_mode: bool, sender: mpsc::Sender<SystemEvent>, hwnd_value: isize) -> windows::core::Result<()> {
+    let access = Radio::RequestAccessAsync()?.get()?;
+    if access != RadioAccessStatus::Allowed {
+        warn!("无线电状态监控已禁用: 访问被拒绝 (状态 {})。", access.0);
+        return Ok(());
+    }
+
+    let (changed_tx, changed_rx) = mpsc::channel::<(RadioKind, bool)>();
+    let mut states: HashMap<RadioKind, bool> = HashMap::new();
+    // The radios and their handlers must stay alive for the callbacks to keep firing.
+    let mut subscriptions = Vec::new();
+    for radio in Radio::GetRadiosAsync()?.get()? {
+        let Some(kind) = radio.Kind().ok().and_then(RadioKind::from_windows) else { continue };
+        states.insert(kind, radio.State().is_ok_and(|s| s == RadioState::On));
+
+        let changed_tx = changed_tx.clone();
+        let handler = TypedEventHandler::<Radio, IInspectable>::new(move |radio, _| {
+            if let Some(radio) = radio.as_ref() {
+                // Disabled means the hardware switch or driver is off, which still counts as "off".
+                let on = radio.State().is_ok_and(|s| s == RadioState::On);
+                changed_tx.send((kind, on)).ok();
+            }
+            Ok(())
+        });
+        let token = radio.StateChanged(&handler)?;
+        subscriptions.push((radio, token));
+    }
+    drop(changed_tx);
+    if states.is_empty() {
+        info!("未找到可监控的无线电设备。");
+        return Ok(());
+    }
+    info!("无线电状态监控已启动: {:?}", states.keys().collect::<Vec<_>>());
+
+    while let Ok(first) = changed_rx.recv() {
+        // Collect everything that changes within the batch window.
+        let mut batch = vec![first];
+        while let Ok(change) = changed_rx.recv_timeout(BATCH_WINDOW) {
+            batch.push(change);
+        }
+
+        let mut toggled: Vec<(RadioKind, bool)> = Vec::new();
+        for (kind, on) in batch {
+            if states.insert(kind, on) != Some(on) {
+                toggled.retain(|(k, _)| *k != kind);
+                toggled.push((kind, on));
+            }
+        }
+        if toggled.is_empty() { continue; }
+
+        let all_switched_together = states.len() > 1 && toggled.len() == states.len()
+            && toggled.iter().all(|(_, on)| *on == toggled[0].1);
+        if report_airplane_mode && all_switched_together {
+            let on = !toggled[0].1;
+            info!("所有无线电同时{}，视为飞行模式{}。", if on { "关闭" } else { "开启" }, if on { "开启" } else { "关闭" });
+            send_event(&sender, hwnd_value, SystemEvent::AirplaneModeChanged { on });
+            continue;
+        }
+        for (kind, on) in toggled {
+            info!("无线电 {:?} 已{}。", kind, if on { "开启" } else { "关闭" });
+            send_event(&sender, hwnd_value, SystemEvent::RadioStateChanged { kind, on });
+        }
+    }
+
+    for (radio, token) in subscriptions {
+        radio.RemoveStateChanged(token).ok();
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+// src/repeat_guard.rs
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use log::info;
+
+struct Repetition {
+    last_seen: Instant,
+    // Repeats suppressed since the text was last spoken.
+    suppressed: u32,
+}
+
+/// Suppresses an announcement whose exact text was already heard within the window, e.g. a
+/// flapping network producing the same "disconnected" / "connected to X" pair every few seconds.
+/// Keyed on the final localized text, so the same event with different values (another SSID)
+/// is always spoken.
+#[derive(Default)]
+pub struct RepeatGuard {
+    recent: HashMap<String, Repetition>,
+}
+
+impl RepeatGuard {
+    /// Records the text and returns whether it should be spoken. The window slides with every
+    /// repeat, so a burst stays suppressed until it has been quiet for `window`.
+    pub fn accept(&mut self, text: &str, window: Duration) -> bool {
+        let now = Instant::now();
+        match self.recent.get_mut(text) {
+            Some(repetition) if now.duration_since(repetition.last_seen) < window => {
+                repetition.last_seen = now;
+                repetition.suppressed += 1;
+                info!("{} 秒内重复的播报，已跳过 (第 {} 次): {}", window.as_secs(), repetition.suppressed, text);
+                false
+            }
+            _ => {
+                self.recent.insert(text.to_string(), Repetition { last_seen: now, suppressed: 0 });
+                true
+            }
+        }
+    }
+
+    /// Bursts that have been quiet for `window`, as (text, suppressed repeats). Texts that were
+    /// never repeated are forgotten without being returned.
+    pub fn take_finished(&mut self, window: Duration) -> Vec<(String, u32)> {
+        let now = Instant::now();
+        let mut finished = Vec::new();
+        self.recent.retain(|text, repetition| {
+            if now.duration_since(repetition.last_seen) < window { return true; }
+            if repetition.suppressed > 0 {
+                finished.push((text.clone(), repetition.suppressed));
+            }
+            false
+        });
+        finished
+    }
+
+    /// Whether a burst is still being suppressed.
+    pub fn has_pending(&self) -> bool {
+        self.recent.values().any(|repetition| repetition.suppressed > 0)
+    }
+}
@@ -0,0 +1,267 @@
+// src/resume_summary.rs
+
+use std::collections::VecDeque;
+use log::{info, warn};
+use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+use windows::Win32::System::SystemInformation::GetLocalTime;
+
+use crate::event_monitor::SystemEvent;
+
+// Only the net effect matters, so a short history is plenty even after a long sleep.
+const MAX_SUPPRESSED: usize = 16;
+// Smaller battery changes during sleep are normal and not worth mentioning.
+const BATTERY_CHANGE_THRESHOLD: u8 = 5;
+
+/// Power state read directly from the system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerSnapshot {
+    pub on_ac: bool,
+    /// `None` on machines without a battery or when the level is unknown.
+    pub battery_level: Option<u8>,
+}
+
+impl PowerSnapshot {
+    pub fn read() -> Option<Self> {
+        let mut status = SYSTEM_POWER_STATUS::default();
+        if let Err(e) = unsafe { GetSystemPowerStatus(&mut status) } {
+            warn!("读取电源状态失败: {}", e);
+            return None;
+        }
+        let has_battery = status.BatteryFlag & 128 == 0 && status.BatteryLifePercent <= 100;
+        Some(PowerSnapshot {
+            on_ac: status.ACLineStatus == 1,
+            battery_level: has_battery.then_some(status.BatteryLifePercent),
+        })
+    }
+}
+
+/// A net change that happened while the system was asleep.
+#[derive(Debug, PartialEq)]
+pub enum SleepChange {
+    PowerConnected,
+    PowerDisconnected,
+    BatteryDropped { level: u8 },
+    BatteryRose { level: u8 },
+    NetworkLost,
+    NetworkChanged { name: String },
+}
+
+/// Remembers the state when the system went to sleep and the events that arrived while it
+/// slept, so that on resume only the overall difference is announced. The same applies to
+/// events held back while Focus Assist was on.
+#[derive(Default)]
+pub struct ResumeSummary {
+    before_sleep: Option<PowerSnapshot>,
+    // Local time (hour, minute) the system went to sleep or the hold began.
+    since: Option<(u16, u16)>,
+    network_before_sleep: Option<Option<String>>,
+    // Last network seen while awake; `None` until the first network event.
+    network: Option<Option<String>>,
+    suppressed: VecDeque<SystemEvent>,
+}
+
+impl ResumeSummary {
+    /// Tracks state from events handled while awake.
+    pub fn observe(&mut self, event: &SystemEvent) {
+        match event {
+            SystemEvent::NetworkConnected { name, .. } => self.network = Some(Some(name.clone())),
+            SystemEvent::NetworkDisconnected => self.network = Some(None),
+            SystemEvent::NetworkSwitched { to, .. } => self.network = Some(Some(to.clone())),
+            _ => {}
+        }
+    }
+
+    pub fn on_sleep(&mut self, snapshot: Option<PowerSnapshot>) {
+        self.before_sleep = snapshot;
+        let now = unsafe { GetLocalTime() };
+        self.since = Some((now.wHour, now.wMinute));
+        self.network_before_sleep = self.network.clone();
+        self.suppressed.clear();
+    }
+
+    /// Starts holding events back while the user is busy (Focus Assist). A pending summary of
+    /// an earlier hold or sleep is extended rather than restarted.
+    pub fn begin_hold(&mut self, snapshot: Option<PowerSnapshot>) {
+        if self.before_sleep.is_some() { return; }
+        self.on_sleep(snapshot);
+    }
+
+    /// Keeps an event that was not announced because the system was asleep or the user busy.
+    pub fn record(&mut self, event: SystemEvent) {
+        if self.suppressed.len() == MAX_SUPPRESSED {
+            self.suppressed.pop_front();
+        }
+        self.suppressed.push_back(event);
+    }
+
+    /// When the pending sleep or hold began, as local (hour, minute); `None` without one.
+    pub fn since(&self) -> Option<(u16, u16)> {
+        self.since
+    }
+
+    /// Compares the state before sleep with the state now and returns the net changes.
+    pub fn on_resume(&mut self, after: Option<PowerSnapshot>) -> Vec<SleepChange> {
+        let suppressed: Vec<SystemEvent> = self.suppressed.drain(..).collect();
+        for event in &suppressed {
+            self.observe(event);
+        }
+        let before = self.before_sleep.take();
+        self.since = None;
+        let network_before = self.network_before_sleep.take();
+        let changes = diff(before, after, network_before.as_ref(), self.network.as_ref());
+        info!("睡眠期间收到 {} 条未播报的事件，合并后的变化: {:?}", suppressed.len(), changes);
+        changes
+    }
+}
+
+fn diff(
+    before: Option<PowerSnapshot>,
+    after: Option<PowerSnapshot>,
+    network_before: Option<&Option<String>>,
+    network_after: Option<&Option<String>>,
+) -> Vec<SleepChange> {
+    let mut changes = Vec::new();
+    if let (Some(before), Some(after)) = (before, after) {
+        if before.on_ac && !after.on_ac {
+            changes.push(SleepChange::PowerDisconnected);
+        } else if !before.on_ac && after.on_ac {
+            changes.push(SleepChange::PowerConnected);
+        }
+        if let (Some(old), Some(new)) = (before.battery_level, after.battery_level) {
+            if new + BATTERY_CHANGE_THRESHOLD <= old {
+                changes.push(SleepChange::BatteryDropped { level: new });
+            } else if new >= old + BATTERY_CHANGE_THRESHOLD {
+                changes.push(SleepChange::BatteryRose { level: new });
+            }
+        }
+    }
+    // The network monitor announces reconnections after resume by itself, so only report
+    // a network change that is already known from events delivered during sleep.
+    if let (Some(before), Some(after)) = (network_before, network_after) {
+        if before != after {
+            changes.push(match after {
+                Some(name) => SleepChange::NetworkChanged { name: name.clone() },
+                None => SleepChange::NetworkLost,
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_monitor::ConnectionType;
+
+    fn power(on_ac: bool, battery_level: u8) -> Option<PowerSnapshot> {
+        Some(PowerSnapshot { on_ac, battery_level: Some(battery_level) })
+    }
+
+    fn connected(name: &str) -> SystemEvent {
+        SystemEvent::NetworkConnected { name: name.to_string(), conn_type: ConnectionType::WiFi }
+    }
+
+    // Goes to sleep on `before` with the given events delivered while asleep, then resumes on `after`.
+    fn sleep_through(summary: &mut ResumeSummary, before: Option<PowerSnapshot>, events: Vec<SystemEvent>, after: Option<PowerSnapshot>) -> Vec<SleepChange> {
+        summary.on_sleep(before);
+        for event in events {
+            summary.record(event);
+        }
+        summary.on_resume(after)
+    }
+
+    #[test]
+    fn reports_unplugged_charger_and_battery_drop() {
+        let changes = sleep_through(&mut ResumeSummary::default(), power(true, 90), Vec::new(), power(false, 60));
+        assert_eq!(changes, [SleepChange::PowerDisconnected, SleepChange::BatteryDropped { level: 60 }]);
+    }
+
+    #[test]
+    fn reports_charging_while_asleep() {
+        let changes = sleep_through(&mut ResumeSummary::default(), power(false, 40), Vec::new(), power(true, 80));
+        assert_eq!(changes, [SleepChange::PowerConnected, SleepChange::BatteryRose { level: 80 }]);
+    }
+
+    #[test]
+    fn ignores_small_battery_changes() {
+        let changes = sleep_through(&mut ResumeSummary::default(), power(false, 80), Vec::new(), power(false, 80 - BATTERY_CHANGE_THRESHOLD + 1));
+        assert!(changes.is_empty());
+        let changes = sleep_through(&mut ResumeSummary::default(), power(false, 80), Vec::new(), power(false, 80 - BATTERY_CHANGE_THRESHOLD));
+        assert_eq!(changes, [SleepChange::BatteryDropped { level: 80 - BATTERY_CHANGE_THRESHOLD }]);
+    }
+
+    #[test]
+    fn unknown_power_state_reports_nothing() {
+        assert!(sleep_through(&mut ResumeSummary::default(), None, Vec::new(), power(false, 10)).is_empty());
+        assert!(sleep_through(&mut ResumeSummary::default(), power(true, 90), Vec::new(), None).is_empty());
+        let no_battery = Some(PowerSnapshot { on_ac: true, battery_level: None });
+        assert!(sleep_through(&mut ResumeSummary::default(), no_battery, Vec::new(), power(true, 10)).is_empty());
+    }
+
+    #[test]
+    fn intermediate_events_collapse_to_the_net_change() {
+        let mut summary = ResumeSummary::default();
+        summary.observe(&connected("Home"));
+        let events = vec![
+            SystemEvent::NetworkDisconnected,
+            connected("Cafe"),
+            SystemEvent::NetworkSwitched { from: "Cafe".to_string(), to: "Office".to_string() },
+        ];
+        let changes = sleep_through(&mut summary, power(true, 90), events, power(true, 90));
+        assert_eq!(changes, [SleepChange::NetworkChanged { name: "Office".to_string() }]);
+    }
+
+    #[test]
+    fn returning_to_the_same_state_reports_nothing() {
+        let mut summary = ResumeSummary::default();
+        summary.observe(&connected("Home"));
+        let events = vec![SystemEvent::NetworkDisconnected, connected("Home")];
+        assert!(sleep_through(&mut summary, power(true, 90), events, power(true, 88)).is_empty());
+    }
+
+    #[test]
+    fn reports_lost_network() {
+        let mut summary = ResumeSummary::default();
+        summary.observe(&connected("Home"));
+        let changes = sleep_through(&mut summary, None, vec![SystemEvent::NetworkDisconnected], None);
+        assert_eq!(changes, [SleepChange::NetworkLost]);
+    }
+
+    #[test]
+    fn network_unknown_before_sleep_reports_nothing() {
+        let changes = sleep_through(&mut ResumeSummary::default(), None, vec![connected("Home")], None);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn keeps_only_the_latest_suppressed_events() {
+        let mut summary = ResumeSummary::default();
+        summary.observe(&connected("Home"));
+        summary.on_sleep(None);
+        summary.record(SystemEvent::NetworkDisconnected);
+        for _ in 0..MAX_SUPPRESSED {
+            summary.record(connected("Home"));
+        }
+        assert_eq!(summary.suppressed.len(), MAX_SUPPRESSED);
+        // The disconnect fell out of the buffer, so the network looks unchanged.
+        assert!(summary.on_resume(None).is_empty());
+    }
+
+    #[test]
+    fn resume_clears_the_pending_summary() {
+        let mut summary = ResumeSummary::default();
+        summary.on_sleep(power(true, 90));
+        assert!(summary.since().is_some());
+        summary.on_resume(power(false, 50));
+        assert!(summary.since().is_none());
+        assert!(summary.on_resume(power(false, 50)).is_empty());
+    }
+
+    #[test]
+    fn hold_does_not_restart_a_pending_sleep() {
+        let mut summary = ResumeSummary::default();
+        summary.on_sleep(power(true, 90));
+        summary.begin_hold(power(false, 70));
+        assert_eq!(summary.on_resume(power(false, 70)), [SleepChange::PowerDisconnected, SleepChange::BatteryDropped { level: 70 }]);
+    }
+}
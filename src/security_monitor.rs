@@ -0,0 +1,91 @@
+// src/security_monitor.rs
+
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+use log::{info, warn};
+use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ};
+use winreg::RegKey;
+use windows::Win32::Foundation::HWND;
+
+use crate::event_monitor::{send_event, SystemEvent};
+
+const DEFENDER_REALTIME_KEY: &str = r"SOFTWARE\Microsoft\Windows Defender\Real-Time Protection";
+const FIREWALL_POLICY_KEY: &str = r"SYSTEM\CurrentControlSet\Services\SharedAccess\Parameters\FirewallPolicy";
+const FIREWALL_PROFILES: [&str; 3] = ["DomainProfile", "StandardProfile", "PublicProfile"];
+
+/// One security setting that is polled for changes. A check that cannot be read (access
+/// denied, or the component is not installed) is switched off after a single warning.
+struct Check {
+    name: &'static str,
+    read: fn() -> io::Result<bool>,
+    on_change: fn(bool) -> SystemEvent,
+    last: Option<bool>,
+}
+
+/// Polls Defender real-time protection and Windows Firewall and reports when either is
+/// switched off or back on. Only transitions are reported, not the state at startup.
+pub fn start_security_monitor(poll_interval_secs: u64, sender: mpsc::Sender<SystemEvent>, hwnd: HWND) {
+    let poll_interval = Duration::from_secs(poll_interval_secs.max(10));
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        let mut checks = vec![
+            Check {
+                name: "Defender 实时保护",
+                read: realtime_protection_enabled,
+                on_change: |on| if on { SystemEvent::RealtimeProtectionEnabled } else { SystemEvent::RealtimeProtectionDisabled },
+                last: None,
+            },
+            Check {
+                name: "Windows 防火墙",
+                read: firewall_enabled,
+                on_change: |on| if on { SystemEvent::FirewallEnabled } else { SystemEvent::FirewallDisabled },
+                last: None,
+            },
+        ];
+        info!("安全状态监控已启动，每 {} 秒检查一次。", poll_interval.as_secs());
+        loop {
+            checks.retain_mut(|check| match (check.read)() {
+                Ok(enabled) => {
+                    if check.last.is_some_and(|last| last != enabled) {
+                        info!("{} 已{}。", check.name, if enabled { "开启" } else { "关闭" });
+                        send_event(&sender, hwnd_value, (check.on_change)(enabled));
+                    }
+                    check.last = Some(enabled);
+                    true
+                }
+                Err(e) => {
+                    warn!("无法读取 {} 的状态，停止监控该项: {}", check.name, e);
+                    false
+                }
+            });
+            if checks.is_empty() {
+                warn!("没有可以监控的安全设置，安全状态监控已停止。");
+                return;
+            }
+            std::thread::sleep(poll_interval);
+        }
+    });
+}
+
+fn realtime_protection_enabled() -> io::Result<bool> {
+    let key = RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(DEFENDER_REALTIME_KEY, KEY_READ)?;
+    // The value is absent while protection is on.
+    match key.get_value::<u32, _>("DisableRealtimeMonitoring") {
+        Ok(disabled) => Ok(disabled == 0),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+/// The firewall counts as on only while every profile has it enabled.
+fn firewall_enabled() -> io::Result<bool> {
+    let policy = RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(FIREWALL_POLICY_KEY, KEY_READ)?;
+    for profile in FIREWALL_PROFILES {
+        let enabled: u32 = policy.open_subkey_with_flags(profile, KEY_READ)?.get_value("EnableFirewall")?;
+        if enabled == 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
@@ -1,28 +1,52 @@
 // src/settings_ui.rs
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicIsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use once_cell::sync::Lazy;
+use tts::Gender;
 
 // --- 核心修复：引入新版API所需的具体枚举和类型 ---
-use windows::core::{w, HSTRING, PCWSTR};
-use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::core::{w, HSTRING, PCWSTR, PWSTR};
+use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, SYSTEMTIME, WPARAM};
 // --- 修改: 引入CreateFontW所需的强类型枚举常量 ---
 use windows::Win32::Graphics::Gdi::{
     CreateFontW, DeleteObject, GetStockObject, HBRUSH, HFONT, WHITE_BRUSH,
-    DEFAULT_GUI_FONT, DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS, DEFAULT_QUALITY, FF_DONTCARE,
+    DrawTextW, FillRect, GetSysColor, GetSysColorBrush, SetBkMode, SetTextColor, COLOR_GRAYTEXT, COLOR_HIGHLIGHT,
+    COLOR_HIGHLIGHTTEXT, COLOR_WINDOW, COLOR_WINDOWTEXT, DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER, TRANSPARENT,
+    GetMonitorInfoW, MapWindowPoints, MonitorFromPoint, MonitorFromRect, MONITORINFO, MONITOR_DEFAULTTONULL, MONITOR_DEFAULTTOPRIMARY, DEFAULT_GUI_FONT, DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS, DEFAULT_QUALITY, FF_DONTCARE,
 };
+use windows::Win32::Globalization::{GetLocaleInfoW, LocaleNameToLCID, LOCALE_SLOCALIZEDDISPLAYNAME};
+use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::SystemInformation::GetLocalTime;
 use windows::Win32::System::SystemServices::SS_LEFT;
-use windows::Win32::UI::Controls::{BST_CHECKED, BST_UNCHECKED};
+use windows::Win32::UI::Accessibility::{CAccPropServices, IAccPropServices, PROPID_ACC_DESCRIPTION, PROPID_ACC_NAME};
+use windows::Win32::UI::Controls::{
+    InitCommonControlsEx, BST_CHECKED, BST_UNCHECKED, DATETIMEPICK_CLASSW, DTM_GETSYSTEMTIME, DTM_SETFORMATW, DTM_SETSYSTEMTIME,
+    DTS_TIMEFORMAT, DTS_UPDOWN, GDT_VALID, DTN_DATETIMECHANGE, ICC_BAR_CLASSES, ICC_DATE_CLASSES, ICC_LISTVIEW_CLASSES, ICC_UPDOWN_CLASS, INITCOMMONCONTROLSEX, UDACCEL, UDM_SETACCEL, UDM_SETRANGE32, UDS_ALIGNRIGHT, UDS_ARROWKEYS, UDS_AUTOBUDDY, UDS_NOTHOUSANDS, UDS_SETBUDDYINT, UPDOWN_CLASSW, DRAWITEMSTRUCT, MEASUREITEMSTRUCT, ODS_SELECTED,
+    TB_ENDTRACK, TBM_SETPOS, TBM_SETRANGEMAX, TBM_SETRANGEMIN, TBM_SETTICFREQ, TBS_AUTOTICKS, TBS_HORZ, TRACKBAR_CLASSW,
+    LIST_VIEW_ITEM_STATE_FLAGS, LVCF_WIDTH, LVCOLUMNW, LVIF_TEXT, LVIS_STATEIMAGEMASK, LVITEMW, LVM_GETITEMSTATE, LVM_INSERTCOLUMNW, LVM_INSERTITEMW, LVM_SETCOLUMNWIDTH,
+    LVM_SETEXTENDEDLISTVIEWSTYLE, LVM_SETITEMSTATE, LVM_SETITEMTEXTW, LVS_EX_CHECKBOXES, LVS_EX_FULLROWSELECT, LVS_NOCOLUMNHEADER, LVS_REPORT,
+    LVS_SHOWSELALWAYS, LVS_SINGLESEL, WC_LISTVIEWW, LVIF_STATE, LVN_ITEMCHANGED, NMHDR, NMLISTVIEW,
+};
+use windows::Win32::UI::Controls::Dialogs::{GetOpenFileNameW, GetSaveFileNameW, OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR, OFN_OVERWRITEPROMPT, OFN_PATHMUSTEXIST, OPENFILENAMEW};
 use windows::Win32::UI::WindowsAndMessaging::{
-    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW, GetWindowLongPtrW, LoadCursorW, PostMessageW, PostQuitMessage, RegisterClassW, SendMessageW, SetWindowLongPtrW, TranslateMessage, BM_GETCHECK, BM_SETCHECK, BS_AUTOCHECKBOX, BS_DEFPUSHBUTTON, CBN_SELCHANGE, CBS_DROPDOWNLIST, CB_ADDSTRING, CB_GETCURSEL, CB_RESETCONTENT, CB_SETCURSEL, CREATESTRUCTW, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, GWLP_USERDATA, HMENU, IDC_ARROW, MSG, WINDOW_STYLE, WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_SETFONT, WNDCLASSW, WS_CAPTION, WS_CHILD, WS_EX_DLGMODALFRAME, WS_SYSMENU, WS_VISIBLE, WS_VSCROLL
+    WNDPROC, CreateWindowExW, DefWindowProcW, DestroyWindow, GetClassNameW, GetCursorPos, GetWindow, GetWindowRect, GW_OWNER, MoveWindow, CHILDID_SELF, OBJID_CLIENT, WS_GROUP, SetWindowPos, GW_CHILD, GW_HWNDNEXT, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOZORDER, WM_DPICHANGED, CB_GETDROPPEDCONTROLRECT, CB_SETITEMHEIGHT, FindWindowW, GetDlgItem, SetDlgItemTextW, GetWindowLongPtrW, LoadCursorW, PostMessageW, RegisterClassW, SetForegroundWindow, SendMessageW, SetWindowLongPtrW, MessageBoxW, SetWindowTextW, GetWindowTextW, GetWindowTextLengthW, IDYES, MB_ICONWARNING, MB_OK, MB_YESNO, ES_AUTOHSCROLL, ES_NUMBER, ES_READONLY, LBN_SELCHANGE, LBS_NOTIFY, LB_INSERTSTRING, BM_GETCHECK, BM_SETCHECK, BS_AUTOCHECKBOX, BS_DEFPUSHBUTTON, CBN_SELCHANGE, CBS_DROPDOWNLIST, CBS_HASSTRINGS, CBS_OWNERDRAWFIXED, CB_ADDSTRING, CB_GETLBTEXT, CB_GETLBTEXTLEN, CB_GETCURSEL, CB_RESETCONTENT, CB_SETCURSEL, CREATESTRUCTW, LB_ADDSTRING, LB_DELETESTRING, LB_ERR, LB_GETCURSEL, LB_SETHORIZONTALEXTENT, LBS_NOINTEGRALHEIGHT, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, GWLP_USERDATA, HMENU, IDC_ARROW, MSG, WINDOW_STYLE, WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_DRAWITEM, WM_MEASUREITEM, WM_SETFONT, WM_APP, WM_HSCROLL, WM_USER, WM_NOTIFY, BN_CLICKED, EN_CHANGE, WS_TABSTOP, DM_GETDEFID, DC_HASDEFID, IsDialogMessageW, WNDCLASSW, WS_CAPTION, WS_CHILD, WS_EX_DLGMODALFRAME, WS_SYSMENU, WS_VISIBLE, WS_VSCROLL, WS_BORDER, WS_HSCROLL
 };
-use windows::Win32::UI::Input::KeyboardAndMouse::{EnableWindow, SetActiveWindow};
+use windows::Win32::UI::HiDpi::{GetDpiForMonitor, GetDpiForWindow, MDT_EFFECTIVE_DPI};
+use windows::Win32::UI::Input::KeyboardAndMouse::{EnableWindow, SetFocus};
 
+use crate::config::{BatteryThresholdsError, USB_WINDOW_MS_RANGE, Config, EventSound, ProfileSettings, Pronunciation, QuietHours, QuietMode, WindowRect};
+use crate::event_monitor::{SOUND_EVENT_NAMES, TOGGLEABLE_EVENT_NAMES};
 use crate::i18n::I18nManager;
-use crate::tts_engine::VoiceDetail;
+use crate::tts_engine::{TtsEngine, VoiceDetail};
 use crate::AppState;
+use crate::status::EngineHealth;
+use crate::text_preprocess;
 use log::{error, info, warn};
 
 const IDC_VOICE_LABEL: i32 = 101;
@@ -30,94 +54,464 @@ const IDC_VOICE_COMBO: i32 = 102;
 const IDC_AUTOSTART_CHECK: i32 = 103;
 const IDC_LANG_LABEL: i32 = 104;
 const IDC_LANG_COMBO: i32 = 105;
+const IDC_IGNORED_LABEL: i32 = 106;
+const IDC_IGNORED_LIST: i32 = 107;
+const IDC_IGNORED_REMOVE: i32 = 108;
+const IDC_SOUND_LABEL: i32 = 109;
+const IDC_SOUND_EVENT_COMBO: i32 = 110;
+const IDC_SOUND_BROWSE: i32 = 111;
+const IDC_SOUND_CLEAR: i32 = 112;
+const IDC_SOUND_FILE_LABEL: i32 = 113;
+const IDC_QUIET_CHECK: i32 = 114;
+const IDC_QUIET_START: i32 = 115;
+const IDC_QUIET_END: i32 = 116;
+const IDC_QUIET_MODE_COMBO: i32 = 117;
+const IDC_ENGINE_STATUS: i32 = 118;
+const IDC_PRON_LABEL: i32 = 119;
+const IDC_PRON_LIST: i32 = 120;
+const IDC_PRON_PATTERN_LABEL: i32 = 121;
+const IDC_PRON_PATTERN: i32 = 122;
+const IDC_PRON_REPLACEMENT_LABEL: i32 = 123;
+const IDC_PRON_REPLACEMENT: i32 = 124;
+const IDC_PRON_REGEX: i32 = 125;
+const IDC_PRON_ADD: i32 = 126;
+const IDC_PRON_REMOVE: i32 = 127;
+const IDC_USB_DEBOUNCE_LABEL: i32 = 128;
+const IDC_USB_DEBOUNCE: i32 = 129;
+const IDC_EXPORT_SETTINGS: i32 = 130;
+const IDC_IMPORT_SETTINGS: i32 = 131;
+const IDC_PROFILE_LABEL: i32 = 132;
+const IDC_PROFILE_COMBO: i32 = 133;
+const IDC_PROFILE_NAME: i32 = 134;
+const IDC_PROFILE_ADD: i32 = 135;
+const IDC_PROFILE_RENAME: i32 = 136;
+const IDC_PROFILE_DELETE: i32 = 137;
+const IDC_MESSAGE_LABEL: i32 = 138;
+const IDC_MESSAGE_COMBO: i32 = 139;
+const IDC_MESSAGE_TEXT: i32 = 140;
+const IDC_MESSAGE_RESET: i32 = 141;
+const IDC_BATTERY_THRESHOLDS_LABEL: i32 = 142;
+const IDC_BATTERY_THRESHOLDS: i32 = 143;
+const IDC_VOICE_PREVIEW: i32 = 144;
+const IDC_RATE_LABEL: i32 = 145;
+const IDC_RATE_SLIDER: i32 = 146;
+const IDC_VOLUME_LABEL: i32 = 147;
+const IDC_VOLUME_SLIDER: i32 = 148;
+const IDC_EVENTS_LABEL: i32 = 149;
+const IDC_EVENTS_LIST: i32 = 150;
+const IDC_APPLY: i32 = 151;
+const IDC_OUTPUT_LABEL: i32 = 152;
+const IDC_OUTPUT_COMBO: i32 = 153;
+const IDC_USB_DEBOUNCE_SPIN: i32 = 154;
+const IDC_OPEN_CONFIG_FOLDER: i32 = 155;
+const IDC_OPEN_LOG_FILE: i32 = 156;
+const IDC_MESSAGE_PREVIEW: i32 = 157;
+const IDC_MESSAGE_PLACEHOLDERS: i32 = 158;
+const IDC_MESSAGE_SET: i32 = 159;
+const IDC_MESSAGE_SPEAK: i32 = 160;
+// windows 0.61 没有导出编辑框的 EM_SETSEL
+const EM_SETSEL: u32 = 0x00B1;
 const IDOK: i32 = 1;
 const IDCANCEL: i32 = 2;
 
+// 试听的语音播完 (或等待超时) 后由等待线程发给设置窗口，wparam 是试听的序号
+const WM_APP_PREVIEW_DONE: u32 = WM_APP + 1;
+// 等待试听播完的最长时间，超时后同样换回原来的语音
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(30);
+
+// 设置窗口在 96 DPI (100% 缩放) 下的大小。控件的位置同样按 96 DPI 书写，其他 DPI 下按比例放大
+const SETTINGS_WIDTH: i32 = 1000;
+const SETTINGS_HEIGHT: i32 = 800;
+pub(crate) const BASE_DPI: u32 = 96;
+// 96 DPI 下的字体高度 (像素) 和事件列表的列宽
+const FONT_HEIGHT: i32 = 15;
+const EVENTS_COLUMN_WIDTH: i32 = 205;
+
+// windows crate 没有定义 TBM_GETPOS (即 WM_USER)
+const TBM_GETPOS: u32 = WM_USER;
+// 语速滑块以 0.1 为一格 (0.5 到 6.0，与 Windows 语音引擎的范围相同)，未设置时在 1.0
+const RATE_SLIDER_MIN: isize = 5;
+const RATE_SLIDER_MAX: isize = 60;
+const RATE_SLIDER_DEFAULT: isize = 10;
+// 音量滑块以百分比为单位，未设置时在 100%
+const VOLUME_SLIDER_MAX: isize = 100;
+
+// 事件列表复选框的状态图像：1 为未选中，2 为选中 (即 INDEXTOSTATEIMAGEMASK)
+const LIST_UNCHECKED: u32 = 1 << 12;
+const LIST_CHECKED: u32 = 2 << 12;
+
+// 自绘语音下拉框每一项的高度
+const VOICE_ITEM_HEIGHT: i32 = 20;
+
+// 安静时段模式下拉框的顺序
+/// 设置窗口中固定的标签和按钮文字：(控件 ID, 语言档案中的条目, 找不到条目时的英文)。
+/// 创建窗口和切换语言后的刷新都使用这张表
+const CONTROL_TEXTS: &[(i32, &str, &str)] = &[
+    (IDOK, "settings_button_ok", "OK"),
+    (IDCANCEL, "settings_button_cancel", "Cancel"),
+    (IDC_APPLY, "settings_button_apply", "Appl&y"),
+    (IDC_VOICE_LABEL, "settings_label_voice", "&Voice:"),
+    (IDC_VOICE_PREVIEW, "settings_button_preview", "&Preview"),
+    (IDC_AUTOSTART_CHECK, "settings_checkbox_autostart", "Start with &Windows"),
+    (IDC_LANG_LABEL, "settings_label_language", "&Language:"),
+    (IDC_IGNORED_LABEL, "settings_label_ignored_devices", "Ignored USB devices:"),
+    (IDC_IGNORED_REMOVE, "settings_button_remove", "Re&move"),
+    (IDC_SOUND_LABEL, "settings_label_event_sound", "Event sound:"),
+    (IDC_SOUND_BROWSE, "settings_button_browse", "&Browse..."),
+    (IDC_SOUND_CLEAR, "settings_button_clear", "Cl&ear"),
+    (IDC_QUIET_CHECK, "settings_checkbox_quiet_hours", "&Quiet hours"),
+    (IDC_PRON_LABEL, "settings_label_pronunciations", "Pronunciations:"),
+    (IDC_PRON_PATTERN_LABEL, "settings_label_pronunciation_pattern", "Text:"),
+    (IDC_PRON_REPLACEMENT_LABEL, "settings_label_pronunciation_replacement", "Say as:"),
+    (IDC_PRON_REGEX, "settings_checkbox_pronunciation_regex", "Regular e&xpression"),
+    (IDC_PRON_ADD, "settings_button_add", "&Add"),
+    (IDC_PRON_REMOVE, "settings_button_remove", "Re&move"),
+    (IDC_USB_DEBOUNCE_LABEL, "settings_label_usb_debounce", "USB duplicate window (ms):"),
+    (IDC_EXPORT_SETTINGS, "settings_button_export", "Expor&t settings..."),
+    (IDC_IMPORT_SETTINGS, "settings_button_import", "&Import settings..."),
+    (IDC_PROFILE_LABEL, "settings_label_profiles", "Profiles:"),
+    (IDC_PROFILE_ADD, "settings_button_add", "&Add"),
+    (IDC_PROFILE_RENAME, "settings_button_rename", "Re&name"),
+    (IDC_PROFILE_DELETE, "settings_button_delete", "&Delete"),
+    (IDC_MESSAGE_LABEL, "settings_label_announcement_text", "Edit announcement:"),
+    (IDC_MESSAGE_SET, "settings_button_set_message", "&Change"),
+    (IDC_MESSAGE_RESET, "settings_button_reset", "Re&set"),
+    (IDC_MESSAGE_SPEAK, "settings_button_speak_message", "Spea&k preview"),
+    (IDC_BATTERY_THRESHOLDS_LABEL, "settings_label_battery_thresholds", "Battery warnings at (%):"),
+    (IDC_RATE_LABEL, "settings_label_speech_rate", "Speech rate:"),
+    (IDC_VOLUME_LABEL, "settings_label_volume", "Volume:"),
+    (IDC_EVENTS_LABEL, "settings_label_events", "Announce:"),
+    (IDC_OUTPUT_LABEL, "settings_label_output_device", "&Output device:"),
+    (IDC_OPEN_CONFIG_FOLDER, "settings_button_open_config_folder", "Open config &folder"),
+    (IDC_OPEN_LOG_FILE, "settings_button_open_log_file", "Open lo&g file"),
+];
+
+/// 屏幕阅读器把控件前面的 STATIC 文字当作控件的名称。这些控件前面没有自己的标签，
+/// 所以直接设置它们的无障碍名称
+const ACCESSIBLE_NAMES: &[(i32, &str, &str)] = &[
+    (IDC_QUIET_START, "settings_name_quiet_start", "Quiet hours start"),
+    (IDC_QUIET_END, "settings_name_quiet_end", "Quiet hours end"),
+    (IDC_QUIET_MODE_COMBO, "settings_name_quiet_mode", "Quiet hours mode"),
+    (IDC_MESSAGE_PREVIEW, "settings_name_message_preview", "Current announcement"),
+    (IDC_MESSAGE_TEXT, "settings_name_message_text", "Announcement text"),
+    (IDC_PROFILE_NAME, "settings_name_profile_name", "Profile name"),
+    (IDC_USB_DEBOUNCE_SPIN, "settings_label_usb_debounce", "USB duplicate window (ms):"),
+];
+
+/// 试听改写的播报时代入参数的示例值。电量和时长按当前语言的说法生成，
+/// 表中没有的参数代入参数名称本身
+const SAMPLE_PARAMS: &[(&str, &str)] = &[
+    ("user", "Alex"),
+    ("count", "3"),
+    ("SSID", "Home"),
+    ("name", "Home"),
+    ("from", "Home"),
+    ("to", "Office"),
+    ("device", "Speakers"),
+    ("watts", "45"),
+    ("app", "Notepad"),
+    ("text", "Hello"),
+    ("minutes", "25"),
+    ("hours", "2"),
+    ("code", "1"),
+    ("driver", "Display"),
+    ("radio", "Bluetooth"),
+    ("document", "Report"),
+    ("value", "80"),
+];
+
+const QUIET_MODES: [QuietMode; 3] = [QuietMode::Silence, QuietMode::BeepOnly, QuietMode::CriticalOnly];
+
+/// 打开窗口时的设置，取消时用来撤销“应用”过的修改
+struct SettingsSnapshot {
+    config: Config,
+    locale: String,
+    voice: Option<String>,
+}
+
+/// 撤销时除了配置本身以外还要恢复的状态。语音、语速、音量和读音规则总是恢复，不在其中
+#[derive(Debug, Default, PartialEq)]
+struct RevertSteps {
+    /// 要换回的语言
+    language: Option<String>,
+    /// 要换回的输出设备，None 表示没有换过
+    output_device: Option<Option<String>>,
+    /// 电量提醒的阈值或回差改过
+    battery: bool,
+    /// 要恢复的开机自启动设置
+    auto_start: Option<bool>,
+}
+
+impl SettingsSnapshot {
+    /// 从当前的配置和语言回到快照需要做的事
+    fn revert_steps(&self, config: &Config, locale: &str) -> RevertSteps {
+        RevertSteps {
+            language: (locale != self.locale).then(|| self.locale.clone()),
+            output_device: (config.output_device != self.config.output_device).then(|| self.config.output_device.clone()),
+            battery: config.battery_thresholds != self.config.battery_thresholds || config.battery_hysteresis != self.config.battery_hysteresis,
+            auto_start: (config.auto_start != self.config.auto_start).then_some(self.config.auto_start),
+        }
+    }
+}
+
+/// apply_settings 应用的修改中，继续编辑时要作为新基准的部分
+#[derive(Default)]
+struct AppliedChanges {
+    /// 切换到的语言 (语言下拉框中的代码)
+    language: Option<String>,
+    /// 语速或音量滑块移动过
+    speech: bool,
+}
+
 static SETTINGS_CLASS_NAME: Lazy<HSTRING> = Lazy::new(|| HSTRING::from("AdvancedBeeperSettingsWindowClass"));
+// 已经打开的设置窗口 (0 表示没有)。再次打开设置时只把它调到前台
+static SETTINGS_WINDOW: AtomicIsize = AtomicIsize::new(0);
+// 打开着的对话框 (设置、关于、日志查看)。它们都是非模态的，由主消息循环分派消息，
+// 打开期间照常播报事件
+static DIALOG_WINDOWS: Mutex<Vec<isize>> = Mutex::new(Vec::new());
 
 struct SettingsWindowData {
     app_state: Arc<Mutex<AppState>>,
     h_voice_combo: HWND,
+    h_voice_preview: HWND,
     h_autostart_check: HWND,
     h_lang_combo: HWND,
+    h_ignored_list: HWND,
+    h_sound_event_combo: HWND,
+    h_sound_file_label: HWND,
+    h_quiet_check: HWND,
+    h_quiet_start: HWND,
+    h_quiet_end: HWND,
+    h_quiet_mode_combo: HWND,
+    h_pron_list: HWND,
+    h_pron_pattern: HWND,
+    h_pron_replacement: HWND,
+    h_pron_regex: HWND,
+    h_usb_debounce_edit: HWND,
+    h_battery_thresholds_edit: HWND,
+    h_rate_slider: HWND,
+    h_volume_slider: HWND,
+    h_events_list: HWND,
+    h_apply: HWND,
+    h_profile_combo: HWND,
+    h_profile_name: HWND,
+    h_message_combo: HWND,
+    h_message_text: HWND,
+    h_message_preview: HWND,
+    h_message_placeholders: HWND,
+    h_output_combo: HWND,
     h_font: HFONT,
-    available_voices_for_lang: Vec<VoiceDetail>,
+    // 窗口所在显示器的 DPI，以及各个控件在 96 DPI 下的位置和大小，移到其他 DPI 的显示器时按比例重新摆放
+    dpi: u32,
+    layout: Vec<(HWND, RECT)>,
+    // 语言下拉框中各项的语言代码
+    language_codes: Vec<String>,
+    // 打开窗口时语言下拉框选中的语言代码：与当前语言 (可能是 "zh-TW" 这样的完整标签) 最接近的一项
+    shown_language: String,
+    // 语音下拉框中列出的语音，按语言和名称排序
+    listed_voices: Vec<VoiceDetail>,
+    // 语音下拉框的第一项是已卸载的配置语音（灰色显示，不能选作新的语音）
+    missing_voice_row: bool,
+    // 接下来一项切换是否列出所有语言的语音：只列出所选语言的语音时显示“(所有语言)”，反之显示“(仅限此语言)”。
+    // 没有说所选语言的语音时总是列出全部，不显示这一项
+    voice_filter_row: bool,
+    all_voices_shown: bool,
+    // 语音下拉框中最后选中的语音，切换是否列出所有语言后仍选中它
+    chosen_voice: Option<String>,
+    // 是否正在试听，以及试听前使用的语音 id，试听结束后换回
+    previewing: bool,
+    preview_restore_voice: Option<String>,
+    // 每次试听的序号，用来忽略已经结束的试听发来的 WM_APP_PREVIEW_DONE
+    preview_serial: usize,
+    // 打开窗口时滑块的位置，点击“确定”时只写回移动过的滑块，避免把超出滑块范围的配置值改掉
+    shown_rate_position: isize,
+    shown_volume_position: isize,
+    // 拖动过滑块后语音引擎已在使用新的语速或音量，关闭窗口时要换回配置中的值
+    speech_adjusted: bool,
+    // 输出设备下拉框中第一项 "(系统默认)" 之后列出的设备名称。配置中的设备没有连接时排在最后一项，灰色显示
+    output_devices: Vec<String>,
+    missing_output_row: bool,
+    // 打开窗口时选中的一项，没有改选时“确定”不改动配置中的 output_device (可能是设备 id)
+    shown_output_row: isize,
+    // 改选过输出设备后语音引擎已在使用它，关闭窗口时要换回配置中的设备
+    output_adjusted: bool,
+    // 上次应用之后是否修改过控件，决定“应用”按钮是否可用
+    dirty: bool,
+    // 本次打开窗口期间点击过“应用”且尚未用“确定”确认：关闭窗口时按快照撤销
+    applied: bool,
+    snapshot: Option<SettingsSnapshot>,
+    // 忽略的 USB 设备，点击“确定”时才写回配置
+    ignored_device_ids: Vec<String>,
+    // 事件提示音，同样在点击“确定”时才写回配置
+    event_sounds: HashMap<String, EventSound>,
+    // 读音替换规则，同样在点击“确定”时才写回配置
+    pronunciations: Vec<Pronunciation>,
+    // 设置方案及当前方案名称，同样在点击“确定”时才写回配置
+    profiles: HashMap<String, ProfileSettings>,
+    active_profile: String,
+    // 自定义播报文字，同样在点击“确定”时才写回配置。编辑的是打开窗口时所用语言的文字
+    custom_messages: HashMap<String, String>,
+    message_keys: Vec<String>,
+    message_locale: String,
+    // 编辑框中正在编辑的条目
+    shown_message: Option<String>,
 }
 
 fn register_settings_class() {
     static REGISTER_ONCE: std::sync::Once = std::sync::Once::new();
-    REGISTER_ONCE.call_once(|| {
-        let instance = unsafe { GetModuleHandleW(None).unwrap() };
-
-        let wc = WNDCLASSW {
-            style: CS_HREDRAW | CS_VREDRAW,
-            lpfnWndProc: Some(settings_wnd_proc),
-            hInstance: instance.into(),
-            hCursor: unsafe { LoadCursorW(None, IDC_ARROW).unwrap_or_default() },
-            hbrBackground: HBRUSH(unsafe { GetStockObject(WHITE_BRUSH) }.0),
-            lpszClassName: PCWSTR((&*SETTINGS_CLASS_NAME).as_ptr()),
-            ..Default::default()
-        };
-        if unsafe { RegisterClassW(&wc) } == 0 {
-            error!("注册设置窗口类失败: {}", windows::core::Error::from_win32());
-        }
-    });
+    REGISTER_ONCE.call_once(|| register_dialog_class(&SETTINGS_CLASS_NAME, Some(settings_wnd_proc)));
 }
 
 pub fn show(parent: HWND, app_state: Arc<Mutex<AppState>>) {
+    if activate_dialog_window(&SETTINGS_WINDOW) {
+        info!("设置窗口已经打开，切换到该窗口。");
+        return;
+    }
     register_settings_class();
     let instance = unsafe { GetModuleHandleW(None).unwrap() };
 
-    let window_title = {
+    let (window_title, saved_position) = {
         let state = app_state.lock().unwrap();
-        state.i18n_manager.get_text("settings_window_title").unwrap_or_else(|| "Settings".to_string())
+        (control_text(&state.i18n_manager, "settings_window_title", "Settings"), state.config.settings_window_pos)
     };
+    let (x, y) = initial_position(saved_position);
 
     let data = Box::new(SettingsWindowData {
         app_state,
         h_voice_combo: HWND::default(),
+        h_voice_preview: HWND::default(),
         h_autostart_check: HWND::default(),
         h_lang_combo: HWND::default(),
+        h_ignored_list: HWND::default(),
+        h_sound_event_combo: HWND::default(),
+        h_sound_file_label: HWND::default(),
+        h_quiet_check: HWND::default(),
+        h_quiet_start: HWND::default(),
+        h_quiet_end: HWND::default(),
+        h_quiet_mode_combo: HWND::default(),
+        h_pron_list: HWND::default(),
+        h_pron_pattern: HWND::default(),
+        h_pron_replacement: HWND::default(),
+        h_pron_regex: HWND::default(),
+        h_usb_debounce_edit: HWND::default(),
+        h_battery_thresholds_edit: HWND::default(),
+        h_rate_slider: HWND::default(),
+        h_volume_slider: HWND::default(),
+        h_events_list: HWND::default(),
+        h_apply: HWND::default(),
+        h_profile_combo: HWND::default(),
+        h_profile_name: HWND::default(),
+        h_message_combo: HWND::default(),
+        h_message_text: HWND::default(),
+        h_message_preview: HWND::default(),
+        h_message_placeholders: HWND::default(),
+        h_output_combo: HWND::default(),
         h_font: HFONT::default(),
-        available_voices_for_lang: vec![],
+        dpi: BASE_DPI,
+        layout: vec![],
+        language_codes: vec![],
+        shown_language: String::new(),
+        listed_voices: vec![],
+        missing_voice_row: false,
+        voice_filter_row: false,
+        all_voices_shown: false,
+        chosen_voice: None,
+        previewing: false,
+        preview_restore_voice: None,
+        preview_serial: 0,
+        shown_rate_position: RATE_SLIDER_DEFAULT,
+        shown_volume_position: VOLUME_SLIDER_MAX,
+        speech_adjusted: false,
+        output_devices: vec![],
+        missing_output_row: false,
+        shown_output_row: 0,
+        output_adjusted: false,
+        dirty: false,
+        applied: false,
+        snapshot: None,
+        ignored_device_ids: vec![],
+        event_sounds: HashMap::new(),
+        pronunciations: vec![],
+        profiles: HashMap::new(),
+        active_profile: String::new(),
+        custom_messages: HashMap::new(),
+        message_keys: vec![],
+        message_locale: String::new(),
+        shown_message: None,
     });
 
     let data_ptr = Box::into_raw(data);
 
-    // 使用 match 或者 ? 来处理 Result
-    if let Err(e) = unsafe {
+    // 时间选择控件、滑块、数值调节钮和事件列表属于通用控件，需要先注册
+    let icc = INITCOMMONCONTROLSEX { dwSize: std::mem::size_of::<INITCOMMONCONTROLSEX>() as u32, dwICC: ICC_DATE_CLASSES | ICC_BAR_CLASSES | ICC_LISTVIEW_CLASSES | ICC_UPDOWN_CLASS };
+    if !unsafe { InitCommonControlsEx(&icc) }.as_bool() {
+        warn!("初始化时间选择控件、滑块和事件列表失败。");
+    }
+
+    let hwnd = match unsafe {
         CreateWindowExW(
             WS_EX_DLGMODALFRAME,
             &*SETTINGS_CLASS_NAME,
             &HSTRING::from(window_title),
             WS_CAPTION | WS_SYSMENU | WS_VISIBLE,
-            CW_USEDEFAULT, CW_USEDEFAULT, 400, 220,
+            x, y, SETTINGS_WIDTH, SETTINGS_HEIGHT,
             Some(parent),
             None,
             Some(instance.into()),
             Some(data_ptr as *mut c_void),
         )
     } {
-        error!("创建设置窗口失败: {}", e);
-        // 如果窗口创建失败，需要释放 data_ptr 以避免内存泄漏
-        unsafe { let _ = Box::from_raw(data_ptr); };
-        return;
+        Ok(hwnd) => hwnd,
+        Err(e) => {
+            error!("创建设置窗口失败: {}", e);
+            // 如果窗口创建失败，需要释放 data_ptr 以避免内存泄漏
+            unsafe { let _ = Box::from_raw(data_ptr); };
+            return;
+        }
+    };
+    register_dialog_window(&SETTINGS_WINDOW, hwnd);
+
+    // 打开窗口时焦点在语音下拉框上，可以直接用键盘操作
+    if let Ok(voice_combo) = unsafe { GetDlgItem(Some(hwnd), IDC_VOICE_COMBO) } {
+        unsafe { let _ = SetFocus(Some(voice_combo)); };
     }
-    
-    unsafe { let _ = EnableWindow(parent, false); };
-    
-    let mut msg = MSG::default();
-    
-    while unsafe { GetMessageW(&mut msg, None, 0, 0) }.as_bool() {
-        unsafe {
-            let _ = TranslateMessage(&msg);
-            DispatchMessageW(&msg);
+}
+
+// 打开设置窗口的位置：上次关闭时的位置 (仍在某个显示器上时)，否则在鼠标所在显示器的工作区居中。
+// 拔掉外接显示器后上次的位置可能已在屏幕之外
+fn initial_position(saved: Option<WindowRect>) -> (i32, i32) {
+    if let Some(saved) = saved {
+        let rect = RECT { left: saved.left, top: saved.top, right: saved.right, bottom: saved.bottom };
+        if !unsafe { MonitorFromRect(&rect, MONITOR_DEFAULTTONULL) }.is_invalid() {
+            return (saved.left, saved.top);
         }
+        info!("设置窗口上次的位置已不在任何显示器上，改为居中显示。");
     }
-    
-    unsafe { 
-        let _ = EnableWindow(parent, true);
-        SetActiveWindow(parent).ok();
+    let mut cursor = POINT::default();
+    let _ = unsafe { GetCursorPos(&mut cursor) };
+    let monitor = unsafe { MonitorFromPoint(cursor, MONITOR_DEFAULTTOPRIMARY) };
+    let mut info = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+    if !unsafe { GetMonitorInfoW(monitor, &mut info) }.as_bool() {
+        return (CW_USEDEFAULT, CW_USEDEFAULT);
+    }
+    // 窗口创建后按所在显示器的 DPI 放大，居中时要按放大后的大小计算
+    let (mut dpi_x, mut dpi_y) = (BASE_DPI, BASE_DPI);
+    let _ = unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) };
+    let work = info.rcWork;
+    let (width, height) = (scale(SETTINGS_WIDTH, dpi_x), scale(SETTINGS_HEIGHT, dpi_x));
+    (work.left + (work.right - work.left - width).max(0) / 2, work.top + (work.bottom - work.top - height).max(0) / 2)
+}
+
+// 关闭窗口时记下窗口位置，下次打开时放回原处
+fn save_window_position(hwnd: HWND, data: &SettingsWindowData) {
+    let mut rect = RECT::default();
+    if unsafe { GetWindowRect(hwnd, &mut rect) }.is_err() { return; }
+    let position = WindowRect { left: rect.left, top: rect.top, right: rect.right, bottom: rect.bottom };
+    let mut app_state = data.app_state.lock().unwrap();
+    if app_state.config.settings_window_pos == Some(position) { return; }
+    app_state.config.settings_window_pos = Some(position);
+    if let Err(e) = app_state.config.save() {
+        error!("保存设置窗口位置失败: {}", e);
     }
 }
 
@@ -130,35 +524,49 @@ extern "system" fn settings_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lpara
 
             let data = unsafe { &mut *data_ptr };
 
-            let font_name = w!("Microsoft YaHei UI");
-            data.h_font = unsafe {
-                CreateFontW(
-                    -15,                // nHeight
-                    0,                  // nWidth
-                    0,                  // nEscapement
-                    0,                  // nOrientation
-                    400,                // --- 核心修复: 直接使用整数 400 替代 FW_NORMAL.0 ---
-                    0,                  // fdwItalic
-                    0,                  // fdwUnderline
-                    0,                  // fdwStrikeOut
-                    DEFAULT_CHARSET,    // fdwCharSet
-                    OUT_DEFAULT_PRECIS, // fdwOutputPrecision
-                    CLIP_DEFAULT_PRECIS,// fdwClipPrecision
-                    DEFAULT_QUALITY,    // fdwQuality
-                    FF_DONTCARE.0.into(),   // fdwPitchAndFamily
-                    font_name,          // pszFaceName
-                )
-            };
-
-            if data.h_font.is_invalid() {
-                warn!("创建 'Microsoft YaHei UI' 字体失败, 回退到系统默认字体。");
-                data.h_font = HFONT(unsafe { GetStockObject(DEFAULT_GUI_FONT) }.0);
-            }
+            data.dpi = window_dpi(hwnd);
+            data.h_font = create_dialog_font(data.dpi);
 
             create_controls(hwnd, data);
+            data.layout = record_layout(hwnd);
+            fit_to_dpi(hwnd, &data.layout, SETTINGS_WIDTH, SETTINGS_HEIGHT, data.dpi);
+            scale_list_metrics(data);
             initialize_controls(data);
+            // 填入初始值时编辑框等控件也会发出修改通知
+            set_dirty(data, false);
+            LRESULT(0)
+        }
+        // IsDialogMessageW 询问默认按钮：按 Enter 时触发“确定”
+        DM_GETDEFID => LRESULT(((DC_HASDEFID as isize) << 16) | IDOK as isize),
+        // 语音和输出设备下拉框由自己绘制，以便把已卸载的语音和没有连接的设备显示为灰色
+        WM_MEASUREITEM => {
+            let measure = unsafe { &mut *(lparam.0 as *mut MEASUREITEMSTRUCT) };
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut SettingsWindowData };
+            if !matches!(measure.CtlID as i32, IDC_VOICE_COMBO | IDC_OUTPUT_COMBO) || data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
+            measure.itemHeight = scale(VOICE_ITEM_HEIGHT, unsafe { &*data_ptr }.dpi) as u32;
+            LRESULT(1)
+        }
+        // 窗口移到 DPI 不同的显示器：换用新大小的字体，重新摆放控件，再采用系统建议的窗口位置和大小
+        WM_DPICHANGED => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut SettingsWindowData };
+            if data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
+            let data = unsafe { &mut *data_ptr };
+            data.dpi = handle_dpi_changed(hwnd, wparam, lparam, &data.layout, &mut data.h_font);
+            scale_list_metrics(data);
             LRESULT(0)
         }
+        WM_DRAWITEM => {
+            let draw = unsafe { &*(lparam.0 as *const DRAWITEMSTRUCT) };
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut SettingsWindowData };
+            if data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
+            let data = unsafe { &*data_ptr };
+            match draw.CtlID as i32 {
+                IDC_VOICE_COMBO => draw_combo_item(data.h_voice_combo, data.missing_voice_row && draw.itemID == 0, draw),
+                IDC_OUTPUT_COMBO => draw_combo_item(data.h_output_combo, data.missing_output_row && draw.itemID as usize == data.output_devices.len() + 1, draw),
+                _ => return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+            }
+            LRESULT(1)
+        }
         WM_COMMAND => {
             let id = (wparam.0 as u16) as i32;
             let event = wparam.0 >> 16; // 获取通知代码 (高位字)
@@ -166,17 +574,79 @@ extern "system" fn settings_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lpara
             if data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
             let data = unsafe { &mut *data_ptr };
 
+            // 切换是否列出所有语言的语音不算修改设置，在标记修改之前处理
+            if id == IDC_VOICE_COMBO && event as u32 == CBN_SELCHANGE && handle_voice_selection_change(data) {
+                return LRESULT(0);
+            }
+            if is_setting_change(id, event as u32) {
+                set_dirty(data, true);
+            }
             if id == IDC_LANG_COMBO && event as u32 == CBN_SELCHANGE {
                 info!("检测到语言下拉框选择发生变化");
                 handle_language_selection_change(data);
                 return LRESULT(0);
             }
+            if id == IDC_SOUND_EVENT_COMBO && event as u32 == CBN_SELCHANGE {
+                update_sound_file_label(data);
+                return LRESULT(0);
+            }
+            if id == IDC_PROFILE_COMBO && event as u32 == CBN_SELCHANGE {
+                show_selected_profile(data);
+                return LRESULT(0);
+            }
+            if id == IDC_MESSAGE_COMBO && event as u32 == CBN_SELCHANGE {
+                // 编辑框中的文字有误时留在原来的条目上，不丢掉用户输入的文字
+                if check_edited_message(hwnd, data) {
+                    show_selected_message(data);
+                } else {
+                    let shown = data.shown_message.as_ref().and_then(|key| data.message_keys.iter().position(|k| k == key)).unwrap_or(0);
+                    unsafe { SendMessageW(data.h_message_combo, CB_SETCURSEL, Some(WPARAM(shown)), Some(LPARAM(0))) };
+                }
+                return LRESULT(0);
+            }
+            if id == IDC_OUTPUT_COMBO && event as u32 == CBN_SELCHANGE {
+                preview_output_device(data);
+                return LRESULT(0);
+            }
+            if id == IDC_QUIET_CHECK && event as u32 == BN_CLICKED {
+                update_quiet_controls(data);
+                return LRESULT(0);
+            }
+            if id == IDC_PRON_LIST && event as u32 == LBN_SELCHANGE {
+                show_selected_pronunciation(data);
+                return LRESULT(0);
+            }
 
             match id {
-                IDOK => {
-                    save_settings(data);
+                IDC_VOICE_PREVIEW => preview_selected_voice(hwnd, data),
+                IDC_IGNORED_REMOVE => remove_selected_ignored_device(data),
+                IDC_SOUND_BROWSE => browse_event_sound(hwnd, data),
+                IDC_SOUND_CLEAR => clear_event_sound(data),
+                IDC_PRON_ADD => add_pronunciation(hwnd, data),
+                IDC_PRON_REMOVE => remove_selected_pronunciation(data),
+                IDC_PROFILE_ADD => add_profile(hwnd, data),
+                IDC_PROFILE_RENAME => rename_selected_profile(hwnd, data),
+                IDC_PROFILE_DELETE => delete_selected_profile(data),
+                IDC_MESSAGE_SET => set_edited_message(hwnd, data),
+                IDC_MESSAGE_RESET => reset_selected_message(data),
+                IDC_MESSAGE_SPEAK => speak_edited_message(data),
+                IDC_EXPORT_SETTINGS => export_settings(hwnd, data),
+                IDC_OPEN_CONFIG_FOLDER => open_config_folder(hwnd, data),
+                IDC_OPEN_LOG_FILE => open_log_file(hwnd, data),
+                IDC_IMPORT_SETTINGS if import_settings(hwnd, data) => {
+                    // 导入的设置取代了快照之后应用的一切，不再撤销
+                    data.applied = false;
                     unsafe { PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0)).ok() };
                 }
+                IDC_APPLY => apply_and_continue(hwnd, data),
+                IDOK => {
+                    // 先换回试听前的语音，再保存选中的语音
+                    end_voice_preview(data);
+                    if apply_settings(hwnd, data).is_some() {
+                        data.applied = false;
+                        unsafe { PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0)).ok() };
+                    }
+                }
                 IDCANCEL => {
                     unsafe { PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0)).ok() };
                 }
@@ -184,6 +654,45 @@ extern "system" fn settings_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lpara
             }
             LRESULT(0)
         }
+        // 松开滑块 (鼠标或键盘) 时立即应用新的语速或音量，并用它试听
+        WM_HSCROLL => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut SettingsWindowData };
+            if !data_ptr.is_null() && (wparam.0 as u16) as u32 == TB_ENDTRACK {
+                let data = unsafe { &mut *data_ptr };
+                set_dirty(data, true);
+                apply_speech_sliders(data);
+                preview_selected_voice(hwnd, data);
+            }
+            LRESULT(0)
+        }
+        // 事件列表的复选框和时间选择控件通过 WM_NOTIFY 报告修改
+        WM_NOTIFY => {
+            let header = unsafe { &*(lparam.0 as *const NMHDR) };
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut SettingsWindowData };
+            if data_ptr.is_null() { return unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }; }
+            let changed = match header.code {
+                LVN_ITEMCHANGED => {
+                    let change = unsafe { &*(lparam.0 as *const NMLISTVIEW) };
+                    change.uChanged.contains(LVIF_STATE) && (change.uNewState ^ change.uOldState) & LVIS_STATEIMAGEMASK.0 != 0
+                }
+                DTN_DATETIMECHANGE => true,
+                _ => false,
+            };
+            if changed {
+                set_dirty(unsafe { &mut *data_ptr }, true);
+            }
+            LRESULT(0)
+        }
+        WM_APP_PREVIEW_DONE => {
+            let data_ptr = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut SettingsWindowData };
+            if !data_ptr.is_null() {
+                let data = unsafe { &mut *data_ptr };
+                if wparam.0 == data.preview_serial {
+                    end_voice_preview(data);
+                }
+            }
+            LRESULT(0)
+        }
         WM_CLOSE => {
             unsafe { DestroyWindow(hwnd).ok() };
             LRESULT(0)
@@ -191,35 +700,298 @@ extern "system" fn settings_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lpara
         WM_DESTROY => {
             let data_ptr = unsafe { SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0) as *mut SettingsWindowData };
             if !data_ptr.is_null() {
-                let data = unsafe { Box::from_raw(data_ptr) };
-                
-                let default_font = HFONT(unsafe { GetStockObject(DEFAULT_GUI_FONT) }.0);
-                if !data.h_font.is_invalid() && data.h_font != default_font {
-                    unsafe { let _ = DeleteObject(data.h_font.into()); };
+                let mut data = unsafe { Box::from_raw(data_ptr) };
+                // 取消或关闭窗口时试听还没播完：语音引擎回到原来的语音
+                end_voice_preview(&mut data);
+                if data.applied {
+                    revert_settings(hwnd, &data);
+                }
+                // 没有保存时语速和音量回到原来的值；已保存时配置中就是新的值
+                if data.speech_adjusted {
+                    let app_state = data.app_state.lock().unwrap();
+                    app_state.tts_engine.set_rate(app_state.config.speech_rate);
+                    app_state.tts_engine.set_volume(app_state.config.volume);
                 }
+                if data.output_adjusted {
+                    let app_state = data.app_state.lock().unwrap();
+                    app_state.tts_engine.set_output_device(app_state.config.output_device.as_deref());
+                }
+                save_window_position(hwnd, &data);
+
+                delete_dialog_font(data.h_font);
             }
-            unsafe { PostQuitMessage(0) };
+            unregister_dialog_window(&SETTINGS_WINDOW, hwnd);
             LRESULT(0)
         }
         _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
     }
 }
 
+/// 注册对话框式窗口的窗口类 (白色背景、箭头光标)，设置窗口、“关于”窗口和日志查看窗口共用
+pub(crate) fn register_dialog_class(class_name: &HSTRING, wnd_proc: WNDPROC) {
+    let instance = unsafe { GetModuleHandleW(None).unwrap() };
+    let wc = WNDCLASSW {
+        style: CS_HREDRAW | CS_VREDRAW,
+        lpfnWndProc: wnd_proc,
+        hInstance: instance.into(),
+        hCursor: unsafe { LoadCursorW(None, IDC_ARROW).unwrap_or_default() },
+        hbrBackground: HBRUSH(unsafe { GetStockObject(WHITE_BRUSH) }.0),
+        lpszClassName: PCWSTR(class_name.as_ptr()),
+        ..Default::default()
+    };
+    if unsafe { RegisterClassW(&wc) } == 0 {
+        error!("注册窗口类 {} 失败: {}", class_name, windows::core::Error::from_win32());
+    }
+}
+
+/// 对话框创建后登记：主消息循环为它处理键盘导航，slot 记下这种对话框唯一打开的窗口
+pub(crate) fn register_dialog_window(slot: &AtomicIsize, hwnd: HWND) {
+    slot.store(hwnd.0 as isize, Ordering::SeqCst);
+    DIALOG_WINDOWS.lock().unwrap().push(hwnd.0 as isize);
+}
+
+/// 对话框在 WM_DESTROY 中取消登记
+pub(crate) fn unregister_dialog_window(slot: &AtomicIsize, hwnd: HWND) {
+    slot.store(0, Ordering::SeqCst);
+    DIALOG_WINDOWS.lock().unwrap().retain(|&open| open != hwnd.0 as isize);
+}
+
+/// 这种对话框已经打开时把它调到前台并返回 true，调用者不再新建窗口
+pub(crate) fn activate_dialog_window(slot: &AtomicIsize) -> bool {
+    let existing = slot.load(Ordering::SeqCst);
+    if existing == 0 { return false; }
+    unsafe { let _ = SetForegroundWindow(HWND(existing as *mut c_void)); };
+    true
+}
+
+/// 主消息循环对每条消息调用：属于某个打开着的对话框并已由 IsDialogMessageW 处理时返回 true。
+/// IsDialogMessageW 处理 Tab 切换控件、标签上的快捷键，以及 Enter (确定) 和 Esc (取消)
+pub(crate) fn is_dialog_message(msg: &MSG) -> bool {
+    // 先复制一份：处理消息时对话框可能关闭并取消登记
+    let windows = DIALOG_WINDOWS.lock().unwrap().clone();
+    windows.into_iter().any(|hwnd| unsafe { IsDialogMessageW(HWND(hwnd as *mut c_void), msg) }.as_bool())
+}
+
+/// 把 96 DPI 下的尺寸换算到指定 DPI
+pub(crate) fn scale(value: i32, dpi: u32) -> i32 {
+    value * dpi as i32 / BASE_DPI as i32
+}
+
+/// 窗口所在显示器的 DPI
+pub(crate) fn window_dpi(hwnd: HWND) -> u32 {
+    match unsafe { GetDpiForWindow(hwnd) } {
+        0 => BASE_DPI,
+        dpi => dpi,
+    }
+}
+
+/// 对话框使用的字体，高度按 DPI 放大。创建失败时返回系统默认字体
+pub(crate) fn create_dialog_font(dpi: u32) -> HFONT {
+    let font = unsafe {
+        CreateFontW(
+            -scale(FONT_HEIGHT, dpi), // nHeight
+            0,                  // nWidth
+            0,                  // nEscapement
+            0,                  // nOrientation
+            400,                // --- 核心修复: 直接使用整数 400 替代 FW_NORMAL.0 ---
+            0,                  // fdwItalic
+            0,                  // fdwUnderline
+            0,                  // fdwStrikeOut
+            DEFAULT_CHARSET,    // fdwCharSet
+            OUT_DEFAULT_PRECIS, // fdwOutputPrecision
+            CLIP_DEFAULT_PRECIS,// fdwClipPrecision
+            DEFAULT_QUALITY,    // fdwQuality
+            FF_DONTCARE.0.into(),   // fdwPitchAndFamily
+            w!("Microsoft YaHei UI"), // pszFaceName
+        )
+    };
+    if font.is_invalid() {
+        warn!("创建 'Microsoft YaHei UI' 字体失败, 回退到系统默认字体。");
+        return HFONT(unsafe { GetStockObject(DEFAULT_GUI_FONT) }.0);
+    }
+    font
+}
+
+/// 释放 create_dialog_font 创建的字体，系统默认字体不释放
+pub(crate) fn delete_dialog_font(font: HFONT) {
+    let default_font = HFONT(unsafe { GetStockObject(DEFAULT_GUI_FONT) }.0);
+    if !font.is_invalid() && font != default_font {
+        unsafe { let _ = DeleteObject(font.into()); };
+    }
+}
+
+/// 记下刚按 96 DPI 创建的各个控件的位置和大小，DPI 改变时按比例重新摆放。
+/// 下拉框记录展开时的大小，否则重新摆放后下拉列表只剩一行高
+pub(crate) fn record_layout(parent: HWND) -> Vec<(HWND, RECT)> {
+    let mut layout = Vec::new();
+    let mut child = unsafe { GetWindow(parent, GW_CHILD) };
+    while let Ok(control) = child {
+        let mut class = [0u16; 16];
+        let len = unsafe { GetClassNameW(control, &mut class) } as usize;
+        let mut rect = RECT::default();
+        if String::from_utf16_lossy(&class[..len]).eq_ignore_ascii_case("ComboBox") {
+            unsafe { SendMessageW(control, CB_GETDROPPEDCONTROLRECT, None, Some(LPARAM(&mut rect as *mut RECT as isize))) };
+        } else {
+            unsafe { let _ = GetWindowRect(control, &mut rect); };
+        }
+        let mut corners = [POINT { x: rect.left, y: rect.top }, POINT { x: rect.right, y: rect.bottom }];
+        unsafe { MapWindowPoints(None, Some(parent), &mut corners) };
+        layout.push((control, RECT { left: corners[0].x, top: corners[0].y, right: corners[1].x, bottom: corners[1].y }));
+        child = unsafe { GetWindow(control, GW_HWNDNEXT) };
+    }
+    layout
+}
+
+fn move_controls(layout: &[(HWND, RECT)], dpi: u32) {
+    for (control, rect) in layout {
+        unsafe { let _ = MoveWindow(*control, scale(rect.left, dpi), scale(rect.top, dpi), scale(rect.right - rect.left, dpi), scale(rect.bottom - rect.top, dpi), true); };
+    }
+}
+
+/// 刚创建的窗口不在 96 DPI 的显示器上时，按比例放大窗口 (96 DPI 下为 width x height) 和其中的控件
+pub(crate) fn fit_to_dpi(hwnd: HWND, layout: &[(HWND, RECT)], width: i32, height: i32, dpi: u32) {
+    if dpi == BASE_DPI { return; }
+    move_controls(layout, dpi);
+    unsafe { let _ = SetWindowPos(hwnd, None, 0, 0, scale(width, dpi), scale(height, dpi), SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE); };
+}
+
+/// 处理 WM_DPICHANGED：换用新大小的字体，按比例重新摆放控件，再采用系统建议的窗口位置和大小。返回新的 DPI
+pub(crate) fn handle_dpi_changed(hwnd: HWND, wparam: WPARAM, lparam: LPARAM, layout: &[(HWND, RECT)], font: &mut HFONT) -> u32 {
+    let dpi = (wparam.0 & 0xFFFF) as u32;
+    let old_font = *font;
+    *font = create_dialog_font(dpi);
+    for (control, _) in layout {
+        unsafe { SendMessageW(*control, WM_SETFONT, Some(WPARAM(font.0 as usize)), Some(LPARAM(1))) };
+    }
+    delete_dialog_font(old_font);
+    move_controls(layout, dpi);
+    let suggested = unsafe { &*(lparam.0 as *const RECT) };
+    unsafe { let _ = SetWindowPos(hwnd, None, suggested.left, suggested.top, suggested.right - suggested.left, suggested.bottom - suggested.top, SWP_NOZORDER | SWP_NOACTIVATE); };
+    dpi
+}
+
+// 事件列表的列宽和语音下拉框的行高不随控件大小变化，要单独按 DPI 设置
+fn scale_list_metrics(data: &SettingsWindowData) {
+    let dpi = data.dpi;
+    unsafe {
+        SendMessageW(data.h_events_list, LVM_SETCOLUMNWIDTH, Some(WPARAM(0)), Some(LPARAM(scale(EVENTS_COLUMN_WIDTH, dpi) as isize)));
+        // wparam 为 -1 时设置下拉框本身 (选中项显示区域) 的高度，0 时设置列表中各项的高度
+        SendMessageW(data.h_voice_combo, CB_SETITEMHEIGHT, Some(WPARAM(usize::MAX)), Some(LPARAM(scale(VOICE_ITEM_HEIGHT, dpi) as isize)));
+        SendMessageW(data.h_voice_combo, CB_SETITEMHEIGHT, Some(WPARAM(0)), Some(LPARAM(scale(VOICE_ITEM_HEIGHT, dpi) as isize)));
+        SendMessageW(data.h_output_combo, CB_SETITEMHEIGHT, Some(WPARAM(usize::MAX)), Some(LPARAM(scale(VOICE_ITEM_HEIGHT, dpi) as isize)));
+        SendMessageW(data.h_output_combo, CB_SETITEMHEIGHT, Some(WPARAM(0)), Some(LPARAM(scale(VOICE_ITEM_HEIGHT, dpi) as isize)));
+    }
+}
+
+pub(crate) fn control_text(i18n: &I18nManager, key: &str, fallback: &str) -> String {
+    i18n.get_text(key).unwrap_or_else(|| fallback.to_string())
+}
+
+pub(crate) fn engine_status_text(i18n: &I18nManager, health: EngineHealth) -> String {
+    let (key, fallback) = match health {
+        EngineHealth::Ok => ("engine_health_ok", "OK"),
+        EngineHealth::Recovering => ("engine_health_recovering", "recovering"),
+        EngineHealth::Failed => ("engine_health_failed", "failed"),
+    };
+    let health = control_text(i18n, key, fallback);
+    i18n.get_text_with_param("settings_label_engine_status", "status", &health)
+        .unwrap_or_else(|| format!("Speech engine: {}", health))
+}
+
+/// 按 QUIET_MODES 的顺序填入安静时段模式的名称，并选中第 selected 项
+fn fill_quiet_modes(combo: HWND, i18n: &I18nManager, selected: usize) {
+    unsafe { SendMessageW(combo, CB_RESETCONTENT, Some(WPARAM(0)), Some(LPARAM(0))); }
+    for (key, fallback) in [("settings_quiet_mode_silence", "Silence"), ("settings_quiet_mode_beep_only", "Beep only"), ("settings_quiet_mode_critical_only", "Critical only")] {
+        let h_name = HSTRING::from(control_text(i18n, key, fallback));
+        unsafe { SendMessageW(combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_name.as_ptr() as isize))); }
+    }
+    unsafe { SendMessageW(combo, CB_SETCURSEL, Some(WPARAM(selected)), Some(LPARAM(0))); }
+}
+
+/// 用 IAccPropServices 设置 ACCESSIBLE_NAMES 中各控件的名称 (讲述人、NVDA 通过 MSAA 和 UI Automation 读取)
+fn set_accessible_names(hwnd: HWND, i18n: &I18nManager) {
+    let services: IAccPropServices = match unsafe { CoCreateInstance(&CAccPropServices, None, CLSCTX_INPROC_SERVER) } {
+        Ok(services) => services,
+        Err(e) => {
+            warn!("无法设置控件的无障碍名称: {}", e);
+            return;
+        }
+    };
+    for (id, key, fallback) in ACCESSIBLE_NAMES {
+        let Ok(control) = (unsafe { GetDlgItem(Some(hwnd), *id) }) else { continue };
+        let name = HSTRING::from(control_text(i18n, key, fallback));
+        if let Err(e) = unsafe { services.SetHwndPropStr(control, OBJID_CLIENT.0 as u32, CHILDID_SELF, PROPID_ACC_NAME, &name) } {
+            warn!("设置控件 {} 的无障碍名称失败: {}", id, e);
+        }
+    }
+}
+
+/// 在编辑框下方列出可用的参数，同时作为编辑框的无障碍描述，屏幕阅读器在编辑框中也能读出
+fn show_placeholders(label: HWND, edit: HWND, text: &str) {
+    unsafe { SetWindowTextW(label, &HSTRING::from(text)).ok() };
+    let services: IAccPropServices = match unsafe { CoCreateInstance(&CAccPropServices, None, CLSCTX_INPROC_SERVER) } {
+        Ok(services) => services,
+        Err(e) => {
+            warn!("无法设置播报文字编辑框的无障碍描述: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = unsafe { services.SetHwndPropStr(edit, OBJID_CLIENT.0 as u32, CHILDID_SELF, PROPID_ACC_DESCRIPTION, &HSTRING::from(text)) } {
+        warn!("设置播报文字编辑框的无障碍描述失败: {}", e);
+    }
+}
+
+/// 条目可以使用的参数，例如 "可用参数: {level}, {count}"
+fn placeholders_text(i18n: &I18nManager, key: &str) -> String {
+    let names = i18n.placeholders_of(key);
+    if names.is_empty() {
+        return control_text(i18n, "settings_message_no_placeholders", "No placeholders.");
+    }
+    let list = names.iter().map(|name| format!("{{{}}}", name)).collect::<Vec<_>>().join(", ");
+    i18n.get_text_with_param("settings_message_placeholders", "list", &list)
+        .unwrap_or_else(|| format!("Placeholders: {}", list))
+}
+
+/// 语言在设置窗口打开期间改变时 (例如 config.json 被外部修改)，把窗口中的文字换成新的语言。
+/// 调用者持有 AppState 的锁，所以这里只使用传入的参数。设置窗口没有打开时什么也不做
+pub fn refresh_texts(i18n: &I18nManager, engine_health: EngineHealth) {
+    let Ok(hwnd) = (unsafe { FindWindowW(&*SETTINGS_CLASS_NAME, PCWSTR::null()) }) else { return };
+    unsafe {
+        SetWindowTextW(hwnd, &HSTRING::from(control_text(i18n, "settings_window_title", "Settings"))).ok();
+        for (id, key, fallback) in CONTROL_TEXTS {
+            SetDlgItemTextW(hwnd, *id, &HSTRING::from(control_text(i18n, key, fallback))).ok();
+        }
+        SetDlgItemTextW(hwnd, IDC_ENGINE_STATUS, &HSTRING::from(engine_status_text(i18n, engine_health))).ok();
+    }
+    set_accessible_names(hwnd, i18n);
+    if let (Ok(combo), Ok(label), Ok(edit)) = unsafe { (GetDlgItem(Some(hwnd), IDC_MESSAGE_COMBO), GetDlgItem(Some(hwnd), IDC_MESSAGE_PLACEHOLDERS), GetDlgItem(Some(hwnd), IDC_MESSAGE_TEXT)) } {
+        // 播报条目的下拉框中显示的是条目名称
+        show_placeholders(label, edit, &placeholders_text(i18n, &window_text(combo)));
+    }
+    if let Ok(combo) = unsafe { GetDlgItem(Some(hwnd), IDC_QUIET_MODE_COMBO) } {
+        let selected = unsafe { SendMessageW(combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+        fill_quiet_modes(combo, i18n, usize::try_from(selected).unwrap_or(0));
+    }
+    if let Ok(list) = unsafe { GetDlgItem(Some(hwnd), IDC_EVENTS_LIST) } {
+        for (index, name) in TOGGLEABLE_EVENT_NAMES.iter().enumerate() {
+            set_event_label(list, index, &event_label(i18n, name));
+        }
+    }
+}
+
 fn create_controls(parent: HWND, data: &mut SettingsWindowData) {
     let instance = unsafe { GetModuleHandleW(None).unwrap() };
     let h_font = data.h_font;
     
-    let (lbl_voice, lbl_lang, chk_autostart, btn_ok, btn_cancel) = {
+    let (texts, lbl_engine) = {
         let app_state = data.app_state.lock().unwrap();
         let i18n = &app_state.i18n_manager;
-        (
-            i18n.get_text("settings_label_voice").unwrap_or_else(|| "Voice:".to_string()),
-            i18n.get_text("settings_label_language").unwrap_or_else(|| "Language:".to_string()),
-            i18n.get_text("settings_checkbox_autostart").unwrap_or_else(|| "Start with Windows".to_string()),
-            i18n.get_text("settings_button_ok").unwrap_or_else(|| "OK".to_string()),
-            i18n.get_text("settings_button_cancel").unwrap_or_else(|| "Cancel".to_string()),
-        )
+        let texts: HashMap<i32, HSTRING> = CONTROL_TEXTS.iter()
+            .map(|(id, key, fallback)| (*id, HSTRING::from(control_text(i18n, key, fallback))))
+            .collect();
+        (texts, engine_status_text(i18n, app_state.engine_health))
     };
+    let text = |id: i32| &texts[&id];
 
     unsafe {
         let set_font = |hwnd: HWND| {
@@ -229,48 +1001,248 @@ fn create_controls(parent: HWND, data: &mut SettingsWindowData) {
             }
         };
 
+        // 每个可以用 Tab 到达的控件各自成组 (WS_GROUP)，方向键不会把焦点从复选框或按钮移到旁边的控件。
+        // 没有文字的控件紧跟在它的 STATIC 标签之后创建，屏幕阅读器据此读出控件的名称
         // --- 语音选择 (Voice) ---
-        let h_voice_label = CreateWindowExW(Default::default(), w!("STATIC"), &HSTRING::from(lbl_voice), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 20, 80, 25, Some(parent), Some(HMENU((IDC_VOICE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        let h_voice_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_VOICE_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 20, 80, 25, Some(parent), Some(HMENU((IDC_VOICE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(h_voice_label);
         
-        data.h_voice_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | (CBS_DROPDOWNLIST as u32) | WS_VSCROLL.0), 100, 20, 250, 200, Some(parent), Some(HMENU((IDC_VOICE_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        data.h_voice_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32) | (CBS_OWNERDRAWFIXED as u32) | (CBS_HASSTRINGS as u32) | WS_VSCROLL.0), 100, 20, 160, 200, Some(parent), Some(HMENU((IDC_VOICE_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(data.h_voice_combo);
 
+        data.h_voice_preview = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_VOICE_PREVIEW), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 270, 18, 80, 28, Some(parent), Some(HMENU((IDC_VOICE_PREVIEW as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_voice_preview);
+
         // --- 语言选择 (Language) ---
-        let h_lang_label = CreateWindowExW(Default::default(), w!("STATIC"), &HSTRING::from(lbl_lang), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 70, 80, 25, Some(parent), Some(HMENU((IDC_LANG_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        let h_lang_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_LANG_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 70, 80, 25, Some(parent), Some(HMENU((IDC_LANG_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(h_lang_label);
 
-        data.h_lang_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | (CBS_DROPDOWNLIST as u32)), 100, 70, 250, 100, Some(parent), Some(HMENU((IDC_LANG_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        data.h_lang_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32)), 100, 70, 250, 100, Some(parent), Some(HMENU((IDC_LANG_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(data.h_lang_combo);
 
         // --- 开机自启动 (Start with Windows) ---
-        data.h_autostart_check = CreateWindowExW(Default::default(), w!("BUTTON"), &HSTRING::from(chk_autostart), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | (BS_AUTOCHECKBOX as u32)), 20, 110, 200, 25, Some(parent), Some(HMENU((IDC_AUTOSTART_CHECK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        data.h_autostart_check = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_AUTOSTART_CHECK), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (BS_AUTOCHECKBOX as u32)), 20, 110, 200, 25, Some(parent), Some(HMENU((IDC_AUTOSTART_CHECK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(data.h_autostart_check);
 
+        // --- 忽略的 USB 设备 (Ignored devices) ---
+        let h_ignored_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_IGNORED_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 145, 330, 20, Some(parent), Some(HMENU((IDC_IGNORED_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_ignored_label);
+
+        data.h_ignored_list = CreateWindowExW(Default::default(), w!("LISTBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | WS_VSCROLL.0 | WS_HSCROLL.0 | (LBS_NOINTEGRALHEIGHT as u32)), 20, 170, 330, 70, Some(parent), Some(HMENU((IDC_IGNORED_LIST as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_ignored_list);
+
+        let h_remove_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_IGNORED_REMOVE), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 250, 245, 100, 28, Some(parent), Some(HMENU((IDC_IGNORED_REMOVE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_remove_btn);
+
+        // --- 事件提示音 ---
+        let h_sound_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_SOUND_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 285, 330, 20, Some(parent), Some(HMENU((IDC_SOUND_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_sound_label);
+
+        data.h_sound_event_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32) | WS_VSCROLL.0), 20, 310, 330, 200, Some(parent), Some(HMENU((IDC_SOUND_EVENT_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_sound_event_combo);
+
+        data.h_sound_file_label = CreateWindowExW(Default::default(), w!("STATIC"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 345, 130, 20, Some(parent), Some(HMENU((IDC_SOUND_FILE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_sound_file_label);
+
+        let h_browse_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_SOUND_BROWSE), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 160, 340, 90, 28, Some(parent), Some(HMENU((IDC_SOUND_BROWSE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_browse_btn);
+
+        let h_clear_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_SOUND_CLEAR), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 260, 340, 90, 28, Some(parent), Some(HMENU((IDC_SOUND_CLEAR as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_clear_btn);
+
+        // --- 安静时段 ---
+        data.h_quiet_check = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_QUIET_CHECK), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (BS_AUTOCHECKBOX as u32)), 20, 385, 120, 25, Some(parent), Some(HMENU((IDC_QUIET_CHECK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_quiet_check);
+
+        data.h_quiet_start = CreateWindowExW(Default::default(), DATETIMEPICK_CLASSW, None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | DTS_TIMEFORMAT | DTS_UPDOWN), 150, 385, 95, 25, Some(parent), Some(HMENU((IDC_QUIET_START as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_quiet_start);
+
+        data.h_quiet_end = CreateWindowExW(Default::default(), DATETIMEPICK_CLASSW, None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | DTS_TIMEFORMAT | DTS_UPDOWN), 255, 385, 95, 25, Some(parent), Some(HMENU((IDC_QUIET_END as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_quiet_end);
+
+        data.h_quiet_mode_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32)), 150, 420, 200, 100, Some(parent), Some(HMENU((IDC_QUIET_MODE_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_quiet_mode_combo);
+
+        // --- 语音引擎状态 ---
+        let h_engine_label = CreateWindowExW(Default::default(), w!("STATIC"), &HSTRING::from(lbl_engine), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 455, 330, 20, Some(parent), Some(HMENU((IDC_ENGINE_STATUS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_engine_label);
+
+        // --- 自定义播报文字 ---
+        let h_message_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_MESSAGE_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 485, 330, 20, Some(parent), Some(HMENU((IDC_MESSAGE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_message_label);
+
+        data.h_message_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32) | WS_VSCROLL.0), 20, 510, 330, 250, Some(parent), Some(HMENU((IDC_MESSAGE_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_message_combo);
+
+        data.h_message_preview = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_AUTOHSCROLL as u32) | (ES_READONLY as u32)), 20, 542, 330, 25, Some(parent), Some(HMENU((IDC_MESSAGE_PREVIEW as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_message_preview);
+
+        data.h_message_text = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_AUTOHSCROLL as u32)), 20, 574, 330, 25, Some(parent), Some(HMENU((IDC_MESSAGE_TEXT as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_message_text);
+
+        data.h_message_placeholders = CreateWindowExW(Default::default(), w!("STATIC"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 604, 330, 20, Some(parent), Some(HMENU((IDC_MESSAGE_PLACEHOLDERS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_message_placeholders);
+
+        let h_message_set_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_MESSAGE_SET), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 20, 628, 90, 28, Some(parent), Some(HMENU((IDC_MESSAGE_SET as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_message_set_btn);
+
+        let h_message_reset_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_MESSAGE_RESET), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 120, 628, 90, 28, Some(parent), Some(HMENU((IDC_MESSAGE_RESET as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_message_reset_btn);
+
+        let h_message_speak_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_MESSAGE_SPEAK), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 220, 628, 130, 28, Some(parent), Some(HMENU((IDC_MESSAGE_SPEAK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_message_speak_btn);
+
+        // --- 语速和音量 ---
+        let h_rate_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_RATE_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 675, 100, 25, Some(parent), Some(HMENU((IDC_RATE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_rate_label);
+
+        data.h_rate_slider = CreateWindowExW(Default::default(), TRACKBAR_CLASSW, None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | TBS_HORZ | TBS_AUTOTICKS), 120, 670, 230, 30, Some(parent), Some(HMENU((IDC_RATE_SLIDER as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_rate_slider);
+
+        let h_volume_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_VOLUME_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 20, 715, 100, 25, Some(parent), Some(HMENU((IDC_VOLUME_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_volume_label);
+
+        data.h_volume_slider = CreateWindowExW(Default::default(), TRACKBAR_CLASSW, None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | TBS_HORZ | TBS_AUTOTICKS), 120, 710, 230, 30, Some(parent), Some(HMENU((IDC_VOLUME_SLIDER as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_volume_slider);
+
+        // --- 读音替换 (右侧一栏) ---
+        let h_pron_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_PRON_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 20, 330, 20, Some(parent), Some(HMENU((IDC_PRON_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_pron_label);
+
+        data.h_pron_list = CreateWindowExW(Default::default(), w!("LISTBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | WS_VSCROLL.0 | (LBS_NOINTEGRALHEIGHT as u32) | (LBS_NOTIFY as u32)), 380, 45, 330, 150, Some(parent), Some(HMENU((IDC_PRON_LIST as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_pron_list);
+
+        let h_pattern_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_PRON_PATTERN_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 208, 80, 25, Some(parent), Some(HMENU((IDC_PRON_PATTERN_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_pattern_label);
+
+        data.h_pron_pattern = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_AUTOHSCROLL as u32)), 460, 205, 250, 25, Some(parent), Some(HMENU((IDC_PRON_PATTERN as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_pron_pattern);
+
+        let h_replacement_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_PRON_REPLACEMENT_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 243, 80, 25, Some(parent), Some(HMENU((IDC_PRON_REPLACEMENT_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_replacement_label);
+
+        data.h_pron_replacement = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_AUTOHSCROLL as u32)), 460, 240, 250, 25, Some(parent), Some(HMENU((IDC_PRON_REPLACEMENT as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_pron_replacement);
+
+        data.h_pron_regex = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_PRON_REGEX), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (BS_AUTOCHECKBOX as u32)), 380, 275, 330, 25, Some(parent), Some(HMENU((IDC_PRON_REGEX as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_pron_regex);
+
+        let h_pron_add_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_PRON_ADD), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 500, 305, 100, 28, Some(parent), Some(HMENU((IDC_PRON_ADD as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_pron_add_btn);
+
+        let h_pron_remove_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_PRON_REMOVE), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 610, 305, 100, 28, Some(parent), Some(HMENU((IDC_PRON_REMOVE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_pron_remove_btn);
+
+        // --- USB 重复通知的时间窗口 ---
+        let h_usb_debounce_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_USB_DEBOUNCE_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 353, 250, 25, Some(parent), Some(HMENU((IDC_USB_DEBOUNCE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_usb_debounce_label);
+
+        data.h_usb_debounce_edit = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_NUMBER as u32)), 640, 350, 70, 25, Some(parent), Some(HMENU((IDC_USB_DEBOUNCE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_usb_debounce_edit);
+
+        // 数值调节钮附在左边的输入框上 (UDS_AUTOBUDDY)，每按一次加减 100 毫秒，按住两秒后每次 1000 毫秒
+        let h_usb_debounce_spin = CreateWindowExW(Default::default(), UPDOWN_CLASSW, None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | UDS_AUTOBUDDY | UDS_SETBUDDYINT | UDS_ALIGNRIGHT | UDS_ARROWKEYS | UDS_NOTHOUSANDS), 0, 0, 0, 0, Some(parent), Some(HMENU((IDC_USB_DEBOUNCE_SPIN as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        SendMessageW(h_usb_debounce_spin, UDM_SETRANGE32, Some(WPARAM(*USB_WINDOW_MS_RANGE.start() as usize)), Some(LPARAM(*USB_WINDOW_MS_RANGE.end() as isize)));
+        let accels = [UDACCEL { nSec: 0, nInc: 100 }, UDACCEL { nSec: 2, nInc: 1000 }];
+        SendMessageW(h_usb_debounce_spin, UDM_SETACCEL, Some(WPARAM(accels.len())), Some(LPARAM(accels.as_ptr() as isize)));
+
+        // --- 电量提醒阈值 ---
+        let h_battery_thresholds_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_BATTERY_THRESHOLDS_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 388, 210, 25, Some(parent), Some(HMENU((IDC_BATTERY_THRESHOLDS_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_battery_thresholds_label);
+
+        data.h_battery_thresholds_edit = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_AUTOHSCROLL as u32)), 590, 385, 120, 25, Some(parent), Some(HMENU((IDC_BATTERY_THRESHOLDS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_battery_thresholds_edit);
+
+        // --- 导出 / 导入设置 ---
+        let h_export_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_EXPORT_SETTINGS), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 380, 420, 160, 28, Some(parent), Some(HMENU((IDC_EXPORT_SETTINGS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_export_btn);
+
+        let h_import_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_IMPORT_SETTINGS), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 550, 420, 160, 28, Some(parent), Some(HMENU((IDC_IMPORT_SETTINGS as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_import_btn);
+
+        // --- 设置方案 ---
+        let h_profile_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_PROFILE_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 468, 70, 25, Some(parent), Some(HMENU((IDC_PROFILE_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_profile_label);
+
+        data.h_profile_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32) | WS_VSCROLL.0), 455, 465, 255, 200, Some(parent), Some(HMENU((IDC_PROFILE_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_profile_combo);
+
+        data.h_profile_name = CreateWindowExW(Default::default(), w!("EDIT"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | (ES_AUTOHSCROLL as u32)), 380, 502, 110, 25, Some(parent), Some(HMENU((IDC_PROFILE_NAME as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_profile_name);
+
+        let h_profile_add_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_PROFILE_ADD), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 495, 500, 70, 28, Some(parent), Some(HMENU((IDC_PROFILE_ADD as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_profile_add_btn);
+
+        let h_profile_rename_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_PROFILE_RENAME), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 570, 500, 70, 28, Some(parent), Some(HMENU((IDC_PROFILE_RENAME as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_profile_rename_btn);
+
+        let h_profile_delete_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_PROFILE_DELETE), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 645, 500, 65, 28, Some(parent), Some(HMENU((IDC_PROFILE_DELETE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_profile_delete_btn);
+
+        // --- 音频输出设备 ---
+        let h_output_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_OUTPUT_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 380, 548, 330, 20, Some(parent), Some(HMENU((IDC_OUTPUT_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_output_label);
+
+        data.h_output_combo = CreateWindowExW(Default::default(), w!("COMBOBOX"), None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (CBS_DROPDOWNLIST as u32) | (CBS_OWNERDRAWFIXED as u32) | (CBS_HASSTRINGS as u32) | WS_VSCROLL.0), 380, 573, 330, 200, Some(parent), Some(HMENU((IDC_OUTPUT_COMBO as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_output_combo);
+
+        // --- 打开配置文件夹 / 日志文件 ---
+        let h_open_config_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_OPEN_CONFIG_FOLDER), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 380, 615, 160, 28, Some(parent), Some(HMENU((IDC_OPEN_CONFIG_FOLDER as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_open_config_btn);
+
+        let h_open_log_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_OPEN_LOG_FILE), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 550, 615, 160, 28, Some(parent), Some(HMENU((IDC_OPEN_LOG_FILE as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_open_log_btn);
+
+        // --- 事件开关 (第三栏) ---
+        let h_events_label = CreateWindowExW(Default::default(), w!("STATIC"), text(IDC_EVENTS_LABEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | SS_LEFT.0), 740, 20, 230, 20, Some(parent), Some(HMENU((IDC_EVENTS_LABEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(h_events_label);
+
+        data.h_events_list = CreateWindowExW(Default::default(), WC_LISTVIEWW, None, WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | WS_BORDER.0 | LVS_REPORT | LVS_NOCOLUMNHEADER | LVS_SINGLESEL | LVS_SHOWSELALWAYS), 740, 45, 230, 565, Some(parent), Some(HMENU((IDC_EVENTS_LIST as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_events_list);
+        SendMessageW(data.h_events_list, LVM_SETEXTENDEDLISTVIEWSTYLE, Some(WPARAM((LVS_EX_CHECKBOXES | LVS_EX_FULLROWSELECT) as usize)), Some(LPARAM((LVS_EX_CHECKBOXES | LVS_EX_FULLROWSELECT) as isize)));
+        let column = LVCOLUMNW { mask: LVCF_WIDTH, cx: scale(EVENTS_COLUMN_WIDTH, data.dpi), ..Default::default() };
+        SendMessageW(data.h_events_list, LVM_INSERTCOLUMNW, Some(WPARAM(0)), Some(LPARAM(&column as *const _ as isize)));
+
         // --- 按钮 ---
-        let h_ok_btn = CreateWindowExW(Default::default(), w!("BUTTON"), &HSTRING::from(btn_ok), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | (BS_DEFPUSHBUTTON as u32)), 120, 150, 100, 30, Some(parent), Some(HMENU((IDOK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        let h_ok_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDOK), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0 | (BS_DEFPUSHBUTTON as u32)), 650, 710, 100, 30, Some(parent), Some(HMENU((IDOK as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(h_ok_btn);
         
-        let h_cancel_btn = CreateWindowExW(Default::default(), w!("BUTTON"), &HSTRING::from(btn_cancel), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0), 240, 150, 100, 30, Some(parent), Some(HMENU((IDCANCEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        let h_cancel_btn = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDCANCEL), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 760, 710, 100, 30, Some(parent), Some(HMENU((IDCANCEL as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
         set_font(h_cancel_btn);
+
+        data.h_apply = CreateWindowExW(Default::default(), w!("BUTTON"), text(IDC_APPLY), WINDOW_STYLE(WS_CHILD.0 | WS_VISIBLE.0 | WS_TABSTOP.0 | WS_GROUP.0), 870, 710, 100, 30, Some(parent), Some(HMENU((IDC_APPLY as isize) as *mut c_void)), Some(instance.into()), None).unwrap();
+        set_font(data.h_apply);
     }
+    set_accessible_names(parent, &data.app_state.lock().unwrap().i18n_manager);
 }
 
 fn initialize_controls(data: &mut SettingsWindowData) {
     {
-        let app_state = data.app_state.lock().unwrap(); 
+        // 打开设置时重新读取已安装的语音，期间可能安装或卸载了语音包
+        let mut app_state = data.app_state.lock().unwrap();
+        if let Some((name, fallback)) = crate::check_configured_voice(&mut app_state) {
+            if let Some(text) = crate::voice_missing_text(&app_state.i18n_manager, &name, &fallback) {
+                if let Err(e) = app_state.speech.speak(&text) {
+                    error!("播报语音缺失提示失败: {}", e);
+                }
+            }
+        }
         let config = &app_state.config;
+        data.snapshot = Some(SettingsSnapshot {
+            config: config.clone(),
+            locale: app_state.i18n_manager.locale().to_string(),
+            voice: app_state.tts_engine.voice().map(|v| v.id),
+        });
 
         // --- 初始化语言下拉框 ---
-        let supported_langs = vec![("en", "English"), ("zh", "简体中文"), ("ja", "日本語")];
-        let mut lang_selected_index = 0;
-        for (i, (code, display_name)) in supported_langs.iter().enumerate() {
-            let h_name = HSTRING::from(*display_name);
+        let supported_langs = crate::i18n::available_locales();
+        for (_, display_name) in &supported_langs {
+            let h_name = HSTRING::from(display_name.as_str());
             unsafe { SendMessageW(data.h_lang_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_name.as_ptr() as isize))); }
-            if config.language.as_deref() == Some(*code) {
-                lang_selected_index = i;
-            }
         }
+        data.language_codes = supported_langs.into_iter().map(|(code, _)| code).collect();
+        let lang_selected_index = crate::i18n::closest_locale(app_state.i18n_manager.locale(), &data.language_codes).unwrap_or(0);
+        data.shown_language = data.language_codes.get(lang_selected_index).cloned().unwrap_or_default();
         unsafe { SendMessageW(data.h_lang_combo, CB_SETCURSEL, Some(WPARAM(lang_selected_index)), Some(LPARAM(0))); }
 
         // --- 初始化自启动复选框 ---
@@ -283,160 +1255,1310 @@ fn initialize_controls(data: &mut SettingsWindowData) {
             );
         }
 
-        // --- 准备填充语音下拉框所需的数据 ---
-        let voices = &app_state.available_voices;
-        let selected_lang_code = config.language.as_deref().unwrap_or("en");
-        data.available_voices_for_lang = voices.iter()
-            .filter(|v| v.language.starts_with(selected_lang_code))
-            .cloned()
-            .collect();
+        unsafe { SetWindowTextW(data.h_usb_debounce_edit, &HSTRING::from(config.usb_debounce_ms.to_string())).ok(); }
+        let thresholds: Vec<String> = config.battery_thresholds.iter().map(u8::to_string).collect();
+        unsafe { SetWindowTextW(data.h_battery_thresholds_edit, &HSTRING::from(thresholds.join(", "))).ok(); }
 
-    } 
-    populate_voice_combo(data);
-}
+        // --- 初始化语速和音量滑块 ---
+        data.shown_rate_position = rate_to_position(config.speech_rate);
+        data.shown_volume_position = volume_to_position(config.volume);
+        init_slider(data.h_rate_slider, RATE_SLIDER_MIN, RATE_SLIDER_MAX, 5, data.shown_rate_position);
+        init_slider(data.h_volume_slider, 0, VOLUME_SLIDER_MAX, 10, data.shown_volume_position);
 
-// --- 新增: 填充语音下拉框的函数 ---
-fn populate_voice_combo(data: &mut SettingsWindowData) {
-    unsafe {
-        SendMessageW(data.h_voice_combo, CB_RESETCONTENT, None, None); // 清空下拉框
-    }
-    
-    if data.available_voices_for_lang.is_empty() {
-        let unavailable_msg = HSTRING::from("<Unavailable>");
-        unsafe { SendMessageW(data.h_voice_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(unavailable_msg.as_ptr() as isize))); }
-        unsafe { SendMessageW(data.h_voice_combo, CB_SETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))); }
-    } else {
-        let custom_voice_name = {
-            let app_state = data.app_state.lock().unwrap();
-            app_state.config.custom_voice.clone()
-        }; 
+        // --- 初始化事件开关 (没有列在 enabled_events 中的事件照常播报) ---
+        for (index, name) in TOGGLEABLE_EVENT_NAMES.iter().enumerate() {
+            let h_label = HSTRING::from(event_label(&app_state.i18n_manager, name));
+            let item = LVITEMW { mask: LVIF_TEXT, iItem: index as i32, pszText: PWSTR(h_label.as_ptr() as *mut u16), ..Default::default() };
+            unsafe { SendMessageW(data.h_events_list, LVM_INSERTITEMW, Some(WPARAM(0)), Some(LPARAM(&item as *const _ as isize))); }
+            set_event_checked(data.h_events_list, index, config.event_enabled(name));
+        }
 
+        // --- 初始化设置方案 ---
+        data.profiles = config.profiles.clone();
+        data.active_profile = config.active_profile.clone();
 
-        let mut selected_index: usize = 0;
-        for (i, voice) in data.available_voices_for_lang.iter().enumerate() {
-            let display_text = format!("{} ({})", voice.name, voice.language);
-            let h_display_text = HSTRING::from(display_text.as_str());
-            
-            unsafe { SendMessageW(data.h_voice_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_display_text.as_ptr() as isize))); }
+        // --- 初始化自定义播报文字 ---
+        data.custom_messages = config.custom_messages.clone();
+        data.message_locale = app_state.i18n_manager.locale().to_string();
+        data.message_keys = app_state.i18n_manager.announcement_keys();
+        for key in &data.message_keys {
+            let h_key = HSTRING::from(key.as_str());
+            unsafe { SendMessageW(data.h_message_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_key.as_ptr() as isize))); }
+        }
+        unsafe { SendMessageW(data.h_message_combo, CB_SETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))); }
 
-            if custom_voice_name.as_deref() == Some(&voice.name) {
-                selected_index = i;
-            }
+        // --- 初始化忽略设备列表 ---
+        data.ignored_device_ids = config.ignored_device_ids.clone();
+        for id in &data.ignored_device_ids {
+            let h_id = HSTRING::from(id.as_str());
+            unsafe { SendMessageW(data.h_ignored_list, LB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_id.as_ptr() as isize))); }
+        }
+        // 设备实例 ID 较长，允许横向滚动查看
+        unsafe { SendMessageW(data.h_ignored_list, LB_SETHORIZONTALEXTENT, Some(WPARAM(800)), Some(LPARAM(0))); }
+
+        // --- 初始化事件提示音 ---
+        data.event_sounds = config.event_sounds.clone();
+        for name in SOUND_EVENT_NAMES {
+            let h_name = HSTRING::from(*name);
+            unsafe { SendMessageW(data.h_sound_event_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_name.as_ptr() as isize))); }
+        }
+        unsafe { SendMessageW(data.h_sound_event_combo, CB_SETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))); }
+
+        // --- 初始化读音替换列表 ---
+        data.pronunciations = config.pronunciations.clone();
+        for entry in &data.pronunciations {
+            let h_entry = HSTRING::from(pronunciation_list_text(entry));
+            unsafe { SendMessageW(data.h_pron_list, LB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_entry.as_ptr() as isize))); }
+        }
+
+        // --- 初始化安静时段 (未设置时显示 22:00 - 07:00 作为建议值)。开始和结束相同的时段是空的，显示为未开启 ---
+        let quiet_hours = config.quiet_hours.as_ref();
+        let (start, end) = quiet_hours.and_then(QuietHours::minutes).unwrap_or((22 * 60, 7 * 60));
+        set_picker_minutes(data.h_quiet_start, start);
+        set_picker_minutes(data.h_quiet_end, end);
+        let quiet_enabled = quiet_hours.is_some_and(|q| !matches!(q.minutes(), Some((start, end)) if start == end));
+        unsafe {
+            SendMessageW(data.h_quiet_check, BM_SETCHECK, Some(WPARAM(if quiet_enabled { BST_CHECKED.0 as usize } else { BST_UNCHECKED.0 as usize })), Some(LPARAM(0)));
         }
-        unsafe { SendMessageW(data.h_voice_combo, CB_SETCURSEL, Some(WPARAM(selected_index)), Some(LPARAM(0))); }
+        let mode_index = QUIET_MODES.iter().position(|mode| Some(*mode) == quiet_hours.map(|q| q.mode)).unwrap_or(0);
+        fill_quiet_modes(data.h_quiet_mode_combo, &app_state.i18n_manager, mode_index);
+        update_quiet_controls(data);
     }
+    let lang_code = data.shown_language.clone();
+    let preferred = preferred_voice(data, &lang_code);
+    populate_voice_combo(data, &lang_code, preferred);
+    update_sound_file_label(data);
+    populate_profile_combo(data, "");
+    show_selected_message(data);
+    populate_output_combo(data);
 }
 
-// --- 新增: 处理语言选择变化的函数 ---
-fn handle_language_selection_change(data: &mut SettingsWindowData) {
-    let lang_index = unsafe { SendMessageW(data.h_lang_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as i32;
-    let lang_codes = ["en", "zh", "ja"];
-    
-    if lang_index >= 0 && (lang_index as usize) < lang_codes.len() {
-        let selected_lang_code = lang_codes[lang_index as usize];
+/// 该语言上次使用的语音，其次是当前的自定义语音。语音说其他语言时同时列出所有语言的语音，以便选中它
+fn preferred_voice(data: &mut SettingsWindowData, lang_code: &str) -> Option<String> {
+    let app_state = data.app_state.lock().unwrap();
+    let voices = &app_state.available_voices;
+    let preferred = app_state.config.remembered_voice(lang_code, voices.iter().map(|v| v.id.as_str()))
+        .or_else(|| app_state.config.custom_voice.clone());
+    data.all_voices_shown = preferred.as_ref()
+        .and_then(|id| voices.iter().find(|v| &v.id == id))
+        .is_some_and(|voice| !voice.speaks(lang_code));
+    preferred
+}
 
+/// 填充语音下拉框并选中 `preferred`；它不在列表中时选中第一个语音
+fn populate_voice_combo(data: &mut SettingsWindowData, lang_code: &str, preferred: Option<String>) {
+    unsafe {
+        SendMessageW(data.h_voice_combo, CB_RESETCONTENT, None, None); // 清空下拉框
+    }
+    let add_row = |text: &str| {
+        let h_text = HSTRING::from(text);
+        unsafe { SendMessageW(data.h_voice_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_text.as_ptr() as isize))); }
+    };
+
+    let app_state = data.app_state.lock().unwrap();
+    let i18n = &app_state.i18n_manager;
+    // 当前语言配置的语音已被卸载时，在最前面显示它并选中，而不是悄悄选中第一个语音
+    let not_installed = control_text(i18n, "settings_voice_not_installed", "(not installed)");
+    let missing_voice = app_state.missing_voice.as_ref()
+        .filter(|_| data.shown_language == lang_code)
+        .map(|(_, name)| format!("{} {}", name, not_installed));
+    data.missing_voice_row = missing_voice.is_some();
+    if let Some(text) = missing_voice {
+        add_row(&text);
+    }
+
+    let mut voices: Vec<(String, VoiceDetail)> = app_state.available_voices.iter()
+        .map(|voice| (language_display_name(&voice.language), voice.clone()))
+        .collect();
+    voices.sort_by(|(a_language, a), (b_language, b)| a_language.cmp(b_language).then_with(|| a.name.cmp(&b.name)));
+    data.voice_filter_row = voices.iter().any(|(_, voice)| voice.speaks(lang_code));
+    if data.voice_filter_row {
+        let (key, fallback) = if data.all_voices_shown {
+            ("settings_voice_this_language", "(This language only)")
+        } else {
+            ("settings_voice_all_languages", "(All languages)")
+        };
+        add_row(&control_text(i18n, key, fallback));
+    }
+    let show_all = data.all_voices_shown || !data.voice_filter_row;
+    voices.retain(|(_, voice)| show_all || voice.speaks(lang_code));
+
+    if voices.is_empty() {
+        if !data.missing_voice_row {
+            add_row("<Unavailable>");
+        }
+        data.listed_voices.clear();
+        data.chosen_voice = None;
+        unsafe { SendMessageW(data.h_voice_combo, CB_SETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))); }
+        return;
+    }
+    // 同名的语音靠语言和性别区分
+    for (language, voice) in &voices {
+        let gender = voice.gender.map(|gender| match gender {
+            Gender::Male => control_text(i18n, "settings_voice_gender_male", "male"),
+            Gender::Female => control_text(i18n, "settings_voice_gender_female", "female"),
+        });
+        match gender {
+            Some(gender) => add_row(&format!("{} - {}, {}", voice.name, language, gender)),
+            None => add_row(&format!("{} - {}", voice.name, language)),
+        }
+    }
+    drop(app_state);
+
+    data.listed_voices = voices.into_iter().map(|(_, voice)| voice).collect();
+    let offset = voice_row_offset(data);
+    let selected_index = match data.listed_voices.iter().position(|v| Some(&v.id) == preferred.as_ref()) {
+        Some(index) => index + offset,
+        // 配置的语音已卸载时选中它那一行
+        None if data.missing_voice_row => 0,
+        None => offset,
+    };
+    unsafe { SendMessageW(data.h_voice_combo, CB_SETCURSEL, Some(WPARAM(selected_index)), Some(LPARAM(0))); }
+    data.chosen_voice = selected_voice(data).map(|voice| voice.id.clone()).or(preferred);
+}
+
+/// 语音下拉框中第一个语音之前的行数 (已卸载的语音、切换是否列出所有语言)
+fn voice_row_offset(data: &SettingsWindowData) -> usize {
+    usize::from(data.missing_voice_row) + usize::from(data.voice_filter_row)
+}
+
+/// 语音下拉框的选择变化。选中切换所有语言那一项时重新填充下拉框并返回 true，这不算修改设置
+fn handle_voice_selection_change(data: &mut SettingsWindowData) -> bool {
+    let index = unsafe { SendMessageW(data.h_voice_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    if !data.voice_filter_row || index != isize::from(data.missing_voice_row) {
+        if let Some(voice) = selected_voice(data) {
+            data.chosen_voice = Some(voice.id.clone());
+        }
+        return false;
+    }
+    data.all_voices_shown = !data.all_voices_shown;
+    let lang_code = selected_language(data).unwrap_or(&data.shown_language).to_string();
+    let chosen = data.chosen_voice.clone();
+    populate_voice_combo(data, &lang_code, chosen);
+    true
+}
+
+/// 语言代码的显示名称 (Windows 界面语言)，例如 "en-US" -> "English (United States)"。
+/// 通过 LCID 查询，Windows 不认识的代码显示代码本身
+fn language_display_name(tag: &str) -> String {
+    let lcid = unsafe { LocaleNameToLCID(&HSTRING::from(tag), 0) };
+    if lcid == 0 { return tag.to_string(); }
+    let mut buffer = [0u16; 128];
+    let len = unsafe { GetLocaleInfoW(lcid, LOCALE_SLOCALIZEDDISPLAYNAME, Some(&mut buffer)) };
+    if len <= 1 { return tag.to_string(); }
+    String::from_utf16_lossy(&buffer[..len as usize - 1])
+}
+
+/// 绘制语音下拉框的一项，已卸载的语音显示为灰色。
+fn draw_combo_item(combo: HWND, missing: bool, draw: &DRAWITEMSTRUCT) {
+    if draw.itemID == u32::MAX { return; } // 下拉框为空时没有可绘制的项
+    let selected = (draw.itemState.0 & ODS_SELECTED.0) != 0;
+    let len = unsafe { SendMessageW(combo, CB_GETLBTEXTLEN, Some(WPARAM(draw.itemID as usize)), None) }.0;
+    if len < 0 { return; }
+    let mut text = vec![0u16; len as usize + 1];
+    unsafe { SendMessageW(combo, CB_GETLBTEXT, Some(WPARAM(draw.itemID as usize)), Some(LPARAM(text.as_mut_ptr() as isize))); }
+    text.truncate(len as usize);
+
+    let (background, foreground) = match (selected, missing) {
+        (true, _) => (COLOR_HIGHLIGHT, COLOR_HIGHLIGHTTEXT),
+        (false, true) => (COLOR_WINDOW, COLOR_GRAYTEXT),
+        (false, false) => (COLOR_WINDOW, COLOR_WINDOWTEXT),
+    };
+    let mut rect = draw.rcItem;
+    unsafe {
+        FillRect(draw.hDC, &rect, GetSysColorBrush(background));
+        SetTextColor(draw.hDC, COLORREF(GetSysColor(foreground)));
+        SetBkMode(draw.hDC, TRANSPARENT);
+        rect.left += 3;
+        DrawTextW(draw.hDC, &mut text, &mut rect, DT_SINGLELINE | DT_VCENTER | DT_NOPREFIX);
+    }
+}
+
+// --- 新增: 处理语言选择变化的函数 ---
+fn handle_language_selection_change(data: &mut SettingsWindowData) {
+    let lang_index = unsafe { SendMessageW(data.h_lang_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as i32;
+    let lang_codes = data.language_codes.clone();
+
+    if lang_index >= 0 && (lang_index as usize) < lang_codes.len() {
+        let selected_lang_code = lang_codes[lang_index as usize].as_str();
+        // 重新填充语音下拉框，选中该语言记住的语音
+        let preferred = preferred_voice(data, selected_lang_code);
+        populate_voice_combo(data, selected_lang_code, preferred);
+    }
+}
+
+/// 事件开关在语言档案中的条目，例如 "PowerSwitchedToAC" -> "settings_event_power_switched_to_ac"
+fn event_label_key(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut key = String::from("settings_event_");
+    for (i, c) in chars.iter().enumerate() {
+        let word_start = i > 0 && c.is_uppercase()
+            && (chars[i - 1].is_lowercase() || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+        if word_start { key.push('_'); }
+        key.push(c.to_ascii_lowercase());
+    }
+    key
+}
+
+/// 事件列表中显示的名称，语言档案中没有时显示事件名称本身
+fn event_label(i18n: &I18nManager, name: &str) -> String {
+    control_text(i18n, &event_label_key(name), name)
+}
+
+fn set_event_label(list: HWND, index: usize, label: &str) {
+    let h_label = HSTRING::from(label);
+    let item = LVITEMW { iSubItem: 0, pszText: PWSTR(h_label.as_ptr() as *mut u16), ..Default::default() };
+    unsafe { SendMessageW(list, LVM_SETITEMTEXTW, Some(WPARAM(index)), Some(LPARAM(&item as *const _ as isize))); }
+}
+
+fn set_event_checked(list: HWND, index: usize, checked: bool) {
+    let state = if checked { LIST_CHECKED } else { LIST_UNCHECKED };
+    let item = LVITEMW { stateMask: LVIS_STATEIMAGEMASK, state: LIST_VIEW_ITEM_STATE_FLAGS(state), ..Default::default() };
+    unsafe { SendMessageW(list, LVM_SETITEMSTATE, Some(WPARAM(index)), Some(LPARAM(&item as *const _ as isize))); }
+}
+
+fn event_checked(list: HWND, index: usize) -> bool {
+    let state = unsafe { SendMessageW(list, LVM_GETITEMSTATE, Some(WPARAM(index)), Some(LPARAM(LVIS_STATEIMAGEMASK.0 as isize))) }.0 as u32;
+    state & LVIS_STATEIMAGEMASK.0 == LIST_CHECKED
+}
+
+fn rate_to_position(rate: Option<f32>) -> isize {
+    rate.map_or(RATE_SLIDER_DEFAULT, |rate| (rate * 10.0).round() as isize).clamp(RATE_SLIDER_MIN, RATE_SLIDER_MAX)
+}
+
+fn position_to_rate(position: isize) -> f32 {
+    position as f32 / 10.0
+}
+
+fn volume_to_position(volume: Option<f32>) -> isize {
+    volume.map_or(VOLUME_SLIDER_MAX, |volume| (volume * 100.0).round() as isize).clamp(0, VOLUME_SLIDER_MAX)
+}
+
+fn position_to_volume(position: isize) -> f32 {
+    position as f32 / 100.0
+}
+
+/// 设置滑块的范围、刻度间隔和位置
+fn init_slider(slider: HWND, min: isize, max: isize, tick_every: usize, position: isize) {
+    unsafe {
+        SendMessageW(slider, TBM_SETRANGEMIN, Some(WPARAM(0)), Some(LPARAM(min)));
+        SendMessageW(slider, TBM_SETRANGEMAX, Some(WPARAM(0)), Some(LPARAM(max)));
+        SendMessageW(slider, TBM_SETTICFREQ, Some(WPARAM(tick_every)), Some(LPARAM(0)));
+        SendMessageW(slider, TBM_SETPOS, Some(WPARAM(1)), Some(LPARAM(position)));
+    }
+}
+
+fn slider_position(slider: HWND) -> isize {
+    unsafe { SendMessageW(slider, TBM_GETPOS, Some(WPARAM(0)), Some(LPARAM(0))) }.0
+}
+
+/// 把滑块上的语速和音量交给语音引擎，不写入配置。取消时由 WM_DESTROY 换回配置中的值
+fn apply_speech_sliders(data: &mut SettingsWindowData) {
+    let rate = position_to_rate(slider_position(data.h_rate_slider));
+    let volume = position_to_volume(slider_position(data.h_volume_slider));
+    let app_state = data.app_state.lock().unwrap();
+    app_state.tts_engine.set_rate(Some(rate));
+    app_state.tts_engine.set_volume(Some(volume));
+    data.speech_adjusted = true;
+}
+
+/// 语言下拉框中选中的语言代码
+fn selected_language(data: &SettingsWindowData) -> Option<&str> {
+    let index = unsafe { SendMessageW(data.h_lang_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    usize::try_from(index).ok().and_then(|i| data.language_codes.get(i)).map(String::as_str)
+}
+
+/// 语音下拉框中选中的语音；选中的是已卸载语音或切换所有语言那一行时为 None
+fn selected_voice(data: &SettingsWindowData) -> Option<&VoiceDetail> {
+    let index = unsafe { SendMessageW(data.h_voice_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    let index = usize::try_from(index).ok()?.checked_sub(voice_row_offset(data))?;
+    data.listed_voices.get(index)
+}
+
+/// 填入输出设备下拉框："(系统默认)"、当前连接的设备，以及没有连接的已配置设备 (灰色，附注“已断开”)，
+/// 并选中配置中的设备。配置中的名称或 id 与设备相符时都算找到
+fn populate_output_combo(data: &mut SettingsWindowData) {
+    // 枚举设备可能要等一会儿，不持有 AppState 的锁
+    let devices = TtsEngine::list_output_devices().unwrap_or_else(|e| {
+        warn!("设置窗口: 获取音频输出设备失败: {}", e);
+        vec![]
+    });
+    let app_state = data.app_state.lock().unwrap();
+    let i18n = &app_state.i18n_manager;
+    let configured = app_state.config.output_device.as_deref();
+    let mut rows = vec![control_text(i18n, "settings_output_default", "(System default)")];
+    rows.extend(devices.iter().map(|device| device.name.clone()));
+    let found = configured.and_then(|name| devices.iter().position(|d| d.name.eq_ignore_ascii_case(name) || d.id == name));
+    data.output_devices = devices.into_iter().map(|device| device.name).collect();
+    data.missing_output_row = configured.is_some() && found.is_none();
+    if let Some(name) = configured.filter(|_| data.missing_output_row) {
+        rows.push(i18n.get_text_with_param("settings_output_disconnected", "name", name)
+            .unwrap_or_else(|| format!("{} (disconnected)", name)));
+    }
+    drop(app_state);
+
+    for row in &rows {
+        let h_row = HSTRING::from(row.as_str());
+        unsafe { SendMessageW(data.h_output_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_row.as_ptr() as isize))); }
+    }
+    data.shown_output_row = match found {
+        Some(index) => index as isize + 1,
+        None if data.missing_output_row => rows.len() as isize - 1,
+        None => 0,
+    };
+    unsafe { SendMessageW(data.h_output_combo, CB_SETCURSEL, Some(WPARAM(data.shown_output_row as usize)), Some(LPARAM(0))); }
+}
+
+/// 输出设备下拉框中选中的设备名称，None 表示系统默认设备。选中灰色的一项时是配置中没有连接的设备
+fn selected_output_device(data: &SettingsWindowData) -> Option<String> {
+    let index = unsafe { SendMessageW(data.h_output_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    let index = usize::try_from(index).ok()?.checked_sub(1)?;
+    if let Some(name) = data.output_devices.get(index) {
+        return Some(name.clone());
+    }
+    data.missing_output_row.then(|| data.app_state.lock().unwrap().config.output_device.clone()).flatten()
+}
+
+/// 改选输出设备：语音引擎立即改用它并播报一句示例，听得出是哪个扬声器。
+/// “确定”或“应用”时才写入配置，没有保存就关闭窗口时换回配置中的设备
+fn preview_output_device(data: &mut SettingsWindowData) {
+    let device = selected_output_device(data);
+    let app_state = data.app_state.lock().unwrap();
+    info!("设置窗口: 试听输出设备 {:?}", device);
+    app_state.tts_engine.set_output_device(device.as_deref());
+    let i18n = &app_state.i18n_manager;
+    let sample = match &device {
+        Some(name) => i18n.get_text_with_param("settings_output_sample", "device", name)
+            .unwrap_or_else(|| format!("Announcements will play on {}.", name)),
+        None => control_text(i18n, "settings_output_sample_default", "Announcements will play on the default device."),
+    };
+    if let Err(e) = app_state.speech.speak_user_triggered(&sample) {
+        error!("设置窗口: 播报输出设备示例失败: {}", e);
+    }
+    drop(app_state);
+    data.output_adjusted = true;
+}
+
+/// “试听”按钮：用语音下拉框中选中的语音播报一句示例，不保存任何设置。选中的是已卸载语音那一行时用当前语音。
+/// 播报前临时切换语音，播完后 (WM_APP_PREVIEW_DONE) 由 end_voice_preview 换回原来的语音。
+/// 松开语速或音量滑块时也调用，这时可能已经在试听
+fn preview_selected_voice(hwnd: HWND, data: &mut SettingsWindowData) {
+    let voice = selected_voice(data).cloned();
+    let language = selected_language(data).map(str::to_string);
+
+    let mut guard = data.app_state.lock().unwrap();
+    let app_state = &mut *guard;
+    // 连续试听时记住的仍是第一次试听前的语音
+    if !data.previewing {
+        data.preview_restore_voice = app_state.tts_engine.voice().map(|v| v.id);
+    }
+    // 示例句子使用下拉框中选中的语言，这样试听的是该语音实际要读的语言
+    let sentence = language.filter(|code| code.as_str() != app_state.i18n_manager.locale())
+        .and_then(|code| I18nManager::new(&code).ok())
+        .and_then(|i18n| i18n.get_text("settings_preview_sentence"))
+        .unwrap_or_else(|| control_text(&app_state.i18n_manager, "settings_preview_sentence", "This is how announcements will sound."));
+    if let Some(voice) = &voice {
+        if let Err(e) = app_state.tts_engine.set_voice(&voice.id) {
+            error!("设置窗口: 切换到试听的语音失败: {}", e);
+            return;
+        }
+        info!("设置窗口: 试听语音 '{}'", voice.name);
+    }
+    let spoken = app_state.speech.speak_user_triggered(&sentence).map(|id| app_state.speech.waiter(id));
+    drop(guard);
+
+    data.previewing = true;
+    let waiter = match spoken {
+        Ok(waiter) => waiter,
+        Err(e) => {
+            error!("设置窗口: 播报试听句子失败: {}", e);
+            end_voice_preview(data);
+            return;
+        }
+    };
+    data.preview_serial += 1;
+    unsafe { let _ = EnableWindow(data.h_voice_preview, false); };
+
+    let serial = data.preview_serial;
+    let hwnd_value = hwnd.0 as isize;
+    std::thread::spawn(move || {
+        if !waiter.wait(PREVIEW_TIMEOUT) {
+            warn!("试听的句子在 {} 秒内未播完，换回原来的语音。", PREVIEW_TIMEOUT.as_secs());
+        }
+        unsafe { PostMessageW(Some(HWND(hwnd_value as *mut c_void)), WM_APP_PREVIEW_DONE, WPARAM(serial), LPARAM(0)).ok() };
+    });
+}
+
+/// 结束试听：换回试听前的语音并重新启用“试听”按钮。没有在试听时什么也不做
+fn end_voice_preview(data: &mut SettingsWindowData) {
+    if !data.previewing { return; }
+    data.previewing = false;
+    if let Some(voice_id) = data.preview_restore_voice.take() {
+        if let Err(e) = data.app_state.lock().unwrap().tts_engine.set_voice(&voice_id) {
+            error!("设置窗口: 试听后换回原来的语音失败: {}", e);
+        }
+    }
+    unsafe { let _ = EnableWindow(data.h_voice_preview, true); };
+}
+
+fn remove_selected_ignored_device(data: &mut SettingsWindowData) {
+    let index = unsafe { SendMessageW(data.h_ignored_list, LB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    if index == LB_ERR as isize || index as usize >= data.ignored_device_ids.len() { return; }
+    let removed = data.ignored_device_ids.remove(index as usize);
+    unsafe { SendMessageW(data.h_ignored_list, LB_DELETESTRING, Some(WPARAM(index as usize)), Some(LPARAM(0))); }
+    info!("设置窗口: 从忽略列表中移除设备 '{}'", removed);
+}
+
+/// 提示音下拉框中选中的事件名称
+fn selected_sound_event(data: &SettingsWindowData) -> Option<&'static str> {
+    let index = unsafe { SendMessageW(data.h_sound_event_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    if index < 0 { return None; }
+    SOUND_EVENT_NAMES.get(index as usize).copied()
+}
+
+/// 显示选中事件当前的提示音文件名，未设置时显示“语音播报”
+fn update_sound_file_label(data: &mut SettingsWindowData) {
+    let file_name = selected_sound_event(data)
+        .and_then(|name| data.event_sounds.get(name))
+        .and_then(|sound| sound.file.file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+    let text = file_name.unwrap_or_else(|| {
+        data.app_state.lock().unwrap().i18n_manager.get_text("settings_sound_none").unwrap_or_else(|| "(spoken)".to_string())
+    });
+    unsafe { SetWindowTextW(data.h_sound_file_label, &HSTRING::from(text)).ok() };
+}
+
+fn browse_event_sound(hwnd: HWND, data: &mut SettingsWindowData) {
+    let Some(event_name) = selected_sound_event(data) else { return; };
+    let filter_name = data.app_state.lock().unwrap().i18n_manager.get_text("settings_sound_file_filter").unwrap_or_else(|| "WAV sounds".to_string());
+    // 过滤器格式为 "名称\0模式\0"，并以两个 \0 结尾
+    let filter: Vec<u16> = format!("{}\0*.wav\0\0", filter_name).encode_utf16().collect();
+    let mut file_buffer = [0u16; 260];
+    let mut ofn = OPENFILENAMEW {
+        lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+        hwndOwner: hwnd,
+        lpstrFilter: PCWSTR(filter.as_ptr()),
+        lpstrFile: windows::core::PWSTR(file_buffer.as_mut_ptr()),
+        nMaxFile: file_buffer.len() as u32,
+        Flags: OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR,
+        ..Default::default()
+    };
+    if !unsafe { GetOpenFileNameW(&mut ofn) }.as_bool() { return; }
+
+    let len = file_buffer.iter().position(|&c| c == 0).unwrap_or(file_buffer.len());
+    let file = PathBuf::from(String::from_utf16_lossy(&file_buffer[..len]));
+    info!("设置窗口: 为事件 '{}' 选择提示音 '{}'", event_name, file.display());
+    // 保留已有的播放方式，新设置的提示音默认代替语音
+    let mode = data.event_sounds.get(event_name).map(|sound| sound.mode).unwrap_or_default();
+    data.event_sounds.insert(event_name.to_string(), EventSound { file, mode });
+    update_sound_file_label(data);
+}
+
+/// 选择导出或导入的设置文件，用户取消时返回 None
+fn settings_file_dialog(hwnd: HWND, data: &SettingsWindowData, save: bool) -> Option<PathBuf> {
+    let filter_name = data.app_state.lock().unwrap().i18n_manager.get_text("settings_file_filter").unwrap_or_else(|| "Settings files".to_string());
+    let filter: Vec<u16> = format!("{}\0*.json\0\0", filter_name).encode_utf16().collect();
+    let mut file_buffer = [0u16; 260];
+    if save {
+        for (slot, c) in file_buffer.iter_mut().zip("co_mp_ut_er_settings.json".encode_utf16()) { *slot = c; }
+    }
+    let mut ofn = OPENFILENAMEW {
+        lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+        hwndOwner: hwnd,
+        lpstrFilter: PCWSTR(filter.as_ptr()),
+        lpstrFile: windows::core::PWSTR(file_buffer.as_mut_ptr()),
+        nMaxFile: file_buffer.len() as u32,
+        lpstrDefExt: w!("json"),
+        Flags: if save { OFN_OVERWRITEPROMPT | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR } else { OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR },
+        ..Default::default()
+    };
+    let chosen = if save { unsafe { GetSaveFileNameW(&mut ofn) } } else { unsafe { GetOpenFileNameW(&mut ofn) } };
+    if !chosen.as_bool() { return None; }
+    let len = file_buffer.iter().position(|&c| c == 0).unwrap_or(file_buffer.len());
+    Some(PathBuf::from(String::from_utf16_lossy(&file_buffer[..len])))
+}
+
+/// 导出已保存的设置（窗口中尚未确定的修改不包括在内）
+fn export_settings(hwnd: HWND, data: &mut SettingsWindowData) {
+    let Some(path) = settings_file_dialog(hwnd, data, true) else { return };
+    let app_state = data.app_state.lock().unwrap();
+    match app_state.config.export(&path) {
+        Ok(()) => info!("设置已导出到 {}", path.display()),
+        Err(e) => {
+            error!("导出设置到 {} 失败: {}", path.display(), e);
+            let text = app_state.i18n_manager.get_text("settings_export_failed").unwrap_or_else(|| "The settings could not be exported.".to_string());
+            drop(app_state);
+            unsafe { MessageBoxW(Some(hwnd), &HSTRING::from(format!("{}\n\n{}", text, e)), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING) };
+        }
+    }
+}
+
+/// 在资源管理器中打开 config.json 所在的文件夹
+fn open_config_folder(hwnd: HWND, data: &SettingsWindowData) {
+    let path = crate::config::config_path();
+    let folder = path.parent().map(Path::to_path_buf).unwrap_or(path);
+    open_existing(hwnd, data, folder);
+}
+
+/// 用关联的程序打开正在写入的日志文件
+fn open_log_file(hwnd: HWND, data: &SettingsWindowData) {
+    let Some(path) = crate::logging::active_path() else {
+        warn!("设置窗口: 没有正在写入的日志文件");
+        report_missing_path(hwnd, data, None);
+        return;
+    };
+    log::logger().flush();
+    open_existing(hwnd, data, path);
+}
+
+/// 打开文件或文件夹。还不存在时 (例如第一次保存设置之前) 播报并显示错误
+fn open_existing(hwnd: HWND, data: &SettingsWindowData, path: PathBuf) {
+    if path.exists() {
+        crate::shell_open(path);
+    } else {
+        warn!("设置窗口: {} 还不存在", path.display());
+        report_missing_path(hwnd, data, Some(&path));
+    }
+}
+
+fn report_missing_path(hwnd: HWND, data: &SettingsWindowData, path: Option<&Path>) {
+    let shown = path.map(|p| p.display().to_string()).unwrap_or_default();
+    let app_state = data.app_state.lock().unwrap();
+    let message = match path {
+        Some(_) => app_state.i18n_manager.get_text_with_params("settings_open_missing", &[("path", &shown)])
+            .unwrap_or_else(|| format!("{} does not exist yet.", shown)),
+        None => app_state.i18n_manager.get_text("settings_open_no_log").unwrap_or_else(|| "No log file is being written.".to_string()),
+    };
+    if let Err(e) = app_state.speech.speak_user_triggered(&message) {
+        error!("播报打开失败提示失败: {}", e);
+    }
+    drop(app_state);
+    unsafe { MessageBoxW(Some(hwnd), &HSTRING::from(message), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING) };
+}
+
+/// 导入设置文件，按重新加载配置的方式立即应用并保存。成功时返回 true，设置窗口应关闭，
+/// 因为窗口中显示的仍是导入前的设置。文件无效时什么都不改变
+fn import_settings(hwnd: HWND, data: &mut SettingsWindowData) -> bool {
+    let Some(path) = settings_file_dialog(hwnd, data, false) else { return false };
+    let mut app_state = data.app_state.lock().unwrap();
+    let mut config = match Config::import(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("导入设置文件 {} 失败，设置保持不变: {}", path.display(), e);
+            let text = app_state.i18n_manager.get_text("settings_import_failed").unwrap_or_else(|| "The settings file is invalid. Nothing was changed.".to_string());
+            if let Err(e) = app_state.speech.speak(&text) {
+                error!("播报导入失败提示失败: {}", e);
+            }
+            drop(app_state);
+            unsafe { MessageBoxW(Some(hwnd), &HSTRING::from(format!("{}\n\n{}", text, e)), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING) };
+            return false;
+        }
+    };
+    info!("从 {} 导入设置。", path.display());
+    // 正在运行的倒计时属于这台电脑，保留
+    config.timers = app_state.config.timers.clone();
+    crate::apply_reloaded_config(&mut app_state, config);
+    if let Err(e) = app_state.config.save() {
+        error!("保存导入的设置失败: {}", e);
+    }
+    if let Some(text) = app_state.i18n_manager.get_text("settings_imported") {
+        if let Err(e) = app_state.speech.speak(&text) {
+            error!("播报导入成功提示失败: {}", e);
+        }
+    }
+    true
+}
+
+fn clear_event_sound(data: &mut SettingsWindowData) {
+    let Some(event_name) = selected_sound_event(data) else { return; };
+    if data.event_sounds.remove(event_name).is_some() {
+        info!("设置窗口: 清除事件 '{}' 的提示音", event_name);
+    }
+    update_sound_file_label(data);
+}
+
+/// 读音替换列表中显示的一行，例如 "CO/MP/UT/ER → computer"
+fn pronunciation_list_text(entry: &Pronunciation) -> String {
+    if entry.regex {
+        format!("/{}/ → {}", entry.pattern, entry.replacement)
+    } else {
+        format!("{} → {}", entry.pattern, entry.replacement)
+    }
+}
+
+pub(crate) fn window_text(hwnd: HWND) -> String {
+    let len = unsafe { GetWindowTextLengthW(hwnd) };
+    let mut buffer = vec![0u16; len as usize + 1];
+    let copied = unsafe { GetWindowTextW(hwnd, &mut buffer) };
+    String::from_utf16_lossy(&buffer[..copied as usize])
+}
+
+/// 选中列表中的一条时把它填入编辑框，方便修改后重新添加
+fn show_selected_pronunciation(data: &mut SettingsWindowData) {
+    let index = unsafe { SendMessageW(data.h_pron_list, LB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    let Some(entry) = usize::try_from(index).ok().and_then(|i| data.pronunciations.get(i)) else { return; };
+    unsafe {
+        SetWindowTextW(data.h_pron_pattern, &HSTRING::from(entry.pattern.as_str())).ok();
+        SetWindowTextW(data.h_pron_replacement, &HSTRING::from(entry.replacement.as_str())).ok();
+        SendMessageW(data.h_pron_regex, BM_SETCHECK, Some(WPARAM(if entry.regex { BST_CHECKED.0 as usize } else { BST_UNCHECKED.0 as usize })), Some(LPARAM(0)));
+    }
+}
+
+/// 添加编辑框中的规则；已有相同原文的规则时替换它。无效的正则表达式不会被接受
+fn add_pronunciation(hwnd: HWND, data: &mut SettingsWindowData) {
+    let entry = Pronunciation {
+        pattern: window_text(data.h_pron_pattern),
+        replacement: window_text(data.h_pron_replacement),
+        regex: unsafe { SendMessageW(data.h_pron_regex, BM_GETCHECK, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as u32 == BST_CHECKED.0,
+    };
+    if entry.pattern.is_empty() { return; }
+    if let Err(e) = text_preprocess::validate(&entry) {
+        warn!("设置窗口: 读音替换规则 '{}' 不是有效的正则表达式: {}", entry.pattern, e);
+        let message = data.app_state.lock().unwrap().i18n_manager.get_text("settings_pronunciation_invalid_regex")
+            .unwrap_or_else(|| "This is not a valid regular expression.".to_string());
+        unsafe { MessageBoxW(Some(hwnd), &HSTRING::from(format!("{}\n\n{}", message, e)), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING) };
+        return;
+    }
+    let h_entry = HSTRING::from(pronunciation_list_text(&entry));
+    match data.pronunciations.iter().position(|existing| existing.pattern == entry.pattern && existing.regex == entry.regex) {
+        Some(index) => unsafe {
+            SendMessageW(data.h_pron_list, LB_DELETESTRING, Some(WPARAM(index)), Some(LPARAM(0)));
+            SendMessageW(data.h_pron_list, LB_INSERTSTRING, Some(WPARAM(index)), Some(LPARAM(h_entry.as_ptr() as isize)));
+            data.pronunciations[index] = entry;
+        },
+        None => unsafe {
+            SendMessageW(data.h_pron_list, LB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_entry.as_ptr() as isize)));
+            data.pronunciations.push(entry);
+        },
+    }
+    unsafe {
+        SetWindowTextW(data.h_pron_pattern, w!("")).ok();
+        SetWindowTextW(data.h_pron_replacement, w!("")).ok();
+    }
+}
+
+fn remove_selected_pronunciation(data: &mut SettingsWindowData) {
+    let index = unsafe { SendMessageW(data.h_pron_list, LB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    if index == LB_ERR as isize || index as usize >= data.pronunciations.len() { return; }
+    let removed = data.pronunciations.remove(index as usize);
+    unsafe { SendMessageW(data.h_pron_list, LB_DELETESTRING, Some(WPARAM(index as usize)), Some(LPARAM(0))); }
+    info!("设置窗口: 移除读音替换规则 '{}'", removed.pattern);
+}
+
+/// 重新填充设置方案下拉框，并选中 `selected`（不存在时选中当前方案）
+fn populate_profile_combo(data: &mut SettingsWindowData, selected: &str) {
+    unsafe { SendMessageW(data.h_profile_combo, CB_RESETCONTENT, None, None); }
+    let mut names: Vec<&String> = data.profiles.keys().collect();
+    names.sort();
+    let selected = if data.profiles.contains_key(selected) { selected } else { data.active_profile.as_str() };
+    for (i, name) in names.iter().enumerate() {
+        let h_name = HSTRING::from(name.as_str());
+        unsafe { SendMessageW(data.h_profile_combo, CB_ADDSTRING, Some(WPARAM(0)), Some(LPARAM(h_name.as_ptr() as isize))); }
+        if name.as_str() == selected {
+            unsafe { SendMessageW(data.h_profile_combo, CB_SETCURSEL, Some(WPARAM(i)), Some(LPARAM(0))); }
+        }
+    }
+    show_selected_profile(data);
+}
+
+fn selected_profile(data: &SettingsWindowData) -> Option<String> {
+    let index = unsafe { SendMessageW(data.h_profile_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    let mut names: Vec<&String> = data.profiles.keys().collect();
+    names.sort();
+    usize::try_from(index).ok().and_then(|i| names.get(i)).map(|name| name.to_string())
+}
+
+fn show_selected_profile(data: &mut SettingsWindowData) {
+    let name = selected_profile(data).unwrap_or_default();
+    unsafe { SetWindowTextW(data.h_profile_name, &HSTRING::from(name)).ok(); }
+}
+
+/// 输入框中的方案名称；为空或与其他方案重名时提示并返回 None
+fn new_profile_name(hwnd: HWND, data: &SettingsWindowData) -> Option<String> {
+    let name = window_text(data.h_profile_name).trim().to_string();
+    if name.is_empty() { return None; }
+    if data.profiles.contains_key(&name) {
+        let text = data.app_state.lock().unwrap().i18n_manager.get_text_with_param("settings_profile_exists", "name", &name)
+            .unwrap_or_else(|| format!("A profile named {} already exists.", name));
+        unsafe { MessageBoxW(Some(hwnd), &HSTRING::from(text), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING) };
+        return None;
+    }
+    Some(name)
+}
+
+/// 以当前保存的设置新建一个方案。还没有使用任何方案时，新方案成为当前方案
+fn add_profile(hwnd: HWND, data: &mut SettingsWindowData) {
+    let Some(name) = new_profile_name(hwnd, data) else { return };
+    let profile = {
         let app_state = data.app_state.lock().unwrap();
-        // 1. 过滤语音
-        data.available_voices_for_lang = app_state.available_voices.iter()
-            .filter(|v| v.language.starts_with(selected_lang_code))
-            .cloned()
-            .collect();
-        drop(app_state); // Drop immutable borrow before mutable borrow
-        // 2. 重新填充语音下拉框
-        populate_voice_combo(data);
+        ProfileSettings::capture(&app_state.config, app_state.i18n_manager.locale())
+    };
+    info!("设置窗口: 新建设置方案 '{}'", name);
+    data.profiles.insert(name.clone(), profile);
+    if data.active_profile.is_empty() {
+        data.active_profile = name.clone();
+    }
+    populate_profile_combo(data, &name);
+}
+
+fn rename_selected_profile(hwnd: HWND, data: &mut SettingsWindowData) {
+    let Some(old_name) = selected_profile(data) else { return };
+    let Some(new_name) = new_profile_name(hwnd, data) else { return };
+    let Some(profile) = data.profiles.remove(&old_name) else { return };
+    info!("设置窗口: 设置方案 '{}' 改名为 '{}'", old_name, new_name);
+    data.profiles.insert(new_name.clone(), profile);
+    if data.active_profile == old_name {
+        data.active_profile = new_name.clone();
+    }
+    populate_profile_combo(data, &new_name);
+}
+
+/// 删除选中的方案。删除当前方案后不再使用方案，当前的设置保持不变
+fn delete_selected_profile(data: &mut SettingsWindowData) {
+    let Some(name) = selected_profile(data) else { return };
+    data.profiles.remove(&name);
+    info!("设置窗口: 删除设置方案 '{}'", name);
+    if data.active_profile == name {
+        data.active_profile.clear();
+    }
+    populate_profile_combo(data, "");
+}
+
+/// 播报文字的原文：适用于所有语言的改写，其次是语言档案
+fn default_message(data: &SettingsWindowData, key: &str) -> String {
+    data.custom_messages.get(key).cloned()
+        .or_else(|| data.app_state.lock().unwrap().i18n_manager.base_text(key))
+        .unwrap_or_default()
+}
+
+/// 把编辑框中的文字记为该语言的改写；与原文相同或留空时取消改写
+fn store_edited_message(data: &mut SettingsWindowData) {
+    let Some(key) = data.shown_message.clone() else { return };
+    let text = window_text(data.h_message_text);
+    let entry = crate::i18n::custom_message_key(&data.message_locale, &key);
+    if text.trim().is_empty() || text == default_message(data, &key) {
+        data.custom_messages.remove(&entry);
+    } else if data.custom_messages.get(&entry) != Some(&text) {
+        info!("设置窗口: 改写播报 '{}'", entry);
+        data.custom_messages.insert(entry, text);
+    }
+}
+
+/// 选中条目在当前语言中生效的文字：窗口中的改写，其次是原文
+fn resolved_message(data: &SettingsWindowData, key: &str) -> String {
+    crate::i18n::custom_override(&data.custom_messages, &data.message_locale, key).cloned()
+        .unwrap_or_else(|| default_message(data, key))
+}
+
+/// 更新只读的预览框 (生效的文字) 和可用参数的说明
+fn show_message_details(data: &SettingsWindowData) {
+    let (preview, placeholders) = match &data.shown_message {
+        Some(key) => (resolved_message(data, key), placeholders_text(&data.app_state.lock().unwrap().i18n_manager, key)),
+        None => (String::new(), String::new()),
+    };
+    unsafe { SetWindowTextW(data.h_message_preview, &HSTRING::from(preview)).ok(); }
+    show_placeholders(data.h_message_placeholders, data.h_message_text, &placeholders);
+}
+
+/// 显示选中条目当前生效的文字
+fn show_selected_message(data: &mut SettingsWindowData) {
+    store_edited_message(data);
+    let index = unsafe { SendMessageW(data.h_message_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    data.shown_message = usize::try_from(index).ok().and_then(|i| data.message_keys.get(i)).cloned();
+    let text = match &data.shown_message {
+        Some(key) => resolved_message(data, key),
+        None => String::new(),
+    };
+    unsafe { SetWindowTextW(data.h_message_text, &HSTRING::from(text)).ok(); }
+    show_message_details(data);
+}
+
+/// 编辑框中的文字只使用条目提供的参数时返回 true。否则播报并显示错误，焦点回到编辑框，
+/// 文字不会被记下
+fn check_edited_message(hwnd: HWND, data: &SettingsWindowData) -> bool {
+    let Some(key) = &data.shown_message else { return true };
+    let text = window_text(data.h_message_text);
+    let message = {
+        let app_state = data.app_state.lock().unwrap();
+        let unknown = app_state.i18n_manager.unknown_placeholders(key, &text);
+        if unknown.is_empty() {
+            return true;
+        }
+        let names = unknown.iter().map(|name| format!("{{{}}}", name)).collect::<Vec<_>>().join(", ");
+        warn!("设置窗口: 播报 '{}' 的改写使用了不存在的参数 {}", key, names);
+        app_state.i18n_manager.get_text_with_param("settings_message_unknown_placeholders", "names", &names)
+            .unwrap_or_else(|| format!("This announcement does not provide {}. Use only the placeholders listed below the text.", names))
+    };
+    report_invalid_field(hwnd, data, data.h_message_text, &message);
+    false
+}
+
+/// “设定”按钮：把编辑框中的文字记为选中条目的改写，预览框随即显示新的文字
+fn set_edited_message(hwnd: HWND, data: &mut SettingsWindowData) {
+    if !check_edited_message(hwnd, data) { return; }
+    store_edited_message(data);
+    show_message_details(data);
+}
+
+/// 取消选中条目的改写 (包括适用于所有语言的改写)，恢复语言档案中的文字
+fn reset_selected_message(data: &mut SettingsWindowData) {
+    let Some(key) = data.shown_message.clone() else { return };
+    data.custom_messages.remove(&crate::i18n::custom_message_key(&data.message_locale, &key));
+    data.custom_messages.remove(&key);
+    info!("设置窗口: 恢复播报 '{}' 的原文", key);
+    let text = data.app_state.lock().unwrap().i18n_manager.base_text(&key).unwrap_or_default();
+    unsafe { SetWindowTextW(data.h_message_text, &HSTRING::from(text)).ok(); }
+    show_message_details(data);
+}
+
+/// “朗读预览”按钮：代入示例值后朗读编辑框中的文字，不记下任何修改。条目不提供的参数照原样读出，
+/// 与实际播报时相同
+fn speak_edited_message(data: &SettingsWindowData) {
+    let Some(key) = &data.shown_message else { return };
+    let text = window_text(data.h_message_text);
+    if text.trim().is_empty() { return; }
+    let app_state = data.app_state.lock().unwrap();
+    let i18n = &app_state.i18n_manager;
+    let level = i18n.get_text_with_param("unit_percent", "value", "80").unwrap_or_else(|| "80%".to_string());
+    let duration = i18n.get_text_plural("unit_minutes", 25, &[]).unwrap_or_else(|| "25 minutes".to_string());
+    let values: Vec<(String, String)> = i18n.placeholders_of(key).into_iter()
+        .map(|name| {
+            let value = match name.as_str() {
+                "level" => level.clone(),
+                "duration" | "remaining" => duration.clone(),
+                other => SAMPLE_PARAMS.iter().find(|(param, _)| *param == other).map_or(other, |(_, value)| *value).to_string(),
+            };
+            (name, value)
+        })
+        .collect();
+    let params: Vec<(&str, &str)> = values.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+    let sample = crate::i18n::fill_params(&text, &params);
+    info!("设置窗口: 朗读播报 '{}' 的预览", key);
+    if let Err(e) = app_state.speech.speak_user_triggered(&sample) {
+        error!("设置窗口: 朗读播报预览失败: {}", e);
+    }
+}
+
+/// 把时间选择控件设为指定的时刻 (从午夜起的分钟数)，只显示时和分
+fn set_picker_minutes(picker: HWND, minutes: u32) {
+    let mut time = unsafe { GetLocalTime() };
+    time.wHour = (minutes / 60) as u16;
+    time.wMinute = (minutes % 60) as u16;
+    time.wSecond = 0;
+    time.wMilliseconds = 0;
+    unsafe {
+        SendMessageW(picker, DTM_SETFORMATW, Some(WPARAM(0)), Some(LPARAM(w!("HH:mm").as_ptr() as isize)));
+        SendMessageW(picker, DTM_SETSYSTEMTIME, Some(WPARAM(GDT_VALID.0 as usize)), Some(LPARAM(&time as *const SYSTEMTIME as isize)));
+    }
+}
+
+/// 读取时间选择控件的时刻，格式为 "HH:MM"
+fn picker_time(picker: HWND) -> String {
+    let mut time = SYSTEMTIME::default();
+    unsafe { SendMessageW(picker, DTM_GETSYSTEMTIME, Some(WPARAM(0)), Some(LPARAM(&mut time as *mut SYSTEMTIME as isize))); }
+    format!("{:02}:{:02}", time.wHour, time.wMinute)
+}
+
+/// 设置窗口不阻塞主消息循环，应用或撤销语言切换后立即更新托盘提示和窗口中的文字
+fn refresh_main_window(hwnd: HWND, app_state: &mut AppState) {
+    if let Ok(owner) = unsafe { GetWindow(hwnd, GW_OWNER) } {
+        crate::refresh_language(owner, app_state);
+    }
+}
+
+/// 没有勾选安静时段时，时间和处理方式不可修改
+fn update_quiet_controls(data: &SettingsWindowData) {
+    let enabled = unsafe { SendMessageW(data.h_quiet_check, BM_GETCHECK, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as u32 == BST_CHECKED.0;
+    for control in [data.h_quiet_start, data.h_quiet_end, data.h_quiet_mode_combo] {
+        unsafe { let _ = EnableWindow(control, enabled); };
+    }
+}
+
+/// 安静时段的修改立即生效，不等下一个事件：刚进入静音的时段时丢弃还没播报的内容，并更新托盘提示
+fn refresh_quiet_hours(hwnd: HWND, app_state: &AppState, before: Option<QuietMode>) {
+    let now = crate::announce_policy::active_quiet_mode(app_state.config.quiet_hours.as_ref());
+    if now == before { return; }
+    info!("设置窗口: 当前的安静时段状态由 {:?} 变为 {:?}", before, now);
+    if now == Some(QuietMode::Silence) {
+        match app_state.speech.flush() {
+            Ok(skipped) if skipped > 0 => info!("安静时段已开始，丢弃 {} 条还没播报的内容。", skipped),
+            Ok(_) => {}
+            Err(e) => error!("清空播报队列失败: {}", e),
+        }
+    }
+    if let Ok(owner) = unsafe { GetWindow(hwnd, GW_OWNER) } {
+        crate::update_tray_tooltip(owner, app_state);
+    }
+}
+
+/// 应用并保存设置。语言切换失败时不做任何修改并返回 false，设置窗口应保持打开。
+/// 会改变设置的控件通知：复选框和按钮、编辑框的内容、下拉框的选择。
+/// 不改变设置的按钮 (确定、取消、应用、试听、导出、导入、打开文件夹和日志、朗读改写的播报) 除外
+fn is_setting_change(id: i32, event: u32) -> bool {
+    if matches!(id, IDOK | IDCANCEL | IDC_APPLY | IDC_VOICE_PREVIEW | IDC_EXPORT_SETTINGS | IDC_IMPORT_SETTINGS | IDC_OPEN_CONFIG_FOLDER | IDC_OPEN_LOG_FILE | IDC_MESSAGE_SPEAK) { return false; }
+    matches!(event, BN_CLICKED | EN_CHANGE | CBN_SELCHANGE)
+}
+
+fn set_dirty(data: &mut SettingsWindowData, dirty: bool) {
+    data.dirty = dirty;
+    unsafe { let _ = EnableWindow(data.h_apply, dirty); };
+}
+
+/// “应用”按钮：应用设置但不关闭窗口，之后的修改以应用后的设置为基准
+fn apply_and_continue(hwnd: HWND, data: &mut SettingsWindowData) {
+    if !data.dirty { return; }
+    end_voice_preview(data);
+    let Some(changes) = apply_settings(hwnd, data) else { return };
+    if let Some(language) = changes.language {
+        data.shown_language = language;
+    }
+    if changes.speech {
+        data.shown_rate_position = slider_position(data.h_rate_slider);
+        data.shown_volume_position = slider_position(data.h_volume_slider);
+    }
+    data.applied = true;
+    set_dirty(data, false);
+    info!("设置窗口: 已应用设置");
+}
+
+/// 取消时撤销本次打开窗口期间“应用”过的修改：配置回到打开窗口时的快照，
+/// 语言、语音、语速、音量、输出设备、读音规则、电量提醒和开机自启动也恢复原状，安静时段随即重新判断
+fn revert_settings(hwnd: HWND, data: &SettingsWindowData) {
+    let Some(snapshot) = &data.snapshot else { return };
+    let mut guard = data.app_state.lock().unwrap();
+    let app_state = &mut *guard;
+    info!("设置窗口: 取消，撤销已应用的修改");
+    let steps = snapshot.revert_steps(&app_state.config, app_state.i18n_manager.locale());
+
+    if let Some(locale) = &steps.language {
+        match I18nManager::load_validated(locale) {
+            Ok(mut manager) => {
+                manager.set_overrides(&snapshot.config.custom_messages);
+                app_state.switch_language(manager);
+            }
+            Err(e) => error!("撤销修改时无法换回语言 '{}': {}", locale, e),
+        }
+    } else {
+        app_state.i18n_manager.set_overrides(&snapshot.config.custom_messages);
+    }
+    if let Some(voice_id) = &snapshot.voice {
+        if let Err(e) = app_state.tts_engine.set_voice(voice_id) {
+            error!("撤销修改时无法换回语音: {}", e);
+        }
+    }
+    app_state.tts_engine.set_rate(snapshot.config.speech_rate);
+    app_state.tts_engine.set_volume(snapshot.config.volume);
+    app_state.speech.set_pronunciations(&snapshot.config.pronunciations);
+    if let Some(device) = &steps.output_device {
+        app_state.tts_engine.set_output_device(device.as_deref());
+    }
+    if steps.battery {
+        crate::event_monitor::set_battery_thresholds(&snapshot.config.battery_thresholds, snapshot.config.battery_hysteresis);
+    }
+    if let Some(auto_start) = steps.auto_start {
+        if let Err(e) = crate::startup::set_auto_start(auto_start) {
+            error!("撤销开机自启动设置失败: {}", e);
+        }
+    }
+    let quiet_mode_before = crate::announce_policy::active_quiet_mode(app_state.config.quiet_hours.as_ref());
+    app_state.config = snapshot.config.clone();
+    if let Err(e) = app_state.config.save() {
+        error!("保存 config.json 文件失败: {}", e);
+    }
+    refresh_quiet_hours(hwnd, app_state, quiet_mode_before);
+    refresh_main_window(hwnd, app_state);
+}
+
+/// 输入框中的值无效：播报并显示错误，然后把焦点移到该输入框并选中其中的文字。
+/// 设置窗口可能不在眼前 (用户可能看不到对话框)，所以一定要播报
+fn report_invalid_field(hwnd: HWND, data: &SettingsWindowData, field: HWND, message: &str) {
+    if let Err(e) = data.app_state.lock().unwrap().speech.speak_user_triggered(message) {
+        error!("播报输入错误失败: {}", e);
+    }
+    unsafe {
+        MessageBoxW(Some(hwnd), &HSTRING::from(message), w!("CO/MP/UT/ER"), MB_OK | MB_ICONWARNING);
+        let _ = SetFocus(Some(field));
+        SendMessageW(field, EM_SETSEL, Some(WPARAM(0)), Some(LPARAM(-1)));
     }
 }
 
-fn save_settings(data: &mut SettingsWindowData) {
+/// 把窗口中的设置应用到程序并写入 config.json，“确定”和“应用”共用。
+/// 输入无效或新的语言无法加载时不做修改并返回 None，窗口保持打开
+fn apply_settings(hwnd: HWND, data: &mut SettingsWindowData) -> Option<AppliedChanges> {
+    // 编辑框中还没有记下的播报文字；使用了不存在的参数时不做任何修改
+    if !check_edited_message(hwnd, data) { return None; }
+    store_edited_message(data);
+
+    // 电量提醒阈值或 USB 时间窗口无效时不做任何修改，焦点移到出错的输入框
+    let battery_thresholds = match crate::config::parse_battery_thresholds(&window_text(data.h_battery_thresholds_edit)) {
+        Ok(thresholds) => thresholds,
+        Err(error) => {
+            warn!("设置窗口: 电量提醒阈值无效: {:?}", error);
+            let message = {
+                let i18n = &data.app_state.lock().unwrap().i18n_manager;
+                match error {
+                    BatteryThresholdsError::Invalid(item) => i18n.get_text_with_param("settings_battery_thresholds_invalid", "value", &item)
+                        .unwrap_or_else(|| format!("Battery warning level {} is not a number from 1 to 99.", item)),
+                    BatteryThresholdsError::Duplicate(value) => i18n.get_text_with_param("settings_battery_thresholds_duplicate", "value", &value.to_string())
+                        .unwrap_or_else(|| format!("Battery warning level {} is listed more than once.", value)),
+                }
+            };
+            report_invalid_field(hwnd, data, data.h_battery_thresholds_edit, &message);
+            return None;
+        }
+    };
+    let Some(usb_debounce_ms) = crate::config::parse_usb_window(&window_text(data.h_usb_debounce_edit)) else {
+        warn!("设置窗口: USB 重复通知时间窗口 '{}' 无效", window_text(data.h_usb_debounce_edit));
+        let (min, max) = (USB_WINDOW_MS_RANGE.start().to_string(), USB_WINDOW_MS_RANGE.end().to_string());
+        let message = data.app_state.lock().unwrap().i18n_manager
+            .get_text_with_params("settings_usb_debounce_invalid", &[("min", &min), ("max", &max)])
+            .unwrap_or_else(|| format!("The USB duplicate window must be a number from {} to {} milliseconds.", min, max));
+        report_invalid_field(hwnd, data, data.h_usb_debounce_edit, &message);
+        return None;
+    };
     // --- 核心修复 1: 首先从 UI 获取用户的所有选择 ---
     let lang_index = unsafe { SendMessageW(data.h_lang_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as i32;
-    let voice_index = unsafe { SendMessageW(data.h_voice_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as i32;
 
-    let lang_codes = ["en", "zh", "ja"];
+    let lang_codes = data.language_codes.clone();
     let newly_selected_lang_code = if lang_index >= 0 && (lang_index as usize) < lang_codes.len() {
-        Some(lang_codes[lang_index as usize])
+        Some(lang_codes[lang_index as usize].as_str())
     } else {
         None
     };
 
-    let newly_selected_voice_name: Option<String> = if voice_index >= 0 {
-        data.available_voices_for_lang.get(voice_index as usize)
-            .map(|v| v.name.clone())
+    // 选中的是已卸载语音那一行时不改动语音。语音可以说其他语言，同样按所选语言记住
+    let newly_selected_voice = selected_voice(data).cloned();
+
+    // --- 以管理员身份运行时，修改自启动项前先确认 (在锁定状态之前询问，避免对话框期间阻塞主线程) ---
+    let autostart_checked = unsafe { SendMessageW(data.h_autostart_check, BM_GETCHECK, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as u32 == BST_CHECKED.0;
+    let (is_elevated, current_autostart, warning) = {
+        let app_state = data.app_state.lock().unwrap();
+        (app_state.is_elevated, app_state.config.auto_start,
+         app_state.i18n_manager.get_text("settings_elevated_autostart_warning"))
+    };
+    let write_autostart = if is_elevated && autostart_checked != current_autostart {
+        let warning = warning.unwrap_or_else(|| "The app is running as administrator. The startup entry may be created for the administrator account instead of you. Continue?".to_string());
+        let answer = unsafe { MessageBoxW(Some(hwnd), &HSTRING::from(warning), w!("CO/MP/UT/ER"), MB_YESNO | MB_ICONWARNING) };
+        if answer != IDYES {
+            info!("用户取消了以管理员身份修改开机自启动设置。");
+        }
+        answer == IDYES
     } else {
-        None
+        true
+    };
+
+    // --- 安静时段 ---
+    let quiet_checked = unsafe { SendMessageW(data.h_quiet_check, BM_GETCHECK, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as u32 == BST_CHECKED.0;
+    let mode_index = unsafe { SendMessageW(data.h_quiet_mode_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    let quiet_hours = quiet_checked.then(|| QuietHours {
+        start: picker_time(data.h_quiet_start),
+        end: picker_time(data.h_quiet_end),
+        mode: usize::try_from(mode_index).ok().and_then(|i| QUIET_MODES.get(i).copied()).unwrap_or_default(),
+    });
+    // 开始和结束时间相同的时段是空的，当作没有开启
+    let quiet_hours = match quiet_hours {
+        Some(q) if q.start == q.end => {
+            info!("设置窗口: 安静时段的开始和结束都是 {}，视为关闭", q.start);
+            unsafe { SendMessageW(data.h_quiet_check, BM_SETCHECK, Some(WPARAM(BST_UNCHECKED.0 as usize)), Some(LPARAM(0))); }
+            update_quiet_controls(data);
+            None
+        }
+        other => other,
     };
 
+    // --- 输出设备：只在改选过时写回，没有连接的已配置设备保持原样 ---
+    let output_row = unsafe { SendMessageW(data.h_output_combo, CB_GETCURSEL, Some(WPARAM(0)), Some(LPARAM(0))) }.0;
+    let output_device = (output_row != data.shown_output_row).then(|| selected_output_device(data));
+
     // --- 现在可以锁定状态并进行所有修改了 ---
     let mut app_state = data.app_state.lock().unwrap();
 
-    let is_lang_changed = newly_selected_lang_code.is_some() &&
-                         app_state.config.language.as_deref() != newly_selected_lang_code;
+    // 与打开窗口时显示的语言比较：未设置语言或设置为 "zh-TW" 而列表中只有 "zh" 时，不动语言下拉框就不算修改
+    let is_lang_changed = newly_selected_lang_code.is_some_and(|code| code != data.shown_language);
+    let mut changes = AppliedChanges::default();
 
     // --- 逻辑分支 1: 如果语言改变了 ---
     if is_lang_changed {
         let selected_lang_code = newly_selected_lang_code.unwrap(); // We know it's Some
-        info!("语言已从 {:?} 更改为 '{}'", app_state.config.language, selected_lang_code);
 
-        app_state.config.language = Some(selected_lang_code.to_string());
-        
-        // 更新 i18n 管理器
-        match I18nManager::new(selected_lang_code) {
-            Ok(new_i18n_manager) => {
-                app_state.i18n_manager = new_i18n_manager;
-                info!("语言已动态切换为 '{}'", selected_lang_code);
-
-                // --- 播报语言切换 ---
-                // --- 核心修复 2: 使用从 UI 新鲜获取的语音选择来播报 ---
-                let voice_to_set = if let Some(ref voice_name) = newly_selected_voice_name {
-                    info!("使用 UI 中新选择的语音 '{}' 进行播报。", voice_name);
-                    Some(voice_name.clone())
-                } else {
-                    info!("UI 中未选择特定语音，使用新语言的第一个可用语音作为备选。");
-                    app_state.available_voices.iter()
-                        .find(|v| v.language.starts_with(selected_lang_code))
-                        .map(|v| v.name.clone())
-                };
-
-                if let Some(voice_name) = voice_to_set {
-                    if let Err(e) = app_state.tts_engine.set_voice(&voice_name) {
-                        error!("为播报设置新语音失败: {}", e);
+        // 先完整加载并校验新的语言档案，成功之后才修改任何状态
+        let mut new_i18n_manager = match I18nManager::load_validated(selected_lang_code) {
+            Ok(manager) => manager,
+            Err(e) => {
+                error!("切换语言到 '{}' 失败，保持当前语言: {}", selected_lang_code, e);
+                if let Some(text) = app_state.i18n_manager.get_text("announcement_language_change_failed") {
+                    if let Err(e) = app_state.speech.speak(&text) {
+                        error!("播报语言切换失败提示失败: {}", e);
                     }
                 }
+                let previous_index = lang_codes.iter().position(|c| *c == data.shown_language).unwrap_or(0);
+                drop(app_state);
 
-                if let Some(text) = app_state.i18n_manager.get_text("announcement_language_changed") {
-                    if let Err(e) = app_state.tts_engine.speak(&text) {
-                        error!("播报语言切换提示失败: {}", e);
-                    }
-                }
-            },
-            Err(e) => error!("动态切换语言失败: {}", e),
+                // 恢复下拉框，并按原语言重新填充语音列表
+                unsafe { SendMessageW(data.h_lang_combo, CB_SETCURSEL, Some(WPARAM(previous_index)), Some(LPARAM(0))); }
+                handle_language_selection_change(data);
+                return None;
+            }
+        };
+
+        info!("语言已从 {:?} 更改为 '{}'", app_state.config.language, selected_lang_code);
+        app_state.config.language = Some(selected_lang_code.to_string());
+        new_i18n_manager.set_overrides(&data.custom_messages);
+        app_state.switch_language(new_i18n_manager);
+        info!("语言已动态切换为 '{}'", selected_lang_code);
+        changes.language = Some(selected_lang_code.to_string());
+
+        // --- 播报语言切换 ---
+        // --- 核心修复 2: 使用从 UI 新鲜获取的语音选择来播报 ---
+        // UI 中选择的语音优先，否则使用该语言记住的语音或第一个匹配的语音
+        let mapped_voice = match &newly_selected_voice {
+            Some(voice) => {
+                info!("使用 UI 中新选择的语音 '{}' 进行播报。", voice.name);
+                Some(voice.id.clone())
+            }
+            None => app_state.config.voice_for_language(selected_lang_code).cloned(),
+        };
+        match app_state.tts_engine.select_voice_for_language(selected_lang_code, mapped_voice.as_deref()) {
+            Ok(Some(voice)) => app_state.config.remember_voice(selected_lang_code, &voice.id),
+            Ok(None) => {}
+            Err(e) => error!("为播报设置新语音失败: {}", e),
+        }
+
+        if let Some(text) = app_state.i18n_manager.get_text("announcement_language_changed") {
+            if let Err(e) = app_state.speech.speak(&text) {
+                error!("播报语言切换提示失败: {}", e);
+            }
         }
     }
     
     // --- 保存语音设置 (无论语言是否改变，都应保存) ---
-    if let Some(voice_name_to_save) = newly_selected_voice_name {
-        info!("设置窗口: 选中的语音是 '{}'", voice_name_to_save);
-        app_state.config.custom_voice = Some(voice_name_to_save.clone());
+    // 语音按语言记录在 voice_by_language 中；custom_voice 只保留给旧的配置作为备选
+    if let Some(voice) = newly_selected_voice {
+        info!("设置窗口: 选中的语音是 '{}' ({})", voice.name, voice.id);
+        if let Some(lang) = newly_selected_lang_code {
+            app_state.config.remember_voice(lang, &voice.id);
+        }
         // 动态应用新语音 (如果语言没变，也需要应用)
         if !is_lang_changed {
-             if let Err(e) = app_state.tts_engine.set_voice(&voice_name_to_save) {
+            if let Err(e) = app_state.tts_engine.set_voice(&voice.id) {
                 error!("动态应用新语音失败: {}", e);
             }
         }
     } else {
-        warn!("未能根据索引 {} 找到对应的语音信息，或没有选择语音。", voice_index);
-        app_state.config.custom_voice = None;
+        warn!("语音下拉框中没有选中可用的语音，保持原来的语音。");
     }
 
     // --- 保存自启动设置 ---
-    let is_checked = unsafe { SendMessageW(data.h_autostart_check, BM_GETCHECK, Some(WPARAM(0)), Some(LPARAM(0))) }.0 as u32 == BST_CHECKED.0;
-    app_state.config.auto_start = is_checked;
-    if let Err(e) = crate::startup::set_auto_start(is_checked) {
-        error!("保存开机自启动设置到注册表失败: {}", e);
+    if write_autostart {
+        app_state.config.auto_start = autostart_checked;
+        if let Err(e) = crate::startup::set_auto_start(autostart_checked) {
+            error!("保存开机自启动设置到注册表失败: {}", e);
+        }
     }
     
+    app_state.config.ignored_device_ids = data.ignored_device_ids.clone();
+    app_state.config.event_sounds = data.event_sounds.clone();
+    let quiet_mode_before = crate::announce_policy::active_quiet_mode(app_state.config.quiet_hours.as_ref());
+    app_state.config.quiet_hours = quiet_hours;
+    app_state.config.pronunciations = data.pronunciations.clone();
+    app_state.speech.set_pronunciations(&app_state.config.pronunciations);
+    app_state.config.custom_messages = data.custom_messages.clone();
+    app_state.i18n_manager.set_overrides(&data.custom_messages);
+    // 事件开关：关闭的事件记为 false，开启的事件从 enabled_events 中去掉 (默认即播报)。
+    // 列表中没有的事件名称保持不变
+    for (index, name) in TOGGLEABLE_EVENT_NAMES.iter().enumerate() {
+        if event_checked(data.h_events_list, index) {
+            app_state.config.enabled_events.remove(*name);
+        } else {
+            app_state.config.enabled_events.insert(name.to_string(), false);
+        }
+    }
+    // 语速和音量：只写回移动过的滑块
+    let rate_position = slider_position(data.h_rate_slider);
+    if rate_position != data.shown_rate_position {
+        changes.speech = true;
+        app_state.config.speech_rate = Some(position_to_rate(rate_position));
+        app_state.tts_engine.set_rate(app_state.config.speech_rate);
+    }
+    let volume_position = slider_position(data.h_volume_slider);
+    if volume_position != data.shown_volume_position {
+        changes.speech = true;
+        app_state.config.volume = Some(position_to_volume(volume_position));
+        app_state.tts_engine.set_volume(app_state.config.volume);
+    }
+    if let Some(device) = output_device {
+        info!("设置窗口: 输出设备改为 {:?}", device);
+        app_state.tts_engine.set_output_device(device.as_deref());
+        app_state.config.output_device = device;
+        data.shown_output_row = output_row;
+    }
+    // 设置方案：当前方案记下这次修改后的设置
+    app_state.config.profiles = data.profiles.clone();
+    app_state.config.active_profile = data.active_profile.clone();
+    let language = app_state.i18n_manager.locale().to_string();
+    app_state.config.capture_active_profile(&language);
+    if battery_thresholds != app_state.config.battery_thresholds {
+        info!("设置窗口: 电量提醒阈值改为 {:?}", battery_thresholds);
+        crate::event_monitor::set_battery_thresholds(&battery_thresholds, app_state.config.battery_hysteresis);
+        app_state.config.battery_thresholds = battery_thresholds;
+    }
+    app_state.config.usb_debounce_ms = usb_debounce_ms;
+
     // --- 最后，将所有变更写入文件 ---
     if let Err(e) = app_state.config.save() {
         error!("保存 config.json 文件失败: {}", e);
     }
-}
\ No newline at end of file
+    refresh_quiet_hours(hwnd, &app_state, quiet_mode_before);
+    refresh_main_window(hwnd, &mut app_state);
+    Some(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> SettingsSnapshot {
+        let config = serde_json::from_str(r#"{"battery_thresholds": [20, 10], "battery_hysteresis": 3, "auto_start": false}"#).unwrap();
+        SettingsSnapshot { config, locale: "en".to_string(), voice: Some("voice-en".to_string()) }
+    }
+
+    #[test]
+    fn nothing_to_revert_without_changes() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.revert_steps(&snapshot.config, "en"), RevertSteps::default());
+    }
+
+    #[test]
+    fn reverts_every_applied_change() {
+        let snapshot = snapshot();
+        let mut applied = snapshot.config.clone();
+        applied.output_device = Some("Headphones".to_string());
+        applied.battery_thresholds = vec![30];
+        applied.auto_start = true;
+        assert_eq!(snapshot.revert_steps(&applied, "zh-TW"), RevertSteps {
+            language: Some("en".to_string()),
+            output_device: Some(None),
+            battery: true,
+            auto_start: Some(false),
+        });
+    }
+
+    #[test]
+    fn hysteresis_change_alone_reverts_battery_thresholds() {
+        let snapshot = snapshot();
+        let mut applied = snapshot.config.clone();
+        applied.battery_hysteresis = 5;
+        assert_eq!(snapshot.revert_steps(&applied, "en"), RevertSteps { battery: true, ..RevertSteps::default() });
+    }
+
+    #[test]
+    fn change_applied_and_undone_needs_no_revert() {
+        // 先应用了另一个输出设备，之后又改回原来的设备再应用
+        let snapshot = snapshot();
+        let mut applied = snapshot.config.clone();
+        applied.output_device = Some("Speakers".to_string());
+        assert_eq!(snapshot.revert_steps(&applied, "en").output_device, Some(None));
+        applied.output_device = None;
+        assert_eq!(snapshot.revert_steps(&applied, "en"), RevertSteps::default());
+    }
+}
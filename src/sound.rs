@@ -0,0 +1,47 @@
+// src/sound.rs
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use log::warn;
+use once_cell::sync::Lazy;
+use windows::core::HSTRING;
+use windows::Win32::Media::Audio::{PlaySoundW, SND_ASYNC, SND_FILENAME, SND_FLAGS, SND_NODEFAULT, SND_SYNC};
+
+// Files that already failed, so a broken path is reported once instead of on every event.
+static REPORTED: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Starts playing a WAV file and returns immediately. Returns `false` if the file cannot be
+/// played, in which case the caller should fall back to speech.
+pub fn play_async(path: &Path) -> bool {
+    play(path, SND_ASYNC)
+}
+
+/// Plays a WAV file to the end. Only for background threads such as the speech worker.
+pub fn play_sync(path: &Path) -> bool {
+    play(path, SND_SYNC)
+}
+
+/// Whether the file exists; a missing file is reported once.
+pub fn is_playable(path: &Path) -> bool {
+    if path.is_file() { return true; }
+    report_once(path, "文件不存在");
+    false
+}
+
+fn play(path: &Path, mode: SND_FLAGS) -> bool {
+    if !is_playable(path) { return false; }
+    let file = HSTRING::from(path.as_os_str());
+    // SND_NODEFAULT: a file that cannot be decoded must not turn into the system default sound.
+    let played = unsafe { PlaySoundW(&file, None, SND_FILENAME | SND_NODEFAULT | mode) }.as_bool();
+    if !played {
+        report_once(path, "无法播放，可能不是有效的 WAV 文件");
+    }
+    played
+}
+
+fn report_once(path: &Path, reason: &str) {
+    if REPORTED.lock().unwrap().insert(path.to_path_buf()) {
+        warn!("提示音 '{}' {}，将不会播放。", path.display(), reason);
+    }
+}
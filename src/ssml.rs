@@ -0,0 +1,118 @@
+// src/ssml.rs
+
+/// Whether an announcement is SSML markup, i.e. it starts with a `<speak>` root element.
+/// Everything else is plain text and goes to the speech engine unchanged.
+pub fn is_ssml(text: &str) -> bool {
+    let Some(rest) = text.trim_start().strip_prefix("<speak") else { return false };
+    rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace())
+}
+
+/// Escapes a value that is inserted into an SSML template, such as a user or network name.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The plain text of an SSML document: tags removed, the predefined entities decoded and
+/// whitespace collapsed. Used when the markup is invalid or cannot be synthesized.
+pub fn strip_tags(ssml: &str) -> String {
+    let mut text = String::with_capacity(ssml.len());
+    let mut in_tag = false;
+    for c in ssml.chars() {
+        match c {
+            '<' => in_tag = true,
+            // A tag such as <break/> separates words.
+            '>' if in_tag => { in_tag = false; text.push(' '); }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A rough well-formedness check: every element is closed in order, attribute values are
+/// quoted and `&` only starts an entity. The synthesizer still validates the SSML itself.
+pub fn is_well_formed(ssml: &str) -> bool {
+    let mut open: Vec<&str> = Vec::new();
+    let mut rest = ssml.trim();
+    while let Some(start) = rest.find(['<', '&']) {
+        let tail = &rest[start..];
+        if tail.starts_with('&') {
+            let Some(end) = tail.find(';') else { return false };
+            let name = &tail[1..end];
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#') { return false; }
+            rest = &tail[end + 1..];
+            continue;
+        }
+        if let Some(comment) = tail.strip_prefix("<!--") {
+            let Some(end) = comment.find("-->") else { return false };
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let Some(end) = tag_end(tail) else { return false };
+        let tag = &tail[1..end];
+        rest = &tail[end + 1..];
+        if tag.starts_with('?') { continue; }
+        if let Some(name) = tag.strip_prefix('/') {
+            if open.pop() != Some(name.trim()) { return false; }
+        } else {
+            let name = tag.trim_end_matches('/').split_whitespace().next().unwrap_or_default();
+            if name.is_empty() { return false; }
+            if !tag.ends_with('/') {
+                open.push(name);
+            }
+        }
+        // Text after the root element is not allowed.
+        if open.is_empty() && !rest.trim().is_empty() { return false; }
+    }
+    open.is_empty()
+}
+
+/// The index of the `>` that closes the tag at the start of `tail`, skipping quoted attribute values.
+fn tag_end(tail: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tail.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '<') => return None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Windows only accepts SSML whose root element declares the version, namespace and language.
+/// Locale files may simply write `<speak>`; the missing attributes are added here.
+pub fn complete_root(ssml: &str, language: &str) -> String {
+    let trimmed = ssml.trim_start();
+    let Some(end) = tag_end(trimmed) else { return ssml.to_string() };
+    let (root, body) = trimmed.split_at(end);
+    let self_closing = root.ends_with('/');
+    let mut root = root.trim_end_matches('/').to_string();
+    if !root.contains("version=") {
+        root.push_str(" version=\"1.0\"");
+    }
+    if !root.contains("xmlns=") {
+        root.push_str(" xmlns=\"http://www.w3.org/2001/10/synthesis\"");
+    }
+    if !root.contains("xml:lang=") {
+        root.push_str(&format!(" xml:lang=\"{}\"", escape(language)));
+    }
+    if self_closing {
+        root.push('/');
+    }
+    root + body
+}
@@ -1,26 +1,30 @@
 // src/startup.rs
 
 use std::env;
+use std::path::Path;
 use winreg::enums::*;
 use winreg::RegKey;
-use log::info;
+use log::{info, warn};
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+use crate::error::{AppError, AppResult};
 
 const APP_NAME: &str = "co_mp_ut_er";
 const REG_KEY_PATH: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+/// 附加在自启动项命令行上的参数，程序据此区分登录时的自动启动和手动启动
+pub const AUTOSTART_ARG: &str = "--autostart";
 
 /// 根据传入的布尔值，在 Windows 注册表中添加或移除本应用的开机自启动项。
-pub fn set_auto_start(enable: bool) -> Result<(), std::io::Error> {
+pub fn set_auto_start(enable: bool) -> AppResult<()> {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let run_key = hkcu.open_subkey_with_flags(REG_KEY_PATH, KEY_WRITE)?;
+    let run_key = hkcu.open_subkey_with_flags(REG_KEY_PATH, KEY_WRITE).map_err(AppError::RegistryAccess)?;
 
     if enable {
-        let exe_path = env::current_exe()?;
-        let exe_path_str = exe_path.to_str().ok_or_else(|| {
-            std::io::Error::new(std::io::ErrorKind::Other, "可执行文件路径包含无效的 UTF-8 字符")
-        })?;
-        // 为路径添加引号，以防路径中包含空格
-        let value = format!("\"{}\"", exe_path_str);
-        run_key.set_value(APP_NAME, &value)?;
+        let exe_path = env::current_exe().map_err(AppError::ExecutablePath)?;
+        let value = autostart_command(&exe_path)?;
+        run_key.set_value(APP_NAME, &value).map_err(AppError::RegistryAccess)?;
         info!("已设置开机自启动。路径: {}", value);
     } else {
         // 如果值不存在，delete_value 会返回错误，这是正常情况，我们忽略它。
@@ -30,4 +34,66 @@ pub fn set_auto_start(enable: bool) -> Result<(), std::io::Error> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 自启动项的命令行：程序路径加上 AUTOSTART_ARG。路径加引号，以防其中包含空格
+fn autostart_command(exe_path: &Path) -> AppResult<String> {
+    let exe_path_str = exe_path.to_str()
+        .ok_or_else(|| AppError::ExecutablePath(std::io::Error::other("可执行文件路径包含无效的 UTF-8 字符")))?;
+    Ok(format!("\"{}\" {}", exe_path_str, AUTOSTART_ARG))
+}
+
+/// 本进程是否由开机自启动项启动
+pub fn launched_by_autostart() -> bool {
+    env::args().skip(1).any(|arg| arg == AUTOSTART_ARG)
+}
+
+/// 检查当前进程是否以管理员身份（提升权限）运行。
+/// 以其他管理员账户提升时，HKCU 指向的是那个账户的注册表，自启动项会写到别人名下。
+pub fn is_elevated() -> bool {
+    let mut token = HANDLE::default();
+    if let Err(e) = unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } {
+        warn!("无法打开进程令牌，假定未以管理员身份运行: {}", e);
+        return false;
+    }
+    let mut elevation = TOKEN_ELEVATION::default();
+    let mut returned = 0u32;
+    let result = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned,
+        )
+    };
+    unsafe { let _ = CloseHandle(token); }
+    match result {
+        Ok(()) => elevation.TokenIsElevated != 0,
+        Err(e) => {
+            warn!("查询进程提升状态失败，假定未以管理员身份运行: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::PathBuf;
+
+    #[test]
+    fn command_quotes_the_path() {
+        let command = autostart_command(Path::new(r"C:\Program Files\co_mp_ut_er.exe")).unwrap();
+        assert_eq!(command, format!("\"C:\\Program Files\\co_mp_ut_er.exe\" {}", AUTOSTART_ARG));
+    }
+
+    #[test]
+    fn non_utf8_path_is_executable_path_error() {
+        // 单独的代理项 0xD800 不能转换为 UTF-8
+        let path = PathBuf::from(OsString::from_wide(&[b'C' as u16, b':' as u16, 0xD800]));
+        assert!(matches!(autostart_command(&path), Err(AppError::ExecutablePath(_))));
+    }
+}
@@ -0,0 +1,87 @@
+// src/status.rs
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicIsize, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_APP};
+
+/// 状态变化时投递给隐藏窗口的消息，收到后刷新托盘提示
+pub const WM_APP_STATUS_CHANGED: u32 = WM_APP + 3;
+
+/// 语音引擎的健康状况
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EngineHealth {
+    #[default]
+    Ok,
+    /// 连续播报失败，正在重新创建语音引擎
+    Recovering,
+    /// 多次重新创建都失败，播报只能以蜂鸣代替
+    Failed,
+}
+
+/// 后台线程与 UI 共享的运行状态。后台线程只负责更新数值，
+/// 托盘提示等界面元素在 UI 线程收到 WM_APP_STATUS_CHANGED 后自行读取。
+#[derive(Default)]
+pub struct SharedStatus {
+    queued: AtomicUsize,
+    window: AtomicIsize,
+    engine_health: AtomicU8,
+    // 出错后已恢复的子系统 (名称, 详细信息)，等待 UI 线程转成播报
+    recoveries: Mutex<Vec<(String, String)>>,
+}
+
+impl SharedStatus {
+    /// 窗口创建后调用，此后的状态变化会通知该窗口
+    pub fn attach_window(&self, hwnd: HWND) {
+        self.window.store(hwnd.0 as isize, Ordering::SeqCst);
+    }
+
+    /// 排队等待播报的条数（不含正在播报的一条）
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    pub fn set_queued(&self, count: usize) {
+        if self.queued.swap(count, Ordering::SeqCst) != count {
+            self.notify();
+        }
+    }
+
+    pub fn engine_health(&self) -> EngineHealth {
+        match self.engine_health.load(Ordering::SeqCst) {
+            1 => EngineHealth::Recovering,
+            2 => EngineHealth::Failed,
+            _ => EngineHealth::Ok,
+        }
+    }
+
+    pub fn set_engine_health(&self, health: EngineHealth) {
+        let value = match health {
+            EngineHealth::Ok => 0,
+            EngineHealth::Recovering => 1,
+            EngineHealth::Failed => 2,
+        };
+        if self.engine_health.swap(value, Ordering::SeqCst) != value {
+            self.notify();
+        }
+    }
+
+    /// 子系统在一段时间的失败之后恢复正常时调用
+    pub fn report_recovery(&self, name: &str, detail: String) {
+        self.recoveries.lock().unwrap().push((name.to_string(), detail));
+        self.notify();
+    }
+
+    pub fn take_recoveries(&self) -> Vec<(String, String)> {
+        std::mem::take(&mut *self.recoveries.lock().unwrap())
+    }
+
+    fn notify(&self) {
+        let hwnd_value = self.window.load(Ordering::SeqCst);
+        if hwnd_value != 0 {
+            let hwnd = HWND(hwnd_value as *mut c_void);
+            unsafe { PostMessageW(Some(hwnd), WM_APP_STATUS_CHANGED, WPARAM(0), LPARAM(0)).ok() };
+        }
+    }
+}
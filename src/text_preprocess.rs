@@ -0,0 +1,169 @@
+// src/text_preprocess.rs
+
+use log::warn;
+use regex::{NoExpand, Regex, RegexBuilder};
+
+use crate::config::Pronunciation;
+
+struct Rule {
+    matcher: Regex,
+    replacement: String,
+    // Regex replacements may refer to groups ($1, ${name}); literal ones are inserted as is.
+    expand: bool,
+}
+
+/// The user's pronunciation overrides, compiled once. All rules are matched in one left-to-right
+/// pass, case-insensitively: where two patterns match at the same position the one listed first
+/// wins, and replaced text is never matched again by a later rule.
+#[derive(Default)]
+pub struct Pronunciations {
+    rules: Vec<Rule>,
+    // Alternation of all rules, group `r{i}` belonging to rule i.
+    combined: Option<Regex>,
+}
+
+impl Pronunciations {
+    /// Compiles the entries, skipping (with a warning) empty patterns and invalid regexes.
+    pub fn new(entries: &[Pronunciation]) -> Self {
+        let rules: Vec<Rule> = entries.iter().filter_map(|entry| {
+            if entry.pattern.is_empty() {
+                warn!("忽略空的读音替换规则 (替换为 '{}')", entry.replacement);
+                return None;
+            }
+            match compile(entry) {
+                Ok(matcher) => Some(Rule { matcher, replacement: entry.replacement.clone(), expand: entry.regex }),
+                Err(e) => {
+                    warn!("读音替换规则 '{}' 不是有效的正则表达式，已忽略: {}", entry.pattern, e);
+                    None
+                }
+            }
+        }).collect();
+        let alternation = rules.iter().enumerate()
+            .map(|(i, rule)| format!("(?P<r{}>{})", i, rule.matcher.as_str()))
+            .collect::<Vec<_>>()
+            .join("|");
+        let combined = (!rules.is_empty()).then(|| RegexBuilder::new(&alternation).case_insensitive(true).build()).and_then(|result| match result {
+            Ok(combined) => Some(combined),
+            Err(e) => {
+                // Only possible when a pattern reuses a group name such as r0; fall back to rule order.
+                warn!("无法合并读音替换规则，将逐条应用: {}", e);
+                None
+            }
+        });
+        Self { rules, combined }
+    }
+
+    /// Applies the overrides to plain text.
+    pub fn apply(&self, text: &str) -> String {
+        if self.rules.is_empty() { return text.to_string(); }
+        let Some(combined) = &self.combined else {
+            return self.rules.iter().fold(text.to_string(), |text, rule| rule.replace(&text));
+        };
+        combined.replace_all(text, |captures: &regex::Captures| {
+            let matched = &captures[0];
+            let rule = (0..self.rules.len()).find(|i| captures.name(&format!("r{}", i)).is_some());
+            match rule {
+                Some(i) => self.rules[i].replace(matched),
+                None => matched.to_string(),
+            }
+        }).into_owned()
+    }
+
+    /// Applies the overrides to the text between the tags of an SSML document, leaving the
+    /// markup itself untouched.
+    pub fn apply_ssml(&self, ssml: &str) -> String {
+        if self.rules.is_empty() { return ssml.to_string(); }
+        let mut result = String::with_capacity(ssml.len());
+        let mut rest = ssml;
+        while let Some(start) = rest.find('<') {
+            result.push_str(&self.apply(&rest[..start]));
+            let end = rest[start..].find('>').map_or(rest.len(), |end| start + end + 1);
+            result.push_str(&rest[start..end]);
+            rest = &rest[end..];
+        }
+        result.push_str(&self.apply(rest));
+        result
+    }
+}
+
+impl Rule {
+    fn replace(&self, text: &str) -> String {
+        if self.expand {
+            self.matcher.replace_all(text, self.replacement.as_str()).into_owned()
+        } else {
+            self.matcher.replace_all(text, NoExpand(&self.replacement)).into_owned()
+        }
+    }
+}
+
+/// Checks that the entry can be compiled, e.g. before the settings window accepts it.
+pub fn validate(entry: &Pronunciation) -> Result<(), regex::Error> {
+    compile(entry).map(|_| ())
+}
+
+fn compile(entry: &Pronunciation) -> Result<Regex, regex::Error> {
+    let pattern = if entry.regex { entry.pattern.clone() } else { regex::escape(&entry.pattern) };
+    RegexBuilder::new(&pattern).case_insensitive(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(pattern: &str, replacement: &str) -> Pronunciation {
+        Pronunciation { pattern: pattern.to_string(), replacement: replacement.to_string(), regex: false }
+    }
+
+    fn regex(pattern: &str, replacement: &str) -> Pronunciation {
+        Pronunciation { pattern: pattern.to_string(), replacement: replacement.to_string(), regex: true }
+    }
+
+    #[test]
+    fn matches_case_insensitively() {
+        let rules = Pronunciations::new(&[literal("usb", "U S B"), regex(r"\bwi-?fi\b", "why fye")]);
+        assert_eq!(rules.apply("USB and usb and Usb"), "U S B and U S B and U S B");
+        assert_eq!(rules.apply("WiFi or WI-FI"), "why fye or why fye");
+    }
+
+    #[test]
+    fn first_listed_rule_wins() {
+        let rules = Pronunciations::new(&[literal("New", "Neu"), literal("New York", "N Y C")]);
+        assert_eq!(rules.apply("New York"), "Neu York");
+        let rules = Pronunciations::new(&[literal("New York", "N Y C"), literal("New", "Neu")]);
+        assert_eq!(rules.apply("New York, New Jersey"), "N Y C, Neu Jersey");
+    }
+
+    #[test]
+    fn replaced_text_is_not_matched_again() {
+        let rules = Pronunciations::new(&[literal("a", "b"), literal("b", "c")]);
+        assert_eq!(rules.apply("ab"), "bc");
+    }
+
+    #[test]
+    fn literal_patterns_are_not_regexes() {
+        let rules = Pronunciations::new(&[literal("C++", "C plus plus"), literal("a.b", "dot")]);
+        assert_eq!(rules.apply("C++ and axb and a.b"), "C plus plus and axb and dot");
+    }
+
+    #[test]
+    fn only_regex_replacements_expand_groups() {
+        let rules = Pronunciations::new(&[regex(r"(\d+)%", "$1 percent")]);
+        assert_eq!(rules.apply("Battery 80%"), "Battery 80 percent");
+        let rules = Pronunciations::new(&[literal("price", "$1 each")]);
+        assert_eq!(rules.apply("price"), "$1 each");
+    }
+
+    #[test]
+    fn skips_empty_and_invalid_patterns() {
+        let rules = Pronunciations::new(&[literal("", "x"), regex("(", "x"), literal("ok", "okay")]);
+        assert_eq!(rules.apply("ok ("), "okay (");
+        assert!(validate(&regex("(", "x")).is_err());
+        assert!(validate(&literal("(", "x")).is_ok());
+    }
+
+    #[test]
+    fn ssml_markup_is_left_alone() {
+        let rules = Pronunciations::new(&[literal("speak", "talk")]);
+        assert_eq!(rules.apply_ssml("<speak>speak up</speak>"), "<speak>talk up</speak>");
+    }
+}
@@ -0,0 +1,79 @@
+// src/timers.rs
+
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 托盘菜单中提供的预设时长（分钟）
+pub const PRESET_MINUTES: [u32; 4] = [5, 15, 30, 60];
+
+/// 一个用户设置的倒计时。使用墙上时间记录结束时刻，
+/// 这样程序重启或系统睡眠之后依然能正确判断是否已经到期。
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TimerEntry {
+    pub id: u32,
+    pub minutes: u32,
+    pub ends_at: u64,
+    #[serde(default)]
+    pub halfway_announced: bool,
+    #[serde(default)]
+    pub last_minute_announced: bool,
+}
+
+/// 某个计时器在本次检查中需要播报的内容
+#[derive(Debug, PartialEq)]
+pub enum TimerNotice {
+    Halfway { minutes: u32, remaining_minutes: u32 },
+    OneMinuteLeft { minutes: u32 },
+    Elapsed { minutes: u32 },
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl TimerEntry {
+    pub fn new(id: u32, minutes: u32) -> Self {
+        TimerEntry {
+            id,
+            minutes,
+            ends_at: now_secs() + u64::from(minutes) * 60,
+            halfway_announced: false,
+            last_minute_announced: false,
+        }
+    }
+
+    pub fn remaining_secs(&self, now: u64) -> u64 {
+        self.ends_at.saturating_sub(now)
+    }
+}
+
+/// 检查所有计时器，返回需要播报的提醒，并移除已到期的计时器。
+/// 中途提醒只对足够长的计时器有意义，否则会和“最后一分钟”提醒挤在一起。
+pub fn collect_due(timers: &mut Vec<TimerEntry>, now: u64, warnings: bool) -> Vec<TimerNotice> {
+    let mut notices = Vec::new();
+    for timer in timers.iter_mut() {
+        let remaining = timer.remaining_secs(now);
+        if remaining == 0 {
+            notices.push(TimerNotice::Elapsed { minutes: timer.minutes });
+            continue;
+        }
+        if !warnings { continue; }
+
+        let total = u64::from(timer.minutes) * 60;
+        if timer.minutes >= 4 && !timer.halfway_announced && remaining <= total / 2 {
+            timer.halfway_announced = true;
+            notices.push(TimerNotice::Halfway { minutes: timer.minutes, remaining_minutes: remaining.div_ceil(60) as u32 });
+        }
+        if timer.minutes > 2 && !timer.last_minute_announced && remaining <= 60 {
+            timer.last_minute_announced = true;
+            notices.push(TimerNotice::OneMinuteLeft { minutes: timer.minutes });
+        }
+    }
+    timers.retain(|t| t.remaining_secs(now) > 0);
+    notices
+}
+
+/// 把剩余秒数格式化为菜单中显示的 mm:ss
+pub fn format_remaining(secs: u64) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
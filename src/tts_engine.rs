@@ -1,19 +1,443 @@
 // src/tts_engine.rs
 
-use crate::config::Config;
-use tts::Tts;
+use crate::config::{Config, Pronunciation};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tts::{Gender, Tts, Voice};
 use log::{info, warn, error};
-use std::error::Error;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+use windows::Win32::System::Diagnostics::Debug::{Beep, MessageBeep};
+use windows::Win32::UI::WindowsAndMessaging::MB_ICONEXCLAMATION;
 
+use crate::error::{AppError, AppResult};
+use crate::status::{EngineHealth, SharedStatus};
+use crate::audio_ducking::AudioDucker;
+use crate::sound;
+use crate::ssml;
+use crate::text_preprocess::Pronunciations;
+use crate::output_device::{self, DeviceSpeaker, OutputDevice};
+use crate::wav_export::{self, WavOptions};
+
+// 如果一直收不到播报结束的回调，最多降低其他程序音量这么久
+const DUCK_WATCHDOG: Duration = Duration::from_secs(60);
+// 一条播报收不到结束回调的最长时间，实际超时还要按文本长度增加
+const UTTERANCE_TIMEOUT_BASE: Duration = Duration::from_secs(20);
+const UTTERANCE_TIMEOUT_PER_CHAR: Duration = Duration::from_millis(150);
+// 语音引擎出错时，两次蜂鸣提示之间至少间隔这么久（Critical 除外）
+const FALLBACK_BEEP_INTERVAL: Duration = Duration::from_secs(10);
+// 连续失败这么多次后重新创建语音引擎，偶尔一次失败多半与引擎本身无关
+const REINIT_AFTER_FAILURES: u32 = 2;
+// 每次重新创建语音引擎前等待的时间，全部失败后放弃，直到有播报成功为止
+const REINIT_BACKOFF: [Duration; 3] = [Duration::from_secs(1), Duration::from_secs(5), Duration::from_secs(30)];
+// 暂时性错误（例如刚从睡眠唤醒时音频设备还没准备好）的重试间隔，全部失败后才算一次播报失败
+const SPEAK_RETRY_DELAYS: [Duration; 3] = [Duration::from_millis(500), Duration::from_secs(1), Duration::from_secs(2)];
+// 视为暂时性错误的 HRESULT: AUDCLNT_E_DEVICE_INVALIDATED、AUDCLNT_E_SERVICE_NOT_RUNNING、
+// RPC_S_SERVER_UNAVAILABLE、RPC_E_DISCONNECTED
+const TRANSIENT_HRESULTS: [u32; 4] = [0x8889_0004, 0x8889_0010, 0x8007_06BA, 0x8001_0108];
+// 语音引擎无法恢复时的最后一段蜂鸣，与普通的蜂鸣提示区分开
+const ENGINE_LOST_BEEPS: &[(u32, u32)] = &[(1320, 150), (990, 150), (660, 400)];
+// 上一条播完后这么久之内的播报不再响提示音，连续的播报只在开头响一次
+const CHIME_SKIP_WINDOW: Duration = Duration::from_secs(3);
+// 提示音与语音之间的停顿
+const CHIME_GAP: Duration = Duration::from_millis(200);
+// 没有指定提示音文件时使用的默认提示音 (频率 Hz, 时长 ms)
+const DEFAULT_CHIME: &[(u32, u32)] = &[(988, 70), (1319, 90)];
+
+/// 一个已安装的语音。`id` 是稳定的标识，保存到配置中；`name` 只用于显示，
+/// 不同语言的两个语音可能同名
 #[derive(Clone, Debug)] // 添加 Clone 和 Debug trait
 pub struct VoiceDetail {
+    pub id: String,
     pub name: String,
     pub language: String,
+    pub gender: Option<Gender>,
+}
+
+impl From<&Voice> for VoiceDetail {
+    fn from(voice: &Voice) -> Self {
+        VoiceDetail { id: voice.id(), name: voice.name(), language: voice.language().to_string(), gender: voice.gender() }
+    }
+}
+
+impl VoiceDetail {
+    /// 语音是否说该语言：比较语言代码的主要部分，例如 "de" 和 "de-CH" 都匹配 "de-DE" 的语音，
+    /// 而 "es" 不会匹配 "est" 之类的代码
+    pub fn speaks(&self, language: &str) -> bool {
+        let primary = |code: &str| code.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+        primary(&self.language) == primary(language)
+    }
+}
+
+/// 播报的优先级。排队时高优先级先播；清空队列时只保留 High 及以上。
+/// High 及以上的播报会打断正在播报的较低优先级内容。
+/// Critical 用于电量严重不足等安全相关的提醒，暂停时也会播报（见 announce_policy）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeechPriority {
+    Low,
+    Normal,
+    High,
+    Critical,
+}
+
+/// 被更高优先级打断的播报如何处理
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterruptedPolicy {
+    /// 高优先级播报结束后从头重播
+    #[default]
+    Requeue,
+    /// 直接丢弃
+    Drop,
+}
+
+/// 一条已加入队列的播报，可用来等待它播完
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UtteranceId(u64);
+
+#[derive(Clone)]
+struct Utterance {
+    id: UtteranceId,
+    text: String,
+    priority: SpeechPriority,
+    // 相对于当前音量的比例，1.0 表示不调整
+    volume_scale: f32,
+    // 播报前先播放的声音
+    prelude: Prelude,
+    // 文本是 SSML 标记（以 <speak> 开头），只能通过 DeviceSpeaker 合成
+    ssml: bool,
+}
+
+/// 播报开始前播放什么
+#[derive(Clone, Debug)]
+enum Prelude {
+    /// 开启了 pre_announcement_chime 时响提示音（连续播报时跳过）
+    Chime,
+    /// 用户主动触发的播报（例如试听语音），不响提示音
+    Nothing,
+    /// 为该事件指定的提示音文件
+    Sound(PathBuf),
+}
+
+/// 交给播报线程执行的语音引擎操作。语音引擎只在播报线程中使用，
+/// 调用者不会因为切换语音、停止播报这类可能很慢的操作而阻塞
+enum EngineCommand {
+    /// 停止指定的一条播报；如果它已经播完则什么都不做
+    StopCurrent(UtteranceId),
+    SetVoice(String),
+    SetRate(Option<f32>),
+    SetVolume(Option<f32>),
+    SetPitch(Option<f32>),
+    /// 按新的设置重新创建语音引擎，正在播报的一条从头重播
+    Rebuild(VoiceSettings),
+    /// 换用另一个输出设备，None 表示默认设备。由播报线程自己处理，不经过 run_command
+    SetOutputDevice(Option<String>),
+}
+
+/// 等待播报的队列，以及正在播报的那一条
+#[derive(Default)]
+struct SpeechQueue {
+    pending: VecDeque<Utterance>,
+    in_flight: Option<Utterance>,
+    // 等待播报线程执行的引擎操作，先于下一条播报执行
+    commands: VecDeque<EngineCommand>,
+    // 上一条播报结束的时间，用来判断是否为连续播报
+    last_finished: Option<Instant>,
+}
+
+impl SpeechQueue {
+    /// 队列已满时为一条 priority 优先级的播报腾出位置：丢弃最早的一条最低优先级播报。
+    /// 排队的都比它重要时返回 false，这条播报不应加入队列
+    fn make_room(&mut self, max_queued: usize, priority: SpeechPriority) -> bool {
+        if self.pending.len() < max_queued { return true; }
+        let lowest = self.pending.iter().map(|u| u.priority).min().unwrap_or(priority);
+        if lowest > priority { return false; }
+        if let Some(index) = self.pending.iter().position(|u| u.priority == lowest) {
+            if let Some(evicted) = self.pending.remove(index) {
+                warn!("播报队列已满 ({} 条)，丢弃最早的低优先级播报: {}", max_queued, evicted.text);
+            }
+        }
+        true
+    }
+}
+
+/// 播报线程与调用者共享的状态
+struct SpeechShared {
+    queue: Mutex<SpeechQueue>,
+    wake: Condvar,
+    status: Arc<SharedStatus>,
+    // 播报时把其他程序的音量降低的百分比，None 表示不降低
+    duck_percent: Option<u32>,
+    // 最多排队的条数，至少为 1
+    max_queued: usize,
+    interrupted_policy: InterruptedPolicy,
+    next_id: AtomicU64,
+    // 重新初始化语音引擎后需要重新应用
+    settings: Mutex<VoiceSettings>,
+    // 语音引擎出错时用蜂鸣代替播报
+    fallback_beeps: bool,
+    // 播报前的提示音：None 表示不响，Some(None) 表示使用默认提示音
+    chime: Option<Option<PathBuf>>,
+    // 用户的读音替换规则，加入队列前应用于文本
+    pronunciations: Mutex<Pronunciations>,
+}
+
+impl SpeechShared {
+    fn next_id(&self) -> UtteranceId {
+        UtteranceId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// 把一项引擎操作交给播报线程
+    fn command(&self, command: EngineCommand) {
+        self.queue.lock().unwrap().commands.push_back(command);
+        self.wake.notify_all();
+    }
+
+    /// 对要播报的文本应用读音替换，SSML 只替换标记之间的文字
+    fn preprocess(&self, text: &str) -> String {
+        let pronunciations = self.pronunciations.lock().unwrap();
+        if ssml::is_ssml(text) { pronunciations.apply_ssml(text) } else { pronunciations.apply(text) }
+    }
+
+    /// 当前一条播完（或被停止）后调用，让播报线程取下一条
+    fn finish_current(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.in_flight = None;
+        queue.last_finished = Some(Instant::now());
+        drop(queue);
+        self.wake.notify_all();
+    }
+}
+
+/// 用户设置的语速、音量和音调。None 表示使用语音引擎的默认值
+#[derive(Clone, Copy, Debug, Default)]
+struct VoiceSettings {
+    rate: Option<f32>,
+    volume: Option<f32>,
+    pitch: Option<f32>,
+}
+
+impl VoiceSettings {
+    /// 配置中的值，尚未限制到语音引擎支持的范围
+    fn requested(config: &Config) -> Self {
+        VoiceSettings { rate: config.speech_rate, volume: config.volume, pitch: config.pitch }
+    }
+
+    /// 把超出语音引擎支持范围的值限制到范围内
+    fn clamped(self, tts: &Tts) -> Self {
+        VoiceSettings {
+            rate: self.rate.map(|rate| clamp_setting("语速", rate, tts.min_rate(), tts.max_rate())),
+            volume: self.volume.map(|volume| clamp_setting("音量", volume, tts.min_volume(), tts.max_volume())),
+            pitch: self.pitch.map(|pitch| clamp_setting("音调", pitch, tts.min_pitch(), tts.max_pitch())),
+        }
+    }
+
+    /// 把设置应用到语音引擎。切换语音后也要重新应用，因为有些语音会恢复成默认值
+    fn apply(&self, tts: &mut Tts) -> AppResult<()> {
+        let rate = self.rate.unwrap_or_else(|| tts.normal_rate());
+        let rate_result = ignore_unsupported("语速", tts.set_rate(rate).map(|_| ()));
+        let volume = self.volume.unwrap_or_else(|| tts.normal_volume());
+        let volume_result = ignore_unsupported("音量", tts.set_volume(volume).map(|_| ()));
+        let pitch = self.pitch.unwrap_or_else(|| tts.normal_pitch());
+        let pitch_result = ignore_unsupported("音调", tts.set_pitch(pitch).map(|_| ()));
+        rate_result.and(volume_result).and(pitch_result)
+    }
+}
+
+/// select_voice_for_language 的选择顺序：mapped，语言标签完全相同的语音，同一语言的第一个语音
+fn choose_voice<'a>(voices: &'a [VoiceDetail], language: &str, mapped: Option<&str>) -> Option<&'a VoiceDetail> {
+    mapped.and_then(|id| voices.iter().find(|v| v.id == id))
+        .or_else(|| voices.iter().find(|v| v.language.eq_ignore_ascii_case(language)))
+        .or_else(|| voices.iter().find(|v| v.speaks(language)))
+}
+
+/// 把超出语音引擎支持范围的值限制到范围内，并记录警告
+fn clamp_setting(name: &str, value: f32, min: f32, max: f32) -> f32 {
+    let clamped = value.clamp(min, max);
+    if clamped != value {
+        warn!("{} {} 超出语音引擎支持的范围 ({} 到 {})，已调整为 {}。", name, value, min, max, clamped);
+    }
+    clamped
+}
+
+/// 语音引擎不支持某项调整时只记录警告，不当作错误
+fn ignore_unsupported(name: &str, result: Result<(), tts::Error>) -> AppResult<()> {
+    match result {
+        Err(tts::Error::UnsupportedFeature) => {
+            warn!("当前语音引擎不支持调整{}，已忽略该设置。", name);
+            Ok(())
+        }
+        result => result.map_err(AppError::Tts),
+    }
+}
+
+/// 等待某条播报结束（播完、被停止或被丢弃）
+pub struct UtteranceWaiter {
+    shared: Arc<SpeechShared>,
+    id: UtteranceId,
+}
+
+impl UtteranceWaiter {
+    /// 播报结束时返回 true；超过 timeout 仍未结束时返回 false
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            let pending = queue.in_flight.as_ref().is_some_and(|u| u.id == self.id)
+                || queue.pending.iter().any(|u| u.id == self.id);
+            if !pending { return true; }
+            let now = Instant::now();
+            if now >= deadline { return false; }
+            queue = self.shared.wake.wait_timeout(queue, deadline - now).unwrap().0;
+        }
+    }
+}
+
+/// 播报队列的句柄，可以克隆后在任何线程使用，调用时不必持有 AppState 的锁。
+/// 所有方法只操作队列并立即返回；需要语音引擎的操作（例如停止当前播报）交给播报线程执行。
+#[derive(Clone)]
+pub struct SpeechHandle {
+    shared: Arc<SpeechShared>,
+}
+
+impl SpeechHandle {
+    /// 以普通优先级把文本加入播报队列。返回的 id 可以交给 `waiter` 等待这条播完。
+    pub fn speak(&self, text: &str) -> AppResult<UtteranceId> {
+        self.speak_with_priority(text, SpeechPriority::Normal)
+    }
+
+    pub fn speak_with_priority(&self, text: &str, priority: SpeechPriority) -> AppResult<UtteranceId> {
+        self.speak_scaled(text, priority, 1.0)
+    }
+
+    /// 以调整后的音量播报一条内容，例如会议中的麦克风静音提示不应盖过会议声音。
+    pub fn speak_scaled(&self, text: &str, priority: SpeechPriority, volume_scale: f32) -> AppResult<UtteranceId> {
+        self.enqueue(text, priority, volume_scale, Prelude::Chime)
+    }
+
+    /// 先播放提示音文件，播完后再播报文本。提示音由播报线程播放，不会阻塞调用者。
+    pub fn speak_after_sound(&self, text: &str, priority: SpeechPriority, sound: PathBuf) -> AppResult<UtteranceId> {
+        self.enqueue(text, priority, 1.0, Prelude::Sound(sound))
+    }
+
+    /// 播报用户刚刚主动触发的内容（例如菜单操作的确认、试听语音）。
+    /// 用户此时正在看着屏幕，不需要播报前的提示音。
+    pub fn speak_user_triggered(&self, text: &str) -> AppResult<UtteranceId> {
+        self.enqueue(text, SpeechPriority::Normal, 1.0, Prelude::Nothing)
+    }
+
+    /// 替换读音规则，之后加入队列的播报使用新的规则
+    pub fn set_pronunciations(&self, entries: &[Pronunciation]) {
+        *self.shared.pronunciations.lock().unwrap() = Pronunciations::new(entries);
+    }
+
+    /// 只响一下与优先级对应的蜂鸣，不播报语音（安静时段使用）。在后台线程播放，不阻塞调用方
+    pub fn beep(&self, priority: SpeechPriority) {
+        std::thread::spawn(move || play_fallback_beeps(priority));
+    }
+
+    fn enqueue(&self, text: &str, priority: SpeechPriority, volume_scale: f32, prelude: Prelude) -> AppResult<UtteranceId> {
+        let id = self.shared.next_id();
+        let text = &self.shared.preprocess(text);
+        let mut queue = self.shared.queue.lock().unwrap();
+        if !queue.make_room(self.shared.max_queued, priority) {
+            warn!("播报队列已满 ({} 条)，丢弃新的低优先级播报: {}", queue.pending.len(), text);
+            return Ok(id);
+        }
+        queue.pending.push_back(Utterance { id, text: text.to_string(), priority, volume_scale: volume_scale.clamp(0.0, 1.0), prelude, ssml: ssml::is_ssml(text) });
+        let interrupted = queue.in_flight.as_ref()
+            .filter(|current| priority >= SpeechPriority::High && current.priority < priority)
+            .cloned();
+        if let Some(current) = interrupted {
+            info!("{:?} 优先级的播报打断了正在播报的 {:?} 内容: {}", priority, current.priority, current.text);
+            queue.commands.push_back(EngineCommand::StopCurrent(current.id));
+            if self.shared.interrupted_policy == InterruptedPolicy::Requeue {
+                if queue.make_room(self.shared.max_queued, current.priority) {
+                    // 排队时按优先级取，放在队首即可保证它在同优先级的内容之前重播
+                    queue.pending.push_front(current);
+                } else {
+                    warn!("播报队列已满 ({} 条)，被打断的播报不再重播: {}", queue.pending.len(), current.text);
+                }
+            }
+        }
+        self.shared.status.set_queued(queue.pending.len());
+        drop(queue);
+        self.shared.wake.notify_all();
+        Ok(id)
+    }
+
+    /// 打断当前正在播报的内容并立即播报指定文本。
+    /// 用于把几乎同时到达的两条相关播报合并成一句。
+    pub fn speak_interrupting(&self, text: &str) -> AppResult<()> {
+        let text = &self.shared.preprocess(text);
+        let mut queue = self.shared.queue.lock().unwrap();
+        if !queue.make_room(self.shared.max_queued, SpeechPriority::High) {
+            warn!("播报队列已满 ({} 条)，丢弃新的播报: {}", queue.pending.len(), text);
+            return Ok(());
+        }
+        queue.pending.push_front(Utterance { id: self.shared.next_id(), text: text.to_string(), priority: SpeechPriority::High, volume_scale: 1.0, prelude: Prelude::Chime, ssml: ssml::is_ssml(text) });
+        self.shared.status.set_queued(queue.pending.len());
+        if let Some(current) = queue.in_flight.as_ref().map(|u| u.id) {
+            queue.commands.push_back(EngineCommand::StopCurrent(current));
+        }
+        drop(queue);
+        self.shared.wake.notify_all();
+        Ok(())
+    }
+
+    /// 丢弃所有排队中的 Low/Normal 播报，并停止正在播报的非 High 内容。
+    /// 返回被跳过的条数。
+    pub fn flush(&self) -> AppResult<usize> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let before = queue.pending.len();
+        queue.pending.retain(|u| u.priority >= SpeechPriority::High);
+        let mut skipped = before - queue.pending.len();
+        self.shared.status.set_queued(queue.pending.len());
+        if let Some(current) = queue.in_flight.as_ref().filter(|u| u.priority < SpeechPriority::High).map(|u| u.id) {
+            queue.commands.push_back(EngineCommand::StopCurrent(current));
+            skipped += 1;
+        }
+        drop(queue);
+        self.shared.wake.notify_all();
+        info!("已跳过 {} 条待播报内容。", skipped);
+        Ok(skipped)
+    }
+
+    /// 立即停止正在播报的内容并清空整个队列（包括高优先级的内容），返回被丢弃的条数。
+    /// 不影响暂停状态，之后的事件照常播报。
+    pub fn stop(&self) -> AppResult<usize> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut stopped = queue.pending.len();
+        queue.pending.clear();
+        self.shared.status.set_queued(0);
+        if let Some(current) = queue.in_flight.as_ref().map(|u| u.id) {
+            queue.commands.push_back(EngineCommand::StopCurrent(current));
+            stopped += 1;
+        }
+        drop(queue);
+        self.shared.wake.notify_all();
+        info!("已停止播报，丢弃了 {} 条内容。", stopped);
+        Ok(stopped)
+    }
+
+    /// 返回一个可以在其他线程等待指定播报结束的句柄，例如退出前等告别语播完
+    pub fn waiter(&self, id: UtteranceId) -> UtteranceWaiter {
+        UtteranceWaiter { shared: self.shared.clone(), id }
+    }
 }
 
 pub struct TtsEngine {
-    // Tts 实例现在是唯一的字段
-    tts: Tts,
+    // 语音引擎由播报线程使用；调用者只在创建时和导出 WAV 时直接读取它
+    tts: Arc<Mutex<Tts>>,
+    speech: SpeechHandle,
+    // 创建时读取的已安装语音，查询时不必等待播报线程
+    voices: Vec<VoiceDetail>,
+    // 最近一次选择的语音。切换由播报线程异步完成，导出 WAV 时以这里为准
+    selected_voice: Option<VoiceDetail>,
 }
 
 
@@ -23,70 +447,867 @@ impl TtsEngine {
     /// 创建一个新的 TtsEngine 实例。
     /// 构造函数现在接收一个对已加载配置的引用，而不是自己加载它。
     /// 这样可以更好地分离关注点。
-    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+    pub fn new(config: &Config, status: Arc<SharedStatus>) -> AppResult<Self> {
         // 1. 初始化 tts 库
-        let mut tts = Tts::default()?;
+        let mut tts = Tts::default().map_err(AppError::TtsInit)?;
+        let installed = tts.voices().unwrap_or_else(|e| {
+            error!("获取已安装的语音失败: {}", e);
+            Vec::new()
+        });
+        let mut selected_voice = None;
         
         // 2. 检查配置中是否指定了自定义语音
-        if let Some(voice_name) = &config.custom_voice {
-            info!("配置文件中指定了语音: '{}'。正在尝试设置...", voice_name);
+        if let Some(voice_key) = &config.custom_voice {
+            info!("配置文件中指定了语音: '{}'。正在尝试设置...", voice_key);
             
-            // 尝试在系统中找到该名称的语音
-            let voice_found = tts.voices()?.into_iter().find(|v| v.name() == voice_name.as_str());
+            // 尝试在系统中找到该语音；旧版本的配置保存的是名称，启动后才会迁移为 id
+            let voice_found = installed.iter().find(|v| v.id() == *voice_key)
+                .or_else(|| installed.iter().find(|v| v.name() == *voice_key));
             
             if let Some(voice) = voice_found {
                 // 如果找到了，就设置它
-                if tts.set_voice(&voice).is_ok() {
+                if tts.set_voice(voice).is_ok() {
                     info!("成功将语音设置为: {}", voice.name());
+                    selected_voice = Some(VoiceDetail::from(voice));
                 } else {
                     // 这种情况很少见，但为了健壮性还是处理一下
-                    error!("尝试设置语音 '{}' 失败，将使用默认语音。", voice_name);
+                    error!("尝试设置语音 '{}' 失败，将使用默认语音。", voice_key);
                 }
             } else {
                 // 如果在系统中找不到配置的语音，发出警告
-                warn!("未在系统中找到名为 '{}' 的语音，将使用默认语音。", voice_name);
+                warn!("未在系统中找到语音 '{}'，将使用默认语音。", voice_key);
             }
         } else {
             // 如果配置中没有指定语音，则直接使用系统默认语音
             info!("未使用自定义语音，将使用系统默认语音。");
         }
 
-        Ok(TtsEngine { tts })
+        // 3. 应用语速等设置；不合适的值只记录警告，不影响引擎的创建
+        let settings = VoiceSettings::requested(config).clamped(&tts);
+        if let Err(e) = settings.apply(&mut tts) {
+            warn!("应用语音设置 {:?} 失败，将使用默认值: {}", settings, e);
+        }
+
+        let shared = Arc::new(SpeechShared {
+            queue: Mutex::new(SpeechQueue::default()),
+            wake: Condvar::new(),
+            status,
+            duck_percent: config.duck_other_audio.then_some(config.duck_other_audio_percent),
+            max_queued: config.max_queued_announcements.max(1),
+            interrupted_policy: config.interrupted_announcements,
+            next_id: AtomicU64::new(0),
+            settings: Mutex::new(settings),
+            fallback_beeps: config.fallback_beeps,
+            chime: config.pre_announcement_chime.then(|| config.pre_announcement_chime_file.clone()),
+            pronunciations: Mutex::new(Pronunciations::new(&config.pronunciations)),
+        });
+        register_callbacks(&mut tts, &shared).map_err(AppError::TtsInit)?;
+
+        let device = config.output_device.as_deref().and_then(|name| open_output_device(name, &shared));
+
+        let on_finished = shared.clone();
+        let ssml_speaker = match DeviceSpeaker::new(None, move || on_finished.finish_current()) {
+            Ok(speaker) => Some(Arc::new(speaker)),
+            Err(e) => {
+                warn!("无法创建 SSML 播报器，SSML 内容将去掉标记后朗读: {}", e);
+                None
+            }
+        };
+
+        let tts = Arc::new(Mutex::new(tts));
+        spawn_speech_worker(tts.clone(), shared.clone(), device, ssml_speaker);
+        Ok(TtsEngine {
+            tts,
+            speech: SpeechHandle { shared },
+            voices: installed.iter().map(VoiceDetail::from).collect(),
+            selected_voice,
+        })
     }
 
-    /// 播报指定的文本。
-    /// 这个函数保持不变。
-    pub fn speak(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
-        self.tts.speak(text, false)?;
+    /// 播报队列的句柄。克隆后可以在释放 AppState 的锁之后再播报
+    pub fn speech(&self) -> SpeechHandle {
+        self.speech.clone()
+    }
+
+    /// 重新创建语音引擎，例如默认音频设备变化后，旧的引擎可能仍绑定在已移除的设备上。
+    /// 保留当前语音并按配置重新应用语速等设置；排队的播报不受影响，正在播报的一条会从头重播。
+    /// 由播报线程完成，调用者不等待。
+    pub fn reinitialize(&self, config: &Config) {
+        self.speech.shared.command(EngineCommand::Rebuild(VoiceSettings::requested(config)));
+    }
+
+    /// 用当前的语音、语速、音调和音量把文本合成为 WAV 文件，不经过播报队列。
+    pub fn synthesize_to_file(&self, text: &str, path: &Path) -> AppResult<()> {
+        let options = {
+            let tts = self.tts.lock().unwrap();
+            WavOptions {
+                ssml: ssml::is_ssml(text),
+                voice: self.current_voice(&tts).map(|v| v.id),
+                rate: tts.get_rate().ok().map(f64::from),
+                pitch: tts.get_pitch().ok().map(f64::from),
+                volume: tts.get_volume().ok().map(f64::from),
+            }
+        };
+        let text = match (&options.voice, options.ssml) {
+            (Some(_), true) => ssml::complete_root(text, &self.current_language().unwrap_or_default()),
+            _ => text.to_string(),
+        };
+        wav_export::synthesize_to_file(&text, &options, path)?;
+        info!("已将播报内容导出到 {}", path.display());
         Ok(())
     }
-    
-    pub fn list_available_voices(&self) -> Result<Vec<VoiceDetail>, Box<dyn Error>> {
-        let voices = self.tts.voices()?;
-        Ok(voices.iter().map(|v| VoiceDetail {
-            name: v.name().to_string(),
-            language: v.language().to_string(),
-        }).collect())
+
+    fn current_voice(&self, tts: &Tts) -> Option<VoiceDetail> {
+        self.selected_voice.clone().or_else(|| tts.voice().ok().flatten().as_ref().map(VoiceDetail::from))
+    }
+
+    fn current_language(&self) -> Option<String> {
+        self.current_voice(&self.tts.lock().unwrap()).map(|v| v.language)
+    }
+
+    /// 在运行时换用另一个输出设备，None 表示跟随系统默认设备。正在通过原设备播放的一条会被停止，
+    /// 之后的播报使用新设备；设备不存在时同样回退到默认设备
+    pub fn set_output_device(&self, device: Option<&str>) {
+        self.speech.shared.command(EngineCommand::SetOutputDevice(device.map(str::to_string)));
+    }
+
+    /// 所有可用的音频输出设备，供设置界面选择 `output_device`
+    pub fn list_output_devices() -> AppResult<Vec<OutputDevice>> {
+        Ok(output_device::list_output_devices()?)
+    }
+
+    pub fn list_available_voices(&self) -> AppResult<Vec<VoiceDetail>> {
+        Ok(self.voices.clone())
+    }
+
+    /// 重新读取已安装的语音（程序运行期间可能安装或卸载了语音包），返回列表是否有变化
+    pub fn refresh_voices(&mut self) -> bool {
+        let installed = match self.tts.lock().unwrap().voices() {
+            Ok(voices) => voices.iter().map(VoiceDetail::from).collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("重新获取已安装的语音失败: {}", e);
+                return false;
+            }
+        };
+        let changed = installed.len() != self.voices.len() || installed.iter().zip(&self.voices).any(|(a, b)| a.id != b.id);
+        if changed {
+            info!("已安装的语音发生变化: {} 个 -> {} 个", self.voices.len(), installed.len());
+            self.voices = installed;
+        }
+        changed
+    }
+
+    /// 语音后端的名称，“关于”窗口和诊断信息中显示。Windows 上 Tts::default() 使用 WinRT 的语音合成
+    pub fn backend_name(&self) -> &'static str {
+        "Windows Runtime (Windows.Media.SpeechSynthesis)"
+    }
+
+    /// 当前使用的语音
+    pub fn voice(&self) -> Option<VoiceDetail> {
+        self.current_voice(&self.tts.lock().unwrap())
+    }
+
+    /// 为一种语言选择语音：优先使用 `mapped`（该语言记住的语音 id），其次是语言标签完全相同的
+    /// 已安装语音 (例如 zh-TW 优先用 zh-TW 的语音)，再其次是同一语言的第一个语音；
+    /// 都没有时保持当前语音。返回实际切换到的语音。
+    pub fn select_voice_for_language(&mut self, language: &str, mapped: Option<&str>) -> AppResult<Option<VoiceDetail>> {
+        let Some(voice) = choose_voice(&self.voices, language, mapped).cloned() else {
+            warn!("没有找到适合语言 '{}' 的语音，继续使用当前语音。", language);
+            return Ok(None);
+        };
+        if mapped.is_some_and(|id| id != voice.id) {
+            info!("语言 '{}' 记住的语音已不可用，改用 '{}'。", language, voice.name);
+        }
+        self.set_voice(&voice.id)?;
+        Ok(Some(voice))
+    }
+
+    /// 在运行时调整语速，None 恢复默认语速。超出范围的值会被限制在引擎支持的范围内。
+    pub fn set_rate(&self, rate: Option<f32>) {
+        self.speech.shared.command(EngineCommand::SetRate(rate));
+    }
+
+    /// 在运行时调整播报音量，None 恢复默认音量。只影响播报，不改变系统音量。
+    pub fn set_volume(&self, volume: Option<f32>) {
+        self.speech.shared.command(EngineCommand::SetVolume(volume));
+    }
+
+    /// 在运行时调整音调，None 恢复默认音调。超出范围的值会被限制在引擎支持的范围内。
+    pub fn set_pitch(&self, pitch: Option<f32>) {
+        self.speech.shared.command(EngineCommand::SetPitch(pitch));
     }
 
     /// --- 新增 ---
     /// 在运行时动态设置要使用的语音。
     /// 当用户在设置窗口中选择一个新语音并点击“OK”时，会调用此方法。
-    pub fn set_voice(&mut self, voice_name: &str) -> Result<(), Box<dyn Error>> {
-        // 在所有可用语音中查找与给定名称匹配的 Voice 对象
-        let voice_to_set = self.tts.voices()?
-            .into_iter()
-            .find(|v| v.name() == voice_name);
-            
-        if let Some(voice) = voice_to_set {
-            // 如果找到，就应用它
-            self.tts.set_voice(&voice)?;
-            info!("语音已动态切换为: {}", voice.name());
-            Ok(())
-        } else {
+    /// `voice_id` 是 `VoiceDetail::id`，而不是显示名称。切换由播报线程完成，之后加入队列的播报都使用新语音。
+    pub fn set_voice(&mut self, voice_id: &str) -> AppResult<()> {
+        let Some(voice) = self.voices.iter().find(|v| v.id == voice_id) else {
             // 如果没找到，返回一个错误，这样调用者（设置窗口）就可以知道操作失败了
-            error!("尝试动态切换语音失败，未找到名为 '{}' 的语音", voice_name);
-            Err(format!("未找到名为 '{}' 的语音", voice_name).into())
+            error!("尝试动态切换语音失败，未找到 id 为 '{}' 的语音", voice_id);
+            return Err(AppError::VoiceNotFound(voice_id.to_string()));
+        };
+        self.selected_voice = Some(voice.clone());
+        self.speech.shared.command(EngineCommand::SetVoice(voice_id.to_string()));
+        Ok(())
+    }
+}
+
+/// 在播报线程中执行一项引擎操作。调用时不能持有队列锁，tts 的回调需要获取它
+fn run_command(command: EngineCommand, tts: &mut Tts, shared: &Arc<SpeechShared>, speakers: &[Arc<DeviceSpeaker>]) {
+    match command {
+        EngineCommand::StopCurrent(id) => {
+            let still_speaking = shared.queue.lock().unwrap().in_flight.as_ref().is_some_and(|u| u.id == id);
+            if still_speaking {
+                if let Err(e) = stop_current(tts, shared, speakers) {
+                    error!("停止播报失败: {}", e);
+                }
+            }
+        }
+        EngineCommand::SetVoice(voice_id) => {
+            let voice = match tts.voices() {
+                Ok(voices) => voices.into_iter().find(|v| v.id() == voice_id),
+                Err(e) => {
+                    error!("获取语音列表失败，无法切换语音: {}", e);
+                    return;
+                }
+            };
+            let Some(voice) = voice else {
+                error!("尝试动态切换语音失败，未找到 id 为 '{}' 的语音", voice_id);
+                return;
+            };
+            if let Err(e) = tts.set_voice(&voice) {
+                error!("切换到语音 '{}' 失败: {}", voice.name(), e);
+                return;
+            }
+            info!("语音已动态切换为: {}", voice.name());
+            if let Err(e) = shared.settings.lock().unwrap().apply(tts) {
+                warn!("切换语音后重新应用语音设置失败: {}", e);
+            }
+        }
+        EngineCommand::SetRate(rate) => {
+            let mut settings = shared.settings.lock().unwrap();
+            settings.rate = rate.map(|rate| clamp_setting("语速", rate, tts.min_rate(), tts.max_rate()));
+            match settings.apply(tts) {
+                Ok(()) => info!("语速已设置为: {:?}", settings.rate),
+                Err(e) => error!("设置语速失败: {}", e),
+            }
+        }
+        EngineCommand::SetVolume(volume) => {
+            let mut settings = shared.settings.lock().unwrap();
+            settings.volume = volume.map(|volume| clamp_setting("音量", volume, tts.min_volume(), tts.max_volume()));
+            match settings.apply(tts) {
+                Ok(()) => info!("播报音量已设置为: {:?}", settings.volume),
+                Err(e) => error!("设置播报音量失败: {}", e),
+            }
+        }
+        EngineCommand::SetPitch(pitch) => {
+            let mut settings = shared.settings.lock().unwrap();
+            settings.pitch = pitch.map(|pitch| clamp_setting("音调", pitch, tts.min_pitch(), tts.max_pitch()));
+            match settings.apply(tts) {
+                Ok(()) => info!("音调已设置为: {:?}", settings.pitch),
+                Err(e) => error!("设置音调失败: {}", e),
+            }
+        }
+        EngineCommand::Rebuild(requested) => {
+            *shared.settings.lock().unwrap() = requested.clamped(tts);
+            let mut queue = shared.queue.lock().unwrap();
+            let interrupted = queue.in_flight.clone();
+            if let Some(current) = &interrupted {
+                queue.pending.push_front(current.clone());
+                shared.status.set_queued(queue.pending.len());
+            }
+            drop(queue);
+            if interrupted.is_some() {
+                // 旧引擎可能已经无法正常停止，下面会自己标记当前一条已结束
+                if let Err(e) = stop_current(tts, shared, speakers) {
+                    warn!("重新初始化前停止播报失败: {}", e);
+                }
+            }
+            match rebuild_tts(tts, shared) {
+                Ok(()) => info!("语音引擎已重新初始化。"),
+                Err(e) => error!("重新初始化语音引擎失败: {}", e),
+            }
+            if interrupted.is_some() {
+                shared.finish_current();
+            }
+        }
+        // 需要更换播报线程持有的 DeviceSpeaker，在 spawn_speech_worker 中处理
+        EngineCommand::SetOutputDevice(_) => {}
+    }
+}
+
+/// 创建通过指定输出设备播报的 DeviceSpeaker。设备当前不存在时仍然创建，在它出现之前使用默认设备
+fn open_output_device(name: &str, shared: &Arc<SpeechShared>) -> Option<Arc<DeviceSpeaker>> {
+    let on_finished = shared.clone();
+    match DeviceSpeaker::new(Some(name), move || on_finished.finish_current()) {
+        Ok(device) => {
+            info!("播报将通过输出设备 '{}' 播放。", name);
+            if let Ok(devices) = TtsEngine::list_output_devices() {
+                if !devices.iter().any(|d| d.name.eq_ignore_ascii_case(name) || d.id == *name) {
+                    let names: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+                    warn!("当前找不到输出设备 '{}'，在它出现之前使用默认设备。可用的设备: {:?}", name, names);
+                }
+            }
+            Some(Arc::new(device))
+        }
+        Err(e) => {
+            warn!("无法使用输出设备 '{}'，将使用默认设备: {}", name, e);
+            None
         }
     }
-}
\ No newline at end of file
+}
+
+/// 停止正在播报的内容，无论它是由 tts、指定的输出设备还是 SSML 播报器播放的。
+/// 调用时不能持有队列锁。
+fn stop_current(tts: &mut Tts, shared: &SpeechShared, speakers: &[Arc<DeviceSpeaker>]) -> Result<(), tts::Error> {
+    // DeviceSpeaker 停止时不会触发结束回调，需要自己标记当前一条已结束
+    let stopped = speakers.iter().filter(|speaker| speaker.stop()).count();
+    if stopped > 0 {
+        shared.finish_current();
+        return Ok(());
+    }
+    tts.stop()?;
+    Ok(())
+}
+
+/// 注册播报结束/停止的回调，让播报线程知道何时可以播下一条。
+/// 回调在 tts 库内部持锁时触发，这里只能更新队列状态，不能再调用 tts
+fn register_callbacks(tts: &mut Tts, shared: &Arc<SpeechShared>) -> Result<(), tts::Error> {
+    let on_end = shared.clone();
+    tts.on_utterance_end(Some(Box::new(move |_| on_end.finish_current())))?;
+    let on_stop = shared.clone();
+    tts.on_utterance_stop(Some(Box::new(move |_| on_stop.finish_current())))?;
+    Ok(())
+}
+
+/// 重新创建语音引擎（例如音频服务重启之后），并恢复原来的语音和语速等设置
+fn rebuild_tts(tts: &mut Tts, shared: &Arc<SpeechShared>) -> Result<(), tts::Error> {
+    let voice = tts.voice().ok().flatten();
+    let mut fresh = Tts::default()?;
+    register_callbacks(&mut fresh, shared)?;
+    if let Some(voice) = voice {
+        if let Err(e) = fresh.set_voice(&voice) {
+            warn!("重新初始化后恢复语音 '{}' 失败: {}", voice.name(), e);
+        }
+    }
+    if let Err(e) = shared.settings.lock().unwrap().apply(&mut fresh) {
+        warn!("重新初始化后恢复语音设置失败: {}", e);
+    }
+    *tts = fresh;
+    Ok(())
+}
+
+/// 语音引擎不可用时代替播报的蜂鸣 (频率 Hz, 时长 ms)。越重要的内容音越高、响的次数越多，
+/// 至少能听出发生了什么级别的事件
+fn fallback_beeps(priority: SpeechPriority) -> &'static [(u32, u32)] {
+    match priority {
+        SpeechPriority::Low => &[(440, 120)],
+        SpeechPriority::Normal => &[(660, 120), (660, 120)],
+        SpeechPriority::High => &[(880, 150), (1175, 150)],
+        SpeechPriority::Critical => &[(1320, 200), (1320, 200), (1320, 200)],
+    }
+}
+
+fn play_fallback_beeps(priority: SpeechPriority) {
+    play_beeps(fallback_beeps(priority));
+}
+
+fn play_beeps(beeps: &[(u32, u32)]) {
+    for (frequency, duration) in beeps {
+        if unsafe { Beep(*frequency, *duration) }.is_err() {
+            // 没有蜂鸣器驱动时退回到系统提示音
+            unsafe { let _ = MessageBeep(MB_ICONEXCLAMATION); }
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(60));
+    }
+}
+
+/// 播报前的提示音。指定的文件无法播放时使用默认提示音
+fn play_chime(file: Option<&Path>) {
+    if !file.is_some_and(sound::play_sync) {
+        play_beeps(DEFAULT_CHIME);
+    }
+    std::thread::sleep(CHIME_GAP);
+}
+
+/// 一条播报在这么久之内应当收到结束回调，否则认为语音引擎已经卡死。按文本长度放宽，
+/// 即使语速调到最慢，正常的长句也不会被误判
+fn utterance_timeout(text: &str) -> Duration {
+    UTTERANCE_TIMEOUT_BASE + UTTERANCE_TIMEOUT_PER_CHAR * text.chars().count() as u32
+}
+
+/// 播报线程的故障处理：记录连续失败（出错或一直没有结束回调）的次数，需要时蜂鸣提示并按退避时间重新创建语音引擎，
+/// 同时把引擎的健康状况报告给 UI
+#[derive(Default)]
+struct EngineWatchdog {
+    consecutive_failures: u32,
+    last_beep: Option<Instant>,
+    // 本轮故障中已经尝试重新创建引擎的次数，有播报成功后清零
+    reinit_attempts: usize,
+}
+
+impl EngineWatchdog {
+    fn on_success(&mut self, shared: &SpeechShared) {
+        if self.consecutive_failures == 0 { return; }
+        shared.status.report_recovery("speech", format!("连续 {} 次播报失败后恢复正常", self.consecutive_failures));
+        shared.status.set_engine_health(EngineHealth::Ok);
+        self.consecutive_failures = 0;
+        self.reinit_attempts = 0;
+    }
+
+    /// 调用时不能持有 tts 锁。`permanent` 表示重试也无济于事的错误，立即重新创建引擎
+    fn on_failure(&mut self, priority: SpeechPriority, reason: &str, permanent: bool, tts_handle: &Mutex<Tts>, shared: &Arc<SpeechShared>) {
+        self.consecutive_failures += 1;
+        let beep_due = priority == SpeechPriority::Critical
+            || self.last_beep.is_none_or(|at| at.elapsed() >= FALLBACK_BEEP_INTERVAL);
+        if shared.fallback_beeps && beep_due {
+            self.last_beep = Some(Instant::now());
+            play_fallback_beeps(priority);
+        }
+        if (self.consecutive_failures < REINIT_AFTER_FAILURES && !permanent) || self.reinit_attempts >= REINIT_BACKOFF.len() { return; }
+        error!("语音引擎连续 {} 次播报失败，最近一次: {}。正在重新初始化。", self.consecutive_failures, reason);
+        shared.status.set_engine_health(EngineHealth::Recovering);
+        let recovered = loop {
+            let Some(delay) = REINIT_BACKOFF.get(self.reinit_attempts) else { break false };
+            self.reinit_attempts += 1;
+            std::thread::sleep(*delay);
+            match rebuild_tts(&mut tts_handle.lock().unwrap(), shared) {
+                Ok(()) => {
+                    info!("语音引擎已重新初始化 (第 {} 次尝试)，下一条播报将使用新的引擎。", self.reinit_attempts);
+                    break true;
+                }
+                Err(e) => warn!("第 {} 次重新初始化语音引擎失败: {}", self.reinit_attempts, e),
+            }
+        };
+        if !recovered {
+            error!("语音引擎无法恢复，之后的播报将只以蜂鸣提示，直到引擎恢复正常。");
+            shared.status.set_engine_health(EngineHealth::Failed);
+            if shared.fallback_beeps {
+                play_beeps(ENGINE_LOST_BEEPS);
+            }
+        }
+    }
+}
+
+/// 播报线程下一步要做的事
+enum Next {
+    /// 播报这一条；第二个值表示它紧接着上一条
+    Speak(Utterance, bool),
+    /// 这一条超时仍未收到结束回调
+    Hung(Utterance),
+}
+
+/// 播报线程：每次取出优先级最高的一条（同优先级先进先出），播完后再取下一条。
+fn spawn_speech_worker(tts_handle: Arc<Mutex<Tts>>, shared: Arc<SpeechShared>, mut device: Option<Arc<DeviceSpeaker>>, ssml_speaker: Option<Arc<DeviceSpeaker>>) {
+    std::thread::spawn(move || {
+        if unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_err() {
+            error!("播报线程 COM 初始化失败。");
+            return;
+        }
+        let mut watchdog = EngineWatchdog::default();
+        let mut ducker = AudioDucker::default();
+        // 正在播报的一条最晚应在何时结束
+        let mut deadline: Option<(UtteranceId, Instant)> = None;
+        let mut speakers: Vec<Arc<DeviceSpeaker>> = device.iter().chain(ssml_speaker.iter()).cloned().collect();
+        loop {
+            let next = {
+                let mut queue = shared.queue.lock().unwrap();
+                let hung = loop {
+                    // 引擎操作先于下一条播报执行，例如切换语音后紧接着的播报使用新语音
+                    if !queue.commands.is_empty() {
+                        let commands: Vec<EngineCommand> = queue.commands.drain(..).collect();
+                        drop(queue);
+                        let mut tts = tts_handle.lock().unwrap();
+                        for command in commands {
+                            let EngineCommand::SetOutputDevice(name) = command else {
+                                run_command(command, &mut tts, &shared, &speakers);
+                                continue;
+                            };
+                            // 原设备上正在播放的一条就此结束，不会再收到它的结束回调
+                            if device.as_ref().is_some_and(|old| old.stop()) {
+                                shared.finish_current();
+                            }
+                            device = name.as_deref().and_then(|name| open_output_device(name, &shared));
+                            speakers = device.iter().chain(ssml_speaker.iter()).cloned().collect();
+                            if name.is_none() {
+                                info!("播报改为通过默认设备播放。");
+                            }
+                        }
+                        drop(tts);
+                        queue = shared.queue.lock().unwrap();
+                        continue;
+                    }
+                    // 播报按时结束（或被停止）才算引擎正常，speak 本身成功还不够
+                    let speaking = queue.in_flight.as_ref().map(|u| u.id);
+                    if deadline.is_some_and(|(id, _)| speaking != Some(id)) {
+                        deadline = None;
+                        watchdog.on_success(&shared);
+                    }
+                    if speaking.is_none() && !queue.pending.is_empty() { break None; }
+                    let hang_at = deadline.map(|(_, at)| at);
+                    if hang_at.is_some_and(|at| Instant::now() >= at) {
+                        break queue.in_flight.clone();
+                    }
+                    // 队列播完后才恢复其他程序的音量，连续几条播报之间不会忽高忽低
+                    if queue.in_flight.is_none() {
+                        ducker.restore();
+                    } else if ducker.ducked_for().is_some_and(|d| d > DUCK_WATCHDOG) {
+                        warn!("播报超过 {} 秒仍未结束，先恢复其他程序的音量。", DUCK_WATCHDOG.as_secs());
+                        ducker.restore();
+                    }
+                    let duck_timeout = ducker.ducked_for().map(|_| DUCK_WATCHDOG);
+                    let hang_timeout = hang_at.map(|at| at.saturating_duration_since(Instant::now()));
+                    queue = match duck_timeout.into_iter().chain(hang_timeout).min() {
+                        Some(timeout) => shared.wake.wait_timeout(queue, timeout).unwrap().0,
+                        None => shared.wake.wait(queue).unwrap(),
+                    };
+                };
+                match hung {
+                    Some(utterance) => Next::Hung(utterance),
+                    None => {
+                        let highest = queue.pending.iter().map(|u| u.priority).max().unwrap_or(SpeechPriority::Normal);
+                        let index = queue.pending.iter().position(|u| u.priority == highest).unwrap_or(0);
+                        let Some(utterance) = queue.pending.remove(index) else { continue };
+                        queue.in_flight = Some(utterance.clone());
+                        shared.status.set_queued(queue.pending.len());
+                        let back_to_back = queue.last_finished.is_some_and(|at| at.elapsed() < CHIME_SKIP_WINDOW);
+                        Next::Speak(utterance, back_to_back)
+                    }
+                }
+            };
+            let (utterance, back_to_back) = match next {
+                Next::Speak(utterance, back_to_back) => (utterance, back_to_back),
+                Next::Hung(utterance) => {
+                    deadline = None;
+                    let reason = format!("{} 秒内没有收到播报结束的回调", utterance_timeout(&utterance.text).as_secs());
+                    error!("语音引擎可能已无响应，{}: {}", reason, utterance.text);
+                    if let Err(e) = stop_current(&mut tts_handle.lock().unwrap(), &shared, &speakers) {
+                        warn!("停止无响应的播报失败: {}", e);
+                    }
+                    shared.finish_current();
+                    watchdog.on_failure(utterance.priority, &reason, false, &tts_handle, &shared);
+                    continue;
+                }
+            };
+
+            if let Some(percent) = shared.duck_percent {
+                ducker.duck(percent);
+            }
+            match &utterance.prelude {
+                Prelude::Sound(sound_file) => { sound::play_sync(sound_file); }
+                Prelude::Chime if !back_to_back => {
+                    if let Some(chime_file) = &shared.chime {
+                        play_chime(chime_file.as_deref());
+                    }
+                }
+                Prelude::Chime | Prelude::Nothing => {}
+            }
+            let mut retries = 0;
+            let result = loop {
+                let result = speak_utterance(&mut tts_handle.lock().unwrap(), &utterance, device.as_deref(), ssml_speaker.as_deref());
+                let Err(e) = &result else { break result };
+                let Some(delay) = SPEAK_RETRY_DELAYS.get(retries).filter(|_| is_transient(e)) else { break result };
+                retries += 1;
+                warn!("播报失败: {}，{} 毫秒后第 {} 次重试: {}", error_detail(e), delay.as_millis(), retries, utterance.text);
+                std::thread::sleep(*delay);
+                // 重试期间这一条仍是正在播报的一条，其他播报排在它后面；被停止或清空时不再重试
+                if shared.queue.lock().unwrap().in_flight.as_ref().map(|u| u.id) != Some(utterance.id) {
+                    info!("重试前播报已被停止: {}", utterance.text);
+                    break Ok(());
+                }
+            };
+            match result {
+                Ok(()) => {
+                    if retries > 0 {
+                        info!("第 {} 次重试后播报成功。", retries);
+                    }
+                    deadline = Some((utterance.id, Instant::now() + utterance_timeout(&utterance.text)));
+                }
+                Err(e) => {
+                    let permanent = !is_transient(&e);
+                    error!("播报失败{}: {}", if permanent { "" } else { "，重试也未成功" }, error_detail(&e));
+                    shared.finish_current();
+                    watchdog.on_failure(utterance.priority, &error_detail(&e), permanent, &tts_handle, &shared);
+                }
+            }
+        }
+    });
+}
+
+/// 播报一条：先尝试指定的输出设备，不可用时回退到默认设备
+fn speak_utterance(tts: &mut Tts, utterance: &Utterance, device: Option<&DeviceSpeaker>, ssml_speaker: Option<&DeviceSpeaker>) -> Result<(), tts::Error> {
+    // 音量在 speak 时随内容一起保存，所以播报开始后即可恢复原音量
+    let normal_volume = (utterance.volume_scale < 1.0).then(|| tts.get_volume().ok()).flatten();
+    if let Some(volume) = normal_volume {
+        tts.set_volume(volume * utterance.volume_scale).ok();
+    }
+    // SSML 在默认设备上也要由 ssml_speaker 合成
+    let voice = tts.voice().ok().flatten();
+    let text = match &voice {
+        Some(voice) if utterance.ssml => ssml::complete_root(&utterance.text, voice.language().as_ref()),
+        _ => utterance.text.clone(),
+    };
+    let stream_speakers = device.into_iter().chain(ssml_speaker.filter(|_| utterance.ssml));
+    let mut on_stream = false;
+    for speaker in stream_speakers {
+        let volume = tts.get_volume().unwrap_or(1.0);
+        let (rate, pitch) = (tts.get_rate().unwrap_or(1.0), tts.get_pitch().unwrap_or(1.0));
+        match speaker.speak(&text, utterance.ssml, voice.as_ref().map(|v| v.id()).as_deref(), rate, pitch, volume) {
+            Ok(true) => { on_stream = true; break; }
+            Ok(false) => {}
+            Err(e) if utterance.ssml => { warn!("SSML 无法合成，将去掉标记后朗读: {} ({})", e, utterance.text); break; }
+            Err(e) => warn!("通过指定的输出设备播报失败，改用默认设备: {}", e),
+        }
+    }
+    let result = if on_stream {
+        Ok(())
+    } else if utterance.ssml {
+        tts.speak(ssml::strip_tags(&utterance.text), false).map(|_| ())
+    } else {
+        tts.speak(utterance.text.as_str(), false).map(|_| ())
+    };
+    if let Some(volume) = normal_volume {
+        tts.set_volume(volume).ok();
+    }
+    result
+}
+
+/// 音频设备失效、音频服务或 RPC 暂时不可用这类错误，稍等片刻再试多半就能成功
+fn is_transient(error: &tts::Error) -> bool {
+    match error {
+        tts::Error::WinRt(e) => TRANSIENT_HRESULTS.contains(&(e.code().0 as u32)),
+        _ => false,
+    }
+}
+
+/// 错误说明，带上 HRESULT 以便排查（tts::Error 本身只显示 "WinRT error"）
+fn error_detail(error: &tts::Error) -> String {
+    match error {
+        tts::Error::WinRt(e) => format!("{} (HRESULT 0x{:08X})", e.message(), e.code().0 as u32),
+        e => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 没有语音引擎和播报线程的队列，只检查队列本身的操作
+    fn handle(max_queued: usize) -> SpeechHandle {
+        SpeechHandle {
+            shared: Arc::new(SpeechShared {
+                queue: Mutex::new(SpeechQueue::default()),
+                wake: Condvar::new(),
+                status: Arc::new(SharedStatus::default()),
+                duck_percent: None,
+                max_queued,
+                interrupted_policy: InterruptedPolicy::Requeue,
+                next_id: AtomicU64::new(0),
+                settings: Mutex::new(VoiceSettings::default()),
+                fallback_beeps: false,
+                chime: None,
+                pronunciations: Mutex::new(Pronunciations::new(&[])),
+            }),
+        }
+    }
+
+    // 模拟播报线程取走队首的一条
+    fn start_next(speech: &SpeechHandle) -> UtteranceId {
+        let mut queue = speech.shared.queue.lock().unwrap();
+        let utterance = queue.pending.pop_front().unwrap();
+        let id = utterance.id;
+        queue.in_flight = Some(utterance);
+        id
+    }
+
+    fn stopped(speech: &SpeechHandle) -> Vec<UtteranceId> {
+        speech.shared.queue.lock().unwrap().commands.iter()
+            .filter_map(|command| match command {
+                EngineCommand::StopCurrent(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn pending_texts(speech: &SpeechHandle) -> Vec<String> {
+        speech.shared.queue.lock().unwrap().pending.iter().map(|u| u.text.clone()).collect()
+    }
+
+    #[test]
+    fn flush_keeps_in_flight_high_utterance() {
+        let speech = handle(10);
+        speech.speak_with_priority("battery low", SpeechPriority::High).unwrap();
+        let current = start_next(&speech);
+        speech.speak_with_priority("usb connected", SpeechPriority::Normal).unwrap();
+        speech.speak_with_priority("print done", SpeechPriority::Low).unwrap();
+        speech.speak_with_priority("battery critical", SpeechPriority::Critical).unwrap();
+
+        assert_eq!(speech.flush().unwrap(), 2);
+        let queue = speech.shared.queue.lock().unwrap();
+        assert_eq!(queue.in_flight.as_ref().map(|u| u.id), Some(current));
+        drop(queue);
+        assert!(stopped(&speech).is_empty());
+        assert_eq!(pending_texts(&speech), ["battery critical"]);
+        assert_eq!(speech.shared.status.queued(), 1);
+    }
+
+    #[test]
+    fn flush_stops_in_flight_normal_utterance() {
+        let speech = handle(10);
+        speech.speak("usb connected").unwrap();
+        let current = start_next(&speech);
+        speech.speak_with_priority("print done", SpeechPriority::Low).unwrap();
+
+        assert_eq!(speech.flush().unwrap(), 2);
+        assert_eq!(stopped(&speech), [current]);
+        assert!(pending_texts(&speech).is_empty());
+    }
+
+    #[test]
+    fn concurrent_enqueue_keeps_every_utterance() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 50;
+        let speech = handle(THREADS * PER_THREAD);
+        let workers: Vec<_> = (0..THREADS).map(|thread| {
+            let speech = speech.clone();
+            std::thread::spawn(move || {
+                (0..PER_THREAD).map(|i| speech.speak(&format!("{} {}", thread, i)).unwrap()).collect::<Vec<_>>()
+            })
+        }).collect();
+        let mut ids: Vec<u64> = workers.into_iter().flat_map(|worker| worker.join().unwrap()).map(|id| id.0).collect();
+
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), THREADS * PER_THREAD);
+        assert_eq!(speech.shared.status.queued(), THREADS * PER_THREAD);
+        // 每个线程加入的播报保持各自的先后顺序
+        let texts = pending_texts(&speech);
+        for thread in 0..THREADS {
+            let order: Vec<usize> = texts.iter()
+                .filter_map(|text| text.strip_prefix(&format!("{} ", thread)))
+                .map(|i| i.parse().unwrap())
+                .collect();
+            assert_eq!(order, (0..PER_THREAD).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn concurrent_enqueue_respects_queue_limit() {
+        let speech = handle(5);
+        let workers: Vec<_> = (0..4).map(|thread| {
+            let speech = speech.clone();
+            std::thread::spawn(move || {
+                for i in 0..100 {
+                    let priority = if i % 10 == 0 { SpeechPriority::High } else { SpeechPriority::Low };
+                    speech.speak_with_priority(&format!("{} {}", thread, i), priority).unwrap();
+                    assert!(speech.shared.queue.lock().unwrap().pending.len() <= 5);
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        let queue = speech.shared.queue.lock().unwrap();
+        assert_eq!(queue.pending.len(), 5);
+        // 队列满后 Low 只会挤掉 Low，每条 High 都挤掉一条 Low，40 条 High 足以占满队列
+        assert!(queue.pending.iter().all(|u| u.priority == SpeechPriority::High));
+    }
+
+    #[test]
+    fn requeued_utterance_respects_queue_limit() {
+        let speech = handle(3);
+        speech.speak_with_priority("print done", SpeechPriority::Low).unwrap();
+        let current = start_next(&speech);
+        for text in ["usb connected", "usb removed", "wifi connected"] {
+            speech.speak(text).unwrap();
+        }
+
+        // High 挤掉最早的 Normal 并打断 Low，Low 比排队的都不重要，不再重播
+        speech.speak_with_priority("battery low", SpeechPriority::High).unwrap();
+        assert_eq!(stopped(&speech), [current]);
+        assert_eq!(pending_texts(&speech), ["usb removed", "wifi connected", "battery low"]);
+        assert_eq!(speech.shared.status.queued(), 3);
+
+        // 被打断的 Normal 挤掉最早的 Normal，放回队首
+        start_next(&speech);
+        speech.speak_with_priority("battery critical", SpeechPriority::Critical).unwrap();
+        assert_eq!(pending_texts(&speech), ["usb removed", "battery low", "battery critical"]);
+        assert_eq!(speech.shared.status.queued(), 3);
+    }
+
+    #[test]
+    fn interrupting_utterance_respects_queue_limit() {
+        let speech = handle(2);
+        speech.speak("usb connected").unwrap();
+        let current = start_next(&speech);
+        speech.speak("usb removed").unwrap();
+        speech.speak_with_priority("print done", SpeechPriority::Low).unwrap();
+
+        speech.speak_interrupting("usb device replaced").unwrap();
+        assert_eq!(stopped(&speech), [current]);
+        assert_eq!(pending_texts(&speech), ["usb device replaced", "usb removed"]);
+        assert_eq!(speech.shared.status.queued(), 2);
+
+        // 排队的都是 Critical 时不再加入，也不打断正在播报的内容
+        let speech = handle(1);
+        speech.speak_with_priority("battery critical", SpeechPriority::Critical).unwrap();
+        start_next(&speech);
+        speech.speak_with_priority("battery removed", SpeechPriority::Critical).unwrap();
+        speech.speak_interrupting("usb device replaced").unwrap();
+        assert_eq!(pending_texts(&speech), ["battery removed"]);
+        assert!(stopped(&speech).is_empty());
+    }
+
+    #[test]
+    fn waiter_returns_when_another_thread_finishes() {
+        let speech = handle(10);
+        let id = speech.speak("usb connected").unwrap();
+        let waiter = speech.waiter(id);
+        let waiting = std::thread::spawn(move || waiter.wait(Duration::from_secs(10)));
+        start_next(&speech);
+        speech.shared.finish_current();
+        assert!(waiting.join().unwrap());
+    }
+
+    fn voice(id: &str, language: &str) -> VoiceDetail {
+        VoiceDetail { id: id.to_string(), name: id.to_string(), language: language.to_string(), gender: None }
+    }
+
+    fn chosen(voices: &[VoiceDetail], language: &str, mapped: Option<&str>) -> Option<String> {
+        choose_voice(voices, language, mapped).map(|v| v.id.clone())
+    }
+
+    #[test]
+    fn voice_resolution_order() {
+        let voices = [voice("en-us", "en-US"), voice("zh-cn", "zh-CN"), voice("zh-tw", "zh-TW"), voice("ja", "ja-JP")];
+        // 记住的语音即使说别的语言也优先
+        assert_eq!(chosen(&voices, "zh-TW", Some("ja")).as_deref(), Some("ja"));
+        // 记住的语音已卸载时，先找语言标签完全相同的语音，不区分大小写
+        assert_eq!(chosen(&voices, "zh-tw", Some("uninstalled")).as_deref(), Some("zh-tw"));
+        // 再找同一语言的第一个语音
+        assert_eq!(chosen(&voices, "zh-HK", None).as_deref(), Some("zh-cn"));
+        assert_eq!(chosen(&voices, "zh", None).as_deref(), Some("zh-cn"));
+        assert_eq!(chosen(&voices, "de", None), None);
+    }
+
+    #[test]
+    fn voice_resolution_from_config() {
+        let voices = [voice("zh-cn", "zh-CN"), voice("zh-tw", "zh-TW"), voice("custom", "en-US")];
+        let mut config = Config::default();
+        let resolve = |config: &Config, language: &str| chosen(&voices, language, config.mapped_voice(language).map(String::as_str));
+
+        // 没有设置语音时按语言选择已安装的语音
+        assert_eq!(resolve(&config, "zh-TW").as_deref(), Some("zh-tw"));
+        assert_eq!(resolve(&config, "zh-SG").as_deref(), Some("zh-cn"));
+        // 没有按语言记住的语音时使用 custom_voice
+        config.custom_voice = Some("custom".to_string());
+        assert_eq!(resolve(&config, "zh-TW").as_deref(), Some("custom"));
+        // 地区没有自己的记录时使用该语言的
+        config.remember_voice("zh", "zh-cn");
+        assert_eq!(resolve(&config, "zh-TW").as_deref(), Some("zh-cn"));
+        config.remember_voice("zh-TW", "zh-tw");
+        assert_eq!(resolve(&config, "zh-TW").as_deref(), Some("zh-tw"));
+    }
+}
@@ -0,0 +1,156 @@
+// src/usb_debounce.rs
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use log::{debug, info};
+
+// 设备在上次变化后这么短时间内再次变化，视为反复插拔
+const FLAP_WINDOW: Duration = Duration::from_secs(10);
+// 反复插拔的设备每次播报后静默的时长，超出后一直使用最后一级
+const FLAP_BACKOFF: [Duration; 2] = [Duration::from_secs(30), Duration::from_secs(120)];
+// 设备安静这么久之后，重新当作正常设备对待
+const STABLE_AFTER: Duration = Duration::from_secs(300);
+
+/// 在去抖时间 (`usb_debounce_ms`) 内，同一设备路径、同一方向的 USB 变化只保留第一条，
+/// 无论是 Windows 发了两次 (有些集线器会这样) 还是来自另一个通知来源
+#[derive(Default)]
+pub struct UsbReconciler {
+    recent: HashMap<(String, bool), Instant>,
+    accepted: u64,
+    dropped: u64,
+}
+
+impl UsbReconciler {
+    /// 重复的通知返回 `false`，不再往后传递。`now` 是收到通知的时间
+    pub fn accept(&mut self, device_path: &str, connected: bool, window: Duration, now: Instant) -> bool {
+        self.recent.retain(|_, at| now.duration_since(*at) < window);
+
+        let key = (device_path.to_lowercase(), connected);
+        if self.recent.contains_key(&key) {
+            self.dropped += 1;
+            info!("丢弃重复的 USB {}通知: {} (累计接受 {} 条，丢弃 {} 条)",
+                if connected { "接入" } else { "断开" }, device_path, self.accepted, self.dropped);
+            return false;
+        }
+        // 相反方向的变化是真实的变化，同时结束原方向的去抖时间
+        self.recent.remove(&(key.0.clone(), !connected));
+        self.recent.insert(key, now);
+        self.accepted += 1;
+        debug!("USB {}通知: {} (累计接受 {} 条，丢弃 {} 条)",
+            if connected { "接入" } else { "断开" }, device_path, self.accepted, self.dropped);
+        true
+    }
+}
+
+struct DeviceHistory {
+    last_change: Instant,
+    backoff_level: usize,
+    silent_until: Option<Instant>,
+}
+
+/// 按设备接口路径 (dbcc_name) 分别去抖，不同设备总会播报，只有反复变化的设备会被压住
+#[derive(Default)]
+pub struct UsbDebouncer {
+    devices: HashMap<String, DeviceHistory>,
+}
+
+impl UsbDebouncer {
+    /// 记录一次变化并返回是否应当播报。重复通知应已由 `UsbReconciler` 去掉
+    pub fn should_announce(&mut self, device_path: &str) -> bool {
+        let now = Instant::now();
+        self.devices.retain(|_, history| now.duration_since(history.last_change) < STABLE_AFTER);
+
+        let key = device_path.to_lowercase();
+        let Some(history) = self.devices.get_mut(&key) else {
+            self.devices.insert(key, DeviceHistory { last_change: now, backoff_level: 0, silent_until: None });
+            return true;
+        };
+
+        let since_last = now.duration_since(history.last_change);
+        history.last_change = now;
+
+        if history.silent_until.is_some_and(|until| now < until) { return false; }
+        if since_last < FLAP_WINDOW {
+            let backoff = FLAP_BACKOFF[history.backoff_level.min(FLAP_BACKOFF.len() - 1)];
+            info!("USB 设备 {} 反复插拔，接下来 {} 秒内不再播报。", device_path, backoff.as_secs());
+            history.silent_until = Some(now + backoff);
+            history.backoff_level += 1;
+        }
+        true
+    }
+}
+
+/// 把 `\\?\USB#VID_046D&PID_C52B#5&2a3b#{a5dcbf10-...}` 这样的设备接口路径
+/// 转换成设备管理器中显示的设备实例 ID `USB\VID_046D&PID_C52B\5&2a3b`
+pub fn device_instance_id(device_path: &str) -> String {
+    let trimmed = device_path.trim_start_matches(r"\\?\").trim_start_matches(r"\??\");
+    let without_class = match trimmed.rfind("#{") {
+        Some(index) => &trimmed[..index],
+        None => trimmed,
+    };
+    without_class.replace('#', "\\").to_uppercase()
+}
+
+/// 忽略项匹配设备本身，以及所有实例 ID 以它开头的设备
+/// (例如 `USB\VID_046D&PID_C52B` 会忽略该型号的所有设备)
+pub fn is_ignored(ignored_ids: &[String], instance_id: &str) -> bool {
+    ignored_ids.iter().any(|entry| {
+        let entry = entry.trim().to_uppercase();
+        !entry.is_empty() && instance_id.starts_with(&entry)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(3);
+    const PATH: &str = r"\\?\USB#VID_046D&PID_C52B#5&2a3b#{a5dcbf10-6530-11d2-901f-00c04fb951ed}";
+    const OTHER: &str = r"\\?\USB#VID_0781&PID_5581#4c53#{a5dcbf10-6530-11d2-901f-00c04fb951ed}";
+
+    // 依次送入 (起始后的毫秒数, 路径, 是否接入)，返回哪些通知被保留
+    fn feed(events: &[(u64, &str, bool)]) -> Vec<bool> {
+        let start = Instant::now();
+        let mut reconciler = UsbReconciler::default();
+        events.iter()
+            .map(|(ms, path, connected)| reconciler.accept(path, *connected, WINDOW, start + Duration::from_millis(*ms)))
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_within_window_is_dropped() {
+        assert_eq!(feed(&[(0, PATH, true), (40, PATH, true), (2900, PATH, true)]), [true, false, false]);
+    }
+
+    #[test]
+    fn path_case_is_ignored() {
+        let lower = PATH.to_lowercase();
+        assert_eq!(feed(&[(0, PATH, true), (10, &lower, true)]), [true, false]);
+    }
+
+    #[test]
+    fn opposite_direction_resets_the_window() {
+        // 拔出后很快又插上：每次变化都是真实的
+        assert_eq!(feed(&[(0, PATH, true), (500, PATH, false), (900, PATH, true), (950, PATH, true)]), [true, true, true, false]);
+    }
+
+    #[test]
+    fn same_event_after_the_window_is_accepted() {
+        assert_eq!(feed(&[(0, PATH, true), (3000, PATH, true), (3100, PATH, true)]), [true, true, false]);
+    }
+
+    #[test]
+    fn interleaved_sources_and_devices() {
+        // 两个通知来源交替送来两个设备的每次变化
+        let events = [
+            (0, PATH, true),
+            (5, OTHER, true),
+            (20, PATH, true),
+            (25, OTHER, true),
+            (1000, OTHER, false),
+            (1010, OTHER, false),
+            (1020, PATH, true),
+        ];
+        assert_eq!(feed(&events), [true, true, false, false, true, false, false]);
+    }
+}
@@ -0,0 +1,186 @@
+// src/wav_export.rs
+//
+// Renders text to a WAV file. Also compiled into the `speak_to_file` tool, so this module must
+// not depend on anything else in the crate.
+
+use std::path::Path;
+use thiserror::Error;
+use windows::core::HSTRING;
+use windows::Media::SpeechSynthesis::SpeechSynthesizer;
+use windows::Storage::Streams::DataReader;
+
+#[derive(Debug, Error)]
+pub enum WavExportError {
+    #[error("未找到名为 '{0}' 的语音")]
+    VoiceNotFound(String),
+
+    #[error("语音合成失败: {0}")]
+    Synthesis(#[from] windows::core::Error),
+
+    #[error("合成结果不是有效的 WAV 数据: {0}")]
+    InvalidWav(&'static str),
+
+    #[error("无法写入 '{path}': {source}")]
+    Write { path: String, #[source] source: std::io::Error },
+}
+
+/// Voice and prosody for a synthesis. `None` keeps the synthesizer default.
+#[derive(Clone, Debug, Default)]
+pub struct WavOptions {
+    /// Voice id or display name.
+    pub voice: Option<String>,
+    /// Speaking rate, 0.5 to 6.0 where 1.0 is normal.
+    pub rate: Option<f64>,
+    /// Pitch, 0.0 to 2.0 where 1.0 is normal.
+    pub pitch: Option<f64>,
+    /// Volume, 0.0 to 1.0.
+    pub volume: Option<f64>,
+    /// Treat the text as SSML instead of plain text.
+    pub ssml: bool,
+}
+
+/// Synthesizes `text` and returns a complete RIFF/WAV file.
+pub fn synthesize_wav(text: &str, options: &WavOptions) -> Result<Vec<u8>, WavExportError> {
+    let synthesizer = SpeechSynthesizer::new()?;
+    if let Some(wanted) = &options.voice {
+        let voice = SpeechSynthesizer::AllVoices()?.into_iter().find(|v| {
+            v.Id().is_ok_and(|id| id == wanted.as_str()) || v.DisplayName().is_ok_and(|name| name == wanted.as_str())
+        });
+        let Some(voice) = voice else { return Err(WavExportError::VoiceNotFound(wanted.clone())) };
+        synthesizer.SetVoice(&voice)?;
+    }
+    let synthesis_options = synthesizer.Options()?;
+    if let Some(rate) = options.rate {
+        synthesis_options.SetSpeakingRate(rate)?;
+    }
+    if let Some(pitch) = options.pitch {
+        synthesis_options.SetAudioPitch(pitch)?;
+    }
+    if let Some(volume) = options.volume {
+        synthesis_options.SetAudioVolume(volume)?;
+    }
+
+    let text = HSTRING::from(text);
+    let stream = if options.ssml {
+        synthesizer.SynthesizeSsmlToStreamAsync(&text)?.get()?
+    } else {
+        synthesizer.SynthesizeTextToStreamAsync(&text)?.get()?
+    };
+    let size = u32::try_from(stream.Size()?).map_err(|_| WavExportError::InvalidWav("合成结果过大"))?;
+    let reader = DataReader::CreateDataReader(&stream.GetInputStreamAt(0)?)?;
+    reader.LoadAsync(size)?.get()?;
+    let mut wav = vec![0u8; size as usize];
+    reader.ReadBytes(&mut wav)?;
+    finalize_wav(&mut wav)?;
+    Ok(wav)
+}
+
+/// Synthesizes `text` and writes it to `path`.
+pub fn synthesize_to_file(text: &str, options: &WavOptions, path: &Path) -> Result<(), WavExportError> {
+    let wav = synthesize_wav(text, options)?;
+    std::fs::write(path, wav).map_err(|source| WavExportError::Write { path: path.display().to_string(), source })
+}
+
+/// Checks the RIFF/WAVE header and makes the RIFF and data chunk sizes match the actual length.
+/// Streamed synthesis may leave them as placeholders, which some players refuse.
+pub fn finalize_wav(wav: &mut [u8]) -> Result<(), WavExportError> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return Err(WavExportError::InvalidWav("缺少 RIFF/WAVE 文件头"));
+    }
+    let mut offset = 12;
+    let mut has_format = false;
+    while offset + 8 <= wav.len() {
+        let id = &wav[offset..offset + 4];
+        let declared = u32::from_le_bytes([wav[offset + 4], wav[offset + 5], wav[offset + 6], wav[offset + 7]]) as usize;
+        let body = offset + 8;
+        if id == b"fmt " {
+            has_format = true;
+        } else if id == b"data" {
+            if !has_format {
+                return Err(WavExportError::InvalidWav("data 块之前没有 fmt 块"));
+            }
+            let actual = wav.len() - body;
+            if declared == 0 || declared > actual {
+                wav[offset + 4..offset + 8].copy_from_slice(&(actual as u32).to_le_bytes());
+            }
+            let riff_size = (wav.len() - 8) as u32;
+            wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+            return Ok(());
+        }
+        // Chunks are padded to an even length.
+        offset = body + declared + (declared & 1);
+    }
+    Err(WavExportError::InvalidWav("缺少 data 块"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: [u8; 16] = [1, 0, 1, 0, 0x80, 0x3e, 0, 0, 0, 0x7d, 0, 0, 2, 0, 16, 0];
+
+    fn chunk(id: &[u8; 4], declared: u32, body: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&declared.to_le_bytes());
+        chunk.extend_from_slice(body);
+        chunk
+    }
+
+    fn riff(declared: u32, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&declared.to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+        for chunk in chunks {
+            wav.extend_from_slice(chunk);
+        }
+        wav
+    }
+
+    fn size_at(wav: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap())
+    }
+
+    // RIFF header (12) + fmt chunk (8 + 16), so the data chunk size is at 40
+    const DATA_SIZE_OFFSET: usize = 40;
+
+    #[test]
+    fn fills_in_placeholder_sizes() {
+        for placeholder in [0, u32::MAX] {
+            let mut wav = riff(placeholder, &[chunk(b"fmt ", 16, &FORMAT), chunk(b"data", placeholder, &[0; 100])]);
+            finalize_wav(&mut wav).unwrap();
+            assert_eq!(size_at(&wav, 4), wav.len() as u32 - 8);
+            assert_eq!(size_at(&wav, 16), 16);
+            assert_eq!(size_at(&wav, DATA_SIZE_OFFSET), 100);
+            assert_eq!(wav.len(), 12 + 24 + 8 + 100);
+        }
+    }
+
+    #[test]
+    fn keeps_correct_data_size() {
+        // With another chunk after the samples the declared size is rightly shorter than the rest of the file.
+        let mut wav = riff(0, &[chunk(b"fmt ", 16, &FORMAT), chunk(b"data", 4, &[0; 4]), chunk(b"LIST", 4, b"INFO")]);
+        finalize_wav(&mut wav).unwrap();
+        assert_eq!(size_at(&wav, DATA_SIZE_OFFSET), 4);
+        assert_eq!(size_at(&wav, 4), wav.len() as u32 - 8);
+    }
+
+    #[test]
+    fn skips_padded_odd_length_chunks() {
+        let mut wav = riff(0, &[chunk(b"fmt ", 16, &FORMAT), chunk(b"LIST", 3, b"abc\0"), chunk(b"data", 0, &[0; 10])]);
+        finalize_wav(&mut wav).unwrap();
+        assert_eq!(size_at(&wav, DATA_SIZE_OFFSET + 12), 10);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let invalid = [
+            b"RIFX\0\0\0\0WAVE".to_vec(),
+            b"RIFF".to_vec(),
+            riff(0, &[chunk(b"data", 0, &[0; 4]), chunk(b"fmt ", 16, &FORMAT)]),
+            riff(0, &[chunk(b"fmt ", 16, &FORMAT)]),
+        ];
+        for mut wav in invalid {
+            assert!(matches!(finalize_wav(&mut wav), Err(WavExportError::InvalidWav(_))));
+        }
+    }
+}